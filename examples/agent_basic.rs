@@ -2,7 +2,6 @@ use lib_ai::{
     agent::{tools::CalculatorTool, AgentBuilder, InMemoryStore},
     providers::OpenAIProvider,
 };
-use tokio;
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
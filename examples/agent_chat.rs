@@ -3,7 +3,6 @@ use lib_ai::{
     providers::AnthropicProvider,
 };
 use std::io::{self, Write};
-use tokio;
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
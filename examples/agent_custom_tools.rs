@@ -5,7 +5,6 @@ use lib_ai::{
     ToolFunction,
 };
 use serde_json::json;
-use tokio;
 
 // Custom tool that gets the current time
 struct TimeTool;
@@ -54,7 +53,7 @@ impl ToolExecutor for DiceTool {
             ));
         }
 
-        if sides < 2 || sides > 1000 {
+        if !(2..=1000).contains(&sides) {
             return Ok(ToolResult::Error(
                 "Number of sides must be between 2 and 1000".to_string(),
             ));
@@ -1,6 +1,5 @@
 use futures::StreamExt;
 use lib_ai::{agent::AgentBuilder, providers::OpenAIProvider};
-use tokio;
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -1,6 +1,6 @@
 use dotenv::dotenv;
 use lib_ai::{
-    providers::CohereProvider, CompletionProvider, CompletionRequest, Message, MessageContent, Role,
+    providers::CohereProvider, CompletionProvider, CompletionRequest, Message, MessageContent,
 };
 
 #[tokio::main]
@@ -25,36 +25,19 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let request = CompletionRequest {
         model: provider.default_model().to_string(),
         messages: vec![
-            Message {
-                role: Role::System,
-                content: MessageContent::text("You are a helpful AI assistant."),
-                tool_calls: None,
-                tool_call_id: None,
-            },
-            Message {
-                role: Role::User,
-                content: MessageContent::text("Write a haiku about programming in Rust"),
-                tool_calls: None,
-                tool_call_id: None,
-            },
+            Message::system("You are a helpful AI assistant."),
+            Message::user("Write a haiku about programming in Rust"),
         ],
         temperature: Some(0.7),
         max_tokens: Some(150),
         stream: Some(false),
-        top_p: None,
-        frequency_penalty: None,
-        presence_penalty: None,
-        stop: None,
-        tools: None,
-        tool_choice: None,
-        response_format: None,
-        json_schema: None,
+        ..Default::default()
     };
 
     println!("\n📝 Sending request to Cohere...");
     let response = provider.complete(request.clone()).await?;
 
-    if let Some(text) = response.choices[0].message.content.as_text() {
+    if let Some(text) = response.text() {
         println!("\n📖 Response:");
         println!("{}", text);
     }
@@ -64,10 +47,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         println!("\n📊 Token Usage:");
         println!("  Prompt tokens: {}", usage.prompt_tokens);
         println!("  Completion tokens: {}", usage.completion_tokens);
-        println!(
-            "  Total tokens: {}",
-            usage.total_tokens
-        );
+        println!("  Total tokens: {}", usage.total_tokens);
     }
 
     // Streaming example
@@ -82,7 +62,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     use futures::StreamExt;
     let mut stream = provider.complete_stream(stream_request).await?;
 
-    print!("\n");
+    println!();
     while let Some(chunk) = stream.next().await {
         match chunk {
             Ok(chunk) => {
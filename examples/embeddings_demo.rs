@@ -25,7 +25,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     println!("\n1. Direct Embedding Generation:");
     println!("-------------------------------");
 
-    let texts = vec![
+    let texts = [
         "Machine learning is amazing",
         "Deep learning uses neural networks",
         "The weather is sunny today",
@@ -17,6 +17,7 @@ struct UnreliableProvider {
 }
 
 #[derive(Debug, Clone)]
+#[allow(dead_code)] // not every pattern is exercised by the demo
 enum FailurePattern {
     /// Always succeeds
     AlwaysSucceed,
@@ -64,7 +65,7 @@ impl UnreliableProvider {
             }
 
             FailurePattern::Alternating => {
-                if count % 2 == 0 {
+                if count.is_multiple_of(2) {
                     Some(AiError::ServiceUnavailable {
                         provider: "unreliable".to_string(),
                         retry_after: Some(Duration::from_millis(100)),
@@ -123,7 +124,9 @@ impl CompletionProvider for UnreliableProvider {
                 prompt_tokens: 10,
                 completion_tokens: 20,
                 total_tokens: 30,
+                reasoning_tokens: None,
             }),
+            metadata: None,
         })
     }
 
@@ -299,10 +302,7 @@ async fn main() -> std::result::Result<(), Box<dyn std::error::Error>> {
         .prompt("You are a helpful assistant")
         .tool("calculator", CalculatorTool)
         .build()
-        .map_err(|e| {
-            Box::new(std::io::Error::new(std::io::ErrorKind::Other, e))
-                as Box<dyn std::error::Error>
-        })?;
+        .map_err(|e| Box::new(std::io::Error::other(e)) as Box<dyn std::error::Error>)?;
 
     println!("Testing agent with flaky provider...");
 
@@ -400,19 +400,5 @@ async fn main() -> std::result::Result<(), Box<dyn std::error::Error>> {
 }
 
 fn create_test_request() -> CompletionRequest {
-    CompletionRequest {
-        model: "test-model".to_string(),
-        messages: vec![],
-        temperature: None,
-        max_tokens: None,
-        stream: None,
-        top_p: None,
-        tools: None,
-        tool_choice: None,
-        response_format: None,
-        frequency_penalty: None,
-        presence_penalty: None,
-        stop: None,
-        json_schema: None,
-    }
+    CompletionRequest::new("test-model", vec![])
 }
@@ -2,7 +2,6 @@ use lib_ai::{
     providers::*, CompletionProvider, CompletionRequest, ContentPart, ImageUrl, Message,
     MessageContent, Role,
 };
-use tokio;
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -12,10 +11,11 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     // Create a multimodal message with text and image
     let request = CompletionRequest {
-        model: "gpt-4o".to_string(), // Use a vision-capable model
-        messages: vec![Message {
-            role: Role::User,
-            content: MessageContent::Parts(vec![
+        model: "gpt-4o".to_string(),
+        // Use a vision-capable model
+        messages: vec![Message::new(
+            Role::User,
+            MessageContent::Parts(vec![
                 ContentPart::Text {
                     text: "What's in this image?".to_string(),
                 },
@@ -26,20 +26,11 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                     },
                 },
             ]),
-            tool_calls: None,
-            tool_call_id: None,
-        }],
+        )],
         temperature: Some(0.7),
         max_tokens: Some(300),
         stream: Some(false),
-        tools: None,
-        tool_choice: None,
-        response_format: None,
-        top_p: None,
-        frequency_penalty: None,
-        presence_penalty: None,
-        stop: None,
-        json_schema: None,
+        ..Default::default()
     };
 
     let response = provider.complete(request).await?;
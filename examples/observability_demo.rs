@@ -32,7 +32,7 @@ impl CompletionProvider for MockProvider {
         &self,
         request: lib_ai::CompletionRequest,
     ) -> Result<lib_ai::CompletionResponse, lib_ai::AiError> {
-        use lib_ai::{Choice, CompletionResponse, Message, MessageContent, Role, Usage};
+        use lib_ai::{Choice, CompletionResponse, Message, Usage};
 
         // Simulate a response
         Ok(CompletionResponse {
@@ -40,21 +40,17 @@ impl CompletionProvider for MockProvider {
             model: request.model,
             choices: vec![Choice {
                 index: 0,
-                message: Message {
-                    role: Role::Assistant,
-                    content: MessageContent::text(
-                        "This is a mock response for observability testing",
-                    ),
-                    tool_calls: None,
-                    tool_call_id: None,
-                },
+                message: Message::assistant("This is a mock response for observability testing"),
                 finish_reason: Some("stop".to_string()),
+                logprobs: None,
             }],
             usage: Some(Usage {
                 prompt_tokens: 50,
                 completion_tokens: 20,
                 total_tokens: 70,
+                reasoning_tokens: None,
             }),
+            metadata: None,
         })
     }
 
@@ -144,7 +140,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Perform some operations to generate metrics and traces
     println!("\n📊 Executing agent tasks...");
 
-    let tasks = vec![
+    let tasks = [
         "What is 42 + 58?",
         "Calculate the square root of 144",
         "What is the meaning of life?",
@@ -27,7 +27,7 @@ impl CompletionProvider for MockProvider {
         &self,
         request: lib_ai::CompletionRequest,
     ) -> Result<lib_ai::CompletionResponse, lib_ai::AiError> {
-        use lib_ai::{Choice, CompletionResponse, Message, MessageContent, Role, Usage};
+        use lib_ai::{Choice, CompletionResponse, Message, Usage};
 
         // Simulate a response
         Ok(CompletionResponse {
@@ -35,21 +35,17 @@ impl CompletionProvider for MockProvider {
             model: request.model,
             choices: vec![Choice {
                 index: 0,
-                message: Message {
-                    role: Role::Assistant,
-                    content: MessageContent::text(
-                        "This is a mock response for observability testing",
-                    ),
-                    tool_calls: None,
-                    tool_call_id: None,
-                },
+                message: Message::assistant("This is a mock response for observability testing"),
                 finish_reason: Some("stop".to_string()),
+                logprobs: None,
             }],
             usage: Some(Usage {
                 prompt_tokens: 50,
                 completion_tokens: 20,
                 total_tokens: 70,
+                reasoning_tokens: None,
             }),
+            metadata: None,
         })
     }
 
@@ -101,7 +97,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Perform some operations to generate metrics and traces
     println!("\n📊 Executing agent tasks...");
 
-    let tasks = vec!["Hello, how are you?", "What is 2 + 2?", "Tell me about AI"];
+    let tasks = ["Hello, how are you?", "What is 2 + 2?", "Tell me about AI"];
 
     for (i, task) in tasks.iter().enumerate() {
         println!("\nTask {}: {}", i + 1, task);
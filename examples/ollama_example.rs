@@ -1,10 +1,9 @@
 use lib_ai::{
-    providers::OllamaProvider, CompletionProvider, CompletionRequest, Message, MessageContent, Role,
+    providers::OllamaProvider, CompletionProvider, CompletionRequest, Message, MessageContent,
 };
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
-
     // Create Ollama provider (connects to local instance)
     let provider = OllamaProvider::new(
         None,                       // Use default URL (http://localhost:11434)
@@ -18,41 +17,23 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     // Basic completion
     let request = CompletionRequest {
-        model: "llama2".to_string(), // You can use any model you have locally
+        model: "llama2".to_string(),
+        // You can use any model you have locally
         messages: vec![
-            Message {
-                role: Role::System,
-                content: MessageContent::text("You are a helpful AI assistant running locally."),
-                tool_calls: None,
-                tool_call_id: None,
-            },
-            Message {
-                role: Role::User,
-                content: MessageContent::text(
-                    "What are the benefits of running AI models locally?",
-                ),
-                tool_calls: None,
-                tool_call_id: None,
-            },
+            Message::system("You are a helpful AI assistant running locally."),
+            Message::user("What are the benefits of running AI models locally?"),
         ],
         temperature: Some(0.7),
         max_tokens: Some(500),
         stream: Some(false),
-        top_p: None,
-        frequency_penalty: None,
-        presence_penalty: None,
-        stop: None,
-        tools: None,
-        tool_choice: None,
-        response_format: None,
-        json_schema: None,
+        ..Default::default()
     };
 
     println!("\n📝 Sending request to local Ollama...");
 
     match provider.complete(request.clone()).await {
         Ok(response) => {
-            if let Some(text) = response.choices[0].message.content.as_text() {
+            if let Some(text) = response.text() {
                 println!("\n📖 Response:");
                 println!("{}", text);
             }
@@ -62,10 +43,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                 println!("\n📊 Token Usage:");
                 println!("  Prompt tokens: {}", usage.prompt_tokens);
                 println!("  Completion tokens: {}", usage.completion_tokens);
-                println!(
-                    "  Total tokens: {}",
-                    usage.total_tokens
-                );
+                println!("  Total tokens: {}", usage.total_tokens);
             }
         }
         Err(e) => {
@@ -90,7 +68,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     use futures::StreamExt;
     match provider.complete_stream(stream_request).await {
         Ok(mut stream) => {
-            print!("\n");
+            println!();
             while let Some(chunk) = stream.next().await {
                 match chunk {
                     Ok(chunk) => {
@@ -1,7 +1,6 @@
 use dotenv::dotenv;
 use lib_ai::{
     providers::ReplicateProvider, CompletionProvider, CompletionRequest, Message, MessageContent,
-    Role,
 };
 
 #[tokio::main]
@@ -27,32 +26,13 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let request = CompletionRequest {
         model: "meta/llama-2-70b-chat".to_string(),
         messages: vec![
-            Message {
-                role: Role::System,
-                content: MessageContent::text("You are a helpful AI assistant."),
-                tool_calls: None,
-                tool_call_id: None,
-            },
-            Message {
-                role: Role::User,
-                content: MessageContent::text(
-                    "What is Replicate and how does it help with AI deployment?",
-                ),
-                tool_calls: None,
-                tool_call_id: None,
-            },
+            Message::system("You are a helpful AI assistant."),
+            Message::user("What is Replicate and how does it help with AI deployment?"),
         ],
         temperature: Some(0.7),
         max_tokens: Some(300),
         stream: Some(false),
-        top_p: None,
-        frequency_penalty: None,
-        presence_penalty: None,
-        stop: None,
-        tools: None,
-        tool_choice: None,
-        response_format: None,
-        json_schema: None,
+        ..Default::default()
     };
 
     println!("📝 Sending request to Replicate (Llama 2 70B)...");
@@ -60,7 +40,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     match provider.complete(request.clone()).await {
         Ok(response) => {
-            if let Some(text) = response.choices[0].message.content.as_text() {
+            if let Some(text) = response.text() {
                 println!("📖 Response:");
                 println!("{}", text);
             }
@@ -94,7 +74,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     match provider.complete(small_request).await {
         Ok(response) => {
-            if let Some(text) = response.choices[0].message.content.as_text() {
+            if let Some(text) = response.text() {
                 println!("\n📖 Haiku:");
                 println!("{}", text);
             }
@@ -1,8 +1,7 @@
 use lib_ai::{
     providers::OpenAIProvider,
     BackoffStrategy, CircuitBreakerConfig, CompletionProvider, CompletionRequest, JitterStrategy,
-    Message, MessageContent, ResilientProvider, ResilientProviderBuilder, RetryConfig,
-    RetryCondition, Role,
+    Message, ResilientProvider, ResilientProviderBuilder, RetryConfig, RetryCondition,
 };
 use std::env;
 use std::sync::Arc;
@@ -29,7 +28,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let request = create_test_request();
     match resilient.complete(request.clone()).await {
         Ok(response) => {
-            if let Some(text) = response.choices[0].message.content.as_text() {
+            if let Some(text) = response.text() {
                 println!("Response: {}", text);
             }
         }
@@ -72,7 +71,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Test with a request
     match builder_resilient.complete(request.clone()).await {
         Ok(response) => {
-            if let Some(text) = response.choices[0].message.content.as_text() {
+            if let Some(text) = response.text() {
                 println!("Response: {}", text.chars().take(100).collect::<String>());
             }
         }
@@ -129,7 +128,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         }
     };
 
-    if let Some(text) = result.choices[0].message.content.as_text() {
+    if let Some(text) = result.text() {
         println!(
             "Final response: {}",
             text.chars().take(100).collect::<String>()
@@ -144,22 +143,10 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 fn create_test_request() -> CompletionRequest {
     CompletionRequest {
         model: "gpt-3.5-turbo".to_string(),
-        messages: vec![Message {
-            role: Role::User,
-            content: MessageContent::text("Say 'Hello, resilient world!' in a creative way"),
-            tool_calls: None,
-            tool_call_id: None,
-        }],
+        messages: vec![Message::user("Say 'Hello, resilient world!' in a creative way")],
         temperature: Some(0.7),
         max_tokens: Some(50),
         stream: Some(false),
-        top_p: None,
-        frequency_penalty: None,
-        presence_penalty: None,
-        stop: None,
-        tools: None,
-        tool_choice: None,
-        response_format: None,
-        json_schema: None,
+        ..Default::default()
     }
 }
@@ -1,8 +1,7 @@
 use lib_ai::{
-    providers::*, CompletionProvider, CompletionRequest, Message, MessageContent, ResponseFormat,
-    ResponseFormatType, Role,
+    providers::*, CompletionProvider, CompletionRequest, Message, ResponseFormat,
+    ResponseFormatType,
 };
-use tokio;
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -14,21 +13,11 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let request = CompletionRequest {
         model: provider.default_model().to_string(),
         messages: vec![
-            Message {
-                role: Role::System,
-                content: MessageContent::text("You are a helpful assistant that outputs JSON."),
-                tool_calls: None,
-                tool_call_id: None,
-            },
-            Message {
-                role: Role::User,
-                content: MessageContent::text(
+            Message::system("You are a helpful assistant that outputs JSON."),
+            Message::user(
                     "Extract the following information from this text and return as JSON: \
                     'John Doe is 30 years old and lives in New York. He works as a software engineer.'"
                 ),
-                tool_calls: None,
-                tool_call_id: None,
-            },
         ],
         temperature: Some(0.7),
         max_tokens: Some(150),
@@ -36,13 +25,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         response_format: Some(ResponseFormat {
             r#type: ResponseFormatType::JsonObject,
         }),
-        tools: None,
-        tool_choice: None,
-        top_p: None,
-        frequency_penalty: None,
-        presence_penalty: None,
-        stop: None,
-        json_schema: None,
+        ..Default::default()
     };
 
     let response = provider.complete(request).await?;
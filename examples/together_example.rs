@@ -1,7 +1,6 @@
 use dotenv::dotenv;
 use lib_ai::{
     providers::TogetherProvider, CompletionProvider, CompletionRequest, Message, MessageContent,
-    Role,
 };
 
 #[tokio::main]
@@ -33,36 +32,19 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let request = CompletionRequest {
         model: "togethercomputer/llama-2-7b-chat".to_string(),
         messages: vec![
-            Message {
-                role: Role::System,
-                content: MessageContent::text("You are a helpful AI assistant."),
-                tool_calls: None,
-                tool_call_id: None,
-            },
-            Message {
-                role: Role::User,
-                content: MessageContent::text("What are the advantages of open source AI models?"),
-                tool_calls: None,
-                tool_call_id: None,
-            },
+            Message::system("You are a helpful AI assistant."),
+            Message::user("What are the advantages of open source AI models?"),
         ],
         temperature: Some(0.7),
         max_tokens: Some(300),
         stream: Some(false),
-        top_p: None,
-        frequency_penalty: None,
-        presence_penalty: None,
-        stop: None,
-        tools: None,
-        tool_choice: None,
-        response_format: None,
-        json_schema: None,
+        ..Default::default()
     };
 
     println!("\n📝 Sending request to Together AI (Llama 2)...");
     let response = provider.complete(request.clone()).await?;
 
-    if let Some(text) = response.choices[0].message.content.as_text() {
+    if let Some(text) = response.text() {
         println!("\n📖 Response:");
         println!("{}", text);
     }
@@ -72,10 +54,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         println!("\n📊 Token Usage:");
         println!("  Prompt tokens: {}", usage.prompt_tokens);
         println!("  Completion tokens: {}", usage.completion_tokens);
-        println!(
-            "  Total tokens: {}",
-            usage.total_tokens
-        );
+        println!("  Total tokens: {}", usage.total_tokens);
     }
 
     // Try another model - Code Llama
@@ -88,7 +67,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     let code_response = provider.complete(code_request).await?;
 
-    if let Some(text) = code_response.choices[0].message.content.as_text() {
+    if let Some(text) = code_response.text() {
         println!("\n📖 Code Response:");
         println!("{}", text);
     }
@@ -97,31 +76,19 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     println!("\n🌊 Streaming Example with Mistral:");
     let stream_request = CompletionRequest {
         model: "mistralai/Mistral-7B-Instruct-v0.1".to_string(),
-        messages: vec![Message {
-            role: Role::User,
-            content: MessageContent::text(
-                "Explain the concept of machine learning in simple terms",
-            ),
-            tool_calls: None,
-            tool_call_id: None,
-        }],
+        messages: vec![Message::user(
+            "Explain the concept of machine learning in simple terms",
+        )],
         temperature: Some(0.7),
         max_tokens: Some(200),
         stream: Some(true),
-        top_p: None,
-        frequency_penalty: None,
-        presence_penalty: None,
-        stop: None,
-        tools: None,
-        tool_choice: None,
-        response_format: None,
-        json_schema: None,
+        ..Default::default()
     };
 
     use futures::StreamExt;
     let mut stream = provider.complete_stream(stream_request).await?;
 
-    print!("\n");
+    println!();
     while let Some(chunk) = stream.next().await {
         match chunk {
             Ok(chunk) => {
@@ -1,9 +1,8 @@
 use lib_ai::{
-    providers::*, CompletionProvider, CompletionRequest, Message, MessageContent, Role, Tool,
-    ToolChoice, ToolFunction, ToolType,
+    providers::*, CompletionProvider, CompletionRequest, Message, Tool, ToolChoice, ToolFunction,
+    ToolType,
 };
 use serde_json::json;
-use tokio;
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -38,23 +37,13 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Make a request with tools
     let request = CompletionRequest {
         model: provider.default_model().to_string(),
-        messages: vec![Message {
-            role: Role::User,
-            content: MessageContent::text("What's the weather like in San Francisco?"),
-            tool_calls: None,
-            tool_call_id: None,
-        }],
+        messages: vec![Message::user("What's the weather like in San Francisco?")],
         temperature: Some(0.7),
         max_tokens: Some(150),
         stream: Some(false),
         tools: Some(vec![weather_tool]),
-        tool_choice: Some(ToolChoice::String("auto".to_string())),
-        top_p: None,
-        frequency_penalty: None,
-        presence_penalty: None,
-        stop: None,
-        response_format: None,
-        json_schema: None,
+        tool_choice: Some(ToolChoice::auto()),
+        ..Default::default()
     };
 
     let response = provider.complete(request).await?;
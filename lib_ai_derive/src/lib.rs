@@ -97,7 +97,7 @@ pub fn derive_ai_tool(input: TokenStream) -> TokenStream {
                 let tool_input: #name = serde_json::from_str(arguments)?;
                 match tool_input.execute().await {
                     Ok(result) => Ok(lib_ai::agent::ToolResult::Success(result)),
-                    Err(e) => Ok(lib_ai::agent::ToolResult::Error(e.to_string())),
+                    Err(e) => Ok(lib_ai::agent::ToolResult::error(e)),
                 }
             }
 
@@ -1,12 +1,19 @@
-use std::sync::Arc;
+use futures::stream::{FuturesOrdered, TryStreamExt};
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
 use std::time::Instant;
 use thiserror::Error;
+use tokio::sync::Semaphore;
 
-use super::{Context, Memory, ToolRegistry, ToolResult};
+use super::{Context, Memory, ToolMeta, ToolRegistry, ToolResult};
 use crate::{
-    observability::{AgentTracer, CostTracker, MetricsCollector, TelemetryExporter},
-    CompletionProvider, CompletionRequest, CompletionResponse, ResponseFormat, ToolCall,
-    ToolChoice,
+    moderation::Moderator,
+    observability::{
+        metrics::TokenUsage, AgentTracer, CostTracker, MetricsCollector, TelemetryExporter,
+    },
+    providers::canonical_json,
+    CompletionProvider, CompletionRequest, CompletionResponse, FinishReason, Message,
+    ResponseFormat, RetryConfig, StreamChunk, ToolCall, ToolCallAccumulator, ToolChoice, Usage,
 };
 
 #[derive(Error, Debug)]
@@ -25,6 +32,55 @@ pub enum AgentError {
 
     #[error("Invalid configuration: {0}")]
     ConfigError(String),
+
+    #[error("Schema validation error: {0}")]
+    ValidationError(String),
+}
+
+impl AgentError {
+    /// The provider error behind this one, if it came from a provider
+    pub fn source_ai_error(&self) -> Option<&crate::AiError> {
+        match self {
+            AgentError::ProviderError(error) => Some(error),
+            _ => None,
+        }
+    }
+
+    /// Convert into an `AiError`, so callers can handle every failure
+    /// through one type
+    pub fn into_ai_error(self) -> crate::AiError {
+        self.into()
+    }
+}
+
+impl From<AgentError> for crate::AiError {
+    fn from(error: AgentError) -> Self {
+        match error {
+            AgentError::ProviderError(error) => error,
+            AgentError::ToolError(message) => crate::AiError::ToolExecutionError {
+                tool_name: "unknown".to_string(),
+                message,
+                retryable: false,
+            },
+            AgentError::ContextError(message) => crate::AiError::InternalError {
+                message,
+                component: Some("context".to_string()),
+            },
+            AgentError::MemoryError(message) => crate::AiError::MemoryError {
+                operation: "agent memory".to_string(),
+                message,
+            },
+            AgentError::ConfigError(message) => crate::AiError::ConfigurationError {
+                field: "agent".to_string(),
+                message,
+                suggestion: None,
+            },
+            AgentError::ValidationError(message) => crate::AiError::MalformedResponse {
+                message,
+                raw_response: None,
+            },
+        }
+    }
 }
 
 pub type Result<T> = std::result::Result<T, AgentError>;
@@ -36,9 +92,49 @@ pub struct AgentConfig {
     pub temperature: Option<f32>,
     pub max_tokens: Option<u32>,
     pub top_p: Option<f32>,
+    pub frequency_penalty: Option<f32>,
+    pub presence_penalty: Option<f32>,
+    pub stop: Option<Vec<String>>,
     pub response_format: Option<ResponseFormat>,
     pub max_iterations: usize,
+    /// Most memories to retrieve into the context per turn
+    pub max_memories: usize,
+    /// Most characters of retrieved memory text per turn
+    pub max_memory_chars: usize,
     pub stream: bool,
+    /// Remove this agent's entry from the metrics collector when it is dropped
+    pub remove_metrics_on_drop: bool,
+    /// W3C `traceparent` of an upstream trace that agent spans should join
+    pub traceparent: Option<String>,
+    /// Retry tool calls that fail with an error the tool marks as retryable,
+    /// independently of provider retries
+    pub tool_retry: Option<RetryConfig>,
+    /// Most tool calls from one reply to run at once; 1 (the default) runs
+    /// them one after another
+    pub max_parallel_tools: usize,
+    /// Run identical tool calls (same function and arguments) in one reply
+    /// once, giving every call the same result
+    pub dedup_tool_calls: bool,
+    /// Record each iteration of `execute` in an [`ExecutionTrace`], read
+    /// back with [`Agent::last_trace`]
+    pub record_trace: bool,
+    /// Tool choice for the first request of a turn, e.g. `required` or a
+    /// specific function; later requests in the turn use `auto` so the
+    /// model can answer. `None` uses `auto` throughout.
+    pub tool_choice: Option<ToolChoice>,
+    /// Tags added to every request's `metadata`, so cost can be broken
+    /// down by them in the cost tracker
+    pub metadata: HashMap<String, String>,
+    /// Answer a call to a tool that isn't registered (for example one
+    /// removed from a dynamic registry) with an error result the model can
+    /// adapt to, instead of failing the run
+    pub tolerate_missing_tools: bool,
+    /// Cancel a streamed reply with [`AiError::QuotaExceeded`] once its
+    /// estimated output passes this many tokens, without waiting for the
+    /// provider to report usage
+    ///
+    /// [`AiError::QuotaExceeded`]: crate::AiError::QuotaExceeded
+    pub stream_output_token_budget: Option<u32>,
 }
 
 impl Default for AgentConfig {
@@ -48,13 +144,52 @@ impl Default for AgentConfig {
             temperature: None,
             max_tokens: None,
             top_p: None,
+            frequency_penalty: None,
+            presence_penalty: None,
+            stop: None,
             response_format: None,
             max_iterations: 10,
+            max_memories: 5,
+            max_memory_chars: 4000,
             stream: false,
+            remove_metrics_on_drop: false,
+            traceparent: None,
+            tool_retry: None,
+            max_parallel_tools: 1,
+            dedup_tool_calls: false,
+            record_trace: false,
+            tool_choice: None,
+            metadata: HashMap::new(),
+            tolerate_missing_tools: false,
+            stream_output_token_budget: None,
         }
     }
 }
 
+/// Iterations of one `execute` call, recorded when
+/// [`AgentConfig::record_trace`] is set
+#[derive(Debug, Clone, Default)]
+pub struct ExecutionTrace {
+    pub steps: Vec<Step>,
+}
+
+/// One model request, its reply and the tool calls it led to
+#[derive(Debug, Clone)]
+pub struct Step {
+    /// The request sent to the provider for this iteration
+    pub request: CompletionRequest,
+    /// The assistant message as returned by the provider
+    pub assistant: Message,
+    /// Tool calls requested in this reply, empty for the final answer
+    pub tool_calls: Vec<ToolCall>,
+    /// Result of each tool call, in the same order
+    pub tool_results: Vec<serde_json::Value>,
+    /// Duration and retries of each tool call, in the same order
+    pub tool_meta: Vec<ToolMeta>,
+    /// Tokens used by this request, if the provider reported them
+    pub usage: Option<Usage>,
+}
+
 /// An AI agent that can complete tasks using tools and memory
 pub struct Agent {
     provider: Arc<dyn CompletionProvider>,
@@ -69,6 +204,10 @@ pub struct Agent {
     tracer: Option<Arc<AgentTracer>>,
     cost_tracker: Option<Arc<std::sync::RwLock<CostTracker>>>,
     telemetry_exporter: Option<Arc<TelemetryExporter>>,
+    moderator: Option<Arc<dyn Moderator>>,
+    prompt_template: Option<String>,
+    template_vars: HashMap<String, String>,
+    last_trace: Option<ExecutionTrace>,
 }
 
 impl Agent {
@@ -93,9 +232,39 @@ impl Agent {
             tracer: None,
             cost_tracker: None,
             telemetry_exporter: None,
+            moderator: None,
+            prompt_template: None,
+            template_vars: HashMap::new(),
+            last_trace: None,
         }
     }
 
+    /// Screen user input, and the final reply of `execute`, with
+    /// `moderator`. Flagged text fails with `AiError::ContentFiltered`.
+    pub fn with_moderator(mut self, moderator: Arc<dyn Moderator>) -> Self {
+        self.moderator = Some(moderator);
+        self
+    }
+
+    /// Render `template` as the system prompt of every request, replacing
+    /// `{name}` placeholders with `vars`. Unknown placeholders are left as is.
+    /// When the context starts with a system message (such as the builder's
+    /// `prompt`), the rendered template is merged ahead of it.
+    pub fn with_prompt_template(
+        mut self,
+        template: impl Into<String>,
+        vars: HashMap<String, String>,
+    ) -> Self {
+        self.prompt_template = Some(template.into());
+        self.template_vars = vars;
+        self
+    }
+
+    /// Set a prompt template variable, used from the next request on
+    pub fn set_template_var(&mut self, name: impl Into<String>, value: impl Into<String>) {
+        self.template_vars.insert(name.into(), value.into());
+    }
+
     /// Set observability components
     pub fn with_observability(
         mut self,
@@ -104,6 +273,9 @@ impl Agent {
         cost_tracker: Option<Arc<std::sync::RwLock<CostTracker>>>,
         telemetry_exporter: Option<Arc<TelemetryExporter>>,
     ) -> Self {
+        if let Some(metrics) = &metrics_collector {
+            metrics.create_agent_metrics(self.agent_id.clone());
+        }
         self.metrics_collector = metrics_collector;
         self.tracer = tracer;
         self.cost_tracker = cost_tracker;
@@ -119,26 +291,36 @@ impl Agent {
     /// Execute a task with the given input
     pub async fn execute(&mut self, input: &str) -> Result<String> {
         let start_time = Instant::now();
-        let mut total_tokens = crate::observability::metrics::TokenUsage::new();
+        let mut total_tokens = TokenUsage::new();
         let mut total_cost = 0.0;
 
         // Start trace span if tracer is available
-        let _trace_span = self
-            .tracer
-            .as_ref()
-            .and_then(|tracer| tracer.start_trace(format!("agent_execute_{}", self.agent_id)));
+        let _trace_span = self.tracer.as_ref().and_then(|tracer| {
+            let operation_name = format!("agent_execute_{}", self.agent_id);
+            match &self.config.traceparent {
+                Some(traceparent) => tracer.start_trace_with_parent(operation_name, traceparent),
+                None => tracer.start_trace(operation_name),
+            }
+        });
 
-        // Add user input to context
-        self.context.add_user_message(input);
+        self.last_trace = self.config.record_trace.then(ExecutionTrace::default);
 
-        // Retrieve relevant memory if available
+        self.moderate(input, "Input").await?;
+
+        // Retrieve relevant memory if available, as one block ahead of the
+        // user turn
         if let Some(memory) = &self.memory {
-            let memories = memory.retrieve(input, 5).await?;
-            for mem in memories {
-                self.context.add_memory(mem);
-            }
+            let memories = memory.retrieve(input, self.config.max_memories).await?;
+            self.context.set_memory_block(
+                &memories,
+                self.config.max_memories,
+                self.config.max_memory_chars,
+            );
         }
 
+        // Add user input to context
+        self.context.add_user_message(input);
+
         // Main execution loop
         let mut iterations = 0;
         let mut final_response = String::new();
@@ -152,44 +334,61 @@ impl Agent {
             }
 
             // Build the completion request
-            let request = self.build_request()?;
+            let request = match self.build_request(iterations == 0).await {
+                Ok(request) => request,
+                Err(e) => break Err(e),
+            };
             let model = request.model.clone();
+            let tags = request.metadata.clone();
+            let estimated_prompt_tokens = request.estimate_tokens() as u32;
+            let traced_request = self.last_trace.is_some().then(|| request.clone());
 
             // Get completion from provider
-            let response = self.provider.complete(request).await?;
-
-            // Track tokens and costs
-            if let Some(usage) = &response.usage {
-                total_tokens.input_tokens += usage.prompt_tokens as u64;
-                total_tokens.output_tokens += usage.completion_tokens as u64;
-
-                // Calculate cost if cost tracker is available
-                if let Some(cost_tracker) = &self.cost_tracker {
-                    if let Ok(mut tracker) = cost_tracker.write() {
-                        let pricing = tracker.get_pricing(self.provider.name(), &model);
-                        let request_cost = pricing.calculate_cost(
-                            usage.prompt_tokens as u64,
-                            usage.completion_tokens as u64,
-                            0, // cache_read_tokens
-                            0, // cache_write_tokens
-                        );
-                        total_cost += request_cost;
-
-                        tracker.record_usage(
-                            self.provider.name(),
-                            &model,
-                            usage.prompt_tokens as u64,
-                            usage.completion_tokens as u64,
-                            0,
-                            0,
-                            &pricing,
-                        );
-                    }
+            let response = match self.provider.complete(request).await {
+                Ok(response) => response,
+                Err(e) => break Err(e.into()),
+            };
+
+            // Track tokens and costs, estimating them for providers that
+            // report no usage (such as Replicate)
+            let usage = match &response.usage {
+                Some(usage) => usage.clone(),
+                None => {
+                    let output_chars = response.choices.first().map_or(0, |choice| {
+                        let message = &choice.message;
+                        message.content.as_text_lossy().len()
+                            + message
+                                .tool_calls
+                                .iter()
+                                .flatten()
+                                .map(|call| {
+                                    call.function.name.len() + call.function.arguments.len()
+                                })
+                                .sum::<usize>()
+                    });
+                    let usage = estimate_usage(estimated_prompt_tokens, output_chars);
+                    total_tokens.estimated_tokens += usage.total_tokens as u64;
+                    usage
                 }
-            }
+            };
+            total_tokens.input_tokens += usage.prompt_tokens as u64;
+            total_tokens.output_tokens += usage.completion_tokens as u64;
+            total_tokens.reasoning_tokens += usage.reasoning_tokens.unwrap_or(0) as u64;
+
+            total_cost += record_cost(
+                self.cost_tracker.as_ref(),
+                self.provider.name(),
+                &model,
+                &usage,
+                &tags,
+            );
 
             // Process the response
-            let (should_continue, response_text) = self.process_response(response).await?;
+            let (should_continue, response_text) =
+                match self.process_response(response, traced_request).await {
+                    Ok(processed) => processed,
+                    Err(e) => break Err(e),
+                };
 
             if !should_continue {
                 final_response = response_text;
@@ -223,6 +422,12 @@ impl Agent {
         // Handle execution result
         execution_result?;
 
+        // A flagged reply is neither returned nor kept in the context
+        if let Err(e) = self.moderate(&final_response, "Response").await {
+            self.context.messages_mut().pop();
+            return Err(e);
+        }
+
         // Store interaction in memory if available
         if let Some(memory) = &mut self.memory {
             memory.store(input, &final_response).await?;
@@ -231,38 +436,143 @@ impl Agent {
         Ok(final_response)
     }
 
-    /// Execute with streaming response
+    /// Execute with streaming response, yielding only the text.
+    ///
+    /// Token usage and cost are recorded once the stream has been fully
+    /// consumed, from the provider's final usage chunk or, when the provider
+    /// does not report one, from a rough estimate of the streamed text. Use
+    /// [`execute_stream_items`](Self::execute_stream_items) to also receive
+    /// tool calls, the finish reason and usage.
     pub async fn execute_stream(
         &mut self,
         input: &str,
     ) -> Result<impl futures::Stream<Item = Result<String>>> {
         use futures::stream::StreamExt;
 
+        let items = self.execute_stream_items(input).await?;
+        Ok(Box::pin(items.filter_map(|item| async move {
+            match item {
+                Ok(StreamItem::Token(text)) => Some(Ok(text)),
+                Ok(_) => None,
+                Err(e) => Some(Err(e)),
+            }
+        })))
+    }
+
+    /// Execute with streaming response, yielding text as it arrives, each
+    /// tool call once its arguments are complete, and a final
+    /// [`StreamItem::Done`] with the finish reason and usage.
+    ///
+    /// With a moderator set, the assembled reply is checked once the
+    /// provider's stream ends; a flagged reply ends the stream with
+    /// `AiError::ContentFiltered` instead of `Done`.
+    ///
+    /// Usage and cost are recorded as for
+    /// [`execute_stream`](Self::execute_stream).
+    pub async fn execute_stream_items(
+        &mut self,
+        input: &str,
+    ) -> Result<impl futures::Stream<Item = Result<StreamItem>>> {
+        use futures::stream::StreamExt;
+
+        let start_time = Instant::now();
+
+        self.moderate(input, "Input").await?;
+
         // Add user input to context
         self.context.add_user_message(input);
 
         // Build the completion request
-        let mut request = self.build_request()?;
+        let mut request = self.build_request(true).await?;
         request.stream = Some(true);
+        let estimated_prompt_tokens = request.estimate_tokens() as u32;
+
+        let accounting = StreamAccounting {
+            agent_id: self.agent_id.clone(),
+            provider: self.provider.name(),
+            model: request.model.clone(),
+            tags: request.metadata.clone(),
+            metrics_collector: self.metrics_collector.clone(),
+            cost_tracker: self.cost_tracker.clone(),
+            start_time,
+            estimated_prompt_tokens,
+            output_chars: 0,
+            output_token_budget: self.config.stream_output_token_budget,
+            usage: None,
+            finish_reason: None,
+            tool_calls: ToolCallAccumulator::new(),
+            success: true,
+            moderator: self.moderator.clone(),
+            text: String::new(),
+        };
 
         // Get streaming completion from provider
         let stream = self.provider.complete_stream(request).await?;
 
-        // Transform the stream
-        let transformed_stream = stream.map(|chunk_result| match chunk_result {
-            Ok(chunk) => {
-                let mut content = String::new();
-                for choice in chunk.choices {
-                    if let Some(delta_content) = choice.delta.content {
-                        content.push_str(&delta_content);
+        // Transform the stream, recording usage after the last chunk
+        let transformed_stream =
+            futures::stream::unfold(Some((stream, accounting)), |state| async move {
+                let (mut stream, mut accounting) = state?;
+                match stream.next().await {
+                    Some(Ok(chunk)) => {
+                        let mut items: Vec<_> =
+                            accounting.observe(chunk).into_iter().map(Ok).collect();
+                        if let Some(error) = accounting.budget_exceeded() {
+                            // Dropping the provider stream cancels the request;
+                            // what was generated so far is still accounted for
+                            accounting.success = false;
+                            items.push(Err(AgentError::ProviderError(error)));
+                            accounting.finish();
+                            return Some((items, None));
+                        }
+                        Some((items, Some((stream, accounting))))
+                    }
+                    Some(Err(e)) => {
+                        accounting.success = false;
+                        Some((
+                            vec![Err(AgentError::ProviderError(e))],
+                            Some((stream, accounting)),
+                        ))
+                    }
+                    None => {
+                        if let Err(e) = accounting.moderate_output().await {
+                            accounting.success = false;
+                            accounting.finish();
+                            return Some((vec![Err(e)], None));
+                        }
+                        Some((vec![Ok(accounting.finish())], None))
                     }
                 }
-                Ok(content)
+            })
+            .flat_map(futures::stream::iter);
+
+        Ok(Box::pin(transformed_stream))
+    }
+
+    /// Execute with streaming, calling `on_token` with each piece of text as
+    /// it arrives, and return the assembled response. Unlike
+    /// [`execute_stream`](Self::execute_stream), the response is also added
+    /// to the context.
+    pub async fn execute_stream_with(
+        &mut self,
+        input: &str,
+        mut on_token: impl FnMut(&str),
+    ) -> Result<String> {
+        use futures::stream::StreamExt;
+
+        let mut stream = self.execute_stream(input).await?;
+        let mut response = String::new();
+        while let Some(token) = stream.next().await {
+            let token = token?;
+            if !token.is_empty() {
+                on_token(&token);
+                response.push_str(&token);
             }
-            Err(e) => Err(AgentError::ProviderError(e)),
-        });
+        }
+        drop(stream);
 
-        Ok(transformed_stream)
+        self.context.add_assistant_message(&response);
+        Ok(response)
     }
 
     /// Chat with the agent (maintains conversation context)
@@ -270,6 +580,31 @@ impl Agent {
         self.execute(message).await
     }
 
+    /// Work on `goal` autonomously: execute it, then feed each output back
+    /// as the next input until `stop` holds for an output, which is
+    /// returned. Each step is a full [`execute`](Self::execute), tool calls
+    /// included. Fails once `max_steps` steps have run without `stop`
+    /// holding; the outputs so far remain in the context.
+    pub async fn run_until(
+        &mut self,
+        goal: &str,
+        stop: impl Fn(&str) -> bool,
+        max_steps: usize,
+    ) -> Result<String> {
+        let mut input = goal.to_string();
+        for _ in 0..max_steps {
+            let output = self.execute(&input).await?;
+            if stop(&output) {
+                return Ok(output);
+            }
+            input = output;
+        }
+        Err(AgentError::ConfigError(format!(
+            "Maximum steps ({}) reached before the stop condition held",
+            max_steps
+        )))
+    }
+
     /// Clear the conversation context
     pub fn clear_context(&mut self) {
         self.context.clear();
@@ -280,17 +615,33 @@ impl Agent {
         &self.context
     }
 
+    /// Steps of the most recent `execute` call, kept even if it failed.
+    /// `None` unless [`AgentConfig::record_trace`] is set.
+    pub fn last_trace(&self) -> Option<&ExecutionTrace> {
+        self.last_trace.as_ref()
+    }
+
     /// Get the current configuration
     pub fn get_config(&self) -> &AgentConfig {
         &self.config
     }
 
+    /// Run `text` through the moderator, if one is set
+    async fn moderate(&self, text: &str, what: &str) -> Result<()> {
+        if let Some(moderator) = &self.moderator {
+            moderator.moderate(text).await?.check(what)?;
+        }
+        Ok(())
+    }
+
     /// Update the agent's configuration
     pub fn update_config(&mut self, config: AgentConfig) {
         self.config = config;
     }
 
-    fn build_request(&self) -> Result<CompletionRequest> {
+    /// Request for the current context; `first_turn` applies the configured
+    /// `tool_choice`
+    async fn build_request(&self, first_turn: bool) -> Result<CompletionRequest> {
         let messages = self.context.to_messages();
 
         let model = self
@@ -299,14 +650,16 @@ impl Agent {
             .clone()
             .unwrap_or_else(|| self.provider.default_model().to_string());
 
-        let tools = self.tools.as_ref().map(|registry| registry.to_tools());
-        let tool_choice = if tools.is_some() {
-            Some(ToolChoice::String("auto".to_string()))
-        } else {
-            None
+        let tools = match &self.tools {
+            Some(registry) => Some(registry.to_tools_async().await),
+            None => None,
         };
+        let tool_choice = tools.as_ref().map(|_| match &self.config.tool_choice {
+            Some(choice) if first_turn => choice.clone(),
+            _ => ToolChoice::auto(),
+        });
 
-        Ok(CompletionRequest {
+        let mut request = CompletionRequest {
             model,
             messages,
             temperature: self.config.temperature,
@@ -316,14 +669,29 @@ impl Agent {
             tools,
             tool_choice,
             response_format: self.config.response_format.clone(),
-            frequency_penalty: None,
-            presence_penalty: None,
-            stop: None,
-            json_schema: None,
-        })
+            frequency_penalty: self.config.frequency_penalty,
+            presence_penalty: self.config.presence_penalty,
+            stop: self.config.stop.clone(),
+            metadata: self.config.metadata.clone(),
+            ..Default::default()
+        };
+        // Merged into the context's leading system message, if any, so a
+        // template and a plain prompt still make one system message
+        if let Some(template) = &self.prompt_template {
+            request.system = Some(render_template(template, &self.template_vars));
+            request.normalize_system();
+        }
+
+        Ok(request)
     }
 
-    async fn process_response(&mut self, response: CompletionResponse) -> Result<(bool, String)> {
+    /// Handle one reply; `request` is the request that produced it, kept
+    /// for the trace when one is being recorded
+    async fn process_response(
+        &mut self,
+        response: CompletionResponse,
+        request: Option<CompletionRequest>,
+    ) -> Result<(bool, String)> {
         if response.choices.is_empty() {
             return Err(AgentError::ProviderError(crate::AiError::InvalidRequest {
                 message: "No choices in response".to_string(),
@@ -332,34 +700,90 @@ impl Agent {
             }));
         }
 
+        let usage = response.usage;
         let choice = &response.choices[0];
         let message = &choice.message;
 
+        if let Some(refusal) = &message.refusal {
+            return Err(AgentError::ProviderError(crate::AiError::ContentFiltered {
+                reason: refusal.clone(),
+                category: Some("refusal".to_string()),
+            }));
+        }
+
         // Add assistant message to context
         self.context.add_message(message.clone());
 
         // Check if there are tool calls
         if let Some(tool_calls) = &message.tool_calls {
-            // Execute tools
-            for tool_call in tool_calls {
-                let result = self.execute_tool(tool_call).await?;
+            // Each call points at the call whose result it gets
+            let (unique, result_index) = if self.config.dedup_tool_calls {
+                dedup_tool_calls(tool_calls)
+            } else {
+                (tool_calls.iter().collect(), (0..tool_calls.len()).collect())
+            };
+
+            // Execute tools, at most `max_parallel_tools` at a time; results
+            // keep the order of the calls
+            let limit = Semaphore::new(self.config.max_parallel_tools.max(1));
+            let agent = &*self;
+            let results: Vec<(serde_json::Value, ToolMeta)> = unique
+                .into_iter()
+                .map(|tool_call| {
+                    let limit = &limit;
+                    async move {
+                        let _permit = limit.acquire().await.map_err(|e| {
+                            AgentError::ToolError(format!("Tool limit closed: {}", e))
+                        })?;
+                        agent.execute_tool(tool_call).await
+                    }
+                })
+                .collect::<FuturesOrdered<_>>()
+                .try_collect()
+                .await?;
 
-                // Add tool result to context
-                self.context.add_tool_result(&tool_call.id, &result);
+            let (results, tool_meta): (Vec<serde_json::Value>, Vec<ToolMeta>) =
+                result_index.into_iter().map(|i| results[i].clone()).unzip();
+
+            // Add tool results to context
+            for (tool_call, result) in tool_calls.iter().zip(&results) {
+                self.context
+                    .add_tool_result_value(&tool_call.id, result.clone());
+            }
+
+            if let (Some(trace), Some(request)) = (&mut self.last_trace, request) {
+                trace.steps.push(Step {
+                    request,
+                    assistant: message.clone(),
+                    tool_calls: tool_calls.clone(),
+                    tool_results: results,
+                    tool_meta,
+                    usage,
+                });
             }
 
             // Continue conversation after tool execution
             Ok((true, String::new()))
         } else {
-            // Extract text content and return
-            let text = message.content.as_text().ok_or_else(|| {
-                AgentError::ContextError("No text content in response".to_string())
-            })?;
-            Ok((false, text.to_string()))
+            if let (Some(trace), Some(request)) = (&mut self.last_trace, request) {
+                trace.steps.push(Step {
+                    request,
+                    assistant: message.clone(),
+                    tool_calls: Vec::new(),
+                    tool_results: Vec::new(),
+                    tool_meta: Vec::new(),
+                    usage,
+                });
+            }
+
+            // Extract text content, including the text parts of multipart replies
+            Ok((false, message.content.as_text_lossy().into_owned()))
         }
     }
 
-    async fn execute_tool(&self, tool_call: &ToolCall) -> Result<String> {
+    /// Run a tool call, returning its value with how long it took and how
+    /// often it was retried
+    async fn execute_tool(&self, tool_call: &ToolCall) -> Result<(serde_json::Value, ToolMeta)> {
         let start_time = Instant::now();
         let tool_name = &tool_call.function.name;
 
@@ -369,48 +793,668 @@ impl Agent {
             .as_ref()
             .and_then(|tracer| tracer.start_trace(format!("tool_execute_{}", tool_name)));
 
-        let tools = self
+        let Some(executor) = self
             .tools
             .as_ref()
-            .ok_or_else(|| AgentError::ToolError("No tools available".to_string()))?;
+            .and_then(|tools| tools.get_executor(tool_name))
+        else {
+            if self.config.tolerate_missing_tools {
+                let error = format!("Tool '{}' is no longer available", tool_name);
+                return Ok((serde_json::json!({ "error": error }), ToolMeta::default()));
+            }
+            return Err(AgentError::ToolError(match self.tools {
+                Some(_) => format!("Tool '{}' not found", tool_name),
+                None => "No tools available".to_string(),
+            }));
+        };
 
-        let executor = tools
-            .get_executor(tool_name)
-            .ok_or_else(|| AgentError::ToolError(format!("Tool '{}' not found", tool_name)))?;
+        // Check the arguments against the tool's schema first, so the model
+        // gets every violation back and can correct the call
+        if let Ok(arguments) = serde_json::from_str(&tool_call.function.arguments) {
+            match executor
+                .definition_async()
+                .await
+                .validate_arguments(&arguments)
+            {
+                Ok(()) => {}
+                Err(e @ crate::AiError::InvalidToolParameters { .. }) => {
+                    return Ok((
+                        serde_json::json!({ "error": e.to_string() }),
+                        ToolMeta::default(),
+                    ));
+                }
+                Err(e) => return Err(AgentError::ToolError(e.to_string())),
+            }
+        }
 
-        let result = executor
-            .execute(&tool_call.function.arguments)
-            .await
-            .map_err(|e| AgentError::ToolError(e.to_string()))?;
+        let mut attempt = 0;
+        let outcome = loop {
+            attempt += 1;
+            let (message, retryable) = match executor.execute(&tool_call.function.arguments).await {
+                Ok(ToolResult::Success(value)) => break Ok(value),
+                Ok(ToolResult::Error(error)) => break Err(error),
+                Err(e) => (e.to_string(), executor.is_retryable(e.as_ref())),
+            };
+            match &self.config.tool_retry {
+                Some(retry) if retryable && attempt < retry.max_attempts => {
+                    tokio::time::sleep(crate::backoff::next_delay(attempt, retry)).await;
+                }
+                _ => break Err(message),
+            }
+        };
 
-        let duration = start_time.elapsed();
-        let success = matches!(result, ToolResult::Success(_));
+        let meta = ToolMeta {
+            duration: start_time.elapsed(),
+            retries: attempt - 1,
+        };
 
-        // Record tool metrics if metrics collector is available
+        // Record tool metrics if metrics collector is available, whatever
+        // the outcome
         if let Some(metrics) = &self.metrics_collector {
-            let error_type = if success {
-                None
-            } else {
-                Some("tool_execution_error".to_string())
-            };
-            metrics.record_tool_execution(&self.agent_id, tool_name, success, duration, error_type);
+            let error_type = outcome.is_err().then(|| "tool_execution_error".to_string());
+            metrics.record_tool_execution(
+                &self.agent_id,
+                tool_name,
+                outcome.is_ok(),
+                meta.duration,
+                error_type,
+            );
+            metrics.record_tool_retries(&self.agent_id, tool_name, meta.retries);
+        }
+
+        outcome
+            .map(|value| (value, meta))
+            .map_err(AgentError::ToolError)
+    }
+}
+
+impl Drop for Agent {
+    fn drop(&mut self) {
+        if self.config.remove_metrics_on_drop {
+            if let Some(metrics) = &self.metrics_collector {
+                metrics.remove_agent_metrics(&self.agent_id);
+            }
+        }
+    }
+}
+
+/// Collapse calls to the same function with the same arguments, comparing
+/// arguments as JSON so key order and whitespace don't matter. Returns the
+/// distinct calls and, for every call, the index of its distinct call.
+fn dedup_tool_calls(tool_calls: &[ToolCall]) -> (Vec<&ToolCall>, Vec<usize>) {
+    let mut unique: Vec<&ToolCall> = Vec::new();
+    let mut seen: HashMap<(String, String), usize> = HashMap::new();
+    let mut result_index = Vec::with_capacity(tool_calls.len());
+
+    for tool_call in tool_calls {
+        let arguments = serde_json::from_str::<serde_json::Value>(&tool_call.function.arguments)
+            .map(|value| canonical_json(&value))
+            .unwrap_or_else(|_| tool_call.function.arguments.clone());
+        let key = (tool_call.function.name.clone(), arguments);
+        let index = *seen.entry(key).or_insert_with(|| {
+            unique.push(tool_call);
+            unique.len() - 1
+        });
+        result_index.push(index);
+    }
+
+    (unique, result_index)
+}
+
+/// Replace `{name}` placeholders with their values in one pass, so values
+/// containing braces are never expanded again
+fn render_template(template: &str, vars: &HashMap<String, String>) -> String {
+    let mut rendered = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        rendered.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        match after
+            .find('}')
+            .and_then(|end| Some((vars.get(&after[..end])?, end)))
+        {
+            Some((value, end)) => {
+                rendered.push_str(value);
+                rest = &after[end + 1..];
+            }
+            None => {
+                rendered.push('{');
+                rest = after;
+            }
+        }
+    }
+    rendered.push_str(rest);
+    rendered
+}
+
+/// Price `usage` and add it to the cost tracker under `tags`, returning the
+/// request cost
+fn record_cost(
+    cost_tracker: Option<&Arc<RwLock<CostTracker>>>,
+    provider: &str,
+    model: &str,
+    usage: &Usage,
+    tags: &HashMap<String, String>,
+) -> f64 {
+    let mut request_cost = 0.0;
+    if let Some(cost_tracker) = cost_tracker {
+        if let Ok(mut tracker) = cost_tracker.write() {
+            let pricing = tracker.get_pricing(provider, model);
+            // Reasoning tokens are billed as output and are already part of
+            // completion_tokens
+            request_cost = pricing.calculate_cost(
+                usage.prompt_tokens as u64,
+                usage.completion_tokens as u64,
+                0, // cache_read_tokens
+                0, // cache_write_tokens
+            );
+
+            tracker.record_tagged_usage(
+                provider,
+                model,
+                usage.prompt_tokens as u64,
+                usage.completion_tokens as u64,
+                0,
+                0,
+                &pricing,
+                tags,
+            );
+        }
+    }
+    request_cost
+}
+
+/// An item of a streamed agent response
+#[derive(Debug, Clone)]
+pub enum StreamItem {
+    /// A piece of the reply text
+    Token(String),
+    /// A tool call requested by the model, once its arguments are complete
+    ToolCall(ToolCall),
+    /// The stream has ended
+    Done {
+        finish_reason: Option<FinishReason>,
+        /// Usage as reported by the provider, `None` if it sent none
+        usage: Option<Usage>,
+    },
+}
+
+/// Usage for a reply the provider reported none for, using the same rough
+/// four-characters-per-token estimate as `Context`
+fn estimate_usage(prompt_tokens: u32, output_chars: usize) -> Usage {
+    let completion_tokens = (output_chars / 4) as u32;
+    Usage {
+        prompt_tokens,
+        completion_tokens,
+        total_tokens: prompt_tokens + completion_tokens,
+        reasoning_tokens: None,
+    }
+}
+
+/// Usage gathered while a streamed response is consumed
+struct StreamAccounting {
+    agent_id: String,
+    provider: &'static str,
+    model: String,
+    tags: HashMap<String, String>,
+    metrics_collector: Option<Arc<MetricsCollector>>,
+    cost_tracker: Option<Arc<RwLock<CostTracker>>>,
+    start_time: Instant,
+    estimated_prompt_tokens: u32,
+    output_chars: usize,
+    output_token_budget: Option<u32>,
+    usage: Option<Usage>,
+    finish_reason: Option<String>,
+    tool_calls: ToolCallAccumulator,
+    success: bool,
+    moderator: Option<Arc<dyn Moderator>>,
+    /// The reply so far, kept only when there is a moderator to check it
+    text: String,
+}
+
+impl StreamAccounting {
+    /// Note the chunk's usage and finish reason and return its text and
+    /// any tool calls it completed
+    fn observe(&mut self, chunk: StreamChunk) -> Vec<StreamItem> {
+        if chunk.usage.is_some() {
+            self.usage = chunk.usage.clone();
+        }
+        let tool_calls = self.tool_calls.push_chunk(&chunk);
+
+        let mut content = String::new();
+        for choice in chunk.choices {
+            if let Some(delta_content) = choice.delta.content {
+                content.push_str(&delta_content);
+            }
+            if choice.finish_reason.is_some() {
+                self.finish_reason = choice.finish_reason;
+            }
+        }
+        self.output_chars += content.len();
+        if self.moderator.is_some() {
+            self.text.push_str(&content);
+        }
+
+        let mut items = Vec::new();
+        if !content.is_empty() {
+            items.push(StreamItem::Token(content));
+        }
+        items.extend(tool_calls.into_iter().map(StreamItem::ToolCall));
+        items
+    }
+
+    /// The error to end the stream with once the output so far, estimated
+    /// as for providers that report no usage, is over budget
+    fn budget_exceeded(&self) -> Option<crate::AiError> {
+        let budget = self.output_token_budget?;
+        let output_tokens = estimate_usage(0, self.output_chars).completion_tokens;
+        (output_tokens > budget).then(|| crate::AiError::QuotaExceeded {
+            provider: self.provider.to_string(),
+            quota_type: "tokens".to_string(),
+            reset_time: None,
+            message: format!(
+                "streamed reply is over the budget of {} output tokens",
+                budget
+            ),
+            request_id: None,
+        })
+    }
+
+    /// Run the assembled reply through the moderator, if one is set
+    async fn moderate_output(&self) -> Result<()> {
+        if let Some(moderator) = &self.moderator {
+            moderator.moderate(&self.text).await?.check("Response")?;
         }
+        Ok(())
+    }
+
+    /// Record usage and cost, returning the final `Done` item
+    fn finish(self) -> StreamItem {
+        let estimated = self.usage.is_none();
+        let usage = self
+            .usage
+            .clone()
+            .unwrap_or_else(|| estimate_usage(self.estimated_prompt_tokens, self.output_chars));
 
-        match result {
-            ToolResult::Success(value) => {
-                Ok(serde_json::to_string(&value).unwrap_or_else(|_| value.to_string()))
+        let cost = record_cost(
+            self.cost_tracker.as_ref(),
+            self.provider,
+            &self.model,
+            &usage,
+            &self.tags,
+        );
+
+        if let Some(metrics) = &self.metrics_collector {
+            let mut tokens = TokenUsage::new();
+            tokens.input_tokens = usage.prompt_tokens as u64;
+            tokens.output_tokens = usage.completion_tokens as u64;
+            tokens.reasoning_tokens = usage.reasoning_tokens.unwrap_or(0) as u64;
+            if estimated {
+                tokens.estimated_tokens = usage.total_tokens as u64;
             }
-            ToolResult::Error(error) => Err(AgentError::ToolError(error)),
+            metrics.record_request(
+                &self.agent_id,
+                self.success,
+                self.start_time.elapsed(),
+                tokens,
+                cost,
+                self.provider,
+                &self.model,
+            );
+        }
+
+        StreamItem::Done {
+            finish_reason: self.finish_reason.as_deref().map(FinishReason::from_raw),
+            usage: self.usage,
         }
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use super::*;
+    use crate::agent::AgentBuilder;
+    use crate::providers::MockProvider;
+    use crate::Role;
+
+    #[test]
+    fn test_agent_error_conversions() {
+        use crate::AiError;
+
+        // AiError -> AgentError keeps the provider error reachable
+        let rate_limited = AiError::RateLimitExceeded {
+            retry_after: None,
+            daily_limit: None,
+            requests_remaining: None,
+        };
+        let error: AgentError = rate_limited.into();
+        assert!(matches!(
+            error.source_ai_error(),
+            Some(AiError::RateLimitExceeded { .. })
+        ));
+        assert!(matches!(
+            error.into_ai_error(),
+            AiError::RateLimitExceeded { .. }
+        ));
+
+        // AgentError -> AiError for the agent's own failures
+        assert!(AgentError::ToolError("boom".to_string())
+            .source_ai_error()
+            .is_none());
+        match AiError::from(AgentError::ToolError("boom".to_string())) {
+            AiError::ToolExecutionError { message, .. } => assert_eq!(message, "boom"),
+            other => panic!("unexpected {:?}", other),
+        }
+        assert!(matches!(
+            AgentError::ContextError("bad".to_string()).into_ai_error(),
+            AiError::InternalError { .. }
+        ));
+        match AgentError::MemoryError("gone".to_string()).into_ai_error() {
+            AiError::MemoryError { message, .. } => assert_eq!(message, "gone"),
+            other => panic!("unexpected {:?}", other),
+        }
+        assert!(matches!(
+            AgentError::ConfigError("no provider".to_string()).into_ai_error(),
+            AiError::ConfigurationError { .. }
+        ));
+        assert!(matches!(
+            AgentError::ValidationError("missing field".to_string()).into_ai_error(),
+            AiError::MalformedResponse { .. }
+        ));
+    }
 
     #[tokio::test]
     async fn test_agent_creation() {
         // This test verifies the agent can be created
         // Real tests would use a mock provider
     }
+
+    #[tokio::test]
+    async fn test_stop_and_penalties_forwarded() {
+        let agent = AgentBuilder::new()
+            .provider(MockProvider::new())
+            .stop(vec!["END".to_string(), "\n\n".to_string()])
+            .frequency_penalty(0.5)
+            .presence_penalty(-0.25)
+            .build()
+            .unwrap();
+
+        let request = agent.build_request(true).await.unwrap();
+        assert_eq!(
+            request.stop,
+            Some(vec!["END".to_string(), "\n\n".to_string()])
+        );
+        assert_eq!(request.frequency_penalty, Some(0.5));
+        assert_eq!(request.presence_penalty, Some(-0.25));
+    }
+
+    #[tokio::test]
+    async fn test_prompt_template_merged_with_prompt() {
+        let agent = AgentBuilder::new()
+            .provider(MockProvider::new())
+            .prompt("Keep answers short.")
+            .prompt_template("You are helping {user}.")
+            .template_var("user", "Ada")
+            .build()
+            .unwrap();
+
+        let request = agent.build_request(true).await.unwrap();
+        let system: Vec<_> = request
+            .messages
+            .iter()
+            .filter(|m| m.role == Role::System)
+            .collect();
+        assert_eq!(system.len(), 1);
+        assert_eq!(
+            system[0].content.as_text(),
+            Some("You are helping Ada.\n\nKeep answers short.")
+        );
+    }
+
+    #[test]
+    fn test_metrics_removed_on_drop() {
+        let collector = Arc::new(MetricsCollector::new());
+
+        let agent = AgentBuilder::new()
+            .provider(MockProvider::new())
+            .metrics_collector(collector.clone())
+            .remove_metrics_on_drop(true)
+            .build()
+            .unwrap();
+        let agent_id = agent.agent_id().to_string();
+        assert!(collector.get_agent_metrics(&agent_id).is_some());
+
+        drop(agent);
+        assert!(collector.get_agent_metrics(&agent_id).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_failed_request_recorded() {
+        let collector = Arc::new(MetricsCollector::new());
+        let mut agent = AgentBuilder::new()
+            .provider(
+                MockProvider::new().with_error(crate::AiError::ServiceUnavailable {
+                    provider: "mock".to_string(),
+                    retry_after: None,
+                }),
+            )
+            .metrics_collector(collector.clone())
+            .build()
+            .unwrap();
+
+        assert!(agent.execute("hi").await.is_err());
+
+        let metrics = collector.get_agent_metrics(agent.agent_id()).unwrap();
+        assert_eq!(metrics.total_requests, 1);
+        assert_eq!(metrics.failed_requests, 1);
+    }
+
+    #[tokio::test]
+    async fn test_stream_usage_recorded_after_stream_ends() {
+        use futures::StreamExt;
+
+        let collector = Arc::new(MetricsCollector::new());
+        let tracker = Arc::new(RwLock::new(CostTracker::new()));
+        let mut agent = AgentBuilder::new()
+            .provider(MockProvider::new().with_response("Hello from the stream"))
+            .metrics_collector(collector.clone())
+            .cost_tracker(tracker.clone())
+            .build()
+            .unwrap();
+
+        let stream = agent.execute_stream("hi").await.unwrap();
+        // Nothing is recorded until the stream has been consumed
+        assert_eq!(tracker.read().unwrap().total_cost, 0.0);
+
+        let text: Vec<String> = stream.map(|chunk| chunk.unwrap()).collect().await;
+        assert_eq!(text.concat(), "Hello from the stream");
+
+        // The mock reports its usage on the final chunk
+        let metrics = collector.get_agent_metrics(agent.agent_id()).unwrap();
+        assert_eq!(metrics.total_requests, 1);
+        assert_eq!(metrics.total_tokens.input_tokens, 10);
+        assert_eq!(metrics.total_tokens.output_tokens, 5);
+
+        let total_cost = tracker.read().unwrap().total_cost;
+        assert!(total_cost > 0.0);
+        assert!((metrics.total_cost - total_cost).abs() < f64::EPSILON);
+    }
+
+    #[tokio::test]
+    async fn test_stream_items_end_with_done() {
+        use futures::StreamExt;
+
+        let mut agent = AgentBuilder::new()
+            .provider(
+                MockProvider::new()
+                    .with_response("Hello from the stream")
+                    .with_stream_chunk_size(5),
+            )
+            .build()
+            .unwrap();
+
+        let items: Vec<StreamItem> = agent
+            .execute_stream_items("hi")
+            .await
+            .unwrap()
+            .map(|item| item.unwrap())
+            .collect()
+            .await;
+
+        let text: String = items
+            .iter()
+            .filter_map(|item| match item {
+                StreamItem::Token(text) => Some(text.as_str()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(text, "Hello from the stream");
+
+        match items.last().unwrap() {
+            StreamItem::Done {
+                finish_reason,
+                usage,
+            } => {
+                assert_eq!(finish_reason, &Some(FinishReason::Stop));
+                let usage = usage.as_ref().unwrap();
+                assert_eq!(usage.prompt_tokens, 10);
+                assert_eq!(usage.completion_tokens, 5);
+            }
+            other => panic!("expected Done, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_stream_cancelled_once_over_token_budget() {
+        use futures::StreamExt;
+
+        let long_reply = "word ".repeat(2000);
+        let mut agent = AgentBuilder::new()
+            .provider(
+                MockProvider::new()
+                    .with_response(&long_reply)
+                    .with_stream_chunk_size(4),
+            )
+            .stream_output_token_budget(5)
+            .build()
+            .unwrap();
+
+        let items: Vec<_> = agent
+            .execute_stream_items("hi")
+            .await
+            .unwrap()
+            .collect()
+            .await;
+
+        let text: String = items
+            .iter()
+            .filter_map(|item| match item {
+                Ok(StreamItem::Token(text)) => Some(text.as_str()),
+                _ => None,
+            })
+            .collect();
+        assert!(text.len() < 40, "streamed {} chars", text.len());
+
+        match items.last().unwrap() {
+            Err(AgentError::ProviderError(crate::AiError::QuotaExceeded {
+                quota_type, ..
+            })) => {
+                assert_eq!(quota_type, "tokens")
+            }
+            other => panic!("expected QuotaExceeded, got {:?}", other),
+        }
+        assert!(!items
+            .iter()
+            .any(|item| matches!(item, Ok(StreamItem::Done { .. }))));
+    }
+
+    #[tokio::test]
+    async fn test_cost_broken_down_by_request_tags() {
+        let tracker = Arc::new(RwLock::new(CostTracker::new()));
+        for feature in ["search", "chat"] {
+            let mut agent = AgentBuilder::new()
+                .provider(MockProvider::new().with_response("ok"))
+                .metadata("feature", feature)
+                .metadata("team", "core")
+                .cost_tracker(tracker.clone())
+                .build()
+                .unwrap();
+            agent.execute("hi").await.unwrap();
+        }
+
+        let tracker = tracker.read().unwrap();
+        let by_feature = tracker.generate_report_by_tag("feature");
+        assert_eq!(by_feature.len(), 2);
+        let mut features: Vec<&str> = by_feature.iter().map(|e| e.value.as_str()).collect();
+        features.sort();
+        assert_eq!(features, vec!["chat", "search"]);
+        assert!(by_feature.iter().all(|e| e.requests == 1));
+
+        let by_team = tracker.generate_report_by_tag("team");
+        assert_eq!(by_team.len(), 1);
+        assert_eq!(by_team[0].requests, 2);
+        assert!((by_team[0].total_cost - tracker.total_cost).abs() < 1e-12);
+        assert!(tracker.generate_report_by_tag("customer").is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_missing_usage_is_estimated() {
+        let collector = Arc::new(MetricsCollector::new());
+        let tracker = Arc::new(RwLock::new(CostTracker::new()));
+        let mut agent = AgentBuilder::new()
+            .provider(MockProvider::new().with_completion(CompletionResponse {
+                id: "r1".to_string(),
+                model: "gpt-4o".to_string(),
+                choices: vec![crate::Choice {
+                    index: 0,
+                    message: Message::assistant("A reply of about ten tokens long."),
+                    finish_reason: Some("stop".to_string()),
+                    logprobs: None,
+                }],
+                usage: None,
+                metadata: None,
+            }))
+            .model("gpt-4o")
+            .metrics_collector(collector.clone())
+            .cost_tracker(tracker.clone())
+            .build()
+            .unwrap();
+        agent.execute("Say something").await.unwrap();
+
+        let metrics = collector.get_agent_metrics(agent.agent_id()).unwrap();
+        assert_eq!(metrics.total_tokens.output_tokens, 8);
+        assert!(metrics.total_tokens.input_tokens > 0);
+        assert_eq!(
+            metrics.total_tokens.estimated_tokens,
+            metrics.total_tokens.total()
+        );
+        assert!(metrics.total_cost > 0.0);
+        assert!((tracker.read().unwrap().total_cost - metrics.total_cost).abs() < f64::EPSILON);
+    }
+
+    #[tokio::test]
+    async fn test_execute_stream_with_callback() {
+        let mut agent = AgentBuilder::new()
+            .provider(
+                MockProvider::new()
+                    .with_response("Streaming straight into a callback")
+                    .with_stream_chunk_size(4),
+            )
+            .build()
+            .unwrap();
+
+        let mut tokens = Vec::new();
+        let response = agent
+            .execute_stream_with("hi", |token| tokens.push(token.to_string()))
+            .await
+            .unwrap();
+
+        assert!(tokens.len() > 1);
+        assert_eq!(tokens.concat(), response);
+        assert_eq!(response, "Streaming straight into a callback");
+
+        let last = agent.context().messages().last().unwrap();
+        assert_eq!(last.role, Role::Assistant);
+        assert_eq!(last.content.as_text(), Some(response.as_str()));
+    }
 }
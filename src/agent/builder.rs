@@ -1,10 +1,12 @@
+use std::collections::HashMap;
 use std::sync::Arc;
 
 use super::agent::AgentConfig;
-use super::{Agent, Context, Memory, ToolExecutor, ToolRegistry};
+use super::{Agent, BackendMemory, Context, Memory, MemoryBackend, ToolExecutor, ToolRegistry};
 use crate::{
+    moderation::Moderator,
     observability::{AgentTracer, CostTracker, MetricsCollector, TelemetryExporter},
-    CompletionProvider,
+    CompletionProvider, ToolChoice,
 };
 
 /// Builder for creating an Agent with a fluent API
@@ -19,6 +21,9 @@ pub struct AgentBuilder {
     tracer: Option<Arc<AgentTracer>>,
     cost_tracker: Option<Arc<std::sync::RwLock<CostTracker>>>,
     telemetry_exporter: Option<Arc<TelemetryExporter>>,
+    moderator: Option<Arc<dyn Moderator>>,
+    prompt_template: Option<String>,
+    template_vars: HashMap<String, String>,
 }
 
 impl AgentBuilder {
@@ -35,6 +40,9 @@ impl AgentBuilder {
             tracer: None,
             cost_tracker: None,
             telemetry_exporter: None,
+            moderator: None,
+            prompt_template: None,
+            template_vars: HashMap::new(),
         }
     }
 
@@ -58,6 +66,25 @@ impl AgentBuilder {
         self
     }
 
+    /// Set a system prompt with `{name}` placeholders, rendered from the
+    /// template variables on every request
+    pub fn prompt_template<S: Into<String>>(mut self, template: S) -> Self {
+        self.prompt_template = Some(template.into());
+        self
+    }
+
+    /// Set a prompt template variable
+    pub fn template_var<K: Into<String>, V: Into<String>>(mut self, name: K, value: V) -> Self {
+        self.template_vars.insert(name.into(), value.into());
+        self
+    }
+
+    /// Set several prompt template variables
+    pub fn template_vars(mut self, vars: HashMap<String, String>) -> Self {
+        self.template_vars.extend(vars);
+        self
+    }
+
     /// Add a preamble message (additional system context)
     pub fn preamble<S: Into<String>>(mut self, preamble: S) -> Self {
         self.context.add_system_message(&preamble.into());
@@ -88,30 +115,124 @@ impl AgentBuilder {
         self
     }
 
+    /// Set the frequency penalty
+    pub fn frequency_penalty(mut self, penalty: f32) -> Self {
+        self.config.frequency_penalty = Some(penalty);
+        self
+    }
+
+    /// Set the presence penalty
+    pub fn presence_penalty(mut self, penalty: f32) -> Self {
+        self.config.presence_penalty = Some(penalty);
+        self
+    }
+
+    /// Set the stop sequences
+    pub fn stop(mut self, stop: Vec<String>) -> Self {
+        self.config.stop = Some(stop);
+        self
+    }
+
     /// Set the response format
     pub fn response_format(mut self, format: crate::ResponseFormat) -> Self {
         self.config.response_format = Some(format);
         self
     }
 
+    /// Retry tool calls that fail with a retryable error
+    pub fn tool_retry(mut self, retry: crate::RetryConfig) -> Self {
+        self.config.tool_retry = Some(retry);
+        self
+    }
+
+    /// Run up to `limit` tool calls from one reply at once
+    pub fn max_parallel_tools(mut self, limit: usize) -> Self {
+        self.config.max_parallel_tools = limit;
+        self
+    }
+
+    /// Run identical tool calls in one reply only once
+    pub fn dedup_tool_calls(mut self, dedup: bool) -> Self {
+        self.config.dedup_tool_calls = dedup;
+        self
+    }
+
+    /// Answer calls to unregistered tools with an error result instead of
+    /// failing the run
+    pub fn tolerate_missing_tools(mut self, tolerate: bool) -> Self {
+        self.config.tolerate_missing_tools = tolerate;
+        self
+    }
+
+    /// Cancel streamed replies whose estimated output passes `tokens`
+    pub fn stream_output_token_budget(mut self, tokens: u32) -> Self {
+        self.config.stream_output_token_budget = Some(tokens);
+        self
+    }
+
+    /// Record each iteration of `execute`, available from `Agent::last_trace`
+    pub fn record_trace(mut self, record: bool) -> Self {
+        self.config.record_trace = record;
+        self
+    }
+
+    /// Force a tool choice, such as `ToolChoice::required()`, on the first
+    /// request of each turn
+    pub fn tool_choice(mut self, tool_choice: ToolChoice) -> Self {
+        self.config.tool_choice = Some(tool_choice);
+        self
+    }
+
+    /// Tag every request, e.g. `metadata("feature", "search")`, so the cost
+    /// tracker can break cost down by tag
+    pub fn metadata(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.config.metadata.insert(key.into(), value.into());
+        self
+    }
+
     /// Set the maximum iterations for tool use
     pub fn max_iterations(mut self, max_iterations: usize) -> Self {
         self.config.max_iterations = max_iterations;
         self
     }
 
+    /// Cap how many retrieved memories, and how many characters of them,
+    /// are added to the context each turn
+    pub fn memory_limits(mut self, max_memories: usize, max_memory_chars: usize) -> Self {
+        self.config.max_memories = max_memories;
+        self.config.max_memory_chars = max_memory_chars;
+        self
+    }
+
+    /// Remove the agent's metrics from the collector when the agent is dropped
+    pub fn remove_metrics_on_drop(mut self, remove: bool) -> Self {
+        self.config.remove_metrics_on_drop = remove;
+        self
+    }
+
+    /// Join an upstream distributed trace given its W3C `traceparent` header
+    pub fn traceparent<S: Into<String>>(mut self, traceparent: S) -> Self {
+        self.config.traceparent = Some(traceparent.into());
+        self
+    }
+
     /// Enable streaming
     pub fn stream(mut self, stream: bool) -> Self {
         self.config.stream = stream;
         self
     }
 
-    /// Add memory to the agent
+    /// Add memory to the agent; a `Box<dyn Memory>` works as well
     pub fn memory<M: Memory + 'static>(mut self, memory: M) -> Self {
         self.memory = Some(Box::new(memory));
         self
     }
 
+    /// Use any storage backend as the agent's memory
+    pub fn memory_backend(self, backend: Box<dyn MemoryBackend>) -> Self {
+        self.memory(BackendMemory::new(backend))
+    }
+
     /// Add a single tool
     pub fn tool<S, E>(mut self, name: S, executor: E) -> Self
     where
@@ -166,12 +287,8 @@ impl AgentBuilder {
     pub fn add_message(mut self, role: crate::Role, content: &str) -> Self {
         use crate::MessageContent;
 
-        self.context.add_message(crate::Message {
-            role,
-            content: MessageContent::text(content),
-            tool_calls: None,
-            tool_call_id: None,
-        });
+        self.context
+            .add_message(crate::Message::new(role, MessageContent::text(content)));
         self
     }
 
@@ -199,6 +316,13 @@ impl AgentBuilder {
         self
     }
 
+    /// Screen input and replies with a moderator, rejecting flagged text
+    /// with `AiError::ContentFiltered`
+    pub fn moderator<M: Moderator + 'static>(mut self, moderator: M) -> Self {
+        self.moderator = Some(Arc::new(moderator));
+        self
+    }
+
     /// Enable full observability with all components
     pub fn with_observability(
         mut self,
@@ -222,7 +346,7 @@ impl AgentBuilder {
 
         let prompt = self.prompt.unwrap_or_default();
 
-        let agent = Agent::new(
+        let mut agent = Agent::new(
             provider,
             prompt,
             self.context,
@@ -236,6 +360,12 @@ impl AgentBuilder {
             self.cost_tracker,
             self.telemetry_exporter,
         );
+        if let Some(moderator) = self.moderator {
+            agent = agent.with_moderator(moderator);
+        }
+        if let Some(template) = self.prompt_template {
+            agent = agent.with_prompt_template(template, self.template_vars);
+        }
 
         Ok(agent)
     }
@@ -1,4 +1,10 @@
-use crate::{Message, MessageContent, Role};
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::{AiError, Message, MessageContent, Result, Role};
+
+/// Version written by [`Context::to_json_transcript`]
+pub const TRANSCRIPT_VERSION: u32 = 1;
 
 /// A message in the context with additional metadata
 #[derive(Clone, Debug)]
@@ -37,52 +43,63 @@ impl Context {
 
     /// Add a system message
     pub fn add_system_message(&mut self, content: &str) {
-        self.add_message(Message {
-            role: Role::System,
-            content: MessageContent::text(content),
-            tool_calls: None,
-            tool_call_id: None,
-        });
+        self.add_message(Message::system(content));
     }
 
     /// Add a user message
     pub fn add_user_message(&mut self, content: &str) {
-        self.add_message(Message {
-            role: Role::User,
-            content: MessageContent::text(content),
-            tool_calls: None,
-            tool_call_id: None,
-        });
+        self.add_message(Message::user(content));
     }
 
     /// Add an assistant message
     pub fn add_assistant_message(&mut self, content: &str) {
-        self.add_message(Message {
-            role: Role::Assistant,
-            content: MessageContent::text(content),
-            tool_calls: None,
-            tool_call_id: None,
-        });
+        self.add_message(Message::assistant(content));
     }
 
     /// Add a tool result message
     pub fn add_tool_result(&mut self, tool_call_id: &str, result: &str) {
-        self.add_message(Message {
-            role: Role::Tool,
-            content: MessageContent::text(result),
-            tool_calls: None,
-            tool_call_id: Some(tool_call_id.to_string()),
-        });
+        self.add_message(Message::tool(tool_call_id, result));
+    }
+
+    /// Add a structured tool result message
+    pub fn add_tool_result_value(&mut self, tool_call_id: &str, result: serde_json::Value) {
+        self.add_message(Message::tool_json(tool_call_id, result));
     }
 
     /// Add a memory context (as a system message)
     pub fn add_memory(&mut self, memory: String) {
-        self.add_message(Message {
-            role: Role::System,
-            content: MessageContent::text(format!("[Memory] {}", memory)),
-            tool_calls: None,
-            tool_call_id: None,
-        });
+        self.add_message(Message::system(format!("[Memory] {}", memory)));
+    }
+
+    /// Replace the memory block with one system message holding `memories`,
+    /// keeping at most `max_count` of them and `max_chars` characters of
+    /// memory text. The block goes at the end of the context, so it sits
+    /// just before the next user turn.
+    pub fn set_memory_block(&mut self, memories: &[String], max_count: usize, max_chars: usize) {
+        self.messages.retain(|cm| !is_memory_block(cm));
+
+        let mut remaining = max_chars;
+        let mut entries = Vec::new();
+        for memory in memories.iter().take(max_count) {
+            if remaining == 0 {
+                break;
+            }
+            let entry: String = memory.chars().take(remaining).collect();
+            remaining -= entry.chars().count();
+            entries.push(entry);
+        }
+        if entries.is_empty() {
+            return;
+        }
+
+        self.add_message_with_metadata(
+            Message::system(format!(
+                "{}\n\n{}",
+                MEMORY_BLOCK_HEADER,
+                entries.join("\n\n---\n\n")
+            )),
+            Some(serde_json::json!({ MEMORY_BLOCK_KEY: true })),
+        );
     }
 
     /// Add a message with metadata
@@ -93,7 +110,7 @@ impl Context {
     ) {
         let context_msg = ContextMessage {
             message,
-            timestamp: std::time::SystemTime::now(),
+            timestamp: SystemTime::now(),
             metadata,
         };
 
@@ -116,6 +133,57 @@ impl Context {
         &mut self.messages
     }
 
+    /// All messages with their metadata, indexed as expected by
+    /// `remove_message`, `edit_message` and `insert_message`
+    pub fn window(&self) -> &[ContextMessage] {
+        &self.messages
+    }
+
+    /// Remove and return the message at `index`
+    pub fn remove_message(&mut self, index: usize) -> Result<Message> {
+        self.check_index(index, self.messages.len())?;
+        Ok(self.messages.remove(index).message)
+    }
+
+    /// Replace the content of the message at `index`, keeping its role,
+    /// tool calls and metadata
+    pub fn edit_message(&mut self, index: usize, content: MessageContent) -> Result<()> {
+        self.check_index(index, self.messages.len())?;
+        self.messages[index].message.content = content;
+        self.enforce_limits();
+        Ok(())
+    }
+
+    /// Insert a message before `index`; `index == len()` appends
+    pub fn insert_message(&mut self, index: usize, message: Message) -> Result<()> {
+        self.check_index(index, self.messages.len() + 1)?;
+        self.messages.insert(
+            index,
+            ContextMessage {
+                message,
+                timestamp: SystemTime::now(),
+                metadata: None,
+            },
+        );
+        self.enforce_limits();
+        Ok(())
+    }
+
+    fn check_index(&self, index: usize, bound: usize) -> Result<()> {
+        if index < bound {
+            return Ok(());
+        }
+        Err(AiError::InvalidRequest {
+            message: format!(
+                "Message index {} out of range for a context of {} messages",
+                index,
+                self.messages.len()
+            ),
+            field: Some("index".to_string()),
+            code: None,
+        })
+    }
+
     /// Convert to a vector of messages for API calls
     pub fn to_messages(&self) -> Vec<Message> {
         self.messages.iter().map(|cm| cm.message.clone()).collect()
@@ -142,27 +210,13 @@ impl Context {
         self.messages.is_empty()
     }
 
-    /// Estimate token count (rough approximation)
+    /// Estimate token count (rough approximation, see
+    /// [`MessageContent::approx_tokens`])
     pub fn estimate_tokens(&self) -> usize {
         self.messages
             .iter()
-            .map(|cm| {
-                let content_len = match &cm.message.content {
-                    MessageContent::Text(text) => text.len() / 4, // Rough estimate
-                    MessageContent::Parts(parts) => {
-                        parts
-                            .iter()
-                            .map(|p| match p {
-                                crate::ContentPart::Text { text } => text.len() / 4,
-                                crate::ContentPart::Image { .. } => 100, // Rough estimate for image
-                            })
-                            .sum()
-                    }
-                };
-
-                // Add some overhead for role and structure
-                content_len + 10
-            })
+            // Add some overhead for role and structure
+            .map(|cm| cm.message.content.approx_tokens() + 10)
             .sum()
     }
 
@@ -218,6 +272,86 @@ impl Context {
             self.estimate_tokens()
         )
     }
+
+    /// Serialize every message, with its tool calls, tool results and
+    /// metadata, to a JSON transcript. Messages use the OpenAI chat format,
+    /// so a transcript can be replayed against any provider.
+    pub fn to_json_transcript(&self) -> Result<String> {
+        let transcript = Transcript {
+            version: TRANSCRIPT_VERSION,
+            messages: self
+                .messages
+                .iter()
+                .map(|cm| TranscriptMessage {
+                    message: cm.message.clone(),
+                    timestamp_ms: cm
+                        .timestamp
+                        .duration_since(UNIX_EPOCH)
+                        .map(|d| d.as_millis() as u64)
+                        .ok(),
+                    metadata: cm.metadata.clone(),
+                })
+                .collect(),
+        };
+        Ok(serde_json::to_string_pretty(&transcript)?)
+    }
+
+    /// Rebuild a context from a transcript written by `to_json_transcript`.
+    /// The context has no limits, so every message is kept.
+    pub fn from_json_transcript(json: &str) -> Result<Self> {
+        let transcript: Transcript = serde_json::from_str(json)?;
+        if transcript.version != TRANSCRIPT_VERSION {
+            return Err(AiError::SerializationError {
+                message: format!(
+                    "Unsupported transcript version {} (expected {})",
+                    transcript.version, TRANSCRIPT_VERSION
+                ),
+            });
+        }
+
+        let mut context = Self::new();
+        context.messages = transcript
+            .messages
+            .into_iter()
+            .map(|tm| ContextMessage {
+                message: tm.message,
+                timestamp: tm
+                    .timestamp_ms
+                    .map(|ms| UNIX_EPOCH + Duration::from_millis(ms))
+                    .unwrap_or_else(SystemTime::now),
+                metadata: tm.metadata,
+            })
+            .collect();
+        Ok(context)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct Transcript {
+    version: u32,
+    messages: Vec<TranscriptMessage>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct TranscriptMessage {
+    #[serde(flatten)]
+    message: Message,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    timestamp_ms: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    metadata: Option<serde_json::Value>,
+}
+
+const MEMORY_BLOCK_HEADER: &str = "Relevant context from memory:";
+const MEMORY_BLOCK_KEY: &str = "memory_block";
+
+fn is_memory_block(message: &ContextMessage) -> bool {
+    message
+        .metadata
+        .as_ref()
+        .and_then(|m| m.get(MEMORY_BLOCK_KEY))
+        .and_then(|v| v.as_bool())
+        .unwrap_or(false)
 }
 
 impl Default for Context {
@@ -256,4 +390,124 @@ mod tests {
         assert_eq!(ctx.len(), 1); // Only system message remains
         assert_eq!(ctx.messages().next().unwrap().role, Role::System);
     }
+
+    #[test]
+    fn test_edit_remove_and_insert_messages() {
+        let mut ctx = Context::new();
+        ctx.add_system_message("System prompt");
+        ctx.add_user_message("What is 2 + 2?");
+        ctx.add_assistant_message("5");
+        ctx.add_user_message("Are you sure?");
+
+        ctx.edit_message(1, MessageContent::text("What is 2 + 3?"))
+            .unwrap();
+        let removed = ctx.remove_message(3).unwrap();
+        assert_eq!(removed.content.as_text(), Some("Are you sure?"));
+        ctx.insert_message(2, Message::user("Think carefully"))
+            .unwrap();
+        ctx.insert_message(4, Message::user("Thanks")).unwrap();
+
+        let texts: Vec<_> = ctx
+            .to_messages()
+            .iter()
+            .map(|m| m.content.as_text().unwrap().to_string())
+            .collect();
+        assert_eq!(
+            texts,
+            [
+                "System prompt",
+                "What is 2 + 3?",
+                "Think carefully",
+                "5",
+                "Thanks"
+            ]
+        );
+        assert_eq!(ctx.window().len(), 5);
+        assert_eq!(ctx.window()[2].message.role, Role::User);
+
+        assert!(matches!(
+            ctx.remove_message(5),
+            Err(AiError::InvalidRequest { .. })
+        ));
+        assert!(ctx
+            .edit_message(5, MessageContent::text("out of range"))
+            .is_err());
+        assert!(ctx.insert_message(6, Message::user("too far")).is_err());
+        assert_eq!(ctx.len(), 5);
+    }
+
+    #[test]
+    fn test_memory_block_caps_count_and_size() {
+        let mut ctx = Context::new();
+        let memories = vec!["a".repeat(10), "b".repeat(10), "c".repeat(10)];
+
+        ctx.set_memory_block(&memories, 2, 15);
+        assert_eq!(ctx.len(), 1);
+        let text = ctx.to_messages()[0].content.as_text().unwrap().to_string();
+        assert!(text.starts_with(MEMORY_BLOCK_HEADER));
+        assert!(text.contains(&"a".repeat(10)));
+        assert!(text.contains(&"b".repeat(5)));
+        assert!(!text.contains(&"b".repeat(6)));
+        assert!(!text.contains("ccc"));
+
+        // A new block replaces the old one; none at all removes it
+        ctx.add_user_message("hi");
+        ctx.set_memory_block(&memories[2..], 5, 100);
+        assert_eq!(ctx.len(), 2);
+        assert!(ctx.to_messages()[1]
+            .content
+            .as_text()
+            .unwrap()
+            .contains("ccc"));
+        ctx.set_memory_block(&[], 5, 100);
+        assert_eq!(ctx.len(), 1);
+    }
+
+    #[test]
+    fn test_json_transcript_round_trip() {
+        use crate::{FunctionCall, ToolCall, ToolType};
+
+        let mut ctx = Context::new();
+        ctx.add_system_message("You are a calculator");
+        ctx.add_user_message("What is 2 + 3?");
+        ctx.add_message(Message {
+            tool_calls: Some(vec![ToolCall {
+                id: "call_1".to_string(),
+                r#type: ToolType::Function,
+                function: FunctionCall {
+                    name: "calculator".to_string(),
+                    arguments: r#"{"operation":"add","a":2,"b":3}"#.to_string(),
+                },
+            }]),
+            reasoning_content: Some("Use the tool".to_string()),
+            ..Message::assistant("")
+        });
+        ctx.add_tool_result_value("call_1", serde_json::json!({ "result": 5 }));
+        ctx.add_message_with_metadata(
+            Message::assistant("2 + 3 = 5"),
+            Some(serde_json::json!({ "source": "test" })),
+        );
+
+        let json = ctx.to_json_transcript().unwrap();
+        let parsed: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed["version"], TRANSCRIPT_VERSION);
+        assert_eq!(parsed["messages"][2]["tool_calls"][0]["id"], "call_1");
+        assert_eq!(parsed["messages"][3]["tool_call_id"], "call_1");
+
+        let restored = Context::from_json_transcript(&json).unwrap();
+        assert_eq!(restored.to_messages(), ctx.to_messages());
+        for (a, b) in restored.messages.iter().zip(&ctx.messages) {
+            assert_eq!(a.metadata, b.metadata);
+            assert_eq!(
+                a.timestamp.duration_since(UNIX_EPOCH).unwrap().as_millis(),
+                b.timestamp.duration_since(UNIX_EPOCH).unwrap().as_millis()
+            );
+        }
+
+        let future = json.replacen("\"version\": 1", "\"version\": 2", 1);
+        assert!(matches!(
+            Context::from_json_transcript(&future),
+            Err(AiError::SerializationError { .. })
+        ));
+    }
 }
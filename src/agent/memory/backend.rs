@@ -0,0 +1,80 @@
+use async_trait::async_trait;
+use std::time::SystemTime;
+
+use super::{Memory, MemoryStats};
+use crate::agent::AgentError;
+
+/// One stored conversation turn
+#[derive(Debug, Clone)]
+pub struct MemoryRecord {
+    pub input: String,
+    pub output: String,
+    pub timestamp: SystemTime,
+}
+
+impl MemoryRecord {
+    /// Create a record stamped with the current time
+    pub fn new(input: impl Into<String>, output: impl Into<String>) -> Self {
+        Self {
+            input: input.into(),
+            output: output.into(),
+            timestamp: SystemTime::now(),
+        }
+    }
+}
+
+/// Storage for memory records (Redis, Surreal, Postgres, ...), plugged into
+/// an agent with [`AgentBuilder::memory_backend`]
+///
+/// A backend only stores and finds records; formatting them for the
+/// model's context is left to [`BackendMemory`].
+///
+/// [`AgentBuilder::memory_backend`]: crate::agent::AgentBuilder::memory_backend
+#[async_trait]
+pub trait MemoryBackend: Send + Sync {
+    /// Store a record
+    async fn add(&mut self, record: MemoryRecord) -> Result<(), AgentError>;
+
+    /// Find up to `limit` records relevant to `query`, most relevant first
+    async fn search(&self, query: &str, limit: usize) -> Result<Vec<MemoryRecord>, AgentError>;
+
+    /// Remove all records
+    async fn clear(&mut self) -> Result<(), AgentError>;
+
+    /// Get backend statistics
+    async fn stats(&self) -> Result<MemoryStats, AgentError>;
+}
+
+/// [`Memory`] over any [`MemoryBackend`]
+pub struct BackendMemory {
+    backend: Box<dyn MemoryBackend>,
+}
+
+impl BackendMemory {
+    pub fn new(backend: Box<dyn MemoryBackend>) -> Self {
+        Self { backend }
+    }
+}
+
+#[async_trait]
+impl Memory for BackendMemory {
+    async fn store(&mut self, input: &str, output: &str) -> Result<(), AgentError> {
+        self.backend.add(MemoryRecord::new(input, output)).await
+    }
+
+    async fn retrieve(&self, query: &str, limit: usize) -> Result<Vec<String>, AgentError> {
+        let records = self.backend.search(query, limit).await?;
+        Ok(records
+            .into_iter()
+            .map(|record| format!("User: {}\nAssistant: {}", record.input, record.output))
+            .collect())
+    }
+
+    async fn clear(&mut self) -> Result<(), AgentError> {
+        self.backend.clear().await
+    }
+
+    async fn stats(&self) -> Result<MemoryStats, AgentError> {
+        self.backend.stats().await
+    }
+}
@@ -19,6 +19,25 @@ pub trait Memory: Send + Sync {
     async fn stats(&self) -> Result<MemoryStats, AgentError>;
 }
 
+#[async_trait]
+impl<M: Memory + ?Sized> Memory for Box<M> {
+    async fn store(&mut self, input: &str, output: &str) -> Result<(), AgentError> {
+        (**self).store(input, output).await
+    }
+
+    async fn retrieve(&self, query: &str, limit: usize) -> Result<Vec<String>, AgentError> {
+        (**self).retrieve(query, limit).await
+    }
+
+    async fn clear(&mut self) -> Result<(), AgentError> {
+        (**self).clear().await
+    }
+
+    async fn stats(&self) -> Result<MemoryStats, AgentError> {
+        (**self).stats().await
+    }
+}
+
 /// Statistics about the memory store
 #[derive(Debug, Clone)]
 pub struct MemoryStats {
@@ -93,7 +112,7 @@ impl Memory for InMemoryStore {
             .collect();
 
         // Sort by relevance (score) descending
-        matches.sort_by(|a, b| b.0.cmp(&a.0));
+        matches.sort_by_key(|m| std::cmp::Reverse(m.0));
 
         // Take top matches and format
         let results = matches
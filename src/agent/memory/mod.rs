@@ -1,9 +1,11 @@
+mod backend;
 mod base;
 mod semantic;
 mod surrealdb;
 
+pub use backend::{BackendMemory, MemoryBackend, MemoryRecord};
 pub use base::{
     InMemoryStore, Memory, MemoryStats, MemoryStore, PersistentMemoryStore, SemanticMemoryStore,
 };
 pub use semantic::{EnhancedSemanticMemory as SemanticMemory, SemanticMemoryBuilder};
-pub use surrealdb::{SurrealMemoryConfig, SurrealMemoryStore};
+pub use surrealdb::{SurrealMemoryConfig, SurrealMemoryStore, SurrealVectorStore};
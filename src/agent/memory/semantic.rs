@@ -22,6 +22,7 @@ pub struct EnhancedSemanticMemory {
     embedding_provider: Arc<dyn EmbeddingProvider>,
     max_entries: usize,
     similarity_threshold: f32,
+    mmr_lambda: Option<f32>,
 }
 
 impl EnhancedSemanticMemory {
@@ -36,9 +37,18 @@ impl EnhancedSemanticMemory {
             embedding_provider,
             max_entries,
             similarity_threshold,
+            mmr_lambda: None,
         }
     }
 
+    /// Re-rank hits with Maximal Marginal Relevance so near-duplicates don't
+    /// crowd out other relevant memories. `lambda` weighs relevance against
+    /// diversity: 1.0 ranks by similarity alone, 0.0 by diversity alone.
+    pub fn with_mmr(mut self, lambda: f32) -> Self {
+        self.mmr_lambda = Some(lambda.clamp(0.0, 1.0));
+        self
+    }
+
     /// Find the most similar entries
     async fn find_similar(&self, query_embedding: &Embedding, limit: usize) -> Vec<SemanticEntry> {
         let entries = self.entries.lock().await;
@@ -55,6 +65,10 @@ impl EnhancedSemanticMemory {
         // Sort by similarity (descending)
         similarities.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap());
 
+        if let Some(lambda) = self.mmr_lambda {
+            return mmr_select(similarities, limit, lambda);
+        }
+
         // Take top N and clone
         similarities
             .into_iter()
@@ -64,6 +78,36 @@ impl EnhancedSemanticMemory {
     }
 }
 
+/// Greedily pick the candidate with the best balance of similarity to the
+/// query and dissimilarity to the entries already picked
+fn mmr_select(
+    mut candidates: Vec<(f32, &SemanticEntry)>,
+    limit: usize,
+    lambda: f32,
+) -> Vec<SemanticEntry> {
+    let mut selected: Vec<SemanticEntry> = Vec::with_capacity(limit.min(candidates.len()));
+
+    while selected.len() < limit && !candidates.is_empty() {
+        let score = |(relevance, entry): &(f32, &SemanticEntry)| {
+            let redundancy = selected
+                .iter()
+                .map(|chosen| entry.embedding.cosine_similarity(&chosen.embedding))
+                .fold(0.0, f32::max);
+            lambda * relevance - (1.0 - lambda) * redundancy
+        };
+
+        let best = candidates
+            .iter()
+            .enumerate()
+            .max_by(|(_, a), (_, b)| score(a).total_cmp(&score(b)))
+            .map(|(index, _)| index)
+            .unwrap();
+        selected.push(candidates.remove(best).1.clone());
+    }
+
+    selected
+}
+
 #[async_trait]
 impl Memory for EnhancedSemanticMemory {
     async fn store(&mut self, input: &str, output: &str) -> Result<(), AgentError> {
@@ -140,6 +184,7 @@ pub struct SemanticMemoryBuilder {
     embedding_provider: Option<Arc<dyn EmbeddingProvider>>,
     max_entries: usize,
     similarity_threshold: f32,
+    mmr_lambda: Option<f32>,
 }
 
 impl SemanticMemoryBuilder {
@@ -148,6 +193,7 @@ impl SemanticMemoryBuilder {
             embedding_provider: None,
             max_entries: 1000,
             similarity_threshold: 0.7,
+            mmr_lambda: None,
         }
     }
 
@@ -171,16 +217,30 @@ impl SemanticMemoryBuilder {
         self
     }
 
+    /// Drop retrieved memories less similar to the query than `threshold`;
+    /// the same setting as `similarity_threshold`
+    pub fn min_similarity(self, threshold: f32) -> Self {
+        self.similarity_threshold(threshold)
+    }
+
+    /// Re-rank retrieved memories with Maximal Marginal Relevance, see
+    /// [`EnhancedSemanticMemory::with_mmr`]
+    pub fn mmr(mut self, lambda: f32) -> Self {
+        self.mmr_lambda = Some(lambda);
+        self
+    }
+
     pub fn build(self) -> Result<EnhancedSemanticMemory, String> {
         let provider = self
             .embedding_provider
             .ok_or_else(|| "Embedding provider is required".to_string())?;
 
-        Ok(EnhancedSemanticMemory::new(
-            provider,
-            self.max_entries,
-            self.similarity_threshold,
-        ))
+        let memory =
+            EnhancedSemanticMemory::new(provider, self.max_entries, self.similarity_threshold);
+        Ok(match self.mmr_lambda {
+            Some(lambda) => memory.with_mmr(lambda),
+            None => memory,
+        })
     }
 }
 
@@ -7,7 +7,10 @@ use surrealdb::Surreal;
 
 use super::base::{Memory, MemoryStats};
 use crate::agent::AgentError;
-use crate::embeddings::{Embedding, EmbeddingProvider};
+use crate::embeddings::provider::Result as EmbeddingResult;
+use crate::embeddings::{
+    Document, Embedding, EmbeddingError, EmbeddingProvider, SearchResult, VectorStore,
+};
 
 /// A memory entry stored in SurrealDB
 #[derive(Debug, Serialize, Deserialize)]
@@ -107,6 +110,15 @@ impl SurrealMemoryStore {
         })
     }
 
+    /// A [`VectorStore`] keeping documents in `table` on this store's
+    /// connection, e.g. to back a `SemanticSearch`
+    pub fn vector_store(&self, table: impl Into<String>) -> SurrealVectorStore {
+        SurrealVectorStore {
+            db: self.db.clone(),
+            table: table.into(),
+        }
+    }
+
     /// Find similar memories using vector similarity search
     async fn find_similar(
         &self,
@@ -258,6 +270,102 @@ impl Memory for SurrealMemoryStore {
     }
 }
 
+/// A document row in a [`SurrealVectorStore`] table
+#[derive(Debug, Serialize, Deserialize)]
+struct DocumentRecord {
+    text: String,
+    embedding: Vec<f32>,
+}
+
+/// A ranked row returned by [`SurrealVectorStore::search`]
+#[derive(Debug, Deserialize)]
+struct ScoredRecord {
+    id: String,
+    text: String,
+    score: f32,
+}
+
+/// [`VectorStore`] over a SurrealDB table, created with
+/// [`SurrealMemoryStore::vector_store`]. Documents are keyed by their id and
+/// ranked by SurrealDB's `vector::similarity::cosine`.
+#[derive(Clone)]
+pub struct SurrealVectorStore {
+    db: Surreal<Client>,
+    table: String,
+}
+
+fn vector_store_error(action: &str, e: surrealdb::Error) -> EmbeddingError {
+    EmbeddingError::ProviderError(format!("Failed to {}: {}", action, e))
+}
+
+#[async_trait]
+impl VectorStore for SurrealVectorStore {
+    async fn add(&self, documents: Vec<Document>) -> EmbeddingResult<()> {
+        for document in documents {
+            let record = DocumentRecord {
+                text: document.text,
+                embedding: document.embedding.vector,
+            };
+            // Re-adding an id replaces the old document
+            self.db
+                .query("UPSERT type::thing($table, $id) CONTENT $content")
+                .bind(("table", self.table.clone()))
+                .bind(("id", document.id))
+                .bind(("content", record))
+                .await
+                .map_err(|e| vector_store_error("store document", e))?
+                .check()
+                .map_err(|e| vector_store_error("store document", e))?;
+        }
+        Ok(())
+    }
+
+    async fn search(&self, query: &Embedding, k: usize) -> EmbeddingResult<Vec<SearchResult>> {
+        let mut response = self
+            .db
+            .query(
+                "SELECT meta::id(id) AS id, text, \
+                 vector::similarity::cosine(embedding, $query) AS score \
+                 FROM type::table($table) ORDER BY score DESC LIMIT $k",
+            )
+            .bind(("table", self.table.clone()))
+            .bind(("query", query.vector.clone()))
+            .bind(("k", k))
+            .await
+            .map_err(|e| vector_store_error("search documents", e))?;
+
+        let records: Vec<ScoredRecord> = response
+            .take(0)
+            .map_err(|e| vector_store_error("parse search results", e))?;
+        Ok(records
+            .into_iter()
+            .map(|record| SearchResult {
+                id: record.id,
+                text: record.text,
+                score: record.score,
+            })
+            .collect())
+    }
+
+    async fn len(&self) -> EmbeddingResult<usize> {
+        let mut response = self
+            .db
+            .query("SELECT count() FROM type::table($table) GROUP ALL")
+            .bind(("table", self.table.clone()))
+            .await
+            .map_err(|e| vector_store_error("count documents", e))?;
+
+        let count: Option<serde_json::Value> = response
+            .take(0)
+            .map_err(|e| vector_store_error("parse count", e))?;
+        Ok(count
+            .as_ref()
+            .and_then(|v| v.get("count"))
+            .and_then(|v| v.as_u64())
+            .unwrap_or(0) as usize)
+    }
+}
+
 /// Builder for SurrealMemoryStore
 #[allow(dead_code)]
 pub struct SurrealMemoryBuilder {
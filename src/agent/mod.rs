@@ -6,12 +6,17 @@ pub mod memory;
 pub mod structured;
 pub mod tools;
 
-pub use agent::{Agent, AgentConfig, AgentError};
+pub use agent::{Agent, AgentConfig, AgentError, ExecutionTrace, Step, StreamItem};
 pub use builder::AgentBuilder;
 pub use context::{Context, ContextMessage};
-pub use memory::{InMemoryStore, Memory, MemoryStore, SurrealMemoryStore};
-pub use structured::{StructuredOutput, StructuredProvider, TypedAgent, TypedAgentBuilder};
+pub use memory::{
+    BackendMemory, InMemoryStore, Memory, MemoryBackend, MemoryRecord, MemoryStore,
+    SurrealMemoryStore,
+};
+pub use structured::{
+    parse_with_repair, StructuredOutput, StructuredProvider, TypedAgent, TypedAgentBuilder,
+};
 pub use tools::{
     CalculatorTool, CodeExecutorTool, DatabaseTool, FileSystemTool, FunctionTool, HttpTool,
-    KeyValueStoreTool, ToolExecutor, ToolRegistry, ToolResult, WebFetchTool,
+    KeyValueStoreTool, ToolExecutor, ToolMeta, ToolRegistry, ToolResult, WebFetchTool,
 };
@@ -1,9 +1,11 @@
 use async_trait::async_trait;
 use serde::de::DeserializeOwned;
+use serde_json::Value;
 use std::marker::PhantomData;
 
 use super::{Agent, AgentError};
-use crate::JsonSchema;
+use crate::providers::json_mode::strip_code_fence;
+use crate::{CompiledSchema, CompletionProvider, CompletionRequest, JsonSchema, Message};
 
 /// Trait for types that can provide a JSON schema
 pub trait StructuredProvider {
@@ -25,12 +27,80 @@ pub trait StructuredOutput {
         T: DeserializeOwned + StructuredProvider + Send;
 }
 
+/// Complete `request` and parse the reply as `T`, validating it against
+/// `T::schema()`. A reply that is not valid JSON or fails validation is sent
+/// back with the validator's errors and the model is asked to fix it, for at
+/// most `max_attempts` completions in total.
+pub async fn parse_with_repair<T>(
+    provider: &dyn CompletionProvider,
+    mut request: CompletionRequest,
+    max_attempts: usize,
+) -> Result<T, AgentError>
+where
+    T: DeserializeOwned + StructuredProvider,
+{
+    // Fail on an invalid schema before spending any completions
+    let compiled = compile_schema(&T::schema())?;
+
+    let mut errors = Vec::new();
+    for _ in 0..max_attempts.max(1) {
+        let response = provider.complete(request.clone()).await?;
+        let text = response
+            .choices
+            .first()
+            .map(|choice| choice.message.content.as_text_lossy().into_owned())
+            .unwrap_or_default();
+
+        errors = match parse_validated(&compiled, &text) {
+            Ok(parsed) => return Ok(parsed),
+            Err(errors) => errors,
+        };
+
+        request.messages.push(Message::assistant(text));
+        request.messages.push(Message::user(format!(
+            "Your response does not match the required JSON schema:\n- {}\n\nRespond again with only the corrected JSON.",
+            errors.join("\n- ")
+        )));
+    }
+
+    Err(AgentError::ValidationError(format!(
+        "Structured response still invalid after {} attempts: {}",
+        max_attempts.max(1),
+        errors.join("; ")
+    )))
+}
+
+/// Parse `text` as `T`, checking it against `schema` first so failures
+/// name the offending paths, e.g. `/items/2/age: "x" is not of type
+/// "integer"`
+fn parse_validated<T: DeserializeOwned>(
+    schema: &CompiledSchema,
+    text: &str,
+) -> Result<T, Vec<String>> {
+    let value = serde_json::from_str::<Value>(strip_code_fence(text))
+        .map_err(|e| vec![format!("invalid JSON: {}", e)])?;
+    let violations = schema.violations(&value);
+    if !violations.is_empty() {
+        return Err(violations.iter().map(ToString::to_string).collect());
+    }
+    serde_json::from_value(value).map_err(|e| vec![e.to_string()])
+}
+
+fn compile_schema(schema: &JsonSchema) -> Result<CompiledSchema, AgentError> {
+    schema.compile().map_err(|e| {
+        AgentError::ConfigError(format!("Invalid JSON schema for {}: {}", schema.name, e))
+    })
+}
+
 #[async_trait]
 impl StructuredOutput for Agent {
     async fn execute_typed<T>(&mut self, input: &str) -> Result<T, AgentError>
     where
         T: DeserializeOwned + StructuredProvider + Send,
     {
+        let schema = T::schema();
+        let compiled = compile_schema(&schema)?;
+
         // Store original config
         let original_config = self.get_config().clone();
 
@@ -44,7 +114,6 @@ impl StructuredOutput for Agent {
         self.update_config(config);
 
         // We'll include the schema requirement in the input message
-        let schema = T::schema();
         let schema_instruction = format!(
             "IMPORTANT: You must respond with valid JSON that matches this schema:\n{}",
             serde_json::to_string_pretty(&schema.schema).unwrap_or_default()
@@ -58,8 +127,11 @@ impl StructuredOutput for Agent {
         self.update_config(original_config);
 
         // Parse the response
-        serde_json::from_str(&response).map_err(|e| {
-            AgentError::ContextError(format!("Failed to parse structured response: {}", e))
+        parse_validated(&compiled, &response).map_err(|errors| {
+            AgentError::ValidationError(format!(
+                "Failed to parse structured response: {}",
+                errors.join("; ")
+            ))
         })
     }
 
@@ -201,6 +273,7 @@ macro_rules! impl_json_schema {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::providers::MockProvider;
     use serde::{Deserialize, Serialize};
 
     #[derive(Debug, Serialize, Deserialize)]
@@ -227,6 +300,101 @@ mod tests {
         }
     }
 
+    fn request() -> CompletionRequest {
+        CompletionRequest::new("mock-model", vec![Message::user("Answer in JSON")])
+    }
+
+    #[tokio::test]
+    async fn test_parse_with_repair_feeds_back_errors() {
+        let provider = MockProvider::new()
+            .with_response("{\"answer\": \"yes\"")
+            .with_response(r#"{"answer": "yes", "confidence": "high"}"#)
+            .with_response("```json\n{\"answer\": \"yes\", \"confidence\": 0.9}\n```");
+
+        let parsed: TestResponse = parse_with_repair(&provider, request(), 3).await.unwrap();
+        assert_eq!(parsed.answer, "yes");
+        assert_eq!(provider.request_count(), 3);
+
+        // The schema violation from the second reply is quoted back to the model
+        let last = provider.last_request().unwrap();
+        let feedback = last.messages.last().unwrap().content.as_text().unwrap();
+        assert!(feedback.contains("\"high\" is not of type \"number\""));
+        assert!(feedback.contains("/confidence"));
+    }
+
+    #[tokio::test]
+    async fn test_parse_with_repair_gives_up() {
+        let provider = MockProvider::new().with_default_response(r#"{"answer": 1}"#);
+
+        let result: Result<TestResponse, _> = parse_with_repair(&provider, request(), 2).await;
+        assert!(matches!(result, Err(AgentError::ValidationError(_))));
+        assert_eq!(provider.request_count(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_schema_errors_report_field_paths() {
+        let schema = serde_json::json!({
+            "type": "object",
+            "properties": {
+                "people": {
+                    "type": "array",
+                    "items": {
+                        "type": "object",
+                        "properties": {
+                            "name": { "type": "string" },
+                            "age": { "type": "integer" }
+                        },
+                        "required": ["name", "age"]
+                    }
+                }
+            },
+            "required": ["people"]
+        });
+        let document = serde_json::json!({
+            "people": [
+                { "name": "Ada", "age": 36 },
+                { "name": "Alan", "age": "41" },
+                { "name": "Grace" }
+            ]
+        });
+
+        let violations = crate::schema_violations(&schema, &document).unwrap();
+        let paths: Vec<&str> = violations.iter().map(|v| v.path.as_str()).collect();
+        assert_eq!(paths, ["/people/1/age", "/people/2"]);
+        assert!(violations[1]
+            .to_string()
+            .contains("\"age\" is a required property"));
+
+        // Tool arguments fail with the same paths
+        let tool = crate::ToolFunction {
+            name: "add_people".to_string(),
+            description: None,
+            parameters: schema,
+        };
+        match tool.validate_arguments(&document) {
+            Err(crate::AiError::InvalidToolParameters {
+                tool_name, message, ..
+            }) => {
+                assert_eq!(tool_name, "add_people");
+                assert!(message.starts_with("/people/1/age: "));
+                assert!(message.contains("; /people/2: "));
+            }
+            other => panic!("expected InvalidToolParameters, got {:?}", other),
+        }
+
+        // And so does a typed agent's parse error
+        let mut agent = TypedAgentBuilder::<TestResponse>::new()
+            .provider(MockProvider::new().with_response(r#"{"answer": "yes"}"#))
+            .build()
+            .unwrap();
+        match agent.execute("Answer in JSON").await {
+            Err(AgentError::ValidationError(message)) => {
+                assert!(message.contains("\"confidence\" is a required property"));
+            }
+            other => panic!("expected ValidationError, got {:?}", other.map(|_| ())),
+        }
+    }
+
     #[test]
     fn test_typed_agent_builder() {
         let _builder = TypedAgentBuilder::<TestResponse>::new()
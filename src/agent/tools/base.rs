@@ -1,7 +1,10 @@
 use async_trait::async_trait;
+use serde::{de::DeserializeOwned, Serialize};
 use serde_json::Value;
 use std::collections::HashMap;
+use std::fmt::Display;
 use std::sync::Arc;
+use std::time::Duration;
 
 use crate::{Tool, ToolFunction, ToolType};
 
@@ -12,6 +15,41 @@ pub enum ToolResult {
     Error(String),
 }
 
+/// How a tool execution went, as recorded by the agent alongside the
+/// result
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ToolMeta {
+    /// Time taken, including any retries
+    pub duration: Duration,
+    /// Attempts made after the first failed with a retryable error
+    pub retries: u32,
+}
+
+impl ToolResult {
+    /// Successful result carrying any serializable value. A value that
+    /// fails to serialize becomes an `Error` result.
+    pub fn success<T: Serialize>(value: T) -> Self {
+        match serde_json::to_value(value) {
+            Ok(value) => ToolResult::Success(value),
+            Err(e) => ToolResult::Error(format!("Failed to serialize tool result: {}", e)),
+        }
+    }
+
+    /// Failed result with the given message
+    pub fn error(message: impl Display) -> Self {
+        ToolResult::Error(message.to_string())
+    }
+
+    /// Deserialize a successful result into `T`. An `Error` result is
+    /// returned as the error.
+    pub fn success_as<T: DeserializeOwned>(&self) -> Result<T, Box<dyn std::error::Error>> {
+        match self {
+            ToolResult::Success(value) => Ok(T::deserialize(value)?),
+            ToolResult::Error(message) => Err(message.clone().into()),
+        }
+    }
+}
+
 /// Trait for implementing tool executors
 #[async_trait]
 pub trait ToolExecutor: Send + Sync {
@@ -20,6 +58,54 @@ pub trait ToolExecutor: Send + Sync {
 
     /// Get the tool definition
     fn definition(&self) -> ToolFunction;
+
+    /// Get the tool definition when it depends on live state, such as the
+    /// tables a database tool can query. Defaults to `definition`.
+    async fn definition_async(&self) -> ToolFunction {
+        self.definition()
+    }
+
+    /// Whether an error returned from `execute` is transient and worth
+    /// retrying under the agent's `tool_retry` policy. A returned
+    /// `ToolResult::Error` is never retried.
+    ///
+    /// By default only timeouts and dropped connections (as `io::Error`,
+    /// `reqwest::Error` or a retryable [`AiError`](crate::AiError)) are
+    /// retried; anything else is assumed to fail the same way again.
+    fn is_retryable(&self, error: &(dyn std::error::Error + 'static)) -> bool {
+        is_retryable_by_default(error)
+    }
+}
+
+fn is_retryable_by_default(error: &(dyn std::error::Error + 'static)) -> bool {
+    use std::io::ErrorKind;
+
+    if let Some(e) = error.downcast_ref::<std::io::Error>() {
+        return matches!(
+            e.kind(),
+            ErrorKind::TimedOut
+                | ErrorKind::Interrupted
+                | ErrorKind::ConnectionReset
+                | ErrorKind::ConnectionAborted
+                | ErrorKind::ConnectionRefused
+                | ErrorKind::BrokenPipe
+        );
+    }
+    if let Some(e) = error.downcast_ref::<reqwest::Error>() {
+        return e.is_timeout() || e.is_connect();
+    }
+    if let Some(e) = error.downcast_ref::<crate::AiError>() {
+        return e.is_retryable();
+    }
+    false
+}
+
+fn named_tool(name: &str, mut definition: ToolFunction) -> Tool {
+    definition.name = name.to_string(); // Ensure name matches registry key
+    Tool {
+        r#type: ToolType::Function,
+        function: definition,
+    }
 }
 
 /// Registry for managing tools
@@ -49,18 +135,35 @@ impl ToolRegistry {
     pub fn to_tools(&self) -> Vec<Tool> {
         self.tools
             .iter()
-            .map(|(name, executor)| {
-                let mut definition = executor.definition();
-                definition.name = name.clone(); // Ensure name matches registry key
-
-                Tool {
-                    r#type: ToolType::Function,
-                    function: definition,
-                }
-            })
+            .map(|(name, executor)| named_tool(name, executor.definition()))
             .collect()
     }
 
+    /// Like [`to_tools`](Self::to_tools), but with each tool's
+    /// [`definition_async`](ToolExecutor::definition_async), so definitions
+    /// that depend on live state are current. The agent uses this for every
+    /// request.
+    pub async fn to_tools_async(&self) -> Vec<Tool> {
+        let mut tools = Vec::with_capacity(self.tools.len());
+        for (name, executor) in &self.tools {
+            tools.push(named_tool(name, executor.definition_async().await));
+        }
+        tools
+    }
+
+    /// Describe every tool as plain text, for models without native tool
+    /// support: one block per tool (sorted by name) with its description
+    /// and a line per parameter
+    pub async fn describe(&self) -> String {
+        let mut tools = self.to_tools_async().await;
+        tools.sort_by(|a, b| a.function.name.cmp(&b.function.name));
+        tools
+            .iter()
+            .map(|tool| describe_tool(&tool.function))
+            .collect::<Vec<_>>()
+            .join("\n\n")
+    }
+
     /// Get all tool names
     pub fn names(&self) -> Vec<&str> {
         self.tools.keys().map(|s| s.as_str()).collect()
@@ -92,6 +195,38 @@ impl ToolRegistry {
     }
 }
 
+/// `name: description` followed by `- param (type, required): description`
+/// lines
+fn describe_tool(function: &ToolFunction) -> String {
+    let mut text = match &function.description {
+        Some(description) => format!("{}: {}", function.name, description),
+        None => function.name.clone(),
+    };
+
+    let required: Vec<&str> = function.parameters["required"]
+        .as_array()
+        .map(|names| names.iter().filter_map(Value::as_str).collect())
+        .unwrap_or_default();
+    let Some(properties) = function.parameters["properties"].as_object() else {
+        return text;
+    };
+    for (name, schema) in properties {
+        let mut details = vec![schema["type"].as_str().unwrap_or("any").to_string()];
+        if required.contains(&name.as_str()) {
+            details.push("required".to_string());
+        }
+        text.push_str(&format!("\n  - {} ({})", name, details.join(", ")));
+        if let Some(description) = schema["description"].as_str() {
+            text.push_str(&format!(": {}", description));
+        }
+        if let Some(options) = schema["enum"].as_array() {
+            let options: Vec<String> = options.iter().map(Value::to_string).collect();
+            text.push_str(&format!(" [one of {}]", options.join(", ")));
+        }
+    }
+    text
+}
+
 impl Default for ToolRegistry {
     fn default() -> Self {
         Self::new()
@@ -120,19 +255,19 @@ impl ToolExecutor for CalculatorTool {
             "multiply" => a * b,
             "divide" => {
                 if b == 0.0 {
-                    return Ok(ToolResult::Error("Division by zero".to_string()));
+                    return Ok(ToolResult::error("Division by zero"));
                 }
                 a / b
             }
             _ => {
-                return Ok(ToolResult::Error(format!(
+                return Ok(ToolResult::error(format!(
                     "Unknown operation: {}",
                     operation
                 )))
             }
         };
 
-        Ok(ToolResult::Success(serde_json::json!({
+        Ok(ToolResult::success(serde_json::json!({
             "result": result,
             "operation": operation,
             "a": a,
@@ -204,16 +339,16 @@ impl ToolExecutor for WebFetchTool {
                     } else {
                         text
                     };
-                    Ok(ToolResult::Success(serde_json::json!({
+                    Ok(ToolResult::success(serde_json::json!({
                         "url": url,
                         "status": status.as_u16(),
                         "content": truncated
                     })))
                 } else {
-                    Ok(ToolResult::Error(format!("HTTP {}", status)))
+                    Ok(ToolResult::error(format!("HTTP {}", status)))
                 }
             }
-            Err(e) => Ok(ToolResult::Error(format!("Request failed: {}", e))),
+            Err(e) => Ok(ToolResult::error(format!("Request failed: {}", e))),
         }
     }
 
@@ -267,12 +402,12 @@ impl ToolExecutor for KeyValueStoreTool {
 
         match action {
             "get" => match store.get(key) {
-                Some(value) => Ok(ToolResult::Success(serde_json::json!({
+                Some(value) => Ok(ToolResult::success(serde_json::json!({
                     "key": key,
                     "value": value,
                     "found": true
                 }))),
-                None => Ok(ToolResult::Success(serde_json::json!({
+                None => Ok(ToolResult::success(serde_json::json!({
                     "key": key,
                     "value": null,
                     "found": false
@@ -283,7 +418,7 @@ impl ToolExecutor for KeyValueStoreTool {
                     .as_str()
                     .ok_or("Missing value for set action")?;
                 store.insert(key.to_string(), value.to_string());
-                Ok(ToolResult::Success(serde_json::json!({
+                Ok(ToolResult::success(serde_json::json!({
                     "key": key,
                     "value": value,
                     "action": "set",
@@ -292,7 +427,7 @@ impl ToolExecutor for KeyValueStoreTool {
             }
             "delete" => {
                 store.remove(key);
-                Ok(ToolResult::Success(serde_json::json!({
+                Ok(ToolResult::success(serde_json::json!({
                     "key": key,
                     "action": "delete",
                     "success": true
@@ -300,12 +435,12 @@ impl ToolExecutor for KeyValueStoreTool {
             }
             "list" => {
                 let keys: Vec<&str> = store.keys().map(|k| k.as_str()).collect();
-                Ok(ToolResult::Success(serde_json::json!({
+                Ok(ToolResult::success(serde_json::json!({
                     "keys": keys,
                     "count": keys.len()
                 })))
             }
-            _ => Ok(ToolResult::Error(format!("Unknown action: {}", action))),
+            _ => Ok(ToolResult::error(format!("Unknown action: {}", action))),
         }
     }
 
@@ -365,8 +500,8 @@ where
 {
     async fn execute(&self, arguments: &str) -> Result<ToolResult, Box<dyn std::error::Error>> {
         match (self.func)(arguments) {
-            Ok(result) => Ok(ToolResult::Success(result)),
-            Err(e) => Ok(ToolResult::Error(e.to_string())),
+            Ok(result) => Ok(ToolResult::success(result)),
+            Err(e) => Ok(ToolResult::error(e)),
         }
     }
 
@@ -400,6 +535,29 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_typed_tool_result() {
+        #[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+        struct Forecast {
+            city: String,
+            high: i32,
+        }
+
+        let forecast = Forecast {
+            city: "Lisbon".to_string(),
+            high: 24,
+        };
+        let result = ToolResult::success(&forecast);
+        assert!(matches!(&result, ToolResult::Success(value) if value["high"] == 24));
+        assert_eq!(result.success_as::<Forecast>().unwrap(), forecast);
+
+        // Wrong shape and error results both fail to deserialize
+        assert!(result.success_as::<Vec<String>>().is_err());
+        let failed = ToolResult::error(format_args!("city {} not found", "Atlantis"));
+        let error = failed.success_as::<Forecast>().unwrap_err();
+        assert_eq!(error.to_string(), "city Atlantis not found");
+    }
+
     #[test]
     fn test_tool_registry() {
         let mut registry = ToolRegistry::new();
@@ -413,4 +571,97 @@ mod tests {
         assert_eq!(tools.len(), 1);
         assert_eq!(tools[0].function.name, "calculator");
     }
+
+    #[tokio::test]
+    async fn test_describe_lists_tools_and_parameters() {
+        let mut registry = ToolRegistry::new();
+        registry.register("calculator", CalculatorTool);
+        registry.register("kv", KeyValueStoreTool::new());
+
+        let text = registry.describe().await;
+        assert!(text.starts_with("calculator: Perform basic arithmetic operations"));
+        assert!(text.contains("\n\nkv: A simple key-value store"));
+        assert!(text.contains("- a (number, required): The first operand"));
+        assert!(text.contains("- key (string, required)"));
+        assert!(text.contains("- value (string): "));
+        assert!(text.contains(r#"[one of "get", "set", "delete", "list"]"#));
+    }
+
+    /// Tool whose schema lists the tables that currently exist
+    struct TableTool {
+        tables: Arc<tokio::sync::Mutex<Vec<String>>>,
+    }
+
+    #[async_trait]
+    impl ToolExecutor for TableTool {
+        async fn execute(
+            &self,
+            _arguments: &str,
+        ) -> Result<ToolResult, Box<dyn std::error::Error>> {
+            Ok(ToolResult::success(Value::Null))
+        }
+
+        fn definition(&self) -> ToolFunction {
+            ToolFunction {
+                name: "query".to_string(),
+                description: Some("Query a table".to_string()),
+                parameters: serde_json::json!({"type": "object", "properties": {}}),
+            }
+        }
+
+        async fn definition_async(&self) -> ToolFunction {
+            let tables = self.tables.lock().await.clone();
+            ToolFunction {
+                parameters: serde_json::json!({
+                    "type": "object",
+                    "properties": {
+                        "table": {"type": "string", "enum": tables}
+                    },
+                    "required": ["table"]
+                }),
+                ..self.definition()
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_async_definition_reflects_runtime_state() {
+        let tables = Arc::new(tokio::sync::Mutex::new(vec!["users".to_string()]));
+        let mut registry = ToolRegistry::new();
+        registry.register(
+            "query",
+            TableTool {
+                tables: tables.clone(),
+            },
+        );
+
+        let tools = registry.to_tools_async().await;
+        assert_eq!(
+            tools[0].function.parameters["properties"]["table"]["enum"],
+            serde_json::json!(["users"])
+        );
+
+        tables.lock().await.push("orders".to_string());
+        let tools = registry.to_tools_async().await;
+        assert_eq!(
+            tools[0].function.parameters["properties"]["table"]["enum"],
+            serde_json::json!(["users", "orders"])
+        );
+        // The sync definition is the static one
+        assert!(registry.to_tools()[0].function.parameters["properties"]
+            .get("table")
+            .is_none());
+    }
+
+    #[test]
+    fn test_only_transient_errors_are_retryable_by_default() {
+        let reset = std::io::Error::from(std::io::ErrorKind::ConnectionReset);
+        assert!(CalculatorTool.is_retryable(&reset));
+
+        let missing = std::io::Error::from(std::io::ErrorKind::NotFound);
+        assert!(!CalculatorTool.is_retryable(&missing));
+
+        let parse = serde_json::from_str::<Value>("{").unwrap_err();
+        assert!(!CalculatorTool.is_retryable(&parse));
+    }
 }
@@ -102,7 +102,7 @@ impl ToolExecutor for CodeExecutorTool {
             .allowed_languages
             .contains(&request.language.to_lowercase())
         {
-            return Ok(ToolResult::Error(format!(
+            return Ok(ToolResult::error(format!(
                 "Language '{}' is not allowed. Allowed languages: {:?}",
                 request.language, self.allowed_languages
             )));
@@ -166,7 +166,7 @@ impl ToolExecutor for CodeExecutorTool {
         let stderr_len = output.stderr.len();
 
         if stdout_len + stderr_len > self.max_output_size {
-            return Ok(ToolResult::Error(format!(
+            return Ok(ToolResult::error(format!(
                 "Output too large: {} bytes (max: {} bytes)",
                 stdout_len + stderr_len,
                 self.max_output_size
@@ -177,7 +177,7 @@ impl ToolExecutor for CodeExecutorTool {
         let stdout = String::from_utf8_lossy(&output.stdout);
         let stderr = String::from_utf8_lossy(&output.stderr);
 
-        Ok(ToolResult::Success(serde_json::json!({
+        Ok(ToolResult::success(serde_json::json!({
             "success": output.status.success(),
             "exit_code": output.status.code(),
             "stdout": stdout,
@@ -86,9 +86,7 @@ impl ToolExecutor for DatabaseTool {
             DatabaseOperation::Query { sql, params } => {
                 // Check permissions
                 if !self.allow_write && !Self::is_read_only(&sql) {
-                    return Ok(ToolResult::Error(
-                        "Write operations are not allowed".to_string(),
-                    ));
+                    return Ok(ToolResult::error("Write operations are not allowed"));
                 }
 
                 // Build query
@@ -106,30 +104,24 @@ impl ToolExecutor for DatabaseTool {
                                 } else if let Some(f) = n.as_f64() {
                                     query.bind(f)
                                 } else {
-                                    return Ok(ToolResult::Error(
-                                        "Invalid number parameter".to_string(),
-                                    ));
+                                    return Ok(ToolResult::error("Invalid number parameter"));
                                 }
                             }
                             Value::String(s) => query.bind(s),
-                            _ => {
-                                return Ok(ToolResult::Error("Invalid parameter type".to_string()))
-                            }
+                            _ => return Ok(ToolResult::error("Invalid parameter type")),
                         };
                     }
                 }
 
                 // Execute query
                 let rows = query.fetch_all(&self.pool).await.map_err(|e| {
-                    Box::new(std::io::Error::new(
-                        std::io::ErrorKind::Other,
-                        format!("Query failed: {}", e),
-                    )) as Box<dyn std::error::Error>
+                    Box::new(std::io::Error::other(format!("Query failed: {}", e)))
+                        as Box<dyn std::error::Error>
                 })?;
 
                 // Check row limit
                 if rows.len() > self.max_rows {
-                    return Ok(ToolResult::Error(format!(
+                    return Ok(ToolResult::error(format!(
                         "Query returned too many rows: {} (max: {})",
                         rows.len(),
                         self.max_rows
@@ -164,7 +156,7 @@ impl ToolExecutor for DatabaseTool {
                     results.push(Value::Object(row_map.into_iter().collect()));
                 }
 
-                Ok(ToolResult::Success(serde_json::json!({
+                Ok(ToolResult::success(serde_json::json!({
                     "rows": results,
                     "row_count": results.len()
                 })))
@@ -185,19 +177,15 @@ impl ToolExecutor for DatabaseTool {
                         )
                     }
                 } else {
-                    return Ok(ToolResult::Error(
-                        "Table name required for schema query".to_string(),
-                    ));
+                    return Ok(ToolResult::error("Table name required for schema query"));
                 };
 
                 let rows = sqlx::query(&schema_query)
                     .fetch_all(&self.pool)
                     .await
                     .map_err(|e| {
-                        Box::new(std::io::Error::new(
-                            std::io::ErrorKind::Other,
-                            format!("Schema query failed: {}", e),
-                        )) as Box<dyn std::error::Error>
+                        Box::new(std::io::Error::other(format!("Schema query failed: {}", e)))
+                            as Box<dyn std::error::Error>
                     })?;
 
                 let mut columns = Vec::new();
@@ -211,7 +199,7 @@ impl ToolExecutor for DatabaseTool {
                     columns.push(column_info);
                 }
 
-                Ok(ToolResult::Success(serde_json::json!({
+                Ok(ToolResult::success(serde_json::json!({
                     "table": table,
                     "columns": columns
                 })))
@@ -227,10 +215,8 @@ impl ToolExecutor for DatabaseTool {
                     .fetch_all(&self.pool)
                     .await
                     .map_err(|e| {
-                        Box::new(std::io::Error::new(
-                            std::io::ErrorKind::Other,
-                            format!("Tables query failed: {}", e),
-                        )) as Box<dyn std::error::Error>
+                        Box::new(std::io::Error::other(format!("Tables query failed: {}", e)))
+                            as Box<dyn std::error::Error>
                     })?;
 
                 let mut tables = Vec::new();
@@ -240,7 +226,7 @@ impl ToolExecutor for DatabaseTool {
                     }
                 }
 
-                Ok(ToolResult::Success(serde_json::json!({
+                Ok(ToolResult::success(serde_json::json!({
                     "tables": tables
                 })))
             }
@@ -113,7 +113,7 @@ impl ToolExecutor for FileSystemTool {
                     .map_err(|e| format!("Failed to get file metadata: {}", e))?;
 
                 if metadata.len() > self.max_file_size as u64 {
-                    return Ok(ToolResult::Error(format!(
+                    return Ok(ToolResult::error(format!(
                         "File too large: {} bytes (max: {} bytes)",
                         metadata.len(),
                         self.max_file_size
@@ -123,7 +123,7 @@ impl ToolExecutor for FileSystemTool {
                 let content = fs::read_to_string(&full_path)
                     .map_err(|e| format!("Failed to read file: {}", e))?;
 
-                Ok(ToolResult::Success(serde_json::json!({
+                Ok(ToolResult::success(serde_json::json!({
                     "path": full_path.display().to_string(),
                     "content": content,
                     "size": metadata.len()
@@ -132,9 +132,7 @@ impl ToolExecutor for FileSystemTool {
 
             FileOperation::Write { path, content } => {
                 if !self.allow_write {
-                    return Ok(ToolResult::Error(
-                        "Write operations are not allowed".to_string(),
-                    ));
+                    return Ok(ToolResult::error("Write operations are not allowed"));
                 }
 
                 let full_path = self.resolve_path(&path).map_err(|e| {
@@ -151,7 +149,7 @@ impl ToolExecutor for FileSystemTool {
                 fs::write(&full_path, content)
                     .map_err(|e| format!("Failed to write file: {}", e))?;
 
-                Ok(ToolResult::Success(serde_json::json!({
+                Ok(ToolResult::success(serde_json::json!({
                     "path": full_path.display().to_string(),
                     "success": true
                 })))
@@ -191,7 +189,7 @@ impl ToolExecutor for FileSystemTool {
                     }
                 }
 
-                Ok(ToolResult::Success(serde_json::json!({
+                Ok(ToolResult::success(serde_json::json!({
                     "path": dir_path.display().to_string(),
                     "files": files,
                     "directories": dirs
@@ -200,9 +198,7 @@ impl ToolExecutor for FileSystemTool {
 
             FileOperation::Delete { path } => {
                 if !self.allow_write {
-                    return Ok(ToolResult::Error(
-                        "Delete operations are not allowed".to_string(),
-                    ));
+                    return Ok(ToolResult::error("Delete operations are not allowed"));
                 }
 
                 let full_path = self.resolve_path(&path).map_err(|e| {
@@ -218,7 +214,7 @@ impl ToolExecutor for FileSystemTool {
                         .map_err(|e| format!("Failed to delete file: {}", e))?;
                 }
 
-                Ok(ToolResult::Success(serde_json::json!({
+                Ok(ToolResult::success(serde_json::json!({
                     "path": full_path.display().to_string(),
                     "deleted": true
                 })))
@@ -228,11 +224,11 @@ impl ToolExecutor for FileSystemTool {
                 let full_path = match self.resolve_path(&path) {
                     Ok(p) => p,
                     Err(_) => {
-                        return Ok(ToolResult::Success(serde_json::json!({ "exists": false })))
+                        return Ok(ToolResult::success(serde_json::json!({ "exists": false })))
                     }
                 };
 
-                Ok(ToolResult::Success(serde_json::json!({
+                Ok(ToolResult::success(serde_json::json!({
                     "path": full_path.display().to_string(),
                     "exists": full_path.exists()
                 })))
@@ -240,8 +236,8 @@ impl ToolExecutor for FileSystemTool {
 
             FileOperation::CreateDir { path } => {
                 if !self.allow_write {
-                    return Ok(ToolResult::Error(
-                        "Create directory operations are not allowed".to_string(),
+                    return Ok(ToolResult::error(
+                        "Create directory operations are not allowed",
                     ));
                 }
 
@@ -253,7 +249,7 @@ impl ToolExecutor for FileSystemTool {
                 fs::create_dir_all(&full_path)
                     .map_err(|e| format!("Failed to create directory: {}", e))?;
 
-                Ok(ToolResult::Success(serde_json::json!({
+                Ok(ToolResult::success(serde_json::json!({
                     "path": full_path.display().to_string(),
                     "created": true
                 })))
@@ -119,7 +119,7 @@ impl ToolExecutor for HttpTool {
 
         // Validate domain
         if !self.is_domain_allowed(&request.url) {
-            return Ok(ToolResult::Error(format!(
+            return Ok(ToolResult::error(format!(
                 "Domain not allowed: {}",
                 request.url
             )));
@@ -172,7 +172,7 @@ impl ToolExecutor for HttpTool {
         // Check content length
         if let Some(content_length) = response.content_length() {
             if content_length > self.max_response_size as u64 {
-                return Ok(ToolResult::Error(format!(
+                return Ok(ToolResult::error(format!(
                     "Response too large: {} bytes (max: {} bytes)",
                     content_length, self.max_response_size
                 )));
@@ -186,7 +186,7 @@ impl ToolExecutor for HttpTool {
             .map_err(|e| Box::new(e) as Box<dyn std::error::Error>)?;
 
         if body_bytes.len() > self.max_response_size {
-            return Ok(ToolResult::Error(format!(
+            return Ok(ToolResult::error(format!(
                 "Response too large: {} bytes (max: {} bytes)",
                 body_bytes.len(),
                 self.max_response_size
@@ -206,7 +206,7 @@ impl ToolExecutor for HttpTool {
             response_headers.insert(key.to_string(), value.to_str().unwrap_or("").to_string());
         }
 
-        Ok(ToolResult::Success(serde_json::json!({
+        Ok(ToolResult::success(serde_json::json!({
             "status": status.as_u16(),
             "status_text": status.canonical_reason().unwrap_or(""),
             "headers": response_headers,
@@ -214,6 +214,13 @@ impl ToolExecutor for HttpTool {
         })))
     }
 
+    /// Only failures to reach the server are retried, not bad input
+    fn is_retryable(&self, error: &(dyn std::error::Error + 'static)) -> bool {
+        error
+            .downcast_ref::<reqwest::Error>()
+            .is_some_and(|e| e.is_timeout() || e.is_connect() || e.is_request())
+    }
+
     fn definition(&self) -> ToolFunction {
         ToolFunction {
             name: "http".to_string(),
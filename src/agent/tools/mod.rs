@@ -1,8 +1,8 @@
 // Base tools functionality
 mod base;
 pub use base::{
-    CalculatorTool, FunctionTool, KeyValueStoreTool, ToolExecutor, ToolRegistry, ToolResult,
-    WebFetchTool,
+    CalculatorTool, FunctionTool, KeyValueStoreTool, ToolExecutor, ToolMeta, ToolRegistry,
+    ToolResult, WebFetchTool,
 };
 
 // Tool implementations
@@ -0,0 +1,140 @@
+//! Backoff delays computed from a [`RetryConfig`], shared by
+//! [`RetryExecutor`](crate::RetryExecutor) and usable on their own, for
+//! example when polling a long-running job.
+
+use rand::Rng;
+use std::time::Duration;
+
+use crate::{BackoffStrategy, JitterStrategy, RetryConfig};
+
+/// Delay to wait after the given 1-based `attempt`, with the configured
+/// backoff and jitter applied and capped at `max_delay`
+pub fn next_delay(attempt: u32, config: &RetryConfig) -> Duration {
+    delay_after(attempt, None, config)
+}
+
+/// Endless sequence of delays for attempts 1, 2, 3, ...; use `take` to
+/// bound it
+pub fn iter(config: &RetryConfig) -> Backoff {
+    Backoff {
+        config: config.clone(),
+        attempt: 0,
+        last_delay: None,
+    }
+}
+
+/// Iterator returned by [`iter`]
+#[derive(Debug, Clone)]
+pub struct Backoff {
+    config: RetryConfig,
+    attempt: u32,
+    last_delay: Option<Duration>,
+}
+
+impl Iterator for Backoff {
+    type Item = Duration;
+
+    fn next(&mut self) -> Option<Duration> {
+        self.attempt = self.attempt.saturating_add(1);
+        let delay = delay_after(self.attempt, self.last_delay, &self.config);
+        self.last_delay = Some(delay);
+        Some(delay)
+    }
+}
+
+/// `last_delay` feeds decorrelated jitter; without it the initial delay is
+/// used
+pub(crate) fn delay_after(
+    attempt: u32,
+    last_delay: Option<Duration>,
+    config: &RetryConfig,
+) -> Duration {
+    let index = attempt.saturating_sub(1);
+    let base_delay = match &config.backoff {
+        BackoffStrategy::Fixed => config.initial_delay,
+
+        BackoffStrategy::Linear => config.initial_delay.saturating_mul(attempt),
+
+        BackoffStrategy::Exponential { multiplier } => {
+            let delay_ms = config.initial_delay.as_millis() as f64 * multiplier.powi(index as i32);
+            Duration::from_millis(delay_ms as u64)
+        }
+
+        BackoffStrategy::Custom(delays) => delays
+            .get(index as usize)
+            .copied()
+            .unwrap_or(config.max_delay),
+    };
+
+    let jittered_delay = apply_jitter(base_delay, last_delay, config);
+
+    // Ensure delay doesn't exceed maximum
+    std::cmp::min(jittered_delay, config.max_delay)
+}
+
+fn apply_jitter(delay: Duration, last_delay: Option<Duration>, config: &RetryConfig) -> Duration {
+    let mut rng = rand::thread_rng();
+
+    match &config.jitter {
+        JitterStrategy::None => delay,
+
+        JitterStrategy::Full => {
+            let jitter_ms = rng.gen_range(0..=delay.as_millis() as u64);
+            Duration::from_millis(jitter_ms)
+        }
+
+        JitterStrategy::Half => {
+            let base_ms = delay.as_millis() as u64 / 2;
+            let jitter_ms = base_ms + rng.gen_range(0..=base_ms);
+            Duration::from_millis(jitter_ms)
+        }
+
+        JitterStrategy::Fixed(jitter_amount) => {
+            let jitter_ms = rng.gen_range(0..=jitter_amount.as_millis() as u64);
+            delay + Duration::from_millis(jitter_ms)
+        }
+
+        JitterStrategy::Decorrelated => {
+            // Decorrelated jitter: next_delay = random(base_delay, last_delay * 3)
+            let last_delay = last_delay.unwrap_or(config.initial_delay);
+
+            let min_delay = delay.as_millis() as u64;
+            let max_delay = (last_delay.as_millis() as u64 * 3).max(min_delay);
+
+            let jitter_ms = rng.gen_range(min_delay..=max_delay);
+            Duration::from_millis(jitter_ms)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(jitter: JitterStrategy) -> RetryConfig {
+        RetryConfig {
+            initial_delay: Duration::from_millis(100),
+            max_delay: Duration::from_secs(2),
+            backoff: BackoffStrategy::Exponential { multiplier: 2.0 },
+            jitter,
+            ..RetryConfig::default()
+        }
+    }
+
+    #[test]
+    fn test_delays_grow_within_bounds() {
+        let plain = config(JitterStrategy::None);
+        let delays: Vec<Duration> = iter(&plain).take(8).collect();
+        assert_eq!(delays[0], Duration::from_millis(100));
+        assert_eq!(delays[3], Duration::from_millis(800));
+        assert!(delays.windows(2).all(|w| w[0] <= w[1]));
+        assert_eq!(delays[7], plain.max_delay);
+        assert_eq!(next_delay(3, &plain), Duration::from_millis(400));
+
+        // Full jitter stays under the un-jittered delay for each attempt
+        let jittered = config(JitterStrategy::Full);
+        for (attempt, delay) in (1..).zip(iter(&jittered).take(8)) {
+            assert!(delay <= next_delay(attempt, &plain));
+        }
+    }
+}
@@ -0,0 +1,347 @@
+use async_trait::async_trait;
+use lru::LruCache;
+use serde::{Deserialize, Serialize};
+use std::num::NonZeroUsize;
+use std::sync::{Arc, Mutex};
+
+use super::{
+    models::{Embedding, EmbeddingRequest, EmbeddingResponse},
+    provider::{EmbeddingProvider, Result},
+};
+use crate::providers::recording::fnv1a;
+
+/// A cached vector together with the `(model, text)` it was computed for.
+/// Keys are hashes, so the pair is compared on read to rule out collisions.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CachedEmbedding {
+    pub model: String,
+    pub text: String,
+    pub vector: Vec<f32>,
+}
+
+/// Storage for cached embedding vectors. Lookups and writes are best effort:
+/// a backend failure is treated as a miss rather than failing the request.
+#[async_trait]
+pub trait EmbeddingCache: Send + Sync {
+    async fn get(&self, key: &str) -> Option<CachedEmbedding>;
+
+    async fn put(&self, key: &str, entry: CachedEmbedding);
+}
+
+/// In-memory cache that evicts the least recently used vector when full
+pub struct LruEmbeddingCache {
+    entries: Mutex<LruCache<String, CachedEmbedding>>,
+}
+
+impl LruEmbeddingCache {
+    pub fn new(capacity: usize) -> Self {
+        let capacity = NonZeroUsize::new(capacity).unwrap_or(NonZeroUsize::MIN);
+        Self {
+            entries: Mutex::new(LruCache::new(capacity)),
+        }
+    }
+
+    /// Number of cached vectors
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[async_trait]
+impl EmbeddingCache for LruEmbeddingCache {
+    async fn get(&self, key: &str) -> Option<CachedEmbedding> {
+        self.entries.lock().unwrap().get(key).cloned()
+    }
+
+    async fn put(&self, key: &str, entry: CachedEmbedding) {
+        self.entries.lock().unwrap().put(key.to_string(), entry);
+    }
+}
+
+/// Cache backed by Redis, so vectors survive restarts and are shared
+/// between processes
+#[cfg(feature = "redis")]
+pub struct RedisEmbeddingCache {
+    connection: redis::aio::MultiplexedConnection,
+    prefix: String,
+    ttl: Option<std::time::Duration>,
+}
+
+#[cfg(feature = "redis")]
+impl RedisEmbeddingCache {
+    /// Connect to the Redis server at `url`
+    pub async fn new(url: &str) -> Result<Self> {
+        let connection = redis::Client::open(url)
+            .map_err(|e| super::EmbeddingError::ProviderError(e.to_string()))?
+            .get_multiplexed_async_connection()
+            .await
+            .map_err(|e| super::EmbeddingError::ProviderError(e.to_string()))?;
+
+        Ok(Self {
+            connection,
+            prefix: "lib_ai:embedding:".to_string(),
+            ttl: None,
+        })
+    }
+
+    /// Prefix prepended to every key (default `lib_ai:embedding:`)
+    pub fn with_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.prefix = prefix.into();
+        self
+    }
+
+    /// Expire cached vectors after `ttl`
+    pub fn with_ttl(mut self, ttl: std::time::Duration) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+}
+
+#[cfg(feature = "redis")]
+#[async_trait]
+impl EmbeddingCache for RedisEmbeddingCache {
+    async fn get(&self, key: &str) -> Option<CachedEmbedding> {
+        use redis::AsyncCommands;
+
+        let mut connection = self.connection.clone();
+        let value: Option<String> = connection
+            .get(format!("{}{}", self.prefix, key))
+            .await
+            .ok()?;
+        serde_json::from_str(&value?).ok()
+    }
+
+    async fn put(&self, key: &str, entry: CachedEmbedding) {
+        use redis::AsyncCommands;
+
+        let Ok(value) = serde_json::to_string(&entry) else {
+            return;
+        };
+        let key = format!("{}{}", self.prefix, key);
+        let mut connection = self.connection.clone();
+        let _: redis::RedisResult<()> = match self.ttl {
+            Some(ttl) => connection.set_ex(key, value, ttl.as_secs().max(1)).await,
+            None => connection.set(key, value).await,
+        };
+    }
+}
+
+/// Wraps an embedding provider and serves vectors for previously seen
+/// `(model, text)` pairs from a cache, only sending the misses upstream
+pub struct CachingEmbeddingProvider {
+    inner: Arc<dyn EmbeddingProvider>,
+    cache: Arc<dyn EmbeddingCache>,
+}
+
+impl CachingEmbeddingProvider {
+    /// Cache up to `capacity` vectors in memory
+    pub fn new(inner: Arc<dyn EmbeddingProvider>, capacity: usize) -> Self {
+        Self::with_cache(inner, Arc::new(LruEmbeddingCache::new(capacity)))
+    }
+
+    /// Use a custom cache backend, such as `RedisEmbeddingCache` (behind the
+    /// `redis` feature)
+    pub fn with_cache(inner: Arc<dyn EmbeddingProvider>, cache: Arc<dyn EmbeddingCache>) -> Self {
+        Self { inner, cache }
+    }
+
+    /// Stable cache key for a text embedded with `model`. Different pairs can
+    /// share a key; entries are checked against the pair when read.
+    pub fn cache_key(model: &str, text: &str) -> String {
+        // The separator keeps ("ab", "c") and ("a", "bc") apart
+        format!("{:016x}", fnv1a(format!("{}\0{}", model, text).as_bytes()))
+    }
+}
+
+#[async_trait]
+impl EmbeddingProvider for CachingEmbeddingProvider {
+    async fn embed(&self, request: EmbeddingRequest) -> Result<EmbeddingResponse> {
+        let keys: Vec<String> = request
+            .input
+            .iter()
+            .map(|text| Self::cache_key(&request.model, text))
+            .collect();
+
+        let mut vectors = Vec::with_capacity(keys.len());
+        let mut misses = Vec::new();
+        for (index, key) in keys.iter().enumerate() {
+            let vector = self
+                .cache
+                .get(key)
+                .await
+                .filter(|entry| entry.model == request.model && entry.text == request.input[index])
+                .map(|entry| entry.vector);
+            if vector.is_none() {
+                misses.push(index);
+            }
+            vectors.push(vector);
+        }
+
+        let mut model = request.model.clone();
+        let mut usage = None;
+        if !misses.is_empty() {
+            let response = self
+                .inner
+                .embed(EmbeddingRequest {
+                    input: misses.iter().map(|&i| request.input[i].clone()).collect(),
+                    model: request.model.clone(),
+                })
+                .await?;
+
+            for embedding in response.embeddings {
+                // The inner index points into the list of misses
+                if let Some(&index) = misses.get(embedding.index) {
+                    let entry = CachedEmbedding {
+                        model: request.model.clone(),
+                        text: request.input[index].clone(),
+                        vector: embedding.vector.clone(),
+                    };
+                    self.cache.put(&keys[index], entry).await;
+                    vectors[index] = Some(embedding.vector);
+                }
+            }
+            if !response.model.is_empty() {
+                model = response.model;
+            }
+            usage = response.usage;
+        }
+
+        Ok(EmbeddingResponse {
+            embeddings: vectors
+                .into_iter()
+                .enumerate()
+                .filter_map(|(index, vector)| {
+                    Some(Embedding {
+                        vector: vector?,
+                        index,
+                    })
+                })
+                .collect(),
+            model,
+            usage,
+        })
+    }
+
+    fn default_model(&self) -> &str {
+        self.inner.default_model()
+    }
+
+    fn dimension(&self) -> usize {
+        self.inner.dimension()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::embeddings::MockEmbeddingProvider;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Counts calls and texts sent to the wrapped provider
+    struct CountingProvider {
+        inner: MockEmbeddingProvider,
+        calls: AtomicUsize,
+        texts: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl EmbeddingProvider for CountingProvider {
+        async fn embed(&self, request: EmbeddingRequest) -> Result<EmbeddingResponse> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            self.texts.fetch_add(request.input.len(), Ordering::SeqCst);
+            self.inner.embed(request).await
+        }
+
+        fn default_model(&self) -> &str {
+            self.inner.default_model()
+        }
+
+        fn dimension(&self) -> usize {
+            self.inner.dimension()
+        }
+    }
+
+    fn request(input: &[&str]) -> EmbeddingRequest {
+        EmbeddingRequest {
+            input: input.iter().map(|s| s.to_string()).collect(),
+            model: "mock".to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_repeated_inputs_call_inner_once() {
+        let inner = Arc::new(CountingProvider {
+            inner: MockEmbeddingProvider::new(8),
+            calls: AtomicUsize::new(0),
+            texts: AtomicUsize::new(0),
+        });
+        let provider = CachingEmbeddingProvider::new(inner.clone(), 16);
+
+        let first = provider.embed(request(&["alpha", "beta"])).await.unwrap();
+        let second = provider.embed(request(&["alpha", "beta"])).await.unwrap();
+        assert_eq!(inner.calls.load(Ordering::SeqCst), 1);
+        // The mock adds random noise, so equal vectors mean a cache hit
+        for (a, b) in first.embeddings.iter().zip(&second.embeddings) {
+            assert_eq!(a.index, b.index);
+            assert_eq!(a.vector, b.vector);
+        }
+
+        // Only the new text goes upstream, and results keep request order
+        let mixed = provider.embed(request(&["gamma", "alpha"])).await.unwrap();
+        assert_eq!(inner.calls.load(Ordering::SeqCst), 2);
+        assert_eq!(inner.texts.load(Ordering::SeqCst), 3);
+        assert_eq!(mixed.embeddings[1].index, 1);
+        assert_eq!(mixed.embeddings[1].vector, first.embeddings[0].vector);
+
+        // The model is part of the key
+        let mut other_model = request(&["alpha"]);
+        other_model.model = "other".to_string();
+        provider.embed(other_model).await.unwrap();
+        assert_eq!(inner.calls.load(Ordering::SeqCst), 3);
+    }
+
+    fn entry(text: &str, vector: Vec<f32>) -> CachedEmbedding {
+        CachedEmbedding {
+            model: "mock".to_string(),
+            text: text.to_string(),
+            vector,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_lru_evicts_least_recently_used() {
+        let cache = LruEmbeddingCache::new(2);
+        cache.put("a", entry("a", vec![1.0])).await;
+        cache.put("b", entry("b", vec![2.0])).await;
+        assert!(cache.get("a").await.is_some());
+        cache.put("c", entry("c", vec![3.0])).await;
+
+        assert_eq!(cache.len(), 2);
+        assert!(cache.get("b").await.is_none());
+        assert_eq!(cache.get("a").await.map(|e| e.vector), Some(vec![1.0]));
+    }
+
+    #[tokio::test]
+    async fn test_colliding_key_is_a_miss() {
+        let inner = Arc::new(CountingProvider {
+            inner: MockEmbeddingProvider::new(8),
+            calls: AtomicUsize::new(0),
+            texts: AtomicUsize::new(0),
+        });
+        let cache = Arc::new(LruEmbeddingCache::new(16));
+        let provider = CachingEmbeddingProvider::with_cache(inner.clone(), cache.clone());
+
+        // Another text stored under alpha's key must not be served for alpha
+        let key = CachingEmbeddingProvider::cache_key("mock", "alpha");
+        cache.put(&key, entry("not alpha", vec![9.0; 8])).await;
+
+        let response = provider.embed(request(&["alpha"])).await.unwrap();
+        assert_eq!(inner.calls.load(Ordering::SeqCst), 1);
+        assert_ne!(response.embeddings[0].vector, vec![9.0; 8]);
+        assert_eq!(cache.get(&key).await.unwrap().text, "alpha");
+    }
+}
@@ -29,6 +29,13 @@ impl LocalEmbeddingProvider {
         }
     }
 
+    /// Send requests through `client`, sharing its connection pool and DNS
+    /// cache with other providers
+    pub fn with_client(mut self, client: Client) -> Self {
+        self.client = client;
+        self
+    }
+
     /// Create provider for sentence-transformers all-MiniLM-L6-v2
     pub fn all_minilm_l6_v2(base_url: String) -> Self {
         Self::new(base_url, "all-MiniLM-L6-v2".to_string(), 384)
@@ -85,6 +92,7 @@ impl EmbeddingProvider for LocalEmbeddingProvider {
 
         Ok(EmbeddingResponse {
             embeddings,
+            model: self.model_name.clone(),
             usage: None, // Local models typically don't report usage
         })
     }
@@ -143,6 +151,7 @@ impl EmbeddingProvider for MockEmbeddingProvider {
 
         Ok(EmbeddingResponse {
             embeddings,
+            model: request.model,
             usage: None,
         })
     }
@@ -1,9 +1,17 @@
+pub mod cache;
 pub mod local;
 pub mod models;
 pub mod openai;
 pub mod provider;
+pub mod search;
 
+#[cfg(feature = "redis")]
+pub use cache::RedisEmbeddingCache;
+pub use cache::{CachedEmbedding, CachingEmbeddingProvider, EmbeddingCache, LruEmbeddingCache};
 pub use local::{LocalEmbeddingProvider, MockEmbeddingProvider};
-pub use models::{Embedding, EmbeddingRequest, EmbeddingResponse};
+pub use models::{Embedding, EmbeddingRequest, EmbeddingResponse, EmbeddingUsage};
 pub use openai::{OpenAIEmbeddingModel, OpenAIEmbeddingProvider};
 pub use provider::{EmbeddingError, EmbeddingProvider};
+pub use search::{
+    similarity_search, Document, InMemoryVectorStore, SearchResult, SemanticSearch, VectorStore,
+};
@@ -1,4 +1,6 @@
 use serde::{Deserialize, Serialize};
+use std::cmp::{Ordering, Reverse};
+use std::collections::BinaryHeap;
 
 /// A single embedding vector
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -26,6 +28,10 @@ pub struct EmbeddingResponse {
     /// The generated embeddings
     pub embeddings: Vec<Embedding>,
 
+    /// The model that produced the embeddings
+    #[serde(default)]
+    pub model: String,
+
     /// Token usage information
     pub usage: Option<EmbeddingUsage>,
 }
@@ -75,4 +81,119 @@ impl Embedding {
             .sum::<f32>()
             .sqrt()
     }
+
+    /// Return the `k` corpus entries most similar to `query` by cosine
+    /// similarity, most similar first.
+    ///
+    /// Keeps a heap of at most `k` entries, so the cost is `O(n log k)`
+    /// rather than sorting the whole corpus.
+    pub fn top_k<'a>(
+        query: &Embedding,
+        corpus: &[(&'a str, Embedding)],
+        k: usize,
+    ) -> Vec<(&'a str, f32)> {
+        Self::top_k_indices(query, corpus.iter().map(|(_, embedding)| embedding), k)
+            .into_iter()
+            .map(|(index, score)| (corpus[index].0, score))
+            .collect()
+    }
+
+    /// Like [`top_k`](Self::top_k) over borrowed embeddings, returning the
+    /// position of each of the best `k` in `corpus`
+    pub fn top_k_indices<'a>(
+        query: &Embedding,
+        corpus: impl IntoIterator<Item = &'a Embedding>,
+        k: usize,
+    ) -> Vec<(usize, f32)> {
+        if k == 0 {
+            return Vec::new();
+        }
+
+        // Min-heap on score: the root is the weakest of the current best k.
+        // `k` may be far larger than the corpus, so size the heap by both.
+        let corpus = corpus.into_iter();
+        let capacity = k.min(corpus.size_hint().0).saturating_add(1);
+        let mut heap: BinaryHeap<Reverse<Scored>> = BinaryHeap::with_capacity(capacity);
+        for (index, embedding) in corpus.enumerate() {
+            let scored = Scored {
+                score: query.cosine_similarity(embedding),
+                index,
+            };
+            if heap.len() < k {
+                heap.push(Reverse(scored));
+            } else if heap.peek().is_some_and(|weakest| scored > weakest.0) {
+                heap.pop();
+                heap.push(Reverse(scored));
+            }
+        }
+
+        heap.into_sorted_vec()
+            .into_iter()
+            .map(|Reverse(scored)| (scored.index, scored.score))
+            .collect()
+    }
+}
+
+/// Similarity score of a corpus entry; ties rank the earlier entry higher
+#[derive(Debug, Clone, Copy)]
+struct Scored {
+    score: f32,
+    index: usize,
+}
+
+impl PartialEq for Scored {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Scored {}
+
+impl PartialOrd for Scored {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Scored {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.score
+            .total_cmp(&other.score)
+            .then_with(|| other.index.cmp(&self.index))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn embedding(vector: Vec<f32>) -> Embedding {
+        Embedding { vector, index: 0 }
+    }
+
+    #[test]
+    fn test_top_k_orders_by_similarity() {
+        let corpus = vec![
+            ("east", embedding(vec![1.0, 0.0])),
+            ("north", embedding(vec![0.0, 1.0])),
+            ("north-east", embedding(vec![1.0, 1.0])),
+            ("west", embedding(vec![-1.0, 0.0])),
+            ("mostly-north", embedding(vec![0.1, 1.0])),
+        ];
+        let query = embedding(vec![0.0, 2.0]);
+
+        let results = Embedding::top_k(&query, &corpus, 3);
+        let names: Vec<&str> = results.iter().map(|(name, _)| *name).collect();
+        assert_eq!(names, vec!["north", "mostly-north", "north-east"]);
+        assert!((results[0].1 - 1.0).abs() < 1e-6);
+        assert!(results.windows(2).all(|w| w[0].1 >= w[1].1));
+
+        // k larger than the corpus returns everything, k of zero nothing
+        assert_eq!(Embedding::top_k(&query, &corpus, 10).len(), corpus.len());
+        assert_eq!(
+            Embedding::top_k(&query, &corpus, usize::MAX).len(),
+            corpus.len()
+        );
+        assert!(Embedding::top_k(&query, &corpus, 0).is_empty());
+    }
 }
@@ -1,15 +1,17 @@
 use async_trait::async_trait;
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
+use std::sync::Arc;
 
 use super::{
     models::{Embedding, EmbeddingRequest, EmbeddingResponse, EmbeddingUsage},
     provider::{EmbeddingError, EmbeddingProvider, Result},
 };
+use crate::providers::{auth::SendAuthenticated, ApiKeyAuth, Authenticator};
 
 pub struct OpenAIEmbeddingProvider {
     client: Client,
-    api_key: String,
+    auth: Arc<dyn Authenticator>,
     base_url: String,
 }
 
@@ -17,7 +19,7 @@ impl OpenAIEmbeddingProvider {
     pub fn new(api_key: String) -> Self {
         Self {
             client: Client::new(),
-            api_key,
+            auth: Arc::new(ApiKeyAuth::bearer(api_key)),
             base_url: "https://api.openai.com/v1".to_string(),
         }
     }
@@ -25,10 +27,24 @@ impl OpenAIEmbeddingProvider {
     pub fn with_base_url(api_key: String, base_url: String) -> Self {
         Self {
             client: Client::new(),
-            api_key,
+            auth: Arc::new(ApiKeyAuth::bearer(api_key)),
             base_url,
         }
     }
+
+    /// Send requests through `client`, sharing its connection pool and DNS
+    /// cache with other providers
+    pub fn with_client(mut self, client: Client) -> Self {
+        self.client = client;
+        self
+    }
+
+    /// Authenticate requests with `auth` instead of the API key, e.g. for a
+    /// proxy or gateway with its own credentials
+    pub fn with_authenticator(mut self, auth: impl Authenticator + 'static) -> Self {
+        self.auth = Arc::new(auth);
+        self
+    }
 }
 
 #[derive(Serialize)]
@@ -40,6 +56,8 @@ struct OpenAIEmbeddingRequest {
 #[derive(Deserialize)]
 struct OpenAIEmbeddingResponse {
     data: Vec<OpenAIEmbedding>,
+    #[serde(default)]
+    model: Option<String>,
     usage: OpenAIUsage,
 }
 
@@ -58,6 +76,7 @@ struct OpenAIUsage {
 #[async_trait]
 impl EmbeddingProvider for OpenAIEmbeddingProvider {
     async fn embed(&self, request: EmbeddingRequest) -> Result<EmbeddingResponse> {
+        let model = request.model.clone();
         let openai_request = OpenAIEmbeddingRequest {
             input: request.input,
             model: request.model,
@@ -66,10 +85,10 @@ impl EmbeddingProvider for OpenAIEmbeddingProvider {
         let response = self
             .client
             .post(format!("{}/embeddings", self.base_url))
-            .header("Authorization", format!("Bearer {}", self.api_key))
             .json(&openai_request)
-            .send()
-            .await?;
+            .send_authenticated(self.auth.as_ref())
+            .await
+            .map_err(|e| EmbeddingError::ProviderError(e.to_string()))?;
 
         if !response.status().is_success() {
             let error_text = response.text().await?;
@@ -81,22 +100,7 @@ impl EmbeddingProvider for OpenAIEmbeddingProvider {
 
         let openai_response: OpenAIEmbeddingResponse = response.json().await?;
 
-        let embeddings = openai_response
-            .data
-            .into_iter()
-            .map(|e| Embedding {
-                vector: e.embedding,
-                index: e.index,
-            })
-            .collect();
-
-        Ok(EmbeddingResponse {
-            embeddings,
-            usage: Some(EmbeddingUsage {
-                prompt_tokens: openai_response.usage.prompt_tokens,
-                total_tokens: openai_response.usage.total_tokens,
-            }),
-        })
+        Ok(convert_embedding_response(openai_response, model))
     }
 
     fn default_model(&self) -> &str {
@@ -108,6 +112,30 @@ impl EmbeddingProvider for OpenAIEmbeddingProvider {
     }
 }
 
+/// Convert an API response, falling back to the requested model name
+fn convert_embedding_response(
+    response: OpenAIEmbeddingResponse,
+    requested_model: String,
+) -> EmbeddingResponse {
+    let embeddings = response
+        .data
+        .into_iter()
+        .map(|e| Embedding {
+            vector: e.embedding,
+            index: e.index,
+        })
+        .collect();
+
+    EmbeddingResponse {
+        embeddings,
+        model: response.model.unwrap_or(requested_model),
+        usage: Some(EmbeddingUsage {
+            prompt_tokens: response.usage.prompt_tokens,
+            total_tokens: response.usage.total_tokens,
+        }),
+    }
+}
+
 /// Different OpenAI embedding models
 pub enum OpenAIEmbeddingModel {
     /// text-embedding-3-small: 1536 dimensions
@@ -135,3 +163,37 @@ impl OpenAIEmbeddingModel {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::observability::CostTracker;
+
+    #[test]
+    fn test_embedding_usage_captured_and_costed() {
+        let raw = r#"{
+            "object": "list",
+            "data": [{"object": "embedding", "embedding": [0.1, 0.2, 0.3], "index": 0}],
+            "model": "text-embedding-3-small",
+            "usage": {"prompt_tokens": 2000, "total_tokens": 2000}
+        }"#;
+        let response: OpenAIEmbeddingResponse = serde_json::from_str(raw).unwrap();
+        let response = convert_embedding_response(response, "requested".to_string());
+
+        assert_eq!(response.model, "text-embedding-3-small");
+        let usage = response.usage.clone().unwrap();
+        assert_eq!(usage.prompt_tokens, 2000);
+
+        let mut tracker = CostTracker::new();
+        let pricing = tracker.get_pricing("openai", &response.model);
+        tracker.record_embedding_usage("openai", &response.model, &usage, &pricing);
+
+        // $0.02 per 1M tokens
+        assert!((tracker.total_cost - 0.00004).abs() < 1e-9);
+        let model_costs = tracker
+            .get_cost_by_model("openai", "text-embedding-3-small")
+            .unwrap();
+        assert_eq!(model_costs.input_tokens, 2000);
+        assert_eq!(model_costs.output_tokens, 0);
+    }
+}
@@ -0,0 +1,254 @@
+use async_trait::async_trait;
+use std::sync::{Arc, RwLock};
+
+use super::{
+    models::{Embedding, EmbeddingRequest},
+    provider::{EmbeddingError, EmbeddingProvider, Result},
+};
+
+/// A document with its embedding
+#[derive(Debug, Clone)]
+pub struct Document {
+    pub id: String,
+    pub text: String,
+    pub embedding: Embedding,
+}
+
+/// A document ranked against a query
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchResult {
+    pub id: String,
+    pub text: String,
+    /// Cosine similarity to the query
+    pub score: f32,
+}
+
+/// Storage for embedded documents that can rank them against a query
+/// vector. Backends with native vector search can implement `search`
+/// directly; `SurrealVectorStore` in `agent::memory` stores documents in
+/// SurrealDB next to the agent's memories.
+#[async_trait]
+pub trait VectorStore: Send + Sync {
+    async fn add(&self, documents: Vec<Document>) -> Result<()>;
+
+    /// The `k` documents most similar to `query`, most similar first
+    async fn search(&self, query: &Embedding, k: usize) -> Result<Vec<SearchResult>>;
+
+    async fn len(&self) -> Result<usize>;
+
+    async fn is_empty(&self) -> Result<bool> {
+        Ok(self.len().await? == 0)
+    }
+}
+
+/// Keeps documents in memory and ranks them with a linear scan
+#[derive(Default)]
+pub struct InMemoryVectorStore {
+    documents: RwLock<Vec<Document>>,
+}
+
+impl InMemoryVectorStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl VectorStore for InMemoryVectorStore {
+    async fn add(&self, documents: Vec<Document>) -> Result<()> {
+        let mut stored = self.documents.write().unwrap();
+        for document in documents {
+            // Re-adding an id replaces the old document
+            stored.retain(|d| d.id != document.id);
+            stored.push(document);
+        }
+        Ok(())
+    }
+
+    async fn search(&self, query: &Embedding, k: usize) -> Result<Vec<SearchResult>> {
+        let documents = self.documents.read().unwrap();
+        let ranked = Embedding::top_k_indices(query, documents.iter().map(|d| &d.embedding), k);
+
+        Ok(ranked
+            .into_iter()
+            .map(|(index, score)| SearchResult {
+                id: documents[index].id.clone(),
+                text: documents[index].text.clone(),
+                score,
+            })
+            .collect())
+    }
+
+    async fn len(&self) -> Result<usize> {
+        Ok(self.documents.read().unwrap().len())
+    }
+}
+
+/// Embed `query` and return the `k` corpus entries most similar to it,
+/// most similar first
+pub async fn similarity_search<'a>(
+    provider: &dyn EmbeddingProvider,
+    query: &str,
+    corpus: &[(&'a str, Embedding)],
+    k: usize,
+) -> Result<Vec<(&'a str, f32)>> {
+    let query = provider.embed_single(query).await?;
+    Ok(Embedding::top_k(&query, corpus, k))
+}
+
+/// Semantic search over a document collection: documents are embedded when
+/// added, and queries are embedded and ranked by cosine similarity
+pub struct SemanticSearch {
+    provider: Arc<dyn EmbeddingProvider>,
+    store: Arc<dyn VectorStore>,
+}
+
+impl SemanticSearch {
+    /// Keep documents in memory
+    pub fn new(provider: Arc<dyn EmbeddingProvider>) -> Self {
+        Self::with_store(provider, Arc::new(InMemoryVectorStore::new()))
+    }
+
+    /// Keep documents in a custom vector store
+    pub fn with_store(provider: Arc<dyn EmbeddingProvider>, store: Arc<dyn VectorStore>) -> Self {
+        Self { provider, store }
+    }
+
+    /// Embed `(id, text)` pairs in one request and store them
+    pub async fn add_documents(&self, documents: &[(&str, &str)]) -> Result<()> {
+        if documents.is_empty() {
+            return Ok(());
+        }
+
+        let response = self
+            .provider
+            .embed(EmbeddingRequest {
+                input: documents.iter().map(|(_, text)| text.to_string()).collect(),
+                model: self.provider.default_model().to_string(),
+            })
+            .await?;
+        if response.embeddings.len() != documents.len() {
+            return Err(EmbeddingError::ProviderError(format!(
+                "Expected {} embeddings, got {}",
+                documents.len(),
+                response.embeddings.len()
+            )));
+        }
+
+        let documents = response
+            .embeddings
+            .into_iter()
+            .filter_map(|embedding| {
+                let (id, text) = documents.get(embedding.index)?;
+                Some(Document {
+                    id: id.to_string(),
+                    text: text.to_string(),
+                    embedding,
+                })
+            })
+            .collect();
+        self.store.add(documents).await
+    }
+
+    /// The `k` stored documents most similar to `query`, most similar first
+    pub async fn search(&self, query: &str, k: usize) -> Result<Vec<SearchResult>> {
+        let query = self.provider.embed_single(query).await?;
+        self.store.search(&query, k).await
+    }
+
+    /// Number of stored documents
+    pub async fn len(&self) -> Result<usize> {
+        self.store.len().await
+    }
+
+    pub async fn is_empty(&self) -> Result<bool> {
+        self.store.is_empty().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::embeddings::{EmbeddingResponse, MockEmbeddingProvider};
+
+    /// Embeds text as counts of a few keywords, so rankings are predictable
+    struct KeywordProvider;
+
+    const KEYWORDS: [&str; 4] = ["rust", "python", "cat", "dog"];
+
+    #[async_trait]
+    impl EmbeddingProvider for KeywordProvider {
+        async fn embed(&self, request: EmbeddingRequest) -> Result<EmbeddingResponse> {
+            let embeddings = request
+                .input
+                .iter()
+                .enumerate()
+                .map(|(index, text)| {
+                    let text = text.to_lowercase();
+                    Embedding {
+                        vector: KEYWORDS
+                            .iter()
+                            .map(|k| text.matches(k).count() as f32)
+                            .collect(),
+                        index,
+                    }
+                })
+                .collect();
+            Ok(EmbeddingResponse {
+                embeddings,
+                model: request.model,
+                usage: None,
+            })
+        }
+
+        fn default_model(&self) -> &str {
+            "keywords"
+        }
+
+        fn dimension(&self) -> usize {
+            KEYWORDS.len()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_search_ranks_documents_by_similarity() {
+        let search = SemanticSearch::new(Arc::new(KeywordProvider));
+        search
+            .add_documents(&[
+                ("pets", "My cat and my dog"),
+                ("rust", "Rust ownership explained, with more Rust"),
+                ("langs", "Rust or Python for scripting?"),
+                ("python", "Python packaging"),
+            ])
+            .await
+            .unwrap();
+
+        let results = search.search("learning rust", 2).await.unwrap();
+        let ids: Vec<&str> = results.iter().map(|r| r.id.as_str()).collect();
+        assert_eq!(ids, vec!["rust", "langs"]);
+        assert!(results[0].score > results[1].score);
+        assert_eq!(results[0].text, "Rust ownership explained, with more Rust");
+
+        let results = search.search("a dog", 1).await.unwrap();
+        assert_eq!(results[0].id, "pets");
+    }
+
+    #[tokio::test]
+    async fn test_search_with_mock_provider() {
+        let search = SemanticSearch::new(Arc::new(MockEmbeddingProvider::new(16)));
+        search
+            .add_documents(&[("a", "first"), ("b", "second"), ("c", "third")])
+            .await
+            .unwrap();
+        // Re-adding an id replaces the document
+        search
+            .add_documents(&[("a", "first, again")])
+            .await
+            .unwrap();
+        assert_eq!(search.len().await.unwrap(), 3);
+
+        let results = search.search("second", 2).await.unwrap();
+        assert_eq!(results.len(), 2);
+        assert!(results[0].score >= results[1].score);
+    }
+}
@@ -1,6 +1,5 @@
 use async_trait::async_trait;
-use futures::stream::Stream;
-use rand::Rng;
+use futures::stream::{Stream, StreamExt};
 use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, VecDeque};
 use std::future::Future;
@@ -11,6 +10,8 @@ use std::time::{Duration, SystemTime};
 use thiserror::Error;
 use tokio::time::sleep;
 
+use crate::observability::MetricsCollector;
+
 /// Comprehensive error types for AI operations
 #[derive(Error, Debug, Clone)]
 pub enum AiError {
@@ -46,11 +47,13 @@ pub enum AiError {
         requests_remaining: Option<u64>,
     },
 
-    #[error("Quota exceeded for provider {provider}")]
+    #[error("Quota exceeded for provider {provider}: {message}")]
     QuotaExceeded {
         provider: String,
         quota_type: String, // "monthly", "daily", "requests", "tokens"
         reset_time: Option<SystemTime>,
+        message: String,
+        request_id: Option<String>,
     },
 
     // Request/Response Errors
@@ -81,6 +84,10 @@ pub enum AiError {
         message: String,
         error_code: Option<String>,
         retryable: bool,
+        /// HTTP status of the failed response, when there was one
+        status_code: Option<u16>,
+        /// Provider-assigned request id, useful when contacting support
+        request_id: Option<String>,
     },
 
     #[error("Service unavailable for provider {provider}")]
@@ -174,6 +181,15 @@ pub enum AiError {
         retry_after: Duration,
     },
 
+    // Retry Errors
+    #[error("Gave up after {attempts} attempts in {elapsed:?}: {source}")]
+    RetriesExhausted {
+        attempts: u32,
+        elapsed: Duration,
+        /// Error returned by the final attempt
+        source: Box<AiError>,
+    },
+
     // Internal Errors
     #[error("Internal error: {message}")]
     InternalError {
@@ -232,6 +248,7 @@ impl AiError {
             AiError::MemoryError { .. } => false,
             AiError::ContextTooLarge { .. } => false,
             AiError::CircuitBreakerOpen { .. } => false, // Handle differently
+            AiError::RetriesExhausted { .. } => false,
             AiError::InternalError { .. } => true,
             AiError::Custom { metadata, .. } => {
                 metadata.get("retryable").is_some_and(|v| v == "true")
@@ -239,6 +256,62 @@ impl AiError {
         }
     }
 
+    /// Whether the failure comes from the service's current condition
+    /// (network trouble, timeouts, 5xx, rate limiting, overload) rather than
+    /// from the request itself (auth, validation, content filtering).
+    ///
+    /// Unlike [`is_retryable`](Self::is_retryable) this says nothing about
+    /// whether repeating the request is safe; it is what circuit breakers
+    /// and alerting care about.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            AiError::NetworkError { status_code, .. } => {
+                status_code.is_none_or(is_transient_status)
+            }
+            AiError::ProviderError {
+                status_code,
+                retryable,
+                ..
+            } => status_code.map_or(*retryable, is_transient_status),
+            AiError::TimeoutError { .. }
+            | AiError::ConnectionRefused { .. }
+            | AiError::RateLimitExceeded { .. }
+            | AiError::ServiceUnavailable { .. }
+            | AiError::StreamError { .. }
+            | AiError::StreamInterrupted { .. }
+            | AiError::CircuitBreakerOpen { .. } => true,
+            AiError::ToolExecutionError { retryable, .. } => *retryable,
+            AiError::RetriesExhausted { source, .. } => source.is_transient(),
+            AiError::Custom { metadata, .. } => metadata
+                .get("transient")
+                .or_else(|| metadata.get("retryable"))
+                .is_some_and(|v| v == "true"),
+
+            AiError::InvalidApiKey { .. }
+            | AiError::AuthenticationFailed { .. }
+            | AiError::ApiKeyExpired { .. }
+            | AiError::QuotaExceeded { .. }
+            | AiError::InvalidRequest { .. }
+            | AiError::MalformedResponse { .. }
+            | AiError::UnsupportedModel { .. }
+            | AiError::ContentFiltered { .. }
+            | AiError::RequestTooLarge { .. }
+            | AiError::ResponseTooLarge { .. }
+            | AiError::TokenLimitExceeded { .. }
+            | AiError::InsufficientTokens { .. }
+            | AiError::SerializationError { .. }
+            | AiError::JsonError { .. }
+            | AiError::ToolNotFound { .. }
+            | AiError::InvalidToolParameters { .. }
+            | AiError::MemoryError { .. }
+            | AiError::ContextTooLarge { .. }
+            | AiError::ConfigurationError { .. }
+            | AiError::MissingConfiguration { .. }
+            | AiError::InternalError { .. }
+            | AiError::NotImplemented { .. } => false,
+        }
+    }
+
     /// Get retry delay if applicable
     pub fn retry_after(&self) -> Option<Duration> {
         match self {
@@ -249,6 +322,26 @@ impl AiError {
         }
     }
 
+    /// HTTP status code of the failed response, if the error carries one
+    pub fn http_status(&self) -> Option<u16> {
+        match self {
+            AiError::NetworkError { status_code, .. }
+            | AiError::ProviderError { status_code, .. } => *status_code,
+            AiError::RetriesExhausted { source, .. } => source.http_status(),
+            _ => None,
+        }
+    }
+
+    /// Provider-assigned request id of the failed response, if known
+    pub fn request_id(&self) -> Option<&str> {
+        match self {
+            AiError::ProviderError { request_id, .. }
+            | AiError::QuotaExceeded { request_id, .. } => request_id.as_deref(),
+            AiError::RetriesExhausted { source, .. } => source.request_id(),
+            _ => None,
+        }
+    }
+
     /// Get error severity level
     pub fn severity(&self) -> ErrorSeverity {
         match self {
@@ -289,6 +382,7 @@ impl AiError {
             | AiError::UnsupportedModel { provider, .. }
             | AiError::ProviderError { provider, .. }
             | AiError::ServiceUnavailable { provider, .. } => Some(provider),
+            AiError::RetriesExhausted { source, .. } => source.provider(),
             _ => None,
         }
     }
@@ -314,6 +408,12 @@ impl AiError {
     }
 }
 
+/// Timeouts, rate limiting and server-side failures, including Anthropic's
+/// 529 overloaded status
+fn is_transient_status(status: u16) -> bool {
+    matches!(status, 408 | 429) || status >= 500
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub enum ErrorSeverity {
     Low,
@@ -507,45 +607,98 @@ impl RetryContext {
     }
 }
 
+/// Called with the failed attempt number and the delay before the next one
+pub type RetryHook = Arc<dyn Fn(u32, Duration) + Send + Sync>;
+
 /// Main retry executor
 pub struct RetryExecutor {
     config: RetryConfig,
-    start_time: Instant,
+    on_retry: Option<RetryHook>,
 }
 
 impl RetryExecutor {
     pub fn new(config: RetryConfig) -> Self {
         Self {
             config,
-            start_time: Instant::now(),
+            on_retry: None,
         }
     }
 
-    /// Execute a function with retry logic
-    pub async fn execute<F, Fut, T>(&self, mut operation: F) -> Result<T>
+    /// Call `hook` each time a retry is scheduled
+    pub fn on_retry(mut self, hook: RetryHook) -> Self {
+        self.on_retry = Some(hook);
+        self
+    }
+
+    /// Execute a function with retry logic.
+    ///
+    /// When every allowed attempt fails with a retryable error, or the time
+    /// budget runs out, the last error is returned wrapped in
+    /// [`AiError::RetriesExhausted`] with the attempt count and elapsed time.
+    pub async fn execute<F, Fut, T>(&self, operation: F) -> Result<T>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        self.execute_until(operation, None).await
+    }
+
+    /// Like [`execute`](Self::execute), but never runs past `deadline`: an
+    /// attempt still running at the deadline is abandoned, a retry whose
+    /// delay would end after it is not scheduled, and either way the call
+    /// fails with [`AiError::TimeoutError`].
+    pub async fn execute_until<F, Fut, T>(
+        &self,
+        mut operation: F,
+        deadline: Option<Instant>,
+    ) -> Result<T>
     where
         F: FnMut() -> Fut,
         Fut: Future<Output = Result<T>>,
     {
+        let start_time = Instant::now();
         let mut context = RetryContext::new();
         let mut last_error = None;
 
+        let exhausted = |attempts: u32, error: AiError| AiError::RetriesExhausted {
+            attempts,
+            elapsed: start_time.elapsed(),
+            source: Box::new(error),
+        };
+        let deadline_exceeded = |deadline: Instant| AiError::TimeoutError {
+            timeout: deadline.saturating_duration_since(start_time),
+            retryable: false,
+        };
+
         for attempt in 1..=self.config.max_attempts {
             context.attempt = attempt;
-            context.total_elapsed = self.start_time.elapsed();
+            context.total_elapsed = start_time.elapsed();
 
             // Check if we've exceeded maximum total time
             if let Some(max_time) = self.config.max_total_time {
                 if context.total_elapsed >= max_time {
-                    return Err(last_error.unwrap_or(AiError::TimeoutError {
-                        timeout: max_time,
-                        retryable: false,
-                    }));
+                    return Err(match last_error {
+                        Some(error) => exhausted(attempt - 1, error),
+                        None => AiError::TimeoutError {
+                            timeout: max_time,
+                            retryable: false,
+                        },
+                    });
                 }
             }
 
+            if let Some(deadline) = deadline.filter(|d| Instant::now() >= *d) {
+                return Err(deadline_exceeded(deadline));
+            }
+
             // Execute the operation
-            match operation().await {
+            let outcome = match deadline {
+                Some(deadline) => tokio::time::timeout_at(deadline.into(), operation())
+                    .await
+                    .unwrap_or_else(|_| Err(deadline_exceeded(deadline))),
+                None => operation().await,
+            };
+            match outcome {
                 Ok(result) => return Ok(result),
                 Err(error) => {
                     last_error = Some(error.clone());
@@ -564,20 +717,30 @@ impl RetryExecutor {
                         // Check total time again after calculating delay
                         if let Some(max_time) = self.config.max_total_time {
                             if context.total_elapsed + delay >= max_time {
-                                return Err(error);
+                                return Err(exhausted(attempt, error));
                             }
                         }
 
+                        if let Some(deadline) = deadline.filter(|d| Instant::now() + delay >= *d) {
+                            return Err(deadline_exceeded(deadline));
+                        }
+
+                        if let Some(hook) = &self.on_retry {
+                            hook(attempt, delay);
+                        }
                         sleep(delay).await;
                     }
                 }
             }
         }
 
-        Err(last_error.unwrap_or_else(|| AiError::InternalError {
-            message: "Retry loop completed without error".to_string(),
-            component: Some("retry".to_string()),
-        }))
+        Err(match last_error {
+            Some(error) => exhausted(self.config.max_attempts, error),
+            None => AiError::InternalError {
+                message: "Retry loop completed without error".to_string(),
+                component: Some("retry".to_string()),
+            },
+        })
     }
 
     /// Determine if an error should be retried
@@ -606,71 +769,11 @@ impl RetryExecutor {
             }
         }
 
-        // Calculate base delay based on backoff strategy
-        let base_delay = match &self.config.backoff {
-            BackoffStrategy::Fixed => self.config.initial_delay,
-
-            BackoffStrategy::Linear => Duration::from_millis(
-                self.config.initial_delay.as_millis() as u64 * context.attempt as u64,
-            ),
-
-            BackoffStrategy::Exponential { multiplier } => {
-                let delay_ms = self.config.initial_delay.as_millis() as f64
-                    * multiplier.powi((context.attempt - 1) as i32);
-                Duration::from_millis(delay_ms as u64)
-            }
-
-            BackoffStrategy::Custom(delays) => delays
-                .get((context.attempt - 1) as usize)
-                .copied()
-                .unwrap_or(self.config.max_delay),
-        };
-
-        // Apply jitter
-        let jittered_delay = self.apply_jitter(base_delay, context);
-
-        // Ensure delay doesn't exceed maximum
-        std::cmp::min(jittered_delay, self.config.max_delay)
-    }
-
-    /// Apply jitter to the delay
-    fn apply_jitter(&self, delay: Duration, context: &RetryContext) -> Duration {
-        let mut rng = rand::thread_rng();
-
-        match &self.config.jitter {
-            JitterStrategy::None => delay,
-
-            JitterStrategy::Full => {
-                let jitter_ms = rng.gen_range(0..=delay.as_millis() as u64);
-                Duration::from_millis(jitter_ms)
-            }
-
-            JitterStrategy::Half => {
-                let base_ms = delay.as_millis() as u64 / 2;
-                let jitter_ms = base_ms + rng.gen_range(0..=base_ms);
-                Duration::from_millis(jitter_ms)
-            }
-
-            JitterStrategy::Fixed(jitter_amount) => {
-                let jitter_ms = rng.gen_range(0..=jitter_amount.as_millis() as u64);
-                delay + Duration::from_millis(jitter_ms)
-            }
-
-            JitterStrategy::Decorrelated => {
-                // Decorrelated jitter: next_delay = random(base_delay, last_delay * 3)
-                let last_delay = context
-                    .delay_history
-                    .last()
-                    .copied()
-                    .unwrap_or(self.config.initial_delay);
-
-                let min_delay = delay.as_millis() as u64;
-                let max_delay = (last_delay.as_millis() as u64 * 3).max(min_delay);
-
-                let jitter_ms = rng.gen_range(min_delay..=max_delay);
-                Duration::from_millis(jitter_ms)
-            }
-        }
+        crate::backoff::delay_after(
+            context.attempt,
+            context.delay_history.last().copied(),
+            &self.config,
+        )
     }
 }
 
@@ -801,6 +904,17 @@ pub enum CircuitState {
     },
 }
 
+impl CircuitState {
+    /// `closed`, `open` or `half_open`
+    pub fn name(&self) -> &'static str {
+        match self {
+            CircuitState::Closed => "closed",
+            CircuitState::Open { .. } => "open",
+            CircuitState::HalfOpen { .. } => "half_open",
+        }
+    }
+}
+
 /// Request outcome for circuit breaker tracking
 #[derive(Debug, Clone)]
 enum RequestOutcome {
@@ -851,7 +965,11 @@ impl CircuitBreaker {
                 Ok(result)
             }
             Err(error) => {
-                self.record_failure(start_time);
+                // A permanent error such as a rejected request is the
+                // caller's fault, so it counts neither way
+                if error.is_transient() {
+                    self.record_failure(start_time);
+                }
                 Err(error)
             }
         }
@@ -956,9 +1074,18 @@ impl CircuitBreaker {
     /// Add a request outcome to the history
     fn add_request_outcome(&self, outcome: RequestOutcome) {
         let mut history = self.request_history.lock().unwrap();
+        self.prune_history(&mut history);
+
+        // Add the new outcome
+        history.push_back(outcome);
+    }
+
+    /// Remove entries outside the measurement window. Called on every read
+    /// as well as on insert, so a quiet period cannot leave stale outcomes
+    /// in the failure rate.
+    fn prune_history(&self, history: &mut VecDeque<RequestOutcome>) {
         let now = Instant::now();
 
-        // Remove old entries outside the measurement window
         while let Some(front) = history.front() {
             let request_time = match front {
                 RequestOutcome::Success(time) | RequestOutcome::Failure(time) => *time,
@@ -970,41 +1097,25 @@ impl CircuitBreaker {
                 break;
             }
         }
-
-        // Add the new outcome
-        history.push_back(outcome);
     }
 
     /// Check if the circuit should be opened based on failure rate
     fn should_open_circuit(&self) -> bool {
-        let history = self.request_history.lock().unwrap();
+        let mut history = self.request_history.lock().unwrap();
+        self.prune_history(&mut history);
 
         if history.len() < self.config.minimum_request_count as usize {
             return false;
         }
 
-        let failure_rate = self.calculate_failure_rate();
-        failure_rate >= self.config.failure_threshold
+        failure_rate(&history) >= self.config.failure_threshold
     }
 
     /// Calculate the current failure rate
     fn calculate_failure_rate(&self) -> f64 {
-        let history = self.request_history.lock().unwrap();
-
-        if history.is_empty() {
-            return 0.0;
-        }
-
-        let mut failures = 0;
-        let total = history.len();
-
-        for outcome in history.iter() {
-            if matches!(outcome, RequestOutcome::Failure(_)) {
-                failures += 1;
-            }
-        }
-
-        (failures as f64 / total as f64) * 100.0
+        let mut history = self.request_history.lock().unwrap();
+        self.prune_history(&mut history);
+        failure_rate(&history)
     }
 
     /// Get the current state of the circuit breaker
@@ -1015,7 +1126,8 @@ impl CircuitBreaker {
     /// Get circuit breaker metrics
     pub fn metrics(&self) -> CircuitBreakerMetrics {
         let state = self.state.lock().unwrap().clone();
-        let history = self.request_history.lock().unwrap();
+        let mut history = self.request_history.lock().unwrap();
+        self.prune_history(&mut history);
 
         let mut successes = 0;
         let mut failures = 0;
@@ -1072,6 +1184,20 @@ impl CircuitBreaker {
     }
 }
 
+/// Percentage of failed requests in `history`
+fn failure_rate(history: &VecDeque<RequestOutcome>) -> f64 {
+    if history.is_empty() {
+        return 0.0;
+    }
+
+    let failures = history
+        .iter()
+        .filter(|outcome| matches!(outcome, RequestOutcome::Failure(_)))
+        .count();
+
+    (failures as f64 / history.len() as f64) * 100.0
+}
+
 /// Circuit breaker metrics
 #[derive(Debug, Clone)]
 pub struct CircuitBreakerMetrics {
@@ -1153,6 +1279,8 @@ pub struct ResilientProvider {
     inner: Arc<dyn crate::CompletionProvider>,
     retry_executor: RetryExecutor,
     circuit_breaker: Arc<CircuitBreaker>,
+    metrics: Option<Arc<MetricsCollector>>,
+    stream_reconnects: u32,
 }
 
 impl ResilientProvider {
@@ -1180,9 +1308,100 @@ impl ResilientProvider {
             inner: provider,
             retry_executor,
             circuit_breaker,
+            metrics: None,
+            stream_reconnects: 0,
+        }
+    }
+
+    /// Resume a stream that fails with a transient error part-way through, up
+    /// to `attempts` times, by re-issuing the request with the text received
+    /// so far as an assistant prefill. Only applies to providers that
+    /// support prefill (see [`ProviderCapabilities::assistant_prefill`]);
+    /// streams that carried tool calls are not resumed.
+    ///
+    /// [`ProviderCapabilities::assistant_prefill`]: crate::ProviderCapabilities::assistant_prefill
+    pub fn with_stream_reconnects(mut self, attempts: u32) -> Self {
+        self.stream_reconnects = attempts;
+        self
+    }
+
+    /// Record every attempt, retry delay and circuit state change in
+    /// `metrics`, under the inner provider's name
+    pub fn with_metrics(mut self, metrics: Arc<MetricsCollector>) -> Self {
+        let provider = self.inner.name();
+        let collector = metrics.clone();
+        self.retry_executor.on_retry = Some(Arc::new(move |_, delay| {
+            collector.record_retry(provider, delay)
+        }));
+        self.metrics = Some(metrics);
+        self
+    }
+
+    /// Record a circuit state change since `before`
+    fn record_transition(&self, before: &CircuitState) {
+        if let Some(metrics) = &self.metrics {
+            let after = self.circuit_breaker.state();
+            if before.name() != after.name() {
+                metrics.record_circuit_transition(self.inner.name(), before.name(), after.name());
+            }
         }
     }
 
+    /// Complete `request` without running past `deadline`, across every
+    /// retry. Fails with [`AiError::TimeoutError`] once the deadline passes,
+    /// or straight away, without consulting the circuit breaker, if it
+    /// already has.
+    pub async fn complete_with_deadline(
+        &self,
+        request: crate::CompletionRequest,
+        deadline: Option<Instant>,
+    ) -> Result<crate::CompletionResponse> {
+        if deadline.is_some_and(|d| Instant::now() >= d) {
+            return Err(AiError::TimeoutError {
+                timeout: Duration::ZERO,
+                retryable: false,
+            });
+        }
+
+        let circuit_breaker = self.circuit_breaker.clone();
+        let inner = self.inner.clone();
+        let request_clone = request.clone();
+        let state_before = circuit_breaker.state();
+
+        // Execute with circuit breaker protection
+        let result = circuit_breaker
+            .execute(|| {
+                let inner = inner.clone();
+                let request = request_clone.clone();
+
+                // Execute with retry logic
+                self.retry_executor.execute_until(
+                    move || {
+                        let inner = inner.clone();
+                        let request = request.clone();
+                        let metrics = self.metrics.clone();
+
+                        async move {
+                            // Transform provider-specific errors to our enhanced error types
+                            let result = inner
+                                .complete(request)
+                                .await
+                                .map_err(|e| enhance_error(e, inner.name()));
+                            if let Some(metrics) = metrics {
+                                metrics.record_attempt(inner.name(), result.is_ok());
+                            }
+                            result
+                        }
+                    },
+                    deadline,
+                )
+            })
+            .await;
+
+        self.record_transition(&state_before);
+        result
+    }
+
     /// Get the underlying provider
     pub fn inner(&self) -> &Arc<dyn crate::CompletionProvider> {
         &self.inner
@@ -1215,56 +1434,36 @@ impl crate::CompletionProvider for ResilientProvider {
         &self,
         request: crate::CompletionRequest,
     ) -> Result<crate::CompletionResponse> {
-        let circuit_breaker = self.circuit_breaker.clone();
-        let inner = self.inner.clone();
-        let request_clone = request.clone();
-
-        // Execute with circuit breaker protection
-        circuit_breaker
-            .execute(|| {
-                let inner = inner.clone();
-                let request = request_clone.clone();
-
-                // Execute with retry logic
-                self.retry_executor.execute(move || {
-                    let inner = inner.clone();
-                    let request = request.clone();
-
-                    async move {
-                        // Transform provider-specific errors to our enhanced error types
-                        inner
-                            .complete(request)
-                            .await
-                            .map_err(|e| enhance_error(e, inner.name()))
-                    }
-                })
-            })
-            .await
+        self.complete_with_deadline(request, None).await
     }
 
     async fn complete_stream(
         &self,
         request: crate::CompletionRequest,
     ) -> Result<Pin<Box<dyn Stream<Item = Result<crate::StreamChunk>> + Send>>> {
-        let circuit_breaker = self.circuit_breaker.clone();
-        let inner = self.inner.clone();
-        let request_clone = request.clone();
-
         // For streaming, we apply circuit breaker but not retry logic
         // (since streams are typically long-lived)
-        circuit_breaker
-            .execute(|| {
-                let inner = inner.clone();
-                let request = request_clone.clone();
-
-                async move {
-                    inner
-                        .complete_stream(request)
-                        .await
-                        .map_err(|e| enhance_error(e, inner.name()))
-                }
-            })
-            .await
+        let stream = open_stream(
+            &self.inner,
+            &self.circuit_breaker,
+            self.metrics.as_deref(),
+            request.clone(),
+        )
+        .await?;
+        if self.stream_reconnects == 0 || !self.inner.capabilities().assistant_prefill {
+            return Ok(stream);
+        }
+        Ok(resume_on_drop(ResumableStream {
+            inner: self.inner.clone(),
+            circuit_breaker: self.circuit_breaker.clone(),
+            metrics: self.metrics.clone(),
+            request,
+            stream,
+            received: String::new(),
+            trimmed_whitespace: String::new(),
+            saw_tool_calls: false,
+            reconnects_left: self.stream_reconnects,
+        }))
     }
 
     fn name(&self) -> &'static str {
@@ -1278,6 +1477,146 @@ impl crate::CompletionProvider for ResilientProvider {
     fn available_models(&self) -> Vec<&'static str> {
         self.inner.available_models()
     }
+
+    fn capabilities(&self) -> crate::ProviderCapabilities {
+        self.inner.capabilities()
+    }
+
+    fn model_catalog(&self) -> Vec<crate::ModelInfo> {
+        self.inner.model_catalog()
+    }
+}
+
+/// A stream along with what is needed to re-issue its request
+struct ResumableStream {
+    inner: Arc<dyn crate::CompletionProvider>,
+    circuit_breaker: Arc<CircuitBreaker>,
+    metrics: Option<Arc<MetricsCollector>>,
+    request: crate::CompletionRequest,
+    stream: Pin<Box<dyn Stream<Item = Result<crate::StreamChunk>> + Send>>,
+    received: String,
+    /// Trailing whitespace of `received` the provider may have trimmed from
+    /// the prefill, to be dropped from the start of the continuation
+    trimmed_whitespace: String,
+    saw_tool_calls: bool,
+    reconnects_left: u32,
+}
+
+/// Start a stream through the circuit breaker, recording the attempt and
+/// any circuit state change in `metrics`
+async fn open_stream(
+    inner: &Arc<dyn crate::CompletionProvider>,
+    circuit_breaker: &CircuitBreaker,
+    metrics: Option<&MetricsCollector>,
+    request: crate::CompletionRequest,
+) -> Result<Pin<Box<dyn Stream<Item = Result<crate::StreamChunk>> + Send>>> {
+    let state_before = circuit_breaker.state();
+    let result = circuit_breaker
+        .execute(|| {
+            let inner = inner.clone();
+            let request = request.clone();
+            async move {
+                inner
+                    .complete_stream(request)
+                    .await
+                    .map_err(|e| enhance_error(e, inner.name()))
+            }
+        })
+        .await;
+
+    if let Some(metrics) = metrics {
+        metrics.record_attempt(inner.name(), result.is_ok());
+        let after = circuit_breaker.state();
+        if state_before.name() != after.name() {
+            metrics.record_circuit_transition(inner.name(), state_before.name(), after.name());
+        }
+    }
+    result
+}
+
+/// Pass chunks through, re-issuing the request with the text received so
+/// far as a prefill when the stream drops with a transient error, such as
+/// a connection reset while reading the body
+fn resume_on_drop(
+    state: ResumableStream,
+) -> Pin<Box<dyn Stream<Item = Result<crate::StreamChunk>> + Send>> {
+    Box::pin(futures::stream::unfold(Some(state), |state| async move {
+        let mut state = state?;
+        loop {
+            match state.stream.next().await {
+                Some(Ok(mut chunk)) => {
+                    if let Some(choice) = chunk.choices.first_mut() {
+                        if let Some(text) = &mut choice.delta.content {
+                            strip_resumed_whitespace(text, &mut state.trimmed_whitespace);
+                            state.received.push_str(text);
+                        }
+                        state.saw_tool_calls |= choice.delta.tool_calls.is_some();
+                    }
+                    return Some((Ok(chunk), Some(state)));
+                }
+                Some(Err(e))
+                    if e.is_transient() && state.reconnects_left > 0 && !state.saw_tool_calls =>
+                {
+                    state.reconnects_left -= 1;
+                    let trimmed = state.received.trim_end().len();
+                    state.trimmed_whitespace = state.received[trimmed..].to_string();
+                    let request = with_prefill(state.request.clone(), &state.received);
+                    let resumed = open_stream(
+                        &state.inner,
+                        &state.circuit_breaker,
+                        state.metrics.as_deref(),
+                        request,
+                    )
+                    .await;
+                    match resumed {
+                        Ok(stream) => state.stream = stream,
+                        Err(e) => return Some((Err(e), None)),
+                    }
+                }
+                Some(Err(e)) => return Some((Err(e), None)),
+                None => return None,
+            }
+        }
+    }))
+}
+
+/// Drop the part of `pending` that `text` starts with. Providers such as
+/// Anthropic trim trailing whitespace from a prefill, so the continuation
+/// may repeat whitespace the caller has already received; anything other
+/// than whitespace ends the check.
+fn strip_resumed_whitespace(text: &mut String, pending: &mut String) {
+    if pending.is_empty() {
+        return;
+    }
+    let common: usize = text
+        .chars()
+        .zip(pending.chars())
+        .take_while(|(a, b)| a == b)
+        .map(|(c, _)| c.len_utf8())
+        .sum();
+    text.drain(..common);
+    if text.is_empty() {
+        pending.drain(..common);
+    } else {
+        pending.clear();
+    }
+}
+
+/// `request` with `text` appended to its trailing assistant prefill, or
+/// added as one
+fn with_prefill(mut request: crate::CompletionRequest, text: &str) -> crate::CompletionRequest {
+    if let Some(last) = request.messages.last_mut() {
+        if last.role == crate::Role::Assistant && last.tool_calls.is_none() {
+            if let crate::MessageContent::Text(prefill) = &mut last.content {
+                prefill.push_str(text);
+                return request;
+            }
+        }
+    }
+    if !text.is_empty() {
+        request.messages.push(crate::Message::assistant(text));
+    }
+    request
 }
 
 /// Enhance basic errors with more detailed error information
@@ -1287,6 +1626,7 @@ fn enhance_error(error: AiError, provider_name: &str) -> AiError {
         e @ AiError::NetworkError { .. }
         | e @ AiError::TimeoutError { .. }
         | e @ AiError::RateLimitExceeded { .. }
+        | e @ AiError::QuotaExceeded { .. }
         | e @ AiError::InvalidApiKey { .. }
         | e @ AiError::ProviderError { .. } => e,
 
@@ -1312,14 +1652,89 @@ fn enhance_error(error: AiError, provider_name: &str) -> AiError {
             message: e.to_string(),
             error_code: None,
             retryable: e.is_retryable(),
+            status_code: e.http_status(),
+            request_id: None,
         },
     }
 }
 
+/// Named retry and circuit breaker settings for [`ResilientProviderBuilder::preset`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResiliencePreset {
+    /// Patient and gentle on the upstream: 5 attempts with exponential
+    /// backoff from 2s (capped at 60s, 5 minutes in total); the circuit opens
+    /// at a 30% failure rate over 20+ requests in 2 minutes and stays open
+    /// for 60s
+    Conservative,
+    /// Fail fast: 2 attempts 250ms apart (capped at 5s, 30s in total); the
+    /// circuit only opens at a 70% failure rate over 5+ requests in 30s and
+    /// probes again after 10s
+    Aggressive,
+    /// A single attempt and a circuit that never opens
+    None,
+}
+
+impl ResiliencePreset {
+    pub fn retry_config(self) -> RetryConfig {
+        match self {
+            Self::Conservative => RetryConfig {
+                max_attempts: 5,
+                initial_delay: Duration::from_secs(2),
+                max_delay: Duration::from_secs(60),
+                backoff: BackoffStrategy::Exponential { multiplier: 2.0 },
+                jitter: JitterStrategy::Full,
+                max_total_time: Some(Duration::from_secs(300)),
+                ..Default::default()
+            },
+            Self::Aggressive => RetryConfig {
+                max_attempts: 2,
+                initial_delay: Duration::from_millis(250),
+                max_delay: Duration::from_secs(5),
+                backoff: BackoffStrategy::Exponential { multiplier: 2.0 },
+                jitter: JitterStrategy::Half,
+                max_total_time: Some(Duration::from_secs(30)),
+                ..Default::default()
+            },
+            Self::None => RetryConfig {
+                max_attempts: 1,
+                ..Default::default()
+            },
+        }
+    }
+
+    pub fn circuit_breaker_config(self) -> CircuitBreakerConfig {
+        match self {
+            Self::Conservative => CircuitBreakerConfig {
+                failure_threshold: 30.0,
+                minimum_request_count: 20,
+                measurement_window: Duration::from_secs(120),
+                recovery_timeout: Duration::from_secs(60),
+                half_open_max_requests: 1,
+                success_threshold: 80.0,
+            },
+            Self::Aggressive => CircuitBreakerConfig {
+                failure_threshold: 70.0,
+                minimum_request_count: 5,
+                measurement_window: Duration::from_secs(30),
+                recovery_timeout: Duration::from_secs(10),
+                half_open_max_requests: 5,
+                success_threshold: 50.0,
+            },
+            // The request count is never reached, so the circuit stays closed
+            Self::None => CircuitBreakerConfig {
+                minimum_request_count: u32::MAX,
+                ..Default::default()
+            },
+        }
+    }
+}
+
 /// Builder for creating resilient providers with custom configuration
 pub struct ResilientProviderBuilder {
     retry_config: RetryConfig,
     circuit_breaker_config: CircuitBreakerConfig,
+    metrics: Option<Arc<MetricsCollector>>,
+    stream_reconnects: u32,
 }
 
 impl ResilientProviderBuilder {
@@ -1327,9 +1742,19 @@ impl ResilientProviderBuilder {
         Self {
             retry_config: RetryConfig::default(),
             circuit_breaker_config: CircuitBreakerConfig::default(),
+            metrics: None,
+            stream_reconnects: 0,
         }
     }
 
+    /// Start from a preset's retry and circuit breaker settings; later
+    /// builder calls override individual values
+    pub fn preset(mut self, preset: ResiliencePreset) -> Self {
+        self.retry_config = preset.retry_config();
+        self.circuit_breaker_config = preset.circuit_breaker_config();
+        self
+    }
+
     pub fn retry_config(mut self, config: RetryConfig) -> Self {
         self.retry_config = config;
         self
@@ -1355,8 +1780,29 @@ impl ResilientProviderBuilder {
         self
     }
 
+    /// Record attempts, retries and circuit state changes in `metrics`
+    pub fn metrics(mut self, metrics: Arc<MetricsCollector>) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    /// See [`ResilientProvider::with_stream_reconnects`]
+    pub fn stream_reconnects(mut self, attempts: u32) -> Self {
+        self.stream_reconnects = attempts;
+        self
+    }
+
     pub fn build(self, provider: Arc<dyn crate::CompletionProvider>) -> ResilientProvider {
-        ResilientProvider::with_config(provider, self.retry_config, self.circuit_breaker_config)
+        let provider = ResilientProvider::with_config(
+            provider,
+            self.retry_config,
+            self.circuit_breaker_config,
+        )
+        .with_stream_reconnects(self.stream_reconnects);
+        match self.metrics {
+            Some(metrics) => provider.with_metrics(metrics),
+            None => provider,
+        }
     }
 }
 
@@ -1375,3 +1821,520 @@ where
     let executor = RetryExecutor::new(RetryConfig::default());
     executor.execute(operation).await
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    fn executor(max_attempts: u32) -> RetryExecutor {
+        RetryExecutor::new(
+            RetryConfigBuilder::new()
+                .max_attempts(max_attempts)
+                .initial_delay(Duration::from_millis(1))
+                .fixed_backoff()
+                .no_jitter()
+                .build(),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_retries_exhausted_reports_attempts() {
+        let calls = AtomicU32::new(0);
+        let result: Result<()> = executor(3)
+            .execute(|| {
+                calls.fetch_add(1, Ordering::SeqCst);
+                async {
+                    Err(AiError::NetworkError {
+                        message: "connection reset".to_string(),
+                        retryable: true,
+                        status_code: Some(503),
+                    })
+                }
+            })
+            .await;
+
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+        let err = result.unwrap_err();
+        assert_eq!(err.http_status(), Some(503));
+        assert!(!err.is_retryable());
+        match err {
+            AiError::RetriesExhausted {
+                attempts,
+                elapsed,
+                source,
+            } => {
+                assert_eq!(attempts, 3);
+                assert!(elapsed >= Duration::from_millis(2));
+                assert!(matches!(*source, AiError::NetworkError { .. }));
+            }
+            other => panic!("expected RetriesExhausted, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_non_retryable_error_is_returned_unwrapped() {
+        let result: Result<()> = executor(3)
+            .execute(|| async {
+                Err(AiError::InvalidApiKey {
+                    provider: "openai".to_string(),
+                })
+            })
+            .await;
+
+        assert!(matches!(result, Err(AiError::InvalidApiKey { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_circuit_breaker_failure_rate_excludes_expired_entries() {
+        let breaker = CircuitBreaker::new(
+            "test",
+            CircuitBreakerConfig {
+                minimum_request_count: 100,
+                measurement_window: Duration::from_millis(400),
+                ..Default::default()
+            },
+        );
+        let fail = || async {
+            Err::<(), _>(AiError::NetworkError {
+                message: "timeout".to_string(),
+                retryable: true,
+                status_code: None,
+            })
+        };
+
+        for _ in 0..2 {
+            let _ = breaker.execute(fail).await;
+        }
+        sleep(Duration::from_millis(200)).await;
+        for _ in 0..2 {
+            breaker.execute(|| async { Ok(()) }).await.unwrap();
+        }
+        assert_eq!(breaker.metrics().failure_rate, 50.0);
+
+        // The failures are now outside the window; no new request has been
+        // recorded, so only the read can drop them
+        sleep(Duration::from_millis(300)).await;
+        let metrics = breaker.metrics();
+        assert_eq!(metrics.failure_rate, 0.0);
+        assert_eq!(metrics.failed_requests, 0);
+        assert_eq!(metrics.requests_in_window, 2);
+    }
+
+    #[tokio::test]
+    async fn test_transient_errors_classified_separately_from_retryable() {
+        let provider_error = |status_code, retryable| AiError::ProviderError {
+            provider: "test".to_string(),
+            message: "failed".to_string(),
+            error_code: None,
+            retryable,
+            status_code,
+            request_id: None,
+        };
+
+        let transient = [
+            AiError::NetworkError {
+                message: "reset".to_string(),
+                retryable: false,
+                status_code: None,
+            },
+            AiError::TimeoutError {
+                timeout: Duration::from_secs(30),
+                retryable: false,
+            },
+            AiError::RateLimitExceeded {
+                retry_after: None,
+                daily_limit: None,
+                requests_remaining: None,
+            },
+            AiError::ServiceUnavailable {
+                provider: "test".to_string(),
+                retry_after: None,
+            },
+            provider_error(Some(503), false),
+            provider_error(Some(529), true),
+        ];
+        for error in &transient {
+            assert!(error.is_transient(), "{:?}", error);
+        }
+
+        let permanent = [
+            AiError::InvalidApiKey {
+                provider: "test".to_string(),
+            },
+            AiError::AuthenticationFailed {
+                reason: "bad key".to_string(),
+            },
+            AiError::InvalidRequest {
+                message: "bad field".to_string(),
+                field: None,
+                code: None,
+            },
+            AiError::ContentFiltered {
+                reason: "policy".to_string(),
+                category: None,
+            },
+            // Retrying is allowed, but the request itself is at fault
+            provider_error(Some(400), true),
+            AiError::InternalError {
+                message: "bug".to_string(),
+                component: None,
+            },
+        ];
+        for error in &permanent {
+            assert!(!error.is_transient(), "{:?}", error);
+        }
+        assert!(AiError::RetriesExhausted {
+            attempts: 3,
+            elapsed: Duration::from_secs(1),
+            source: Box::new(provider_error(Some(502), true)),
+        }
+        .is_transient());
+
+        // Only transient failures can open the circuit
+        let breaker = CircuitBreaker::new(
+            "test",
+            CircuitBreakerConfig {
+                minimum_request_count: 2,
+                ..Default::default()
+            },
+        );
+        for _ in 0..3 {
+            let _ = breaker
+                .execute(|| async { Err::<(), _>(provider_error(Some(400), false)) })
+                .await;
+        }
+        assert_eq!(breaker.metrics().total_requests, 0);
+        assert_eq!(breaker.metrics().state, CircuitState::Closed);
+
+        for _ in 0..4 {
+            let _ = breaker
+                .execute(|| async { Err::<(), _>(provider_error(Some(500), true)) })
+                .await;
+        }
+        assert!(matches!(breaker.metrics().state, CircuitState::Open { .. }));
+    }
+
+    #[tokio::test]
+    async fn test_permanent_error_in_half_open_leaves_circuit_alone() {
+        let breaker = CircuitBreaker::new(
+            "test",
+            CircuitBreakerConfig {
+                minimum_request_count: 2,
+                recovery_timeout: Duration::from_millis(50),
+                half_open_max_requests: 1,
+                success_threshold: 100.0,
+                ..Default::default()
+            },
+        );
+        for _ in 0..2 {
+            let _ = breaker
+                .execute(|| async {
+                    Err::<(), _>(AiError::ServiceUnavailable {
+                        provider: "test".to_string(),
+                        retry_after: None,
+                    })
+                })
+                .await;
+        }
+        assert!(matches!(breaker.state(), CircuitState::Open { .. }));
+
+        sleep(Duration::from_millis(60)).await;
+        let result = breaker
+            .execute(|| async {
+                Err::<(), _>(AiError::InvalidApiKey {
+                    provider: "test".to_string(),
+                })
+            })
+            .await;
+        assert!(matches!(result, Err(AiError::InvalidApiKey { .. })));
+        assert!(matches!(
+            breaker.state(),
+            CircuitState::HalfOpen { attempts: 0, .. }
+        ));
+
+        // The probe slot is still free for a real outcome
+        breaker.execute(|| async { Ok(()) }).await.unwrap();
+        assert_eq!(breaker.state(), CircuitState::Closed);
+    }
+
+    #[test]
+    fn test_resilient_provider_reports_inner_capabilities() {
+        use crate::CompletionProvider;
+
+        let mock = Arc::new(crate::providers::MockProvider::new());
+        let provider = ResilientProviderBuilder::new().build(mock.clone());
+        assert_eq!(provider.capabilities(), mock.capabilities());
+        assert!(provider.capabilities().assistant_prefill);
+
+        let catalog = provider.model_catalog();
+        assert_eq!(catalog.len(), 1);
+        assert!(catalog[0].supports_functions);
+    }
+
+    #[test]
+    fn test_resilience_presets() {
+        let conservative = ResilientProviderBuilder::new().preset(ResiliencePreset::Conservative);
+        let retry = &conservative.retry_config;
+        assert_eq!(retry.max_attempts, 5);
+        assert_eq!(retry.initial_delay, Duration::from_secs(2));
+        assert_eq!(retry.max_delay, Duration::from_secs(60));
+        assert_eq!(retry.max_total_time, Some(Duration::from_secs(300)));
+        let breaker = &conservative.circuit_breaker_config;
+        assert_eq!(breaker.failure_threshold, 30.0);
+        assert_eq!(breaker.minimum_request_count, 20);
+        assert_eq!(breaker.measurement_window, Duration::from_secs(120));
+        assert_eq!(breaker.recovery_timeout, Duration::from_secs(60));
+
+        let aggressive = ResilientProviderBuilder::new().preset(ResiliencePreset::Aggressive);
+        let retry = &aggressive.retry_config;
+        assert_eq!(retry.max_attempts, 2);
+        assert_eq!(retry.initial_delay, Duration::from_millis(250));
+        assert_eq!(retry.max_delay, Duration::from_secs(5));
+        assert_eq!(retry.max_total_time, Some(Duration::from_secs(30)));
+        let breaker = &aggressive.circuit_breaker_config;
+        assert_eq!(breaker.failure_threshold, 70.0);
+        assert_eq!(breaker.minimum_request_count, 5);
+        assert_eq!(breaker.measurement_window, Duration::from_secs(30));
+        assert_eq!(breaker.recovery_timeout, Duration::from_secs(10));
+
+        let none = ResilientProviderBuilder::new().preset(ResiliencePreset::None);
+        assert_eq!(none.retry_config.max_attempts, 1);
+        let breaker = CircuitBreaker::new("none", none.circuit_breaker_config);
+        for _ in 0..100 {
+            breaker.record_failure(Instant::now());
+        }
+        assert!(breaker.allow_request());
+
+        // Later builder calls override the preset
+        let tuned = ResilientProviderBuilder::new()
+            .preset(ResiliencePreset::Aggressive)
+            .max_retries(4)
+            .recovery_timeout(Duration::from_secs(1));
+        assert_eq!(tuned.retry_config.max_attempts, 4);
+        assert_eq!(
+            tuned.circuit_breaker_config.recovery_timeout,
+            Duration::from_secs(1)
+        );
+        assert_eq!(tuned.circuit_breaker_config.failure_threshold, 70.0);
+    }
+
+    #[tokio::test]
+    async fn test_resilient_provider_records_attempt_metrics() {
+        use crate::providers::MockProvider;
+        use crate::CompletionProvider;
+
+        let network_error = || AiError::NetworkError {
+            message: "connection reset".to_string(),
+            retryable: true,
+            status_code: None,
+        };
+        let mock = MockProvider::new()
+            .with_error(network_error())
+            .with_error(network_error())
+            .with_response("ok")
+            .with_error(network_error())
+            .with_error(network_error());
+        let provider_name = mock.name();
+
+        let metrics = Arc::new(MetricsCollector::new());
+        let provider = ResilientProviderBuilder::new()
+            .retry_config(
+                RetryConfigBuilder::new()
+                    .max_attempts(3)
+                    .initial_delay(Duration::from_millis(1))
+                    .fixed_backoff()
+                    .no_jitter()
+                    .build(),
+            )
+            .circuit_breaker_config(CircuitBreakerConfig {
+                minimum_request_count: 1,
+                failure_threshold: 50.0,
+                ..CircuitBreakerConfig::default()
+            })
+            .metrics(metrics.clone())
+            .build(Arc::new(mock));
+
+        let request = crate::CompletionRequest::new("mock", vec![crate::Message::user("hi")]);
+
+        // Two failures then a success
+        provider.complete(request.clone()).await.unwrap();
+        let recorded = metrics.get_resilience_metrics(provider_name).unwrap();
+        assert_eq!(recorded.attempts, 3);
+        assert_eq!(recorded.failed_attempts, 2);
+        assert_eq!(recorded.retries, 2);
+        assert_eq!(recorded.total_retry_delay, Duration::from_millis(2));
+        assert!(recorded.circuit_transitions.is_empty());
+
+        // The queue runs dry after two more failures, so the call fails and
+        // the breaker opens
+        assert!(provider.complete(request).await.is_err());
+        let recorded = metrics.get_resilience_metrics(provider_name).unwrap();
+        assert_eq!(recorded.failed_attempts, 5);
+        assert_eq!(recorded.circuit_transitions.get("closed->open"), Some(&1));
+    }
+
+    #[tokio::test]
+    async fn test_deadline_stops_retries() {
+        use crate::providers::MockProvider;
+
+        let mut mock = MockProvider::new();
+        for _ in 0..10 {
+            mock = mock.with_error(AiError::NetworkError {
+                message: "connection reset".to_string(),
+                retryable: true,
+                status_code: None,
+            });
+        }
+        let mock = Arc::new(mock);
+        let provider = ResilientProviderBuilder::new()
+            .retry_config(
+                RetryConfigBuilder::new()
+                    .max_attempts(10)
+                    .initial_delay(Duration::from_millis(200))
+                    .fixed_backoff()
+                    .no_jitter()
+                    .build(),
+            )
+            .build(mock.clone());
+
+        let request = crate::CompletionRequest::new("mock", vec![crate::Message::user("hi")]);
+
+        // Attempts at 0ms, 200ms and 400ms; the next would start after the
+        // deadline
+        let start = Instant::now();
+        let deadline = start + Duration::from_millis(500);
+        let result = provider
+            .complete_with_deadline(request.clone(), Some(deadline))
+            .await;
+        match result {
+            Err(AiError::TimeoutError { timeout, .. }) => {
+                assert!(timeout <= deadline - start)
+            }
+            other => panic!("expected TimeoutError, got {:?}", other),
+        }
+        assert_eq!(mock.request_count(), 3);
+        assert!(Instant::now() <= deadline);
+        assert_eq!(provider.circuit_breaker_metrics().total_requests, 1);
+
+        // With no time left the circuit breaker is never consulted
+        let result = provider
+            .complete_with_deadline(request, Some(Instant::now()))
+            .await;
+        assert!(matches!(result, Err(AiError::TimeoutError { .. })));
+        assert_eq!(mock.request_count(), 3);
+        assert_eq!(provider.circuit_breaker_metrics().total_requests, 1);
+    }
+
+    /// Streams its text in two chunks, dropping the connection after the
+    /// first one on the initial request
+    struct DroppingProvider {
+        requests: Mutex<Vec<crate::CompletionRequest>>,
+    }
+
+    #[async_trait]
+    impl crate::CompletionProvider for DroppingProvider {
+        async fn complete(
+            &self,
+            _request: crate::CompletionRequest,
+        ) -> Result<crate::CompletionResponse> {
+            unreachable!("only streams are requested")
+        }
+
+        async fn complete_stream(
+            &self,
+            request: crate::CompletionRequest,
+        ) -> Result<Pin<Box<dyn Stream<Item = Result<crate::StreamChunk>> + Send>>> {
+            let chunk = |text: &str| {
+                Ok(crate::StreamChunk {
+                    id: "stream".to_string(),
+                    choices: vec![crate::StreamChoice {
+                        index: 0,
+                        delta: crate::Delta {
+                            role: None,
+                            content: Some(text.to_string()),
+                            tool_calls: None,
+                        },
+                        finish_reason: None,
+                    }],
+                    model: None,
+                    usage: None,
+                    metadata: None,
+                })
+            };
+            let mut requests = self.requests.lock().unwrap();
+            requests.push(request);
+            let items = if requests.len() == 1 {
+                vec![
+                    chunk("The quick brown "),
+                    Err(AiError::NetworkError {
+                        message: "connection reset".to_string(),
+                        retryable: true,
+                        status_code: None,
+                    }),
+                ]
+            } else {
+                vec![chunk("fox jumps.")]
+            };
+            Ok(Box::pin(futures::stream::iter(items)))
+        }
+
+        fn name(&self) -> &'static str {
+            "dropping"
+        }
+
+        fn default_model(&self) -> &'static str {
+            "dropping-model"
+        }
+
+        fn available_models(&self) -> Vec<&'static str> {
+            vec!["dropping-model"]
+        }
+
+        fn capabilities(&self) -> crate::ProviderCapabilities {
+            crate::ProviderCapabilities {
+                streaming: true,
+                assistant_prefill: true,
+                ..Default::default()
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_dropped_stream_resumes_with_prefill() {
+        use crate::CompletionProvider;
+
+        let inner = Arc::new(DroppingProvider {
+            requests: Mutex::new(Vec::new()),
+        });
+        let provider = ResilientProviderBuilder::new()
+            .stream_reconnects(1)
+            .build(inner.clone());
+
+        let request = crate::CompletionRequest {
+            model: "dropping-model".to_string(),
+            messages: vec![crate::Message::user("Tell me about the fox")],
+            stream: Some(true),
+            ..Default::default()
+        };
+        let chunks: Vec<_> = provider
+            .complete_stream(request)
+            .await
+            .unwrap()
+            .collect()
+            .await;
+
+        let text: String = chunks
+            .into_iter()
+            .map(|chunk| chunk.unwrap().choices[0].delta.content.clone().unwrap())
+            .collect();
+        assert_eq!(text, "The quick brown fox jumps.");
+
+        // The retry continues from what was already received
+        let requests = inner.requests.lock().unwrap();
+        assert_eq!(requests.len(), 2);
+        let prefill = requests[1].messages.last().unwrap();
+        assert_eq!(prefill.role, crate::Role::Assistant);
+        assert_eq!(prefill.content.as_text(), Some("The quick brown "));
+    }
+}
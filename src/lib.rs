@@ -1,7 +1,9 @@
 pub mod agent;
+pub mod backoff;
 pub mod embeddings;
 pub mod error;
 pub mod models;
+pub mod moderation;
 pub mod observability;
 pub mod providers;
 pub mod traits;
@@ -10,6 +12,12 @@ pub use error::*;
 pub use models::*;
 pub use traits::*;
 
+// Compile the README examples as doctests so they keep up with the API;
+// fragments that need a provider in scope are marked `ignore` there
+#[cfg(doctest)]
+#[doc = include_str!("../README.md")]
+struct ReadmeDoctests;
+
 // Re-export derive macros when the derive feature is enabled
 #[cfg(feature = "derive")]
 pub use lib_ai_derive::{AiTool, Structured};
@@ -1,5 +1,4 @@
-use lib_ai::{providers::*, CompletionProvider, CompletionRequest, Message, MessageContent, Role};
-use tokio;
+use lib_ai::{providers::*, CompletionProvider, CompletionRequest, Message};
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -10,30 +9,13 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let request = CompletionRequest {
         model: provider.default_model().to_string(),
         messages: vec![
-            Message {
-                role: Role::System,
-                content: MessageContent::text("You are a helpful assistant."),
-                tool_calls: None,
-                tool_call_id: None,
-            },
-            Message {
-                role: Role::User,
-                content: MessageContent::text("What is 2+2?"),
-                tool_calls: None,
-                tool_call_id: None,
-            },
+            Message::system("You are a helpful assistant."),
+            Message::user("What is 2+2?"),
         ],
         temperature: Some(0.7),
         max_tokens: Some(150),
         stream: Some(false),
-        top_p: None,
-        frequency_penalty: None,
-        presence_penalty: None,
-        stop: None,
-        tools: None,
-        tool_choice: None,
-        response_format: None,
-        json_schema: None,
+        ..Default::default()
     };
 
     let response = provider.complete(request).await?;
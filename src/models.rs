@@ -1,15 +1,86 @@
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+use crate::error::AiError;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Message {
     pub role: Role,
     pub content: MessageContent,
     pub tool_calls: Option<Vec<ToolCall>>,
     pub tool_call_id: Option<String>,
+    /// Reasoning/thinking text produced alongside the reply, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reasoning_content: Option<String>,
+    /// Signature returned with the reasoning, which Anthropic requires to
+    /// accept `reasoning_content` back on a later turn
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reasoning_signature: Option<String>,
+    /// Name of the participant sending the message, to tell apart several
+    /// users, agents or tools sharing a role (OpenAI)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// Why the model declined to answer, returned instead of content (OpenAI)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub refusal: Option<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl Message {
+    /// A message from `role` with no tool calls, reasoning or name
+    pub fn new(role: Role, content: MessageContent) -> Self {
+        Self {
+            role,
+            content,
+            tool_calls: None,
+            tool_call_id: None,
+            reasoning_content: None,
+            reasoning_signature: None,
+            name: None,
+            refusal: None,
+        }
+    }
+
+    pub fn system(text: impl Into<String>) -> Self {
+        Self::new(Role::System, MessageContent::text(text))
+    }
+
+    pub fn user(text: impl Into<String>) -> Self {
+        Self::new(Role::User, MessageContent::text(text))
+    }
+
+    pub fn assistant(text: impl Into<String>) -> Self {
+        Self::new(Role::Assistant, MessageContent::text(text))
+    }
+
+    /// The result of the tool call with id `tool_call_id`
+    pub fn tool(tool_call_id: impl Into<String>, text: impl Into<String>) -> Self {
+        Self {
+            tool_call_id: Some(tool_call_id.into()),
+            ..Self::new(Role::Tool, MessageContent::text(text))
+        }
+    }
+
+    /// A structured tool result. Providers that accept JSON tool output
+    /// receive the value as is; the rest get it serialized to a string.
+    pub fn tool_json(tool_call_id: impl Into<String>, value: Value) -> Self {
+        Self {
+            content: MessageContent::Parts(vec![ContentPart::Json { value }]),
+            ..Self::tool(tool_call_id, "")
+        }
+    }
+
+    /// Attribute the message to a named participant
+    pub fn with_name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(untagged)]
 pub enum MessageContent {
     Text(String),
@@ -21,22 +92,139 @@ impl MessageContent {
         MessageContent::Text(s.into())
     }
 
+    /// Append `other`: two texts are joined with a blank line, anything
+    /// else becomes the parts of both
+    fn concat(self, other: MessageContent) -> Self {
+        fn into_parts(content: MessageContent) -> Vec<ContentPart> {
+            match content {
+                MessageContent::Text(text) => vec![ContentPart::Text { text }],
+                MessageContent::Parts(parts) => parts,
+            }
+        }
+
+        match (self, other) {
+            (MessageContent::Text(a), MessageContent::Text(b)) => {
+                MessageContent::Text(format!("{}\n\n{}", a, b))
+            }
+            (a, b) => {
+                let mut parts = into_parts(a);
+                parts.extend(into_parts(b));
+                MessageContent::Parts(parts)
+            }
+        }
+    }
+
     pub fn as_text(&self) -> Option<&str> {
         match self {
             MessageContent::Text(s) => Some(s),
             _ => None,
         }
     }
+
+    /// The text, or for multipart content the text parts concatenated in
+    /// order; other parts are skipped
+    pub fn as_text_lossy(&self) -> Cow<'_, str> {
+        match self {
+            MessageContent::Text(s) => Cow::Borrowed(s),
+            MessageContent::Parts(parts) => Cow::Owned(
+                parts
+                    .iter()
+                    .filter_map(|part| match part {
+                        ContentPart::Text { text } => Some(text.as_str()),
+                        _ => None,
+                    })
+                    .collect(),
+            ),
+        }
+    }
+
+    /// The value of content made of a single JSON part
+    pub fn as_json(&self) -> Option<&Value> {
+        match self {
+            MessageContent::Parts(parts) => match parts.as_slice() {
+                [ContentPart::Json { value }] => Some(value),
+                _ => None,
+            },
+            MessageContent::Text(_) => None,
+        }
+    }
+
+    /// Rough token count: about four bytes per token for text and JSON, and
+    /// a fixed cost per image following OpenAI's rules (85 tokens at `low`
+    /// detail, 765 otherwise, the cost of a 1024x1024 image at high detail).
+    /// Anthropic and Gemini charge in the same range for images of that
+    /// size. Audio and documents are left out: their base64 size says
+    /// little about what they cost, and a few-MB PDF would otherwise count
+    /// as hundreds of thousands of tokens.
+    pub fn approx_tokens(&self) -> usize {
+        match self {
+            MessageContent::Text(text) => text.len() / 4,
+            MessageContent::Parts(parts) => parts
+                .iter()
+                .map(|part| match part {
+                    ContentPart::Image { image_url } => match image_url.detail.as_deref() {
+                        Some("low") => IMAGE_TOKENS_LOW_DETAIL,
+                        _ => IMAGE_TOKENS,
+                    },
+                    ContentPart::Audio { .. } | ContentPart::Document { .. } => 0,
+                    _ => part_byte_len(part) / 4,
+                })
+                .sum(),
+        }
+    }
+
+    /// Size of the content as sent, in bytes: text, image URLs (including
+    /// base64 data URLs), encoded audio and documents, and serialized JSON
+    pub fn byte_len(&self) -> usize {
+        match self {
+            MessageContent::Text(text) => text.len(),
+            MessageContent::Parts(parts) => parts.iter().map(part_byte_len).sum(),
+        }
+    }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Tokens counted for an image at `low` detail
+const IMAGE_TOKENS_LOW_DETAIL: usize = 85;
+/// Tokens counted for an image at any other detail
+const IMAGE_TOKENS: usize = 765;
+
+fn part_byte_len(part: &ContentPart) -> usize {
+    match part {
+        ContentPart::Text { text } => text.len(),
+        ContentPart::Image { image_url } => image_url.url.len(),
+        ContentPart::Audio { data, .. } | ContentPart::Document { data, .. } => data.len(),
+        ContentPart::Json { value } => value.to_string().len(),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(tag = "type", rename_all = "snake_case")]
 pub enum ContentPart {
-    Text { text: String },
-    Image { image_url: ImageUrl },
+    Text {
+        text: String,
+    },
+    Image {
+        image_url: ImageUrl,
+    },
+    /// Base64-encoded audio clip; `format` is the encoding, e.g. "wav" or "mp3"
+    Audio {
+        data: String,
+        format: String,
+    },
+    /// Base64-encoded document such as a PDF
+    Document {
+        data: String,
+        mime_type: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        name: Option<String>,
+    },
+    /// Structured data, such as a tool result
+    Json {
+        value: Value,
+    },
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ImageUrl {
     pub url: String,
     pub detail: Option<String>,
@@ -51,7 +239,43 @@ pub enum Role {
     Tool,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl Role {
+    /// Wire name of the role, as used by OpenAI-compatible APIs
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::System => "system",
+            Role::User => "user",
+            Role::Assistant => "assistant",
+            Role::Tool => "tool",
+        }
+    }
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Role {
+    type Err = AiError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "system" => Ok(Role::System),
+            "user" => Ok(Role::User),
+            "assistant" => Ok(Role::Assistant),
+            "tool" => Ok(Role::Tool),
+            other => Err(AiError::InvalidRequest {
+                message: format!("Unknown role '{}'", other),
+                field: Some("role".to_string()),
+                code: None,
+            }),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct CompletionRequest {
     pub model: String,
     pub messages: Vec<Message>,
@@ -66,6 +290,228 @@ pub struct CompletionRequest {
     pub tool_choice: Option<ToolChoice>,
     pub response_format: Option<ResponseFormat>,
     pub json_schema: Option<JsonSchema>,
+    /// Reasoning effort for models that support it (OpenAI o-series)
+    pub reasoning_effort: Option<ReasoningEffort>,
+    /// Token budget for extended thinking (Anthropic)
+    pub thinking_budget_tokens: Option<u32>,
+    /// System prompt kept separate from `messages`; see [`CompletionRequest::normalize_system`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub system: Option<String>,
+    /// Return log probabilities of the output tokens in
+    /// [`Choice::logprobs`] (OpenAI)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub logprobs: Option<bool>,
+    /// Number of most likely alternatives, 0 to 20, to return for each
+    /// output token; implies `logprobs` (OpenAI)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub top_logprobs: Option<u8>,
+    /// Processing tier such as `auto`, `default` or `flex`, trading latency
+    /// for price (OpenAI)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub service_tier: Option<String>,
+    /// Tags such as `feature=search` that attribute the request's cost in
+    /// a [`CostTracker`](crate::observability::CostTracker); never sent to
+    /// the provider
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub metadata: HashMap<String, String>,
+    /// Vendor-specific parameters with no common field, such as
+    /// `repetition_penalty` or `safe_prompt`. Where they go depends on the
+    /// provider:
+    ///
+    /// - OpenAI, xAI, OpenRouter, Together, Anthropic and Cohere: the top
+    ///   level of the request body
+    /// - Gemini: the top level of the request body, next to
+    ///   `generationConfig` rather than inside it
+    /// - Ollama: the top level of the `/api/chat` body (e.g. `keep_alive`),
+    ///   not the model `options`
+    /// - Replicate: the model's `input`, where they override values set from
+    ///   the fields above
+    ///
+    /// Except on Replicate, a key the provider's body already sets (such as
+    /// `temperature`) is rejected with [`AiError::InvalidRequest`].
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub extra: HashMap<String, Value>,
+}
+
+impl CompletionRequest {
+    /// A request for `model` with every option left unset
+    pub fn new(model: impl Into<String>, messages: Vec<Message>) -> Self {
+        Self {
+            model: model.into(),
+            messages,
+            ..Default::default()
+        }
+    }
+
+    /// Fold the `system` field into the message list.
+    ///
+    /// If the conversation already starts with a system message the two are
+    /// merged, with `system` first (ahead of its first text part when the
+    /// message has parts); otherwise a new system message is prepended.
+    /// Providers call this before converting messages, so callers may use
+    /// either the field or system messages (or both).
+    pub fn normalize_system(&mut self) {
+        let system = match self.system.take() {
+            Some(system) if !system.trim().is_empty() => system,
+            _ => return,
+        };
+
+        if let Some(first) = self.messages.first_mut() {
+            if first.role == Role::System {
+                match &mut first.content {
+                    MessageContent::Text(existing) => {
+                        *existing = format!("{}\n\n{}", system, existing);
+                    }
+                    MessageContent::Parts(parts) => match parts.first_mut() {
+                        Some(ContentPart::Text { text }) => {
+                            *text = format!("{}\n\n{}", system, text);
+                        }
+                        _ => parts.insert(0, ContentPart::Text { text: system }),
+                    },
+                }
+                return;
+            }
+        }
+
+        self.messages.insert(0, Message::system(system));
+    }
+
+    /// Merge runs of consecutive user or assistant messages into one, for
+    /// providers that require the two roles to alternate (see
+    /// [`ProviderCapabilities::alternating_roles`]). Text is joined with a
+    /// blank line; messages carrying tool calls or results, or sent under
+    /// different names, are left alone.
+    ///
+    /// [`ProviderCapabilities::alternating_roles`]: crate::ProviderCapabilities::alternating_roles
+    pub fn merge_consecutive_roles(&mut self) {
+        fn mergeable(message: &Message) -> bool {
+            matches!(message.role, Role::User | Role::Assistant)
+                && message.tool_calls.is_none()
+                && message.tool_call_id.is_none()
+        }
+
+        let mut merged: Vec<Message> = Vec::with_capacity(self.messages.len());
+        for message in std::mem::take(&mut self.messages) {
+            match merged.last_mut() {
+                Some(last)
+                    if last.role == message.role
+                        && last.name == message.name
+                        && mergeable(last)
+                        && mergeable(&message) =>
+                {
+                    let content = std::mem::replace(&mut last.content, MessageContent::text(""));
+                    last.content = content.concat(message.content);
+                }
+                _ => merged.push(message),
+            }
+        }
+        self.messages = merged;
+    }
+
+    /// Rough prompt size in tokens: see [`MessageContent::approx_tokens`],
+    /// plus 10 per message for role and framing
+    pub fn estimate_tokens(&self) -> usize {
+        let system = self.system.as_ref().map_or(0, |s| s.len() / 4);
+        system
+            + self
+                .messages
+                .iter()
+                .map(|m| m.content.approx_tokens() + 10)
+                .sum::<usize>()
+    }
+
+    /// Check the request for mistakes every provider would reject: no
+    /// messages, sampling parameters out of range, or `max_tokens` of zero.
+    /// Providers run it on every request before sending.
+    ///
+    /// A high `temperature` (above 1.0) combined with a narrow `top_p`
+    /// (below 0.5) is also rejected, since the two settings work against
+    /// each other; tune one or the other.
+    pub fn validate(&self) -> Result<(), AiError> {
+        let invalid = |field: &str, message: String| AiError::InvalidRequest {
+            message,
+            field: Some(field.to_string()),
+            code: None,
+        };
+
+        if self.messages.is_empty() {
+            return Err(invalid(
+                "messages",
+                "Request has no messages; add at least one user message".to_string(),
+            ));
+        }
+        if let Some(temperature) = self.temperature {
+            if !(0.0..=2.0).contains(&temperature) {
+                return Err(invalid(
+                    "temperature",
+                    format!(
+                        "temperature must be between 0.0 and 2.0, got {}",
+                        temperature
+                    ),
+                ));
+            }
+        }
+        if let Some(top_p) = self.top_p {
+            if !(0.0..=1.0).contains(&top_p) {
+                return Err(invalid(
+                    "top_p",
+                    format!("top_p must be between 0.0 and 1.0, got {}", top_p),
+                ));
+            }
+        }
+        if let (Some(temperature), Some(top_p)) = (self.temperature, self.top_p) {
+            if temperature > 1.0 && top_p < 0.5 {
+                return Err(invalid(
+                    "top_p",
+                    format!(
+                        "temperature {} with top_p {} work against each other; set only one of them",
+                        temperature, top_p
+                    ),
+                ));
+            }
+        }
+        for (field, penalty) in [
+            ("frequency_penalty", self.frequency_penalty),
+            ("presence_penalty", self.presence_penalty),
+        ] {
+            if let Some(penalty) = penalty.filter(|p| !(-2.0..=2.0).contains(p)) {
+                return Err(invalid(
+                    field,
+                    format!("{} must be between -2.0 and 2.0, got {}", field, penalty),
+                ));
+            }
+        }
+        if self.max_tokens == Some(0) {
+            return Err(invalid(
+                "max_tokens",
+                "max_tokens must be at least 1".to_string(),
+            ));
+        }
+        if let Some(top_logprobs) = self.top_logprobs.filter(|n| *n > 20) {
+            return Err(invalid(
+                "top_logprobs",
+                format!(
+                    "top_logprobs must be between 0 and 20, got {}",
+                    top_logprobs
+                ),
+            ));
+        }
+        if self.top_logprobs.is_some() && self.logprobs == Some(false) {
+            return Err(invalid(
+                "top_logprobs",
+                "top_logprobs requires logprobs; leave logprobs unset or true".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReasoningEffort {
+    Low,
+    Medium,
+    High,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -74,6 +520,22 @@ pub struct CompletionResponse {
     pub model: String,
     pub choices: Vec<Choice>,
     pub usage: Option<Usage>,
+    /// Provider-specific data with no common field, such as the OpenAI
+    /// `service_tier` that processed the request
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<Value>,
+}
+
+impl CompletionResponse {
+    /// Text of the first choice, if it is plain text
+    pub fn text(&self) -> Option<&str> {
+        self.choices.first()?.message.content.as_text()
+    }
+
+    /// Tool calls requested by the first choice
+    pub fn tool_calls(&self) -> Option<&[ToolCall]> {
+        self.choices.first()?.message.tool_calls.as_deref()
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -81,6 +543,47 @@ pub struct Choice {
     pub index: u32,
     pub message: Message,
     pub finish_reason: Option<String>,
+    /// Log probabilities of the output tokens, when requested with
+    /// [`CompletionRequest::logprobs`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub logprobs: Option<LogProbs>,
+}
+
+impl Choice {
+    /// `finish_reason` normalized across providers
+    pub fn stop_reason(&self) -> Option<FinishReason> {
+        self.finish_reason.as_deref().map(FinishReason::from_raw)
+    }
+}
+
+/// Log probabilities of a reply's tokens
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct LogProbs {
+    /// One entry per output token, in order
+    pub content: Vec<TokenLogProb>,
+}
+
+/// An output token with its log probability
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TokenLogProb {
+    pub token: String,
+    pub logprob: f32,
+    /// UTF-8 bytes of the token, for tokens that are not valid text alone
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bytes: Option<Vec<u8>>,
+    /// The most likely tokens at this position, when
+    /// [`CompletionRequest::top_logprobs`] was set
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub top_logprobs: Vec<TopLogProb>,
+}
+
+/// An alternative token considered at a position
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TopLogProb {
+    pub token: String,
+    pub logprob: f32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bytes: Option<Vec<u8>>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -88,6 +591,9 @@ pub struct Usage {
     pub prompt_tokens: u32,
     pub completion_tokens: u32,
     pub total_tokens: u32,
+    /// Portion of `completion_tokens` spent on reasoning, when reported
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reasoning_tokens: Option<u32>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -95,6 +601,14 @@ pub struct StreamChunk {
     pub id: String,
     pub choices: Vec<StreamChoice>,
     pub model: Option<String>,
+    /// Token usage for the whole response, reported on the final chunk by
+    /// providers that support it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub usage: Option<Usage>,
+    /// Provider-specific data with no common field, such as Cohere
+    /// citations under `"citations"`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<Value>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -104,6 +618,45 @@ pub struct StreamChoice {
     pub finish_reason: Option<String>,
 }
 
+impl StreamChoice {
+    /// `finish_reason` normalized across providers
+    pub fn stop_reason(&self) -> Option<FinishReason> {
+        self.finish_reason.as_deref().map(FinishReason::from_raw)
+    }
+}
+
+/// Why a provider stopped generating. The provider's own value stays in
+/// `finish_reason`; this is the common vocabulary to branch on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FinishReason {
+    /// Natural end of the reply or a stop sequence
+    Stop,
+    /// Hit the token limit
+    Length,
+    /// The model is waiting for tool results
+    ToolCalls,
+    /// Withheld or cut off by a safety filter
+    ContentFilter,
+    /// A value not covered above, as sent by the provider
+    Other(String),
+}
+
+impl FinishReason {
+    /// Map a provider's raw finish reason, e.g. OpenAI's `length`,
+    /// Anthropic's `end_turn`, Gemini's `MAX_TOKENS` or Cohere's
+    /// `ERROR_LIMIT`
+    pub fn from_raw(raw: &str) -> Self {
+        match raw.to_ascii_lowercase().as_str() {
+            "stop" | "end_turn" | "stop_sequence" | "complete" | "eos" => Self::Stop,
+            "length" | "max_tokens" | "error_limit" => Self::Length,
+            "tool_calls" | "tool_use" | "tool_call" | "function_call" => Self::ToolCalls,
+            "content_filter" | "safety" | "recitation" | "blocklist" | "prohibited_content"
+            | "spii" | "refusal" | "error_toxic" => Self::ContentFilter,
+            _ => Self::Other(raw.to_string()),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Delta {
     pub role: Option<Role>,
@@ -117,7 +670,7 @@ pub struct Tool {
     pub function: ToolFunction,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 pub enum ToolType {
     Function,
@@ -130,14 +683,41 @@ pub struct ToolFunction {
     pub parameters: Value,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl ToolFunction {
+    /// Check call arguments against `parameters`, failing with
+    /// [`AiError::InvalidToolParameters`] that lists every violation with
+    /// its path
+    pub fn validate_arguments(&self, arguments: &Value) -> Result<(), AiError> {
+        self.check_arguments(&CompiledSchema::new(&self.parameters)?, arguments)
+    }
+
+    /// Like [`validate_arguments`](Self::validate_arguments), with
+    /// `parameters` already compiled
+    pub fn check_arguments(
+        &self,
+        compiled: &CompiledSchema,
+        arguments: &Value,
+    ) -> Result<(), AiError> {
+        let violations = compiled.violations(arguments);
+        if violations.is_empty() {
+            return Ok(());
+        }
+        Err(AiError::InvalidToolParameters {
+            tool_name: self.name.clone(),
+            message: join_violations(&violations),
+            expected_schema: Some(self.parameters.to_string()),
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ToolCall {
     pub id: String,
     pub r#type: ToolType,
     pub function: FunctionCall,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct FunctionCall {
     pub name: String,
     pub arguments: String,
@@ -157,6 +737,136 @@ pub struct FunctionCallDelta {
     pub arguments: Option<String>,
 }
 
+/// Assembles streamed `ToolCallDelta`s into complete `ToolCall`s, for
+/// callers consuming a stream directly rather than through an agent.
+/// Works whether a provider sends each call whole in one delta or spread
+/// over many fragments.
+#[derive(Debug, Default)]
+pub struct ToolCallAccumulator {
+    calls: Vec<PartialToolCall>,
+}
+
+#[derive(Debug)]
+struct PartialToolCall {
+    index: u32,
+    id: String,
+    name: String,
+    arguments: String,
+    emitted: bool,
+}
+
+impl PartialToolCall {
+    fn to_tool_call(&self) -> ToolCall {
+        ToolCall {
+            id: if self.id.is_empty() {
+                format!("call_{}", self.index)
+            } else {
+                self.id.clone()
+            },
+            r#type: ToolType::Function,
+            function: FunctionCall {
+                name: self.name.clone(),
+                arguments: if self.arguments.is_empty() {
+                    "{}".to_string()
+                } else {
+                    self.arguments.clone()
+                },
+            },
+        }
+    }
+
+    /// Named, with arguments that form a whole JSON object
+    fn is_complete(&self) -> bool {
+        !self.name.is_empty()
+            && serde_json::from_str::<Value>(&self.arguments).is_ok_and(|v| v.is_object())
+    }
+}
+
+impl ToolCallAccumulator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Merge a delta into the call at its index. Returns the call once it
+    /// first becomes complete: named, with arguments forming a JSON object.
+    pub fn push(&mut self, delta: &ToolCallDelta) -> Option<ToolCall> {
+        let position = self.position(delta);
+        let call = &mut self.calls[position];
+
+        if let Some(id) = delta.id.as_deref().filter(|id| !id.is_empty()) {
+            call.id = id.to_string();
+        }
+        if let Some(function) = &delta.function {
+            if let Some(name) = function.name.as_deref().filter(|_| call.name.is_empty()) {
+                call.name = name.to_string();
+            }
+            if let Some(arguments) = &function.arguments {
+                call.arguments.push_str(arguments);
+            }
+        }
+
+        if !call.emitted && call.is_complete() {
+            call.emitted = true;
+            return Some(call.to_tool_call());
+        }
+        None
+    }
+
+    /// Merge every tool call delta in a stream chunk, returning the calls
+    /// that became complete
+    pub fn push_chunk(&mut self, chunk: &StreamChunk) -> Vec<ToolCall> {
+        chunk
+            .choices
+            .iter()
+            .filter_map(|choice| choice.delta.tool_calls.as_ref())
+            .flatten()
+            .filter_map(|delta| self.push(delta))
+            .collect()
+    }
+
+    /// All calls seen so far in index order, complete or not
+    pub fn finish(self) -> Vec<ToolCall> {
+        let mut calls = self.calls;
+        calls.sort_by_key(|call| call.index);
+        calls
+            .iter()
+            .filter(|call| !call.name.is_empty())
+            .map(PartialToolCall::to_tool_call)
+            .collect()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.calls.is_empty()
+    }
+
+    /// Find or start the call a delta belongs to. Without an index, a new
+    /// id starts a new call and a missing id continues the latest one.
+    fn position(&mut self, delta: &ToolCallDelta) -> usize {
+        let existing = match (delta.index, delta.id.as_deref()) {
+            (Some(index), _) => self.calls.iter().position(|call| call.index == index),
+            (None, Some(id)) if !id.is_empty() => self.calls.iter().position(|call| call.id == id),
+            (None, _) => self.calls.len().checked_sub(1),
+        };
+        existing.unwrap_or_else(|| {
+            let index = delta.index.unwrap_or_else(|| {
+                self.calls
+                    .iter()
+                    .map(|call| call.index + 1)
+                    .max()
+                    .unwrap_or(0)
+            });
+            self.calls.push(PartialToolCall {
+                index,
+                id: String::new(),
+                name: String::new(),
+                arguments: String::new(),
+                emitted: false,
+            });
+            self.calls.len() - 1
+        })
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(untagged)]
 pub enum ToolChoice {
@@ -164,6 +874,100 @@ pub enum ToolChoice {
     Object(ToolChoiceObject),
 }
 
+impl ToolChoice {
+    /// Let the model decide whether to call a tool
+    pub fn auto() -> Self {
+        ToolChoice::String("auto".to_string())
+    }
+
+    /// Prevent the model from calling any tool
+    pub fn none() -> Self {
+        ToolChoice::String("none".to_string())
+    }
+
+    /// Force the model to call at least one tool
+    pub fn required() -> Self {
+        ToolChoice::String("required".to_string())
+    }
+
+    /// Force the model to call the named function
+    pub fn function(name: impl Into<String>) -> Self {
+        ToolChoice::Object(ToolChoiceObject {
+            r#type: ToolType::Function,
+            function: ToolChoiceFunction { name: name.into() },
+        })
+    }
+
+    /// Name of the forced function, if this choice targets one
+    pub fn function_name(&self) -> Option<&str> {
+        match self {
+            ToolChoice::Object(obj) => Some(&obj.function.name),
+            ToolChoice::String(_) => None,
+        }
+    }
+
+    /// Check that this choice is satisfiable with the given tool set.
+    ///
+    /// `required` and forced functions need at least one tool, and a forced
+    /// function must be one of the provided tools.
+    pub fn validate(&self, tools: Option<&[Tool]>) -> Result<(), AiError> {
+        let tools = tools.unwrap_or_default();
+        match self {
+            ToolChoice::String(mode) => match mode.as_str() {
+                "auto" | "none" => Ok(()),
+                "required" | "any" if tools.is_empty() => Err(AiError::InvalidRequest {
+                    message: format!("tool_choice '{}' requires at least one tool", mode),
+                    field: Some("tool_choice".to_string()),
+                    code: None,
+                }),
+                "required" | "any" => Ok(()),
+                other => Err(AiError::InvalidRequest {
+                    message: format!("Unknown tool_choice mode '{}'", other),
+                    field: Some("tool_choice".to_string()),
+                    code: None,
+                }),
+            },
+            ToolChoice::Object(obj) => {
+                if tools.iter().any(|t| t.function.name == obj.function.name) {
+                    Ok(())
+                } else {
+                    Err(AiError::ToolNotFound {
+                        tool_name: obj.function.name.clone(),
+                        available_tools: tools.iter().map(|t| t.function.name.clone()).collect(),
+                    })
+                }
+            }
+        }
+    }
+}
+
+impl FromStr for ToolChoice {
+    type Err = AiError;
+
+    /// Parses `auto`, `none`, `required` (or its alias `any`), or a bare
+    /// function name to force that function.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        match s {
+            "auto" => Ok(ToolChoice::auto()),
+            "none" => Ok(ToolChoice::none()),
+            "required" | "any" => Ok(ToolChoice::required()),
+            name if !name.is_empty()
+                && name
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') =>
+            {
+                Ok(ToolChoice::function(name))
+            }
+            other => Err(AiError::InvalidRequest {
+                message: format!("Invalid tool_choice '{}'", other),
+                field: Some("tool_choice".to_string()),
+                code: None,
+            }),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ToolChoiceObject {
     pub r#type: ToolType,
@@ -195,3 +999,630 @@ pub struct JsonSchema {
     pub schema: Value,
     pub strict: Option<bool>,
 }
+
+impl JsonSchema {
+    /// Every way `value` fails the schema, in document order; empty when it
+    /// is valid
+    pub fn violations(&self, value: &Value) -> Result<Vec<SchemaViolation>, AiError> {
+        schema_violations(&self.schema, value)
+    }
+
+    /// Compile the schema once for checking many values
+    pub fn compile(&self) -> Result<CompiledSchema, AiError> {
+        CompiledSchema::new(&self.schema)
+    }
+}
+
+/// A JSON schema compiled for repeated validation
+pub struct CompiledSchema {
+    validator: jsonschema::Validator,
+}
+
+impl CompiledSchema {
+    /// Fails with [`AiError::ConfigurationError`] if `schema` is not a
+    /// valid JSON schema
+    pub fn new(schema: &Value) -> Result<Self, AiError> {
+        let validator =
+            jsonschema::validator_for(schema).map_err(|e| AiError::ConfigurationError {
+                field: "schema".to_string(),
+                message: format!("Invalid JSON schema: {}", e),
+                suggestion: None,
+            })?;
+        Ok(Self { validator })
+    }
+
+    /// Every way `value` fails the schema, in document order; empty when it
+    /// is valid
+    pub fn violations(&self, value: &Value) -> Vec<SchemaViolation> {
+        self.validator
+            .iter_errors(value)
+            .map(|e| SchemaViolation {
+                path: e.instance_path.to_string(),
+                message: e.to_string(),
+            })
+            .collect()
+    }
+}
+
+impl fmt::Debug for CompiledSchema {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CompiledSchema").finish_non_exhaustive()
+    }
+}
+
+/// One failed JSON Schema check
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SchemaViolation {
+    /// JSON Pointer to the offending value, such as `/items/2/age`; empty
+    /// for the document itself
+    pub path: String,
+    pub message: String,
+}
+
+impl fmt::Display for SchemaViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.path.is_empty() {
+            write!(f, "{}", self.message)
+        } else {
+            write!(f, "{}: {}", self.path, self.message)
+        }
+    }
+}
+
+/// Validate `value` against `schema`, collecting every violation
+pub fn schema_violations(schema: &Value, value: &Value) -> Result<Vec<SchemaViolation>, AiError> {
+    Ok(CompiledSchema::new(schema)?.violations(value))
+}
+
+/// `path: message` pairs separated by `; `
+pub(crate) fn join_violations(violations: &[SchemaViolation]) -> String {
+    violations
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+/// Reference to a submitted batch job
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BatchHandle {
+    pub id: String,
+    pub provider: String,
+    /// Number of requests submitted, used to order the results
+    pub request_count: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BatchState {
+    Validating,
+    InProgress,
+    Finalizing,
+    Completed,
+    Failed,
+    Expired,
+    Cancelling,
+    Cancelled,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BatchStatus {
+    pub state: BatchState,
+    pub total: usize,
+    pub succeeded: usize,
+    pub failed: usize,
+}
+
+impl BatchStatus {
+    /// Whether the batch has stopped processing and its results can be fetched
+    pub fn is_finished(&self) -> bool {
+        matches!(
+            self.state,
+            BatchState::Completed
+                | BatchState::Failed
+                | BatchState::Expired
+                | BatchState::Cancelled
+        )
+    }
+}
+
+/// Pixel dimensions of a generated image
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ImageSize {
+    pub width: u32,
+    pub height: u32,
+}
+
+impl ImageSize {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self { width, height }
+    }
+
+    pub fn square(side: u32) -> Self {
+        Self::new(side, side)
+    }
+}
+
+impl Default for ImageSize {
+    fn default() -> Self {
+        Self::square(1024)
+    }
+}
+
+impl fmt::Display for ImageSize {
+    /// `WIDTHxHEIGHT`, as the OpenAI images API expects
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}x{}", self.width, self.height)
+    }
+}
+
+/// Where a generated image's bytes can be found
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ImageData {
+    /// Hosted by the provider; such links usually expire after an hour or so
+    Url(String),
+    Base64(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GeneratedImage {
+    pub url_or_b64: ImageData,
+    /// MIME type such as `image/png`
+    pub mime: String,
+}
+
+/// Text recognised in an audio clip
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Transcript {
+    pub text: String,
+    /// Detected or requested language, when reported
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+    /// Clip length in seconds, when reported
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration: Option<f32>,
+    /// Timestamped segments; empty if the model does not provide them
+    #[serde(default)]
+    pub segments: Vec<TranscriptSegment>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TranscriptSegment {
+    /// Start offset in seconds
+    pub start: f32,
+    /// End offset in seconds
+    pub end: f32,
+    pub text: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn response_with(message: Message) -> CompletionResponse {
+        CompletionResponse {
+            id: "resp-1".to_string(),
+            model: "test".to_string(),
+            choices: vec![Choice {
+                index: 0,
+                message,
+                finish_reason: None,
+                logprobs: None,
+            }],
+            usage: None,
+            metadata: None,
+        }
+    }
+
+    #[test]
+    fn test_completion_response_accessors() {
+        let response = response_with(Message::assistant("Hello"));
+        assert_eq!(response.text(), Some("Hello"));
+        assert!(response.tool_calls().is_none());
+
+        let mut message = Message::assistant("");
+        message.tool_calls = Some(vec![ToolCall {
+            id: "call_1".to_string(),
+            r#type: ToolType::Function,
+            function: FunctionCall {
+                name: "get_weather".to_string(),
+                arguments: "{}".to_string(),
+            },
+        }]);
+        let response = response_with(message);
+        let calls = response.tool_calls().unwrap();
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].function.name, "get_weather");
+
+        let empty = CompletionResponse {
+            choices: vec![],
+            ..response
+        };
+        assert!(empty.text().is_none());
+        assert!(empty.tool_calls().is_none());
+    }
+
+    fn weather_tool() -> Tool {
+        Tool {
+            r#type: ToolType::Function,
+            function: ToolFunction {
+                name: "get_weather".to_string(),
+                description: None,
+                parameters: json!({"type": "object"}),
+            },
+        }
+    }
+
+    #[test]
+    fn test_tool_choice_serializes_to_openai_json() {
+        assert_eq!(
+            serde_json::to_value(ToolChoice::auto()).unwrap(),
+            json!("auto")
+        );
+        assert_eq!(
+            serde_json::to_value(ToolChoice::none()).unwrap(),
+            json!("none")
+        );
+        assert_eq!(
+            serde_json::to_value(ToolChoice::required()).unwrap(),
+            json!("required")
+        );
+        assert_eq!(
+            serde_json::to_value(ToolChoice::function("get_weather")).unwrap(),
+            json!({"type": "function", "function": {"name": "get_weather"}})
+        );
+    }
+
+    #[test]
+    fn test_tool_choice_from_str() {
+        let choice: ToolChoice = "required".parse().unwrap();
+        assert_eq!(serde_json::to_value(choice).unwrap(), json!("required"));
+
+        let choice: ToolChoice = "get_weather".parse().unwrap();
+        assert_eq!(choice.function_name(), Some("get_weather"));
+
+        assert!("".parse::<ToolChoice>().is_err());
+        assert!("not a tool".parse::<ToolChoice>().is_err());
+    }
+
+    #[test]
+    fn test_role_round_trip() {
+        for role in [Role::System, Role::User, Role::Assistant, Role::Tool] {
+            let parsed: Role = role.to_string().parse().unwrap();
+            assert_eq!(parsed, role);
+        }
+        assert_eq!(" Assistant ".parse::<Role>().unwrap(), Role::Assistant);
+        assert!(matches!(
+            "narrator".parse::<Role>(),
+            Err(AiError::InvalidRequest { .. })
+        ));
+    }
+
+    #[test]
+    fn test_tool_choice_validate() {
+        let tools = vec![weather_tool()];
+
+        assert!(ToolChoice::auto().validate(None).is_ok());
+        assert!(ToolChoice::required().validate(None).is_err());
+        assert!(ToolChoice::required().validate(Some(&tools)).is_ok());
+        assert!(ToolChoice::function("get_weather")
+            .validate(Some(&tools))
+            .is_ok());
+        assert!(matches!(
+            ToolChoice::function("search").validate(Some(&tools)),
+            Err(AiError::ToolNotFound { .. })
+        ));
+    }
+
+    fn message(role: Role, text: &str) -> Message {
+        Message::new(role, MessageContent::text(text))
+    }
+
+    fn request(messages: Vec<Message>, system: Option<&str>) -> CompletionRequest {
+        CompletionRequest {
+            model: "test".to_string(),
+            messages,
+            system: system.map(String::from),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_message_constructors() {
+        let manual = |role: Role, text: &str, tool_call_id: Option<&str>| Message {
+            role,
+            content: MessageContent::Text(text.to_string()),
+            tool_calls: None,
+            tool_call_id: tool_call_id.map(str::to_string),
+            reasoning_content: None,
+            reasoning_signature: None,
+            name: None,
+            refusal: None,
+        };
+
+        assert_eq!(
+            Message::system("be brief"),
+            manual(Role::System, "be brief", None)
+        );
+        assert_eq!(Message::user("hi"), manual(Role::User, "hi", None));
+        assert_eq!(
+            Message::assistant("hello"),
+            manual(Role::Assistant, "hello", None)
+        );
+        assert_eq!(
+            Message::tool("call_1", "42"),
+            manual(Role::Tool, "42", Some("call_1"))
+        );
+
+        let result = Message::tool_json("call_1", json!({"answer": 42}));
+        assert_eq!(result.role, Role::Tool);
+        assert_eq!(result.tool_call_id.as_deref(), Some("call_1"));
+        assert_eq!(result.content.as_json(), Some(&json!({"answer": 42})));
+        assert_eq!(Message::tool("call_1", "42").content.as_json(), None);
+    }
+
+    #[test]
+    fn test_as_text_lossy_concatenates_text_parts() {
+        let content = MessageContent::Parts(vec![
+            ContentPart::Text {
+                text: "Hello, ".to_string(),
+            },
+            ContentPart::Image {
+                image_url: ImageUrl {
+                    url: "https://example.com/cat.png".to_string(),
+                    detail: None,
+                },
+            },
+            ContentPart::Text {
+                text: "world".to_string(),
+            },
+        ]);
+        assert_eq!(content.as_text(), None);
+        assert_eq!(content.as_text_lossy(), "Hello, world");
+        assert_eq!(MessageContent::text("plain").as_text_lossy(), "plain");
+    }
+
+    #[test]
+    fn test_message_content_size_estimates() {
+        let text = MessageContent::text("a".repeat(400));
+        assert_eq!(text.byte_len(), 400);
+        assert_eq!(text.approx_tokens(), 100);
+
+        let image = |detail: Option<&str>| ContentPart::Image {
+            image_url: ImageUrl {
+                url: "https://example.com/cat.png".to_string(),
+                detail: detail.map(str::to_string),
+            },
+        };
+        let content = MessageContent::Parts(vec![
+            ContentPart::Text {
+                text: "b".repeat(40),
+            },
+            image(None),
+        ]);
+        assert_eq!(content.byte_len(), 40 + 27);
+        assert_eq!(content.approx_tokens(), 10 + 765);
+
+        let low = MessageContent::Parts(vec![image(Some("low"))]);
+        assert_eq!(low.approx_tokens(), 85);
+
+        // A large base64 PDF is sent in full but not counted as tokens
+        let pdf = MessageContent::Parts(vec![ContentPart::Document {
+            data: "J".repeat(4_000_000),
+            mime_type: "application/pdf".to_string(),
+            name: None,
+        }]);
+        assert_eq!(pdf.byte_len(), 4_000_000);
+        assert_eq!(pdf.approx_tokens(), 0);
+    }
+
+    #[test]
+    fn test_normalize_system_prepends_and_merges() {
+        let mut req = request(vec![message(Role::User, "Hi")], Some("Be brief."));
+        req.normalize_system();
+        assert!(req.system.is_none());
+        assert_eq!(req.messages.len(), 2);
+        assert_eq!(req.messages[0].role, Role::System);
+        assert_eq!(req.messages[0].content.as_text(), Some("Be brief."));
+
+        let mut req = request(
+            vec![
+                message(Role::System, "Answer in French."),
+                message(Role::User, "Hi"),
+            ],
+            Some("Be brief."),
+        );
+        req.normalize_system();
+        assert_eq!(req.messages.len(), 2);
+        assert_eq!(
+            req.messages[0].content.as_text(),
+            Some("Be brief.\n\nAnswer in French.")
+        );
+
+        // A system message with parts gets the text merged in, not a second
+        // system message
+        let mut system = message(Role::System, "");
+        system.content = MessageContent::Parts(vec![ContentPart::Text {
+            text: "Answer in French.".to_string(),
+        }]);
+        let mut req = request(vec![system, message(Role::User, "Hi")], Some("Be brief."));
+        req.normalize_system();
+        assert_eq!(req.messages.len(), 2);
+        assert!(matches!(
+            &req.messages[0].content,
+            MessageContent::Parts(parts)
+                if matches!(&parts[..], [ContentPart::Text { text }] if text == "Be brief.\n\nAnswer in French.")
+        ));
+
+        // Without a system field the messages are untouched
+        let mut req = request(vec![message(Role::User, "Hi")], None);
+        req.normalize_system();
+        assert_eq!(req.messages.len(), 1);
+    }
+
+    #[test]
+    fn test_merge_consecutive_roles_keeps_names_apart() {
+        let named = |name: &str, text: &str| Message {
+            name: Some(name.to_string()),
+            ..message(Role::User, text)
+        };
+        let mut req = request(
+            vec![
+                named("alice", "Hi"),
+                named("alice", "Anyone there?"),
+                named("bob", "Hello"),
+            ],
+            None,
+        );
+        req.merge_consecutive_roles();
+        assert_eq!(req.messages.len(), 2);
+        assert_eq!(
+            req.messages[0].content.as_text(),
+            Some("Hi\n\nAnyone there?")
+        );
+        assert_eq!(req.messages[1].name.as_deref(), Some("bob"));
+    }
+
+    #[test]
+    fn test_request_validate() {
+        let field = |req: &CompletionRequest| match req.validate() {
+            Err(AiError::InvalidRequest { field, .. }) => field,
+            other => panic!("expected InvalidRequest, got {:?}", other),
+        };
+
+        let mut req = request(vec![message(Role::User, "Hi")], None);
+        assert!(req.validate().is_ok());
+
+        req.temperature = Some(2.5);
+        assert_eq!(field(&req).as_deref(), Some("temperature"));
+
+        // A high temperature with a narrow top_p is contradictory
+        req.temperature = Some(1.8);
+        req.top_p = Some(0.1);
+        assert_eq!(field(&req).as_deref(), Some("top_p"));
+
+        req.top_p = Some(1.5);
+        assert_eq!(field(&req).as_deref(), Some("top_p"));
+        req.top_p = None;
+
+        req.temperature = Some(0.7);
+        req.max_tokens = Some(0);
+        assert_eq!(field(&req).as_deref(), Some("max_tokens"));
+
+        let empty = request(vec![], None);
+        assert_eq!(field(&empty).as_deref(), Some("messages"));
+    }
+
+    #[test]
+    fn test_finish_reason_normalization() {
+        let cases = [
+            // OpenAI and compatible providers
+            ("stop", FinishReason::Stop),
+            ("length", FinishReason::Length),
+            ("tool_calls", FinishReason::ToolCalls),
+            ("content_filter", FinishReason::ContentFilter),
+            // Anthropic
+            ("end_turn", FinishReason::Stop),
+            ("stop_sequence", FinishReason::Stop),
+            ("max_tokens", FinishReason::Length),
+            ("tool_use", FinishReason::ToolCalls),
+            ("refusal", FinishReason::ContentFilter),
+            // Gemini
+            ("STOP", FinishReason::Stop),
+            ("MAX_TOKENS", FinishReason::Length),
+            ("SAFETY", FinishReason::ContentFilter),
+            // Cohere
+            ("COMPLETE", FinishReason::Stop),
+            ("TOOL_CALL", FinishReason::ToolCalls),
+            ("ERROR_TOXIC", FinishReason::ContentFilter),
+        ];
+        for (raw, expected) in cases {
+            assert_eq!(FinishReason::from_raw(raw), expected, "{}", raw);
+        }
+        assert_eq!(
+            FinishReason::from_raw("pause_turn"),
+            FinishReason::Other("pause_turn".to_string())
+        );
+
+        let choice = Choice {
+            index: 0,
+            message: Message::assistant("truncated"),
+            finish_reason: Some("MAX_TOKENS".to_string()),
+            logprobs: None,
+        };
+        assert_eq!(choice.stop_reason(), Some(FinishReason::Length));
+        assert_eq!(choice.finish_reason.as_deref(), Some("MAX_TOKENS"));
+    }
+
+    fn tool_delta(
+        index: Option<u32>,
+        id: Option<&str>,
+        name: Option<&str>,
+        arguments: &str,
+    ) -> ToolCallDelta {
+        ToolCallDelta {
+            index,
+            id: id.map(str::to_string),
+            r#type: id.map(|_| ToolType::Function),
+            function: Some(FunctionCallDelta {
+                name: name.map(str::to_string),
+                arguments: Some(arguments.to_string()),
+            }),
+        }
+    }
+
+    #[test]
+    fn test_tool_call_accumulator_fragmented_deltas() {
+        let mut accumulator = ToolCallAccumulator::new();
+        let deltas = [
+            tool_delta(Some(0), Some("call_a"), Some("get_weather"), ""),
+            tool_delta(Some(0), None, None, r#"{"loca"#),
+            tool_delta(Some(1), Some("call_b"), Some("get_time"), r#"{"tz":"#),
+            tool_delta(Some(0), None, None, r#"tion":"Paris"}"#),
+            tool_delta(Some(1), None, None, r#""UTC"}"#),
+        ];
+
+        let completed: Vec<Option<ToolCall>> = deltas.iter().map(|d| accumulator.push(d)).collect();
+        assert!(completed[..3].iter().all(Option::is_none));
+        let first = completed[3].as_ref().unwrap();
+        assert_eq!(first.id, "call_a");
+        assert_eq!(first.function.arguments, r#"{"location":"Paris"}"#);
+        assert_eq!(completed[4].as_ref().unwrap().function.name, "get_time");
+
+        let calls = accumulator.finish();
+        assert_eq!(calls.len(), 2);
+        assert_eq!(calls[1].function.arguments, r#"{"tz":"UTC"}"#);
+    }
+
+    #[test]
+    fn test_tool_call_accumulator_whole_call_deltas() {
+        let mut accumulator = ToolCallAccumulator::new();
+        let first = accumulator
+            .push(&tool_delta(
+                Some(0),
+                Some("a"),
+                Some("get_weather"),
+                r#"{"location":"Oslo"}"#,
+            ))
+            .unwrap();
+        assert_eq!(first.function.name, "get_weather");
+
+        // Without indexes, ids tell calls apart and fragments continue the latest
+        assert!(accumulator
+            .push(&tool_delta(None, Some("b"), Some("get_time"), "{"))
+            .is_none());
+        let second = accumulator
+            .push(&tool_delta(None, None, None, "}"))
+            .unwrap();
+        assert_eq!(second.id, "b");
+        assert_eq!(second.function.arguments, "{}");
+
+        let calls = accumulator.finish();
+        assert_eq!(
+            calls.iter().map(|c| c.id.as_str()).collect::<Vec<_>>(),
+            ["a", "b"]
+        );
+    }
+}
@@ -0,0 +1,141 @@
+//! Moderation checks that can screen agent input and output, backed by the
+//! OpenAI Moderations API or a custom classifier.
+
+use async_trait::async_trait;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::providers::{auth::SendAuthenticated, ApiKeyAuth, Authenticator};
+use crate::{AiError, Result};
+
+/// Verdict for one piece of text
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ModerationResult {
+    pub flagged: bool,
+    /// Categories the text was flagged for, e.g. `harassment`
+    pub categories: Vec<String>,
+}
+
+impl ModerationResult {
+    pub fn allowed() -> Self {
+        Self::default()
+    }
+
+    pub fn flagged(categories: Vec<String>) -> Self {
+        Self {
+            flagged: true,
+            categories,
+        }
+    }
+
+    /// `AiError::ContentFiltered` if the text was flagged
+    pub fn check(self, what: &str) -> Result<()> {
+        if !self.flagged {
+            return Ok(());
+        }
+        let category = (!self.categories.is_empty()).then(|| self.categories.join(", "));
+        Err(AiError::ContentFiltered {
+            reason: format!("{} was flagged by moderation", what),
+            category,
+        })
+    }
+}
+
+/// Classifies text as allowed or flagged
+#[async_trait]
+pub trait Moderator: Send + Sync {
+    async fn moderate(&self, text: &str) -> Result<ModerationResult>;
+}
+
+/// Moderator backed by the OpenAI Moderations API
+pub struct OpenAIModerator {
+    client: Client,
+    auth: Arc<dyn Authenticator>,
+    base_url: String,
+    model: String,
+}
+
+impl OpenAIModerator {
+    pub fn new(api_key: String) -> Self {
+        Self::with_base_url(api_key, "https://api.openai.com/v1".to_string())
+    }
+
+    pub fn with_base_url(api_key: String, base_url: String) -> Self {
+        Self {
+            client: crate::providers::config::default_client(),
+            auth: Arc::new(ApiKeyAuth::bearer(api_key)),
+            base_url,
+            model: "omni-moderation-latest".to_string(),
+        }
+    }
+
+    /// Moderation model to use (default `omni-moderation-latest`)
+    pub fn with_model(mut self, model: impl Into<String>) -> Self {
+        self.model = model.into();
+        self
+    }
+
+    /// Authenticate requests with `auth` instead of the API key, e.g. for a
+    /// proxy or gateway with its own credentials
+    pub fn with_authenticator(mut self, auth: impl Authenticator + 'static) -> Self {
+        self.auth = Arc::new(auth);
+        self
+    }
+}
+
+#[derive(Serialize)]
+struct OpenAIModerationRequest<'a> {
+    model: &'a str,
+    input: &'a str,
+}
+
+#[derive(Deserialize)]
+struct OpenAIModerationResponse {
+    results: Vec<OpenAIModerationVerdict>,
+}
+
+#[derive(Deserialize)]
+struct OpenAIModerationVerdict {
+    flagged: bool,
+    #[serde(default)]
+    categories: HashMap<String, bool>,
+}
+
+#[async_trait]
+impl Moderator for OpenAIModerator {
+    async fn moderate(&self, text: &str) -> Result<ModerationResult> {
+        let response = self
+            .client
+            .post(format!("{}/moderations", self.base_url))
+            .json(&OpenAIModerationRequest {
+                model: &self.model,
+                input: text,
+            })
+            .send_authenticated(self.auth.as_ref())
+            .await?;
+
+        let response = crate::providers::check_response(response, |error| {
+            error.into_provider_error("openai", "OpenAI moderation error")
+        })
+        .await?;
+
+        let response: OpenAIModerationResponse = response.json().await?;
+        let flagged = response.results.iter().any(|r| r.flagged);
+        let mut categories: Vec<String> = response
+            .results
+            .into_iter()
+            .flat_map(|r| r.categories)
+            .filter(|(_, hit)| *hit)
+            .map(|(category, _)| category)
+            .collect();
+        categories.sort();
+        categories.dedup();
+
+        Ok(ModerationResult {
+            flagged,
+            categories,
+        })
+    }
+}
@@ -1,6 +1,8 @@
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+
+use crate::embeddings::EmbeddingUsage;
 
 /// Cost tracking for different AI providers
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -9,6 +11,38 @@ pub struct CostTracker {
     pub total_cost: f64,
     pub start_time: DateTime<Utc>,
     pub last_updated: DateTime<Utc>,
+    /// Recent usage events, in recording order. Holds at most
+    /// `usage_log_capacity` events; older ones are dropped first.
+    #[serde(default)]
+    pub usage_log: VecDeque<UsageEvent>,
+    #[serde(default = "default_usage_log_capacity")]
+    pub usage_log_capacity: usize,
+    /// Running costs per tag key, then per tag value
+    #[serde(default)]
+    pub tag_costs: HashMap<String, HashMap<String, TagReportEntry>>,
+}
+
+/// Events kept for hourly and daily reports unless configured otherwise
+pub const DEFAULT_USAGE_LOG_CAPACITY: usize = 10_000;
+
+fn default_usage_log_capacity() -> usize {
+    DEFAULT_USAGE_LOG_CAPACITY
+}
+
+/// A single recorded request with its cost, used for time-bucketed reports
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UsageEvent {
+    pub timestamp: DateTime<Utc>,
+    pub provider: String,
+    pub model: String,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub cache_read_tokens: u64,
+    pub cache_write_tokens: u64,
+    pub cost: f64,
+    /// Tags from the request's metadata, e.g. `feature=search`
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub tags: HashMap<String, String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -73,9 +107,22 @@ impl CostTracker {
             total_cost: 0.0,
             start_time: Utc::now(),
             last_updated: Utc::now(),
+            usage_log: VecDeque::new(),
+            usage_log_capacity: DEFAULT_USAGE_LOG_CAPACITY,
+            tag_costs: HashMap::new(),
         }
     }
 
+    /// Keep at most `capacity` events for the hourly and daily reports,
+    /// dropping the oldest first. `0` turns the log off; totals and
+    /// per-model costs are kept either way.
+    pub fn with_usage_log_capacity(mut self, capacity: usize) -> Self {
+        self.usage_log_capacity = capacity;
+        let excess = self.usage_log.len().saturating_sub(capacity);
+        self.usage_log.drain(..excess);
+        self
+    }
+
     #[allow(clippy::too_many_arguments)]
     pub fn record_usage(
         &mut self,
@@ -86,6 +133,33 @@ impl CostTracker {
         cache_read_tokens: u64,
         cache_write_tokens: u64,
         pricing: &PricingInfo,
+    ) {
+        self.record_tagged_usage(
+            provider,
+            model,
+            input_tokens,
+            output_tokens,
+            cache_read_tokens,
+            cache_write_tokens,
+            pricing,
+            &HashMap::new(),
+        );
+    }
+
+    /// Like [`record_usage`](Self::record_usage), keeping the request's
+    /// tags so [`generate_report_by_tag`](Self::generate_report_by_tag) can
+    /// attribute the cost
+    #[allow(clippy::too_many_arguments)]
+    pub fn record_tagged_usage(
+        &mut self,
+        provider: &str,
+        model: &str,
+        input_tokens: u64,
+        output_tokens: u64,
+        cache_read_tokens: u64,
+        cache_write_tokens: u64,
+        pricing: &PricingInfo,
+        tags: &HashMap<String, String>,
     ) {
         let input_cost = (input_tokens as f64 / 1000.0) * pricing.input_price_per_1k_tokens;
         let output_cost = (output_tokens as f64 / 1000.0) * pricing.output_price_per_1k_tokens;
@@ -139,9 +213,71 @@ impl CostTracker {
         model_costs.cache_write_tokens += cache_write_tokens;
         model_costs.requests += 1;
 
+        // Update tag costs
+        for (key, value) in tags {
+            let entry = self
+                .tag_costs
+                .entry(key.clone())
+                .or_default()
+                .entry(value.clone())
+                .or_insert_with(|| TagReportEntry {
+                    value: value.clone(),
+                    total_cost: 0.0,
+                    requests: 0,
+                    input_tokens: 0,
+                    output_tokens: 0,
+                    cache_read_tokens: 0,
+                    cache_write_tokens: 0,
+                });
+
+            entry.total_cost += total_request_cost;
+            entry.requests += 1;
+            entry.input_tokens += input_tokens;
+            entry.output_tokens += output_tokens;
+            entry.cache_read_tokens += cache_read_tokens;
+            entry.cache_write_tokens += cache_write_tokens;
+        }
+
         // Update total cost
         self.total_cost += total_request_cost;
         self.last_updated = Utc::now();
+
+        if self.usage_log_capacity == 0 {
+            return;
+        }
+        if self.usage_log.len() >= self.usage_log_capacity {
+            self.usage_log.pop_front();
+        }
+        self.usage_log.push_back(UsageEvent {
+            timestamp: self.last_updated,
+            provider: provider.to_string(),
+            model: model.to_string(),
+            input_tokens,
+            output_tokens,
+            cache_read_tokens,
+            cache_write_tokens,
+            cost: total_request_cost,
+            tags: tags.clone(),
+        });
+    }
+
+    /// Record an embeddings call; embeddings only bill input tokens
+    pub fn record_embedding_usage(
+        &mut self,
+        provider: &str,
+        model: &str,
+        usage: &EmbeddingUsage,
+        pricing: &PricingInfo,
+    ) {
+        self.record_usage(
+            provider,
+            model,
+            usage.prompt_tokens as u64,
+            0,
+            0,
+            0,
+            pricing,
+        );
     }
 
     pub fn get_cost_by_provider(&self, provider: &str) -> Option<&ProviderCosts> {
@@ -206,7 +342,7 @@ impl CostTracker {
         }
 
         // Sort by cost descending
-        provider_breakdown.sort_by(|a, b| b.total_cost.partial_cmp(&a.total_cost).unwrap());
+        provider_breakdown.sort_by(|a, b| b.total_cost.total_cmp(&a.total_cost));
 
         CostReport {
             total_cost: self.total_cost,
@@ -222,8 +358,64 @@ impl CostTracker {
         }
     }
 
+    /// Costs grouped into one-hour buckets, oldest first. Covers the events
+    /// still in the usage log.
+    pub fn generate_report_by_hour(&self) -> Vec<CostBucket> {
+        self.generate_report_by_bucket(3600)
+    }
+
+    /// Costs grouped into UTC-day buckets, oldest first
+    pub fn generate_report_by_day(&self) -> Vec<CostBucket> {
+        self.generate_report_by_bucket(86_400)
+    }
+
+    fn generate_report_by_bucket(&self, bucket_secs: i64) -> Vec<CostBucket> {
+        let mut buckets: BTreeMap<i64, CostBucket> = BTreeMap::new();
+
+        for event in &self.usage_log {
+            let secs = event.timestamp.timestamp();
+            let bucket_start = secs - secs.rem_euclid(bucket_secs);
+            let bucket = buckets.entry(bucket_start).or_insert_with(|| {
+                let start = DateTime::from_timestamp(bucket_start, 0).unwrap_or_default();
+                CostBucket {
+                    start,
+                    end: start + chrono::Duration::seconds(bucket_secs),
+                    total_cost: 0.0,
+                    requests: 0,
+                    input_tokens: 0,
+                    output_tokens: 0,
+                    cache_read_tokens: 0,
+                    cache_write_tokens: 0,
+                }
+            });
+
+            bucket.total_cost += event.cost;
+            bucket.requests += 1;
+            bucket.input_tokens += event.input_tokens;
+            bucket.output_tokens += event.output_tokens;
+            bucket.cache_read_tokens += event.cache_read_tokens;
+            bucket.cache_write_tokens += event.cache_write_tokens;
+        }
+
+        buckets.into_values().collect()
+    }
+
+    /// Costs grouped by the value of tag `key`, most expensive first.
+    /// Requests without the tag are left out.
+    pub fn generate_report_by_tag(&self, key: &str) -> Vec<TagReportEntry> {
+        let mut entries: Vec<TagReportEntry> = self
+            .tag_costs
+            .get(key)
+            .map(|values| values.values().cloned().collect())
+            .unwrap_or_default();
+        entries.sort_by(|a, b| b.total_cost.total_cmp(&a.total_cost));
+        entries
+    }
+
     pub fn reset(&mut self) {
         self.provider_costs.clear();
+        self.usage_log.clear();
+        self.tag_costs.clear();
         self.total_cost = 0.0;
         self.start_time = Utc::now();
         self.last_updated = Utc::now();
@@ -240,6 +432,70 @@ pub struct CostReport {
     pub generated_at: DateTime<Utc>,
 }
 
+impl CostReport {
+    /// One row per provider and model, with a header row
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from(
+            "provider,model,requests,input_tokens,output_tokens,cache_read_tokens,\
+             cache_write_tokens,total_cost,cost_per_request,cost_per_token\n",
+        );
+
+        for provider in &self.providers {
+            for model in &provider.models {
+                csv.push_str(&format!(
+                    "{},{},{},{},{},{},{},{:.6},{:.6},{:.8}\n",
+                    csv_field(&provider.provider_name),
+                    csv_field(&model.model_name),
+                    model.requests,
+                    model.input_tokens,
+                    model.output_tokens,
+                    model.cache_read_tokens,
+                    model.cache_write_tokens,
+                    model.total_cost,
+                    model.cost_per_request,
+                    model.cost_per_token,
+                ));
+            }
+        }
+
+        csv
+    }
+}
+
+/// Quote a CSV field if it contains a delimiter, quote or newline
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Aggregated cost for one time bucket
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CostBucket {
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    pub total_cost: f64,
+    pub requests: u64,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub cache_read_tokens: u64,
+    pub cache_write_tokens: u64,
+}
+
+/// Aggregated cost for one value of a tag
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TagReportEntry {
+    pub value: String,
+    pub total_cost: f64,
+    pub requests: u64,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub cache_read_tokens: u64,
+    pub cache_write_tokens: u64,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ProviderReportEntry {
     pub provider_name: String,
@@ -326,6 +582,27 @@ pub fn get_default_pricing() -> HashMap<String, PricingInfo> {
         },
     );
 
+    // OpenAI embedding models
+    for (model, input_price) in [
+        ("text-embedding-3-small", 0.00002),
+        ("text-embedding-3-large", 0.00013),
+        ("text-embedding-ada-002", 0.0001),
+    ] {
+        pricing.insert(
+            format!("openai:{}", model),
+            PricingInfo {
+                provider: "openai".to_string(),
+                model: model.to_string(),
+                input_price_per_1k_tokens: input_price,
+                output_price_per_1k_tokens: 0.0,
+                cache_read_price_per_1k_tokens: None,
+                cache_write_price_per_1k_tokens: None,
+                currency: "USD".to_string(),
+                last_updated: Utc::now(),
+            },
+        );
+    }
+
     // Google Gemini 1.5 Pro
     pricing.insert(
         "google:gemini-1.5-pro".to_string(),
@@ -413,4 +690,126 @@ mod tests {
         let total_percentage: f64 = report.providers.iter().map(|p| p.cost_percentage).sum();
         assert!((total_percentage - 100.0).abs() < 0.01);
     }
+
+    #[test]
+    fn test_report_to_csv() {
+        let mut tracker = CostTracker::new();
+        let pricing = get_default_pricing();
+        tracker.record_usage(
+            "openai",
+            "gpt-4o",
+            1000,
+            500,
+            0,
+            0,
+            pricing.get("openai:gpt-4o").unwrap(),
+        );
+
+        let csv = tracker.generate_report().to_csv();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 2);
+
+        let header: Vec<&str> = lines[0].split(',').collect();
+        assert_eq!(header.len(), 10);
+        assert_eq!(header[0], "provider");
+        assert_eq!(header[7], "total_cost");
+
+        let row: Vec<&str> = lines[1].split(',').collect();
+        assert_eq!(row[0], "openai");
+        assert_eq!(row[1], "gpt-4o");
+        assert_eq!(row[2], "1");
+        assert_eq!(row[3], "1000");
+        assert_eq!(row[7], "0.007500");
+
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+    }
+
+    #[test]
+    fn test_hourly_and_daily_buckets() {
+        let mut tracker = CostTracker::new();
+        let pricing = get_default_pricing();
+        let gpt4o = pricing.get("openai:gpt-4o").unwrap();
+        tracker.record_usage("openai", "gpt-4o", 1000, 0, 0, 0, gpt4o);
+        tracker.record_usage("openai", "gpt-4o", 1000, 0, 0, 0, gpt4o);
+        tracker.record_usage("openai", "gpt-4o", 2000, 0, 0, 0, gpt4o);
+
+        // Place the first two events in one hour and the third in the next
+        let base = DateTime::parse_from_rfc3339("2025-01-15T10:05:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        tracker.usage_log[0].timestamp = base;
+        tracker.usage_log[1].timestamp = base + chrono::Duration::minutes(30);
+        tracker.usage_log[2].timestamp = base + chrono::Duration::minutes(70);
+
+        let hourly = tracker.generate_report_by_hour();
+        assert_eq!(hourly.len(), 2);
+        assert_eq!(hourly[0].start.to_rfc3339(), "2025-01-15T10:00:00+00:00");
+        assert_eq!(hourly[0].requests, 2);
+        assert_eq!(hourly[0].input_tokens, 2000);
+        assert_eq!(hourly[1].start.to_rfc3339(), "2025-01-15T11:00:00+00:00");
+        assert_eq!(hourly[1].requests, 1);
+        assert!((hourly[0].total_cost - hourly[1].total_cost).abs() < 1e-9);
+
+        let daily = tracker.generate_report_by_day();
+        assert_eq!(daily.len(), 1);
+        assert_eq!(daily[0].requests, 3);
+        assert!((daily[0].total_cost - tracker.total_cost).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_usage_log_is_capped() {
+        let mut tracker = CostTracker::new().with_usage_log_capacity(2);
+        let pricing = get_default_pricing();
+        let gpt4o = pricing.get("openai:gpt-4o").unwrap();
+        tracker.record_usage("openai", "gpt-4o", 1000, 0, 0, 0, gpt4o);
+        tracker.record_usage("openai", "gpt-4o", 2000, 0, 0, 0, gpt4o);
+        tracker.record_usage("openai", "gpt-4o", 3000, 0, 0, 0, gpt4o);
+
+        assert_eq!(tracker.usage_log.len(), 2);
+        assert_eq!(tracker.usage_log[0].input_tokens, 2000);
+        // Totals still cover every request
+        let model_costs = tracker.get_cost_by_model("openai", "gpt-4o").unwrap();
+        assert_eq!(model_costs.requests, 3);
+
+        // Lowering the capacity of a filled log keeps the newest events
+        let mut tracker = CostTracker::new();
+        for input_tokens in [1000, 2000, 3000] {
+            tracker.record_usage("openai", "gpt-4o", input_tokens, 0, 0, 0, gpt4o);
+        }
+        let tracker = tracker.with_usage_log_capacity(2);
+        assert_eq!(tracker.usage_log.len(), 2);
+        assert_eq!(tracker.usage_log[0].input_tokens, 2000);
+        assert_eq!(tracker.usage_log[1].input_tokens, 3000);
+
+        let mut tracker = CostTracker::new().with_usage_log_capacity(0);
+        tracker.record_usage("openai", "gpt-4o", 1000, 0, 0, 0, gpt4o);
+        assert!(tracker.usage_log.is_empty());
+        assert!(tracker.generate_report_by_hour().is_empty());
+    }
+
+    #[test]
+    fn test_tag_report_does_not_need_usage_log() {
+        let mut tracker = CostTracker::new().with_usage_log_capacity(0);
+        let pricing = get_default_pricing();
+        let gpt4o = pricing.get("openai:gpt-4o").unwrap();
+        let tags = HashMap::from([("feature".to_string(), "search".to_string())]);
+        tracker.record_tagged_usage("openai", "gpt-4o", 1000, 0, 0, 0, gpt4o, &tags);
+        tracker.record_tagged_usage("openai", "gpt-4o", 1000, 0, 0, 0, gpt4o, &tags);
+
+        let by_feature = tracker.generate_report_by_tag("feature");
+        assert_eq!(by_feature.len(), 1);
+        assert_eq!(by_feature[0].value, "search");
+        assert_eq!(by_feature[0].requests, 2);
+        assert_eq!(by_feature[0].input_tokens, 2000);
+
+        // A NaN cost from bad pricing must not break the sort
+        let broken = PricingInfo {
+            input_price_per_1k_tokens: f64::NAN,
+            ..gpt4o.clone()
+        };
+        let tags = HashMap::from([("feature".to_string(), "broken".to_string())]);
+        tracker.record_tagged_usage("openai", "gpt-4o", 1000, 0, 0, 0, &broken, &tags);
+        assert_eq!(tracker.generate_report_by_tag("feature").len(), 2);
+        assert_eq!(tracker.generate_report().providers.len(), 1);
+    }
 }
@@ -1,6 +1,8 @@
 use chrono::{DateTime, Utc};
+use lru::LruCache;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::num::NonZeroUsize;
 use std::sync::{Arc, RwLock};
 use std::time::Duration;
 
@@ -26,6 +28,13 @@ pub struct TokenUsage {
     pub output_tokens: u64,
     pub cache_read_tokens: u64,
     pub cache_write_tokens: u64,
+    /// Reasoning tokens, already included in `output_tokens`
+    #[serde(default)]
+    pub reasoning_tokens: u64,
+    /// Tokens estimated from text length because the provider reported no
+    /// usage, already included in `input_tokens` and `output_tokens`
+    #[serde(default)]
+    pub estimated_tokens: u64,
 }
 
 impl Default for TokenUsage {
@@ -41,6 +50,8 @@ impl TokenUsage {
             output_tokens: 0,
             cache_read_tokens: 0,
             cache_write_tokens: 0,
+            reasoning_tokens: 0,
+            estimated_tokens: 0,
         }
     }
 
@@ -53,6 +64,8 @@ impl TokenUsage {
         self.output_tokens += other.output_tokens;
         self.cache_read_tokens += other.cache_read_tokens;
         self.cache_write_tokens += other.cache_write_tokens;
+        self.reasoning_tokens += other.reasoning_tokens;
+        self.estimated_tokens += other.estimated_tokens;
     }
 }
 
@@ -80,11 +93,30 @@ pub struct ToolMetrics {
     pub total_duration: Duration,
     pub average_duration: Duration,
     pub error_types: HashMap<String, u64>,
+    /// Attempts made after a retryable failure, under the agent's
+    /// `tool_retry` policy
+    #[serde(default)]
+    pub retries: u64,
+}
+
+/// Retry and circuit breaker activity of one provider behind a
+/// `ResilientProvider`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ResilienceMetrics {
+    pub attempts: u64,
+    pub failed_attempts: u64,
+    pub retries: u64,
+    pub total_retry_delay: Duration,
+    /// Circuit state changes by `"from->to"`, e.g. `"closed->open"`
+    pub circuit_transitions: HashMap<String, u64>,
 }
 
 /// Thread-safe metrics collector
 pub struct MetricsCollector {
-    metrics: Arc<RwLock<HashMap<String, AgentMetrics>>>,
+    /// Per-agent metrics, ordered by last update; when bounded, the least
+    /// recently updated agent is evicted
+    metrics: Arc<RwLock<LruCache<String, AgentMetrics>>>,
+    resilience: Arc<RwLock<HashMap<String, ResilienceMetrics>>>,
     global_metrics: Arc<RwLock<GlobalMetrics>>,
 }
 
@@ -101,7 +133,8 @@ pub struct GlobalMetrics {
 impl MetricsCollector {
     pub fn new() -> Self {
         Self {
-            metrics: Arc::new(RwLock::new(HashMap::new())),
+            metrics: Arc::new(RwLock::new(LruCache::unbounded())),
+            resilience: Arc::new(RwLock::new(HashMap::new())),
             global_metrics: Arc::new(RwLock::new(GlobalMetrics {
                 total_agents: 0,
                 total_requests: 0,
@@ -113,11 +146,26 @@ impl MetricsCollector {
         }
     }
 
+    /// Create a collector that tracks at most `max_agents` agents, evicting
+    /// the least recently updated one when a new agent is registered
+    pub fn with_max_agents(max_agents: usize) -> Self {
+        let max_agents = NonZeroUsize::new(max_agents).unwrap_or(NonZeroUsize::MIN);
+        Self {
+            metrics: Arc::new(RwLock::new(LruCache::new(max_agents))),
+            ..Self::new()
+        }
+    }
+
+    /// Stop tracking an agent, returning its final metrics
+    pub fn remove_agent_metrics(&self, agent_id: &str) -> Option<AgentMetrics> {
+        self.metrics.write().unwrap().pop(agent_id)
+    }
+
     pub fn create_agent_metrics(&self, agent_id: String) {
         let mut metrics = self.metrics.write().unwrap();
-        if let std::collections::hash_map::Entry::Vacant(e) = metrics.entry(agent_id) {
+        if !metrics.contains(&agent_id) {
             let agent_metrics = AgentMetrics {
-                agent_id: e.key().clone(),
+                agent_id: agent_id.clone(),
                 total_requests: 0,
                 successful_requests: 0,
                 failed_requests: 0,
@@ -129,7 +177,8 @@ impl MetricsCollector {
                 created_at: Utc::now(),
                 last_updated: Utc::now(),
             };
-            e.insert(agent_metrics);
+            // Evicts the least recently updated agent when full
+            metrics.put(agent_id, agent_metrics);
 
             // Update global metrics
             let mut global = self.global_metrics.write().unwrap();
@@ -234,6 +283,7 @@ impl MetricsCollector {
                     total_duration: Duration::new(0, 0),
                     average_duration: Duration::new(0, 0),
                     error_types: HashMap::new(),
+                    retries: 0,
                 });
 
             tool_metrics.executions += 1;
@@ -254,9 +304,21 @@ impl MetricsCollector {
         }
     }
 
+    /// Record retries made by one tool execution; call after
+    /// `record_tool_execution` for the same call
+    pub fn record_tool_retries(&self, agent_id: &str, tool_name: &str, retries: u32) {
+        let mut metrics = self.metrics.write().unwrap();
+        if let Some(tool_metrics) = metrics
+            .peek_mut(agent_id)
+            .and_then(|agent_metrics| agent_metrics.tool_usage.get_mut(tool_name))
+        {
+            tool_metrics.retries += u64::from(retries);
+        }
+    }
+
     pub fn record_rate_limit(&self, agent_id: &str, provider: &str, model: &str) {
         let mut metrics = self.metrics.write().unwrap();
-        if let Some(agent_metrics) = metrics.get_mut(agent_id) {
+        if let Some(agent_metrics) = metrics.peek_mut(agent_id) {
             let provider_key = format!("{}:{}", provider, model);
             if let Some(provider_metrics) = agent_metrics.provider_metrics.get_mut(&provider_key) {
                 provider_metrics.rate_limit_hits += 1;
@@ -264,12 +326,50 @@ impl MetricsCollector {
         }
     }
 
+    /// Record one attempt made by a resilient provider
+    pub fn record_attempt(&self, provider: &str, success: bool) {
+        let mut resilience = self.resilience.write().unwrap();
+        let entry = resilience.entry(provider.to_string()).or_default();
+        entry.attempts += 1;
+        if !success {
+            entry.failed_attempts += 1;
+        }
+    }
+
+    /// Record a retry scheduled after `delay`
+    pub fn record_retry(&self, provider: &str, delay: Duration) {
+        let mut resilience = self.resilience.write().unwrap();
+        let entry = resilience.entry(provider.to_string()).or_default();
+        entry.retries += 1;
+        entry.total_retry_delay += delay;
+    }
+
+    /// Record a circuit breaker state change, e.g. `closed` to `open`
+    pub fn record_circuit_transition(&self, provider: &str, from: &str, to: &str) {
+        let mut resilience = self.resilience.write().unwrap();
+        *resilience
+            .entry(provider.to_string())
+            .or_default()
+            .circuit_transitions
+            .entry(format!("{}->{}", from, to))
+            .or_insert(0) += 1;
+    }
+
+    pub fn get_resilience_metrics(&self, provider: &str) -> Option<ResilienceMetrics> {
+        self.resilience.read().unwrap().get(provider).cloned()
+    }
+
     pub fn get_agent_metrics(&self, agent_id: &str) -> Option<AgentMetrics> {
-        self.metrics.read().unwrap().get(agent_id).cloned()
+        self.metrics.read().unwrap().peek(agent_id).cloned()
     }
 
     pub fn get_all_agent_metrics(&self) -> HashMap<String, AgentMetrics> {
-        self.metrics.read().unwrap().clone()
+        self.metrics
+            .read()
+            .unwrap()
+            .iter()
+            .map(|(id, metrics)| (id.clone(), metrics.clone()))
+            .collect()
     }
 
     pub fn get_global_metrics(&self) -> GlobalMetrics {
@@ -278,7 +378,7 @@ impl MetricsCollector {
 
     pub fn reset_agent_metrics(&self, agent_id: &str) {
         let mut metrics = self.metrics.write().unwrap();
-        if let Some(agent_metrics) = metrics.get_mut(agent_id) {
+        if let Some(agent_metrics) = metrics.peek_mut(agent_id) {
             agent_metrics.total_requests = 0;
             agent_metrics.successful_requests = 0;
             agent_metrics.failed_requests = 0;
@@ -294,10 +394,12 @@ impl MetricsCollector {
     pub fn export_metrics(&self) -> serde_json::Value {
         let agent_metrics = self.get_all_agent_metrics();
         let global_metrics = self.get_global_metrics();
+        let resilience = self.resilience.read().unwrap().clone();
 
         serde_json::json!({
             "global": global_metrics,
             "agents": agent_metrics,
+            "resilience": resilience,
             "exported_at": Utc::now()
         })
     }
@@ -327,6 +429,8 @@ mod tests {
             output_tokens: 100,
             cache_read_tokens: 50,
             cache_write_tokens: 25,
+            reasoning_tokens: 30,
+            estimated_tokens: 0,
         };
 
         usage.add(&other);
@@ -334,6 +438,8 @@ mod tests {
         assert_eq!(usage.output_tokens, 150);
         assert_eq!(usage.cache_read_tokens, 50);
         assert_eq!(usage.cache_write_tokens, 25);
+        assert_eq!(usage.reasoning_tokens, 30);
+        // Reasoning tokens are part of output and not double counted
         assert_eq!(usage.total(), 525);
     }
 
@@ -349,6 +455,8 @@ mod tests {
             output_tokens: 50,
             cache_read_tokens: 0,
             cache_write_tokens: 0,
+            reasoning_tokens: 0,
+            estimated_tokens: 0,
         };
 
         collector.record_request(
@@ -371,4 +479,26 @@ mod tests {
         assert_eq!(global.total_agents, 1);
         assert_eq!(global.total_requests, 1);
     }
+
+    #[test]
+    fn test_max_agents_evicts_least_recently_used() {
+        let collector = MetricsCollector::with_max_agents(2);
+        collector.create_agent_metrics("a".to_string());
+        collector.create_agent_metrics("b".to_string());
+
+        // Touch "a" so "b" becomes the least recently used
+        collector.record_tool_execution("a", "search", true, Duration::from_millis(5), None);
+        collector.create_agent_metrics("c".to_string());
+
+        let tracked = collector.get_all_agent_metrics();
+        assert_eq!(tracked.len(), 2);
+        assert!(tracked.contains_key("a"));
+        assert!(tracked.contains_key("c"));
+        assert!(!tracked.contains_key("b"));
+
+        let removed = collector.remove_agent_metrics("a").unwrap();
+        assert_eq!(removed.tool_usage["search"].executions, 1);
+        assert!(collector.get_agent_metrics("a").is_none());
+        assert_eq!(collector.get_all_agent_metrics().len(), 1);
+    }
 }
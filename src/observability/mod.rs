@@ -3,7 +3,11 @@ pub mod metrics;
 pub mod telemetry;
 pub mod tracing;
 
-pub use cost_tracker::{CostReport, CostTracker, ProviderCosts};
-pub use metrics::{AgentMetrics, MetricsCollector, ProviderMetrics, ToolMetrics};
+pub use cost_tracker::{
+    CostBucket, CostReport, CostTracker, ProviderCosts, TagReportEntry, UsageEvent,
+};
+pub use metrics::{
+    AgentMetrics, MetricsCollector, ProviderMetrics, ResilienceMetrics, ToolMetrics,
+};
 pub use telemetry::{TelemetryConfig, TelemetryExporter};
-pub use tracing::{AgentTracer, TraceEvent, TraceSpan};
+pub use tracing::{AgentTracer, TraceContext, TraceEvent, TraceSpan};
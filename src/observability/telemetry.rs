@@ -1,9 +1,10 @@
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
-use tokio::sync::RwLock;
+use tokio::sync::{watch, RwLock};
+use tokio::task::JoinHandle;
 
 use super::{AgentTracer, CostTracker, MetricsCollector};
 
@@ -54,14 +55,22 @@ pub enum HttpFormat {
     Protobuf,
 }
 
-/// Main telemetry system that coordinates all observability components
+/// Main telemetry system that coordinates all observability components.
+///
+/// The export loop started by [`start`](Self::start) exports once more when
+/// it stops, so telemetry recorded since the last interval is not lost. Use
+/// [`shutdown`](Self::shutdown) before the process exits to wait for that
+/// final export; dropping the exporter only signals the loop to stop.
 pub struct TelemetryExporter {
     config: TelemetryConfig,
     metrics_collector: Arc<MetricsCollector>,
     tracer: Arc<AgentTracer>,
     cost_tracker: Arc<RwLock<CostTracker>>,
     exporters: Vec<Box<dyn Exporter>>,
-    running: Arc<RwLock<bool>>,
+    /// Stops the loop from the current `start`; each start gets its own
+    /// channel, so a stop with no loop running can't end a later one
+    stop_signal: Mutex<Option<watch::Sender<bool>>>,
+    task: Mutex<Option<JoinHandle<()>>>,
 }
 
 impl TelemetryExporter {
@@ -108,16 +117,25 @@ impl TelemetryExporter {
             tracer,
             cost_tracker,
             exporters,
-            running: Arc::new(RwLock::new(false)),
+            stop_signal: Mutex::new(None),
+            task: Mutex::new(None),
         }
     }
 
+    /// Also export to `exporter`, e.g. a custom backend
+    pub fn with_exporter(mut self, exporter: Box<dyn Exporter>) -> Self {
+        self.exporters.push(exporter);
+        self
+    }
+
+    /// Start the periodic export loop. A loop that is already running is
+    /// stopped first, and its final export awaited, so nothing it buffered
+    /// is lost.
     pub async fn start(&self) {
         if !self.config.enabled {
             return;
         }
-
-        *self.running.write().await = true;
+        self.shutdown().await;
 
         let metrics_collector = self.metrics_collector.clone();
         let tracer = self.tracer.clone();
@@ -128,55 +146,92 @@ impl TelemetryExporter {
             .map(|e| e.clone_box())
             .collect::<Vec<_>>();
         let export_interval = self.config.export_interval;
-        let running = self.running.clone();
 
-        tokio::spawn(async move {
+        let (stop_sender, mut stop_signal) = watch::channel(false);
+
+        let task = tokio::spawn(async move {
             let mut interval = tokio::time::interval(export_interval);
 
-            while *running.read().await {
-                interval.tick().await;
-
-                // Collect all telemetry data
-                let telemetry_data = TelemetryData {
-                    timestamp: Utc::now(),
-                    metrics: metrics_collector.export_metrics(),
-                    traces: tracer.export_traces(),
-                    costs: {
-                        let tracker = cost_tracker.read().await;
-                        serde_json::to_value(tracker.generate_report()).unwrap_or_default()
-                    },
-                };
-
-                // Export to all configured exporters
-                for exporter in &exporters {
-                    if let Err(e) = exporter.export(&telemetry_data).await {
-                        eprintln!("Failed to export telemetry: {}", e);
-                    }
+            loop {
+                tokio::select! {
+                    _ = interval.tick() => {}
+                    // Also fires when the sender is dropped with the exporter
+                    _ = stop_signal.changed() => break,
                 }
+
+                let telemetry_data =
+                    collect_telemetry(&metrics_collector, &tracer, &cost_tracker).await;
+                export_all(&exporters, &telemetry_data).await;
             }
+
+            // Final flush, so data recorded since the last tick is exported
+            let telemetry_data =
+                collect_telemetry(&metrics_collector, &tracer, &cost_tracker).await;
+            export_all(&exporters, &telemetry_data).await;
         });
+
+        self.stop_signal.lock().unwrap().replace(stop_sender);
+        self.task.lock().unwrap().replace(task);
     }
 
+    /// Stop the export loop; it exports once more before exiting
     pub async fn stop(&self) {
-        *self.running.write().await = false;
+        if let Some(stop_signal) = self.stop_signal.lock().unwrap().take() {
+            let _ = stop_signal.send(true);
+        }
     }
 
-    pub async fn export_now(&self) -> Result<(), Box<dyn std::error::Error>> {
-        let telemetry_data = TelemetryData {
-            timestamp: Utc::now(),
-            metrics: self.metrics_collector.export_metrics(),
-            traces: self.tracer.export_traces(),
-            costs: {
-                let tracker = self.cost_tracker.read().await;
-                serde_json::to_value(tracker.generate_report())?
-            },
-        };
+    /// Stop the export loop and wait for its final export
+    pub async fn shutdown(&self) {
+        self.stop().await;
+        let task = self.task.lock().unwrap().take();
+        if let Some(task) = task {
+            let _ = task.await;
+        }
+    }
+
+    /// Export current telemetry to every exporter now, returning the first
+    /// error after all exporters have been tried
+    pub async fn flush(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let telemetry_data =
+            collect_telemetry(&self.metrics_collector, &self.tracer, &self.cost_tracker).await;
 
+        let mut first_error = None;
         for exporter in &self.exporters {
-            exporter.export(&telemetry_data).await?;
+            if let Err(e) = exporter.export(&telemetry_data).await {
+                first_error.get_or_insert(e);
+            }
         }
+        first_error.map_or(Ok(()), Err)
+    }
 
-        Ok(())
+    pub async fn export_now(&self) -> Result<(), Box<dyn std::error::Error>> {
+        self.flush().await
+    }
+}
+
+async fn collect_telemetry(
+    metrics_collector: &MetricsCollector,
+    tracer: &AgentTracer,
+    cost_tracker: &RwLock<CostTracker>,
+) -> TelemetryData {
+    TelemetryData {
+        timestamp: Utc::now(),
+        metrics: metrics_collector.export_metrics(),
+        traces: tracer.export_traces(),
+        costs: {
+            let tracker = cost_tracker.read().await;
+            serde_json::to_value(tracker.generate_report()).unwrap_or_default()
+        },
+    }
+}
+
+/// Export to every exporter, logging failures
+async fn export_all(exporters: &[Box<dyn Exporter>], data: &TelemetryData) {
+    for exporter in exporters {
+        if let Err(e) = exporter.export(data).await {
+            eprintln!("Failed to export telemetry: {}", e);
+        }
     }
 }
 
@@ -279,6 +334,13 @@ impl HttpExporter {
             client: reqwest::Client::new(),
         }
     }
+
+    /// Export through `client`, e.g. one shared with the providers or
+    /// configured with a proxy or custom TLS roots
+    pub fn with_client(mut self, client: reqwest::Client) -> Self {
+        self.client = client;
+        self
+    }
 }
 
 #[async_trait::async_trait]
@@ -327,7 +389,7 @@ impl Exporter for HttpExporter {
             endpoint: self.endpoint.clone(),
             format: self.format.clone(),
             headers: self.headers.clone(),
-            client: reqwest::Client::new(),
+            client: self.client.clone(),
         })
     }
 }
@@ -345,6 +407,12 @@ impl JaegerExporter {
             client: reqwest::Client::new(),
         }
     }
+
+    /// Export through `client`, as [`HttpExporter::with_client`]
+    pub fn with_client(mut self, client: reqwest::Client) -> Self {
+        self.client = client;
+        self
+    }
 }
 
 #[async_trait::async_trait]
@@ -375,7 +443,7 @@ impl Exporter for JaegerExporter {
     fn clone_box(&self) -> Box<dyn Exporter> {
         Box::new(Self {
             endpoint: self.endpoint.clone(),
-            client: reqwest::Client::new(),
+            client: self.client.clone(),
         })
     }
 }
@@ -393,6 +461,12 @@ impl PrometheusExporter {
             client: reqwest::Client::new(),
         }
     }
+
+    /// Export through `client`, as [`HttpExporter::with_client`]
+    pub fn with_client(mut self, client: reqwest::Client) -> Self {
+        self.client = client;
+        self
+    }
 }
 
 #[async_trait::async_trait]
@@ -424,7 +498,7 @@ impl Exporter for PrometheusExporter {
     fn clone_box(&self) -> Box<dyn Exporter> {
         Box::new(Self {
             endpoint: self.endpoint.clone(),
-            client: reqwest::Client::new(),
+            client: self.client.clone(),
         })
     }
 }
@@ -453,6 +527,12 @@ impl OpenTelemetryExporter {
             client: reqwest::Client::new(),
         }
     }
+
+    /// Export through `client`, as [`HttpExporter::with_client`]
+    pub fn with_client(mut self, client: reqwest::Client) -> Self {
+        self.client = client;
+        self
+    }
 }
 
 #[async_trait::async_trait]
@@ -487,7 +567,7 @@ impl Exporter for OpenTelemetryExporter {
     fn clone_box(&self) -> Box<dyn Exporter> {
         Box::new(Self {
             endpoint: self.endpoint.clone(),
-            client: reqwest::Client::new(),
+            client: self.client.clone(),
         })
     }
 }
@@ -509,3 +589,108 @@ impl Default for TelemetryConfig {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[derive(Clone)]
+    struct CountingExporter(Arc<AtomicUsize>);
+
+    #[async_trait::async_trait]
+    impl Exporter for CountingExporter {
+        async fn export(&self, _data: &TelemetryData) -> Result<(), Box<dyn std::error::Error>> {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        }
+
+        fn clone_box(&self) -> Box<dyn Exporter> {
+            Box::new(self.clone())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_makes_final_export() {
+        let exports = Arc::new(AtomicUsize::new(0));
+        let exporter = TelemetryExporter::new(
+            TelemetryConfig {
+                enabled: true,
+                export_interval: Duration::from_secs(3600),
+                exporters: vec![],
+                ..TelemetryConfig::default()
+            },
+            Arc::new(MetricsCollector::new()),
+            Arc::new(AgentTracer::new(Default::default())),
+            Arc::new(RwLock::new(CostTracker::new())),
+        )
+        .with_exporter(Box::new(CountingExporter(exports.clone())));
+
+        exporter.start().await;
+        // The first interval tick fires immediately
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(exports.load(Ordering::SeqCst), 1);
+
+        // Stopping long before the next interval still exports
+        exporter.shutdown().await;
+        assert_eq!(exports.load(Ordering::SeqCst), 2);
+
+        exporter.flush().await.unwrap();
+        assert_eq!(exports.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_restart_keeps_final_export() {
+        let exports = Arc::new(AtomicUsize::new(0));
+        let exporter = TelemetryExporter::new(
+            TelemetryConfig {
+                enabled: true,
+                export_interval: Duration::from_secs(3600),
+                exporters: vec![],
+                ..TelemetryConfig::default()
+            },
+            Arc::new(MetricsCollector::new()),
+            Arc::new(AgentTracer::new(Default::default())),
+            Arc::new(RwLock::new(CostTracker::new())),
+        )
+        .with_exporter(Box::new(CountingExporter(exports.clone())));
+
+        exporter.start().await;
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(exports.load(Ordering::SeqCst), 1);
+
+        // The first loop's final export runs before the second loop's first
+        exporter.start().await;
+        assert_eq!(exports.load(Ordering::SeqCst), 2);
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(exports.load(Ordering::SeqCst), 3);
+
+        exporter.shutdown().await;
+        assert_eq!(exports.load(Ordering::SeqCst), 4);
+    }
+
+    #[tokio::test]
+    async fn test_stop_before_start_does_not_end_next_loop() {
+        let exports = Arc::new(AtomicUsize::new(0));
+        let exporter = TelemetryExporter::new(
+            TelemetryConfig {
+                enabled: true,
+                export_interval: Duration::from_millis(20),
+                exporters: vec![],
+                ..TelemetryConfig::default()
+            },
+            Arc::new(MetricsCollector::new()),
+            Arc::new(AgentTracer::new(Default::default())),
+            Arc::new(RwLock::new(CostTracker::new())),
+        )
+        .with_exporter(Box::new(CountingExporter(exports.clone())));
+
+        exporter.stop().await;
+        exporter.start().await;
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        // A stored stop signal would have ended the loop after one export
+        // and its final flush
+        assert!(exports.load(Ordering::SeqCst) > 2);
+        exporter.shutdown().await;
+    }
+}
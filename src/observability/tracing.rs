@@ -3,7 +3,6 @@ use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::sync::{Arc, RwLock};
 use std::time::{Duration, Instant};
-use uuid::Uuid;
 
 /// Represents a single trace event in the system
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -59,6 +58,77 @@ pub enum LogLevel {
     Error,
 }
 
+/// Trace context carried in a W3C `traceparent` header
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceContext {
+    /// 32 lowercase hex characters (16 bytes)
+    pub trace_id: String,
+    /// 16 lowercase hex characters (8 bytes)
+    pub parent_span_id: String,
+    pub sampled: bool,
+}
+
+impl TraceContext {
+    /// Parse a `traceparent` header such as
+    /// `00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01`
+    pub fn from_traceparent(header: &str) -> Option<Self> {
+        let mut fields = header.trim().split('-');
+        let version = fields.next()?;
+        let trace_id = fields.next()?;
+        let parent_span_id = fields.next()?;
+        let flags = fields.next()?;
+
+        if version.len() != 2 || version == "ff" || !is_lower_hex(version) {
+            return None;
+        }
+        // Version 00 has exactly four fields
+        if version == "00" && fields.next().is_some() {
+            return None;
+        }
+        if trace_id.len() != 32 || !is_lower_hex(trace_id) || is_all_zero(trace_id) {
+            return None;
+        }
+        if parent_span_id.len() != 16
+            || !is_lower_hex(parent_span_id)
+            || is_all_zero(parent_span_id)
+        {
+            return None;
+        }
+        if flags.len() != 2 || !is_lower_hex(flags) {
+            return None;
+        }
+
+        Some(Self {
+            trace_id: trace_id.to_string(),
+            parent_span_id: parent_span_id.to_string(),
+            sampled: u8::from_str_radix(flags, 16).ok()? & 0x01 == 0x01,
+        })
+    }
+}
+
+fn is_lower_hex(s: &str) -> bool {
+    s.bytes()
+        .all(|b| b.is_ascii_digit() || (b'a'..=b'f').contains(&b))
+}
+
+fn is_all_zero(s: &str) -> bool {
+    s.bytes().all(|b| b == b'0')
+}
+
+/// Random 16-byte trace id as 32 hex characters
+fn new_trace_id() -> String {
+    use rand::Rng;
+    let id: u128 = rand::thread_rng().gen_range(1..=u128::MAX);
+    format!("{:032x}", id)
+}
+
+/// Random 8-byte span id as 16 hex characters
+fn new_span_id() -> String {
+    use rand::Rng;
+    let id: u64 = rand::thread_rng().gen_range(1..=u64::MAX);
+    format!("{:016x}", id)
+}
+
 /// Active span that tracks timing and can be finished
 pub struct TraceSpan {
     pub event: TraceEvent,
@@ -108,6 +178,11 @@ impl TraceSpan {
         self.tracer
             .start_span_with_parent(operation_name, Some(self.event.span_id.clone()))
     }
+
+    /// W3C `traceparent` header value for propagating this span downstream
+    pub fn traceparent(&self) -> String {
+        format!("00-{}-{}-01", self.event.trace_id, self.event.span_id)
+    }
 }
 
 /// Agent tracer for collecting distributed traces
@@ -152,12 +227,37 @@ impl AgentTracer {
             return None;
         }
 
-        let trace_id = Uuid::new_v4().to_string();
+        let trace_id = new_trace_id();
         *self.current_trace.write().unwrap() = Some(trace_id.clone());
 
         Some(self.start_span_with_trace(operation_name, trace_id, None))
     }
 
+    /// Start a trace that continues a distributed trace from an incoming
+    /// `traceparent` header. An invalid header starts a fresh trace, and an
+    /// upstream decision not to sample is respected.
+    pub fn start_trace_with_parent(
+        &self,
+        operation_name: String,
+        traceparent: &str,
+    ) -> Option<TraceSpan> {
+        let context = match TraceContext::from_traceparent(traceparent) {
+            Some(context) => context,
+            None => return self.start_trace(operation_name),
+        };
+        if !self.config.enabled || !context.sampled {
+            return None;
+        }
+
+        *self.current_trace.write().unwrap() = Some(context.trace_id.clone());
+
+        Some(self.start_span_with_trace(
+            operation_name,
+            context.trace_id,
+            Some(context.parent_span_id),
+        ))
+    }
+
     pub fn start_span(&self, operation_name: String) -> Option<TraceSpan> {
         if !self.config.enabled {
             return None;
@@ -177,7 +277,7 @@ impl AgentTracer {
         parent_span_id: Option<String>,
     ) -> TraceSpan {
         let current_trace = self.current_trace.read().unwrap().clone();
-        let trace_id = current_trace.unwrap_or_else(|| Uuid::new_v4().to_string());
+        let trace_id = current_trace.unwrap_or_else(new_trace_id);
 
         self.start_span_with_trace(operation_name, trace_id, parent_span_id)
     }
@@ -188,7 +288,7 @@ impl AgentTracer {
         trace_id: String,
         parent_span_id: Option<String>,
     ) -> TraceSpan {
-        let span_id = Uuid::new_v4().to_string();
+        let span_id = new_span_id();
         let now = Utc::now();
 
         let event = TraceEvent {
@@ -367,4 +467,53 @@ mod tests {
         let span = tracer.start_trace("test_operation".to_string());
         assert!(span.is_none());
     }
+
+    #[test]
+    fn test_w3c_id_formats() {
+        let tracer = AgentTracer::new(TracingConfig::default());
+        let span = tracer.start_trace("op".to_string()).unwrap();
+
+        assert_eq!(span.event.trace_id.len(), 32);
+        assert_eq!(span.event.span_id.len(), 16);
+        assert!(is_lower_hex(&span.event.trace_id));
+        assert!(is_lower_hex(&span.event.span_id));
+
+        let header = span.traceparent();
+        let context = TraceContext::from_traceparent(&header).unwrap();
+        assert_eq!(context.trace_id, span.event.trace_id);
+        assert_eq!(context.parent_span_id, span.event.span_id);
+        assert!(context.sampled);
+    }
+
+    #[test]
+    fn test_traceparent_propagation() {
+        let tracer = AgentTracer::new(TracingConfig::default());
+        let header = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+
+        let span = tracer
+            .start_trace_with_parent("agent".to_string(), header)
+            .unwrap();
+        assert_eq!(span.event.trace_id, "4bf92f3577b34da6a3ce929d0e0e4736");
+        assert_eq!(
+            span.event.parent_span_id.as_deref(),
+            Some("00f067aa0ba902b7")
+        );
+
+        // Children stay in the upstream trace
+        let child = span.child_span("tool".to_string());
+        assert_eq!(child.event.trace_id, span.event.trace_id);
+
+        // Upstream chose not to sample
+        let unsampled = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-00";
+        assert!(tracer
+            .start_trace_with_parent("agent".to_string(), unsampled)
+            .is_none());
+
+        // Malformed headers are rejected
+        assert!(TraceContext::from_traceparent("00-xyz-00f067aa0ba902b7-01").is_none());
+        assert!(TraceContext::from_traceparent(
+            "00-00000000000000000000000000000000-00f067aa0ba902b7-01"
+        )
+        .is_none());
+    }
 }
@@ -2,26 +2,136 @@ use async_trait::async_trait;
 use futures::stream::{Stream, StreamExt};
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
 
 use crate::{
-    AiError, Choice, CompletionProvider, CompletionRequest, CompletionResponse, ContentPart, Delta,
-    FunctionCall, Message, MessageContent, Result, Role, StreamChoice, StreamChunk, ToolCall,
-    ToolCallDelta, ToolChoice, ToolType, Usage,
+    providers::{
+        auth::SendAuthenticated, ApiKeyAuth, Authenticator, EventStream, ProviderConfig, SseEvent,
+    },
+    AiError, BatchHandle, BatchProvider, BatchState, BatchStatus, Choice, CompiledSchema,
+    CompletionProvider, CompletionRequest, CompletionResponse, ContentPart, Delta, FunctionCall,
+    Message, MessageContent, ModelInfo, ProviderCapabilities, Result, Role, StreamChoice,
+    StreamChunk, Tool, ToolCall, ToolCallDelta, ToolChoice, ToolFunction, ToolType, Usage,
 };
 use serde_json::Value;
 
+/// `anthropic-version` header sent when no version is configured
+pub const DEFAULT_API_VERSION: &str = "2024-10-22";
+
+const DEFAULT_BASE_URL: &str = "https://api.anthropic.com/v1";
+
+/// Provider for the Anthropic Messages API.
+///
+/// Ending `messages` with an assistant turn prefills the reply: Claude
+/// continues from that text, and the response holds only the continuation.
 pub struct AnthropicProvider {
     client: Client,
-    api_key: String,
+    /// Set by `with_client`, so `with_config` keeps the injected client
+    custom_client: bool,
+    auth: Arc<dyn Authenticator>,
+    base_url: String,
+    stream_idle_timeout: Option<Duration>,
+    version: String,
+    validate_models: bool,
+    check_context_window: bool,
 }
 
 impl AnthropicProvider {
     pub fn new(api_key: String) -> Self {
+        Self::with_version(api_key, DEFAULT_API_VERSION)
+    }
+
+    /// Create a provider that sends a specific `anthropic-version` header
+    pub fn with_version(api_key: String, version: impl Into<String>) -> Self {
         Self {
-            client: Client::new(),
-            api_key,
+            client: super::config::default_client(),
+            custom_client: false,
+            auth: Arc::new(ApiKeyAuth::header("x-api-key", api_key)),
+            base_url: DEFAULT_BASE_URL.to_string(),
+            stream_idle_timeout: None,
+            version: version.into(),
+            validate_models: false,
+            check_context_window: false,
+        }
+    }
+
+    /// Create a provider that talks to a proxy or gateway instead of api.anthropic.com
+    pub fn with_base_url(api_key: String, base_url: String) -> Self {
+        Self {
+            base_url,
+            ..Self::new(api_key)
+        }
+    }
+
+    /// Reject models missing from `available_models()` before sending,
+    /// instead of waiting for the API to refuse them
+    pub fn with_model_validation(mut self, enabled: bool) -> Self {
+        self.validate_models = enabled;
+        self
+    }
+
+    /// Reject a request whose estimated size exceeds the model's context
+    /// window before sending it (see [`context_window`]). Off by default:
+    /// the estimate is rough and could turn away requests the API accepts.
+    ///
+    /// [`context_window`]: crate::providers::context_window
+    pub fn with_context_window_check(mut self, enabled: bool) -> Self {
+        self.check_context_window = enabled;
+        self
+    }
+
+    /// Send every request with the user agent and default headers from
+    /// `config`. A client given to [`with_client`](Self::with_client),
+    /// before or after this call, is kept; the user agent and headers are
+    /// then up to that client.
+    pub fn with_config(mut self, config: &ProviderConfig) -> Result<Self> {
+        let client = config.build_client()?;
+        if !self.custom_client {
+            self.client = client;
         }
+        self.stream_idle_timeout = config.stream_idle_timeout;
+        Ok(self)
+    }
+
+    /// Send requests through `client`, sharing its connection pool and DNS
+    /// cache with other providers. It takes precedence over the client
+    /// [`with_config`](Self::with_config) builds.
+    pub fn with_client(mut self, client: Client) -> Self {
+        self.client = client;
+        self.custom_client = true;
+        self
+    }
+
+    /// Authenticate requests with `auth` instead of the API key, e.g. for a
+    /// proxy or gateway with its own credentials
+    pub fn with_authenticator(mut self, auth: impl Authenticator + 'static) -> Self {
+        self.auth = Arc::new(auth);
+        self
+    }
+
+    pub fn api_version(&self) -> &str {
+        &self.version
+    }
+
+    fn messages_request(&self) -> reqwest::RequestBuilder {
+        self.versioned(self.client.post(format!("{}/messages", self.base_url)))
+    }
+
+    /// Add the API version and content type every request carries; the
+    /// API key is added by the authenticator when the request is sent
+    fn versioned(&self, builder: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        builder
+            .header("anthropic-version", &self.version)
+            .header("Content-Type", "application/json")
+    }
+
+    /// Create a provider, rejecting an empty or whitespace-only API key
+    pub fn try_new(api_key: String) -> Result<Self> {
+        super::validate_api_key("anthropic", &api_key)?;
+        Ok(Self::new(api_key))
     }
 }
 
@@ -40,6 +150,17 @@ struct AnthropicRequest {
     tools: Option<Vec<AnthropicTool>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     tool_choice: Option<AnthropicToolChoice>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    thinking: Option<AnthropicThinking>,
+    #[serde(flatten)]
+    extra: HashMap<String, Value>,
+}
+
+#[derive(Serialize)]
+struct AnthropicThinking {
+    #[serde(rename = "type")]
+    thinking_type: String,
+    budget_tokens: u32,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -55,7 +176,7 @@ enum AnthropicMessageContent {
     Parts(Vec<AnthropicContentPart>),
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Default, Serialize, Deserialize)]
 struct AnthropicContentPart {
     #[serde(rename = "type")]
     content_type: String,
@@ -63,6 +184,37 @@ struct AnthropicContentPart {
     text: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     source: Option<AnthropicImageSource>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    title: Option<String>,
+    /// tool_use id
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    id: Option<String>,
+    /// tool_use tool name
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+    /// tool_use arguments
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    input: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    tool_use_id: Option<String>,
+    /// tool_result blocks
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    content: Option<Vec<AnthropicContentPart>>,
+    /// thinking blocks
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    thinking: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    signature: Option<String>,
+}
+
+impl AnthropicContentPart {
+    fn text(text: String) -> Self {
+        Self {
+            content_type: "text".to_string(),
+            text: Some(text),
+            ..Default::default()
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize)]
@@ -77,9 +229,10 @@ struct AnthropicImageSource {
 struct AnthropicResponse {
     id: String,
     model: String,
-    #[allow(dead_code)]
     role: String,
     content: Vec<AnthropicContent>,
+    #[serde(default)]
+    stop_reason: Option<String>,
     usage: AnthropicUsage,
 }
 
@@ -95,6 +248,10 @@ struct AnthropicContent {
     name: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     input: Option<Value>,
+    #[serde(default)]
+    thinking: Option<String>,
+    #[serde(default)]
+    signature: Option<String>,
 }
 
 #[derive(Deserialize)]
@@ -117,6 +274,8 @@ enum AnthropicToolChoice {
     Any,
     #[serde(rename = "auto")]
     Auto,
+    #[serde(rename = "none")]
+    None,
     #[serde(rename = "tool")]
     Tool { name: String },
 }
@@ -133,205 +292,70 @@ struct AnthropicStreamEvent {
 #[async_trait]
 impl CompletionProvider for AnthropicProvider {
     async fn complete(&self, request: CompletionRequest) -> Result<CompletionResponse> {
-        let (system, messages) = split_system_message(request.messages);
-
-        // Convert tools if present
-        let tools = request.tools.map(|tools| {
-            tools
-                .into_iter()
-                .map(|tool| AnthropicTool {
-                    name: tool.function.name,
-                    description: tool.function.description.unwrap_or_default(),
-                    input_schema: tool.function.parameters,
-                })
-                .collect()
-        });
-
-        // Convert tool choice if present
-        let tool_choice = request.tool_choice.map(|tc| match tc {
-            ToolChoice::String(s) => match s.as_str() {
-                "auto" => AnthropicToolChoice::Auto,
-                "any" => AnthropicToolChoice::Any,
-                _ => AnthropicToolChoice::Auto,
-            },
-            ToolChoice::Object(obj) => AnthropicToolChoice::Tool {
-                name: obj.function.name,
-            },
-        });
-
-        let anthropic_request = AnthropicRequest {
-            model: request.model,
-            messages: messages
-                .into_iter()
-                .map(convert_message_to_anthropic)
-                .collect(),
-            max_tokens: request.max_tokens.unwrap_or(1024),
-            temperature: request.temperature,
-            stream: Some(false),
-            system,
-            tools,
-            tool_choice,
-        };
+        request.validate()?;
+        if self.validate_models {
+            super::validate_model(self, &request.model)?;
+        }
+        if self.check_context_window {
+            super::check_context_window(&request)?;
+        }
+        let anthropic_request = build_anthropic_request(request, false)?;
 
         let response = self
-            .client
-            .post("https://api.anthropic.com/v1/messages")
-            .header("X-API-Key", &self.api_key)
-            .header("anthropic-version", "2024-10-22")
-            .header("Content-Type", "application/json")
+            .messages_request()
             .json(&anthropic_request)
-            .send()
+            .send_authenticated(self.auth.as_ref())
             .await?;
 
-        if !response.status().is_success() {
-            let error_text = response.text().await?;
-            return Err(AiError::ProviderError {
-                provider: "anthropic".to_string(),
-                message: format!("Anthropic API error: {}", error_text),
-                error_code: None,
-                retryable: true,
-            });
-        }
+        let response = super::check_response(response, |error| {
+            anthropic_api_error(
+                "Anthropic API",
+                error.status_code,
+                &error.body,
+                error.request_id,
+            )
+        })
+        .await?;
 
         let anthropic_response: AnthropicResponse = response.json().await?;
 
-        // Extract text content and tool calls
-        let mut text_parts = Vec::new();
-        let mut tool_calls = Vec::new();
-
-        for content in anthropic_response.content {
-            match content.content_type.as_str() {
-                "text" => {
-                    if let Some(text) = content.text {
-                        text_parts.push(text);
-                    }
-                }
-                "tool_use" => {
-                    if let (Some(id), Some(name), Some(input)) =
-                        (content.id, content.name, content.input)
-                    {
-                        tool_calls.push(ToolCall {
-                            id,
-                            r#type: ToolType::Function,
-                            function: FunctionCall {
-                                name,
-                                arguments: serde_json::to_string(&input).unwrap_or_default(),
-                            },
-                        });
-                    }
-                }
-                _ => {}
-            }
-        }
-
-        let message_content = if text_parts.is_empty() {
-            MessageContent::Text("".to_string())
-        } else {
-            MessageContent::Text(text_parts.join(""))
-        };
-
-        Ok(CompletionResponse {
-            id: anthropic_response.id,
-            model: anthropic_response.model,
-            choices: vec![Choice {
-                index: 0,
-                message: Message {
-                    role: Role::Assistant,
-                    content: message_content,
-                    tool_calls: if tool_calls.is_empty() {
-                        None
-                    } else {
-                        Some(tool_calls)
-                    },
-                    tool_call_id: None,
-                },
-                finish_reason: Some("stop".to_string()),
-            }],
-            usage: Some(Usage {
-                prompt_tokens: anthropic_response.usage.input_tokens,
-                completion_tokens: anthropic_response.usage.output_tokens,
-                total_tokens: anthropic_response.usage.input_tokens
-                    + anthropic_response.usage.output_tokens,
-            }),
-        })
+        Ok(convert_anthropic_response(anthropic_response))
     }
 
     async fn complete_stream(
         &self,
         request: CompletionRequest,
     ) -> Result<Pin<Box<dyn Stream<Item = Result<StreamChunk>> + Send>>> {
-        let (system, messages) = split_system_message(request.messages);
-
-        // Convert tools if present
-        let tools = request.tools.map(|tools| {
-            tools
-                .into_iter()
-                .map(|tool| AnthropicTool {
-                    name: tool.function.name,
-                    description: tool.function.description.unwrap_or_default(),
-                    input_schema: tool.function.parameters,
-                })
-                .collect()
-        });
-
-        // Convert tool choice if present
-        let tool_choice = request.tool_choice.map(|tc| match tc {
-            ToolChoice::String(s) => match s.as_str() {
-                "auto" => AnthropicToolChoice::Auto,
-                "any" => AnthropicToolChoice::Any,
-                _ => AnthropicToolChoice::Auto,
-            },
-            ToolChoice::Object(obj) => AnthropicToolChoice::Tool {
-                name: obj.function.name,
-            },
-        });
-
-        let anthropic_request = AnthropicRequest {
-            model: request.model,
-            messages: messages
-                .into_iter()
-                .map(convert_message_to_anthropic)
-                .collect(),
-            max_tokens: request.max_tokens.unwrap_or(1024),
-            temperature: request.temperature,
-            stream: Some(true),
-            system,
-            tools,
-            tool_choice,
-        };
+        request.validate()?;
+        if self.validate_models {
+            super::validate_model(self, &request.model)?;
+        }
+        if self.check_context_window {
+            super::check_context_window(&request)?;
+        }
+        let mut state = AnthropicStreamState::new(request.model.clone())
+            .with_tools(request.tools.as_deref().unwrap_or_default())?;
+        let anthropic_request = build_anthropic_request(request, true)?;
 
         let response = self
-            .client
-            .post("https://api.anthropic.com/v1/messages")
-            .header("X-API-Key", &self.api_key)
-            .header("anthropic-version", "2024-10-22")
-            .header("Content-Type", "application/json")
+            .messages_request()
             .json(&anthropic_request)
-            .send()
+            .send_authenticated(self.auth.as_ref())
             .await?;
 
-        if !response.status().is_success() {
-            let error_text = response.text().await?;
-            return Err(AiError::ProviderError {
-                provider: "anthropic".to_string(),
-                message: format!("Anthropic API error: {}", error_text),
-                error_code: None,
-                retryable: true,
-            });
-        }
+        let response = super::check_response(response, |error| {
+            anthropic_api_error(
+                "Anthropic API",
+                error.status_code,
+                &error.body,
+                error.request_id,
+            )
+        })
+        .await?;
 
-        let stream = response.bytes_stream();
-        let stream = stream
-            .map(|result| match result {
-                Ok(bytes) => {
-                    let text = String::from_utf8_lossy(&bytes);
-                    parse_anthropic_sse(&text)
-                }
-                Err(e) => Err(AiError::StreamError {
-                    message: e.to_string(),
-                    retryable: true,
-                }),
-            })
+        let stream = EventStream::new(response.bytes_stream())
+            .with_optional_idle_timeout(self.stream_idle_timeout)
+            .map(move |event| event.and_then(|event| parse_anthropic_event(&event, &mut state)))
             .filter_map(|result| async move {
                 match result {
                     Ok(Some(chunk)) => Some(Ok(chunk)),
@@ -360,10 +384,265 @@ impl CompletionProvider for AnthropicProvider {
             "claude-3-haiku-20240307",
         ]
     }
+
+    fn capabilities(&self) -> ProviderCapabilities {
+        CAPABILITIES
+    }
+
+    fn model_catalog(&self) -> Vec<ModelInfo> {
+        let capabilities = self.capabilities();
+        self.available_models()
+            .into_iter()
+            .map(|model| {
+                let mut info = ModelInfo::from_capabilities(self.name(), model, &capabilities);
+                // Claude 3.5 Haiku takes text only
+                info.supports_vision = !model.starts_with("claude-3-5-haiku");
+                info
+            })
+            .collect()
+    }
+}
+
+/// Classify a failed response from its status and `error.type`: 529 /
+/// `overloaded_error` is retryable, an exhausted credit balance is
+/// `QuotaExceeded`, and otherwise only 429 and 5xx are retried
+fn anthropic_api_error(
+    api: &str,
+    status_code: u16,
+    body: &str,
+    request_id: Option<String>,
+) -> AiError {
+    let error_type = serde_json::from_str::<Value>(body)
+        .ok()
+        .and_then(|v| v["error"]["type"].as_str().map(str::to_string));
+    let message = format!("{} error: {}", api, body);
+
+    let out_of_credits = error_type.as_deref() == Some("billing_error")
+        || body
+            .to_ascii_lowercase()
+            .contains("credit balance is too low");
+    if out_of_credits {
+        return AiError::QuotaExceeded {
+            provider: "anthropic".to_string(),
+            quota_type: "billing".to_string(),
+            reset_time: None,
+            message,
+            request_id,
+        };
+    }
+
+    let overloaded = status_code == 529 || error_type.as_deref() == Some("overloaded_error");
+    AiError::ProviderError {
+        provider: "anthropic".to_string(),
+        message,
+        error_code: error_type,
+        retryable: overloaded || status_code == 429 || status_code >= 500,
+        status_code: Some(status_code),
+        request_id,
+    }
+}
+
+/// Anthropic rejects a prefill ending in whitespace, and extended thinking
+/// cannot be combined with a prefill
+fn prepare_prefill(request: &mut CompletionRequest) -> Result<()> {
+    let Some(last) = request.messages.last_mut() else {
+        return Ok(());
+    };
+    let has_tool_calls = last.tool_calls.as_ref().is_some_and(|c| !c.is_empty());
+    let is_prefill = last.role == Role::Assistant && !has_tool_calls;
+    if !is_prefill {
+        return Ok(());
+    }
+
+    if request.thinking_budget_tokens.is_some() {
+        return Err(AiError::InvalidRequest {
+            message: "Extended thinking cannot be used with an assistant prefill".to_string(),
+            field: Some("messages".to_string()),
+            code: None,
+        });
+    }
+    if let MessageContent::Text(text) = &mut last.content {
+        text.truncate(text.trim_end().len());
+    }
+    Ok(())
+}
+
+const CAPABILITIES: ProviderCapabilities = ProviderCapabilities {
+    streaming: true,
+    tools: true,
+    vision: true,
+    json_mode: false,
+    max_context_tokens: Some(200_000),
+    max_output_tokens: Some(8_192),
+    alternating_roles: true,
+    assistant_prefill: true,
+};
+
+/// Convert a generic request into the Anthropic Messages API format. A
+/// trailing assistant message is kept as a prefill.
+fn build_anthropic_request(
+    mut request: CompletionRequest,
+    stream: bool,
+) -> Result<AnthropicRequest> {
+    request.normalize_system();
+    prepare_prefill(&mut request)?;
+
+    if let Some(tool_choice) = &request.tool_choice {
+        tool_choice.validate(request.tools.as_deref())?;
+    }
+
+    let (system, messages) = split_system_message(request.messages);
+
+    // Convert tools if present
+    let tools = request.tools.map(|tools| {
+        tools
+            .into_iter()
+            .map(|tool| AnthropicTool {
+                name: tool.function.name,
+                description: tool.function.description.unwrap_or_default(),
+                input_schema: tool.function.parameters,
+            })
+            .collect()
+    });
+
+    // Convert tool choice if present
+    let tool_choice = request.tool_choice.map(|tc| match tc {
+        ToolChoice::String(s) => match s.as_str() {
+            "any" | "required" => AnthropicToolChoice::Any,
+            "none" => AnthropicToolChoice::None,
+            _ => AnthropicToolChoice::Auto,
+        },
+        ToolChoice::Object(obj) => AnthropicToolChoice::Tool {
+            name: obj.function.name,
+        },
+    });
+
+    let mut body = AnthropicRequest {
+        model: request.model,
+        messages: merge_consecutive_roles(
+            messages
+                .into_iter()
+                .map(convert_message_to_anthropic)
+                .collect(),
+        ),
+        // Anthropic requires max_tokens to exceed the thinking budget
+        max_tokens: request.max_tokens.unwrap_or_else(|| {
+            request
+                .thinking_budget_tokens
+                .map_or(1024, |budget| budget + 1024)
+        }),
+        temperature: request.temperature,
+        stream: Some(stream),
+        system,
+        tools,
+        tool_choice,
+        thinking: request
+            .thinking_budget_tokens
+            .map(|budget_tokens| AnthropicThinking {
+                thinking_type: "enabled".to_string(),
+                budget_tokens,
+            }),
+        extra: HashMap::new(),
+    };
+    super::check_extra(&body, &request.extra)?;
+    body.extra = request.extra;
+    Ok(body)
+}
+
+fn convert_anthropic_response(anthropic_response: AnthropicResponse) -> CompletionResponse {
+    // Extract text content and tool calls
+    let mut text_parts = Vec::new();
+    let mut tool_calls = Vec::new();
+    let mut thinking_parts = Vec::new();
+    let mut signature = None;
+
+    for content in anthropic_response.content {
+        match content.content_type.as_str() {
+            "text" => {
+                if let Some(text) = content.text {
+                    text_parts.push(text);
+                }
+            }
+            "thinking" => {
+                if let Some(thinking) = content.thinking {
+                    thinking_parts.push(thinking);
+                }
+                if content.signature.is_some() {
+                    signature = content.signature;
+                }
+            }
+            "tool_use" => {
+                if let (Some(id), Some(name), Some(input)) =
+                    (content.id, content.name, content.input)
+                {
+                    tool_calls.push(ToolCall {
+                        id,
+                        r#type: ToolType::Function,
+                        function: FunctionCall {
+                            name,
+                            arguments: serde_json::to_string(&input).unwrap_or_default(),
+                        },
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let message_content = if text_parts.is_empty() {
+        MessageContent::Text("".to_string())
+    } else {
+        MessageContent::Text(text_parts.join(""))
+    };
+
+    CompletionResponse {
+        id: anthropic_response.id,
+        model: anthropic_response.model,
+        choices: vec![Choice {
+            index: 0,
+            message: Message {
+                tool_calls: if tool_calls.is_empty() {
+                    None
+                } else {
+                    Some(tool_calls)
+                },
+                reasoning_content: if thinking_parts.is_empty() {
+                    None
+                } else {
+                    Some(thinking_parts.join(""))
+                },
+                reasoning_signature: signature,
+                ..Message::new(
+                    anthropic_response.role.parse().unwrap_or(Role::Assistant),
+                    message_content,
+                )
+            },
+            finish_reason: Some(
+                anthropic_response
+                    .stop_reason
+                    .unwrap_or_else(|| "stop".to_string()),
+            ),
+            logprobs: None,
+        }],
+        usage: Some(Usage {
+            prompt_tokens: anthropic_response.usage.input_tokens,
+            completion_tokens: anthropic_response.usage.output_tokens,
+            total_tokens: anthropic_response.usage.input_tokens
+                + anthropic_response.usage.output_tokens,
+            reasoning_tokens: None,
+        }),
+        metadata: None,
+    }
 }
 
 fn convert_message_to_anthropic(msg: Message) -> AnthropicMessage {
-    let content = match msg.content {
+    if msg.role == Role::Tool {
+        if let Some(tool_use_id) = msg.tool_call_id.clone() {
+            return convert_tool_result_to_anthropic(tool_use_id, msg.content);
+        }
+    }
+
+    let mut content = match msg.content {
         MessageContent::Text(text) => AnthropicMessageContent::Text(text),
         MessageContent::Parts(parts) => AnthropicMessageContent::Parts(
             parts
@@ -373,6 +652,8 @@ fn convert_message_to_anthropic(msg: Message) -> AnthropicMessage {
                         content_type: "text".to_string(),
                         text: Some(text),
                         source: None,
+                        title: None,
+                        ..Default::default()
                     },
                     ContentPart::Image { image_url } => {
                         // Anthropic expects base64 images
@@ -386,6 +667,8 @@ fn convert_message_to_anthropic(msg: Message) -> AnthropicMessage {
                                         media_type: media_type.to_string(),
                                         data: data.to_string(),
                                     }),
+                                    title: None,
+                                    ..Default::default()
                                 }
                             } else {
                                 // Fallback to text if not base64
@@ -393,6 +676,8 @@ fn convert_message_to_anthropic(msg: Message) -> AnthropicMessage {
                                     content_type: "text".to_string(),
                                     text: Some(format!("[Image: {}]", image_url.url)),
                                     source: None,
+                                    title: None,
+                                    ..Default::default()
                                 }
                             }
                         } else {
@@ -401,25 +686,148 @@ fn convert_message_to_anthropic(msg: Message) -> AnthropicMessage {
                                 content_type: "text".to_string(),
                                 text: Some(format!("[Image: {}]", image_url.url)),
                                 source: None,
+                                title: None,
+                                ..Default::default()
                             }
                         }
                     }
+                    ContentPart::Document {
+                        data,
+                        mime_type,
+                        name,
+                    } => AnthropicContentPart {
+                        content_type: "document".to_string(),
+                        text: None,
+                        source: Some(AnthropicImageSource {
+                            source_type: "base64".to_string(),
+                            media_type: mime_type,
+                            data,
+                        }),
+                        title: name,
+                        ..Default::default()
+                    },
+                    ContentPart::Json { value } => AnthropicContentPart::text(value.to_string()),
+                    // Anthropic has no audio input, so leave a marker in the text
+                    ContentPart::Audio { format, .. } => AnthropicContentPart {
+                        content_type: "text".to_string(),
+                        text: Some(format!("[Audio: {}]", format)),
+                        source: None,
+                        title: None,
+                        ..Default::default()
+                    },
                 })
                 .collect(),
         ),
     };
 
+    // Tool calls become tool_use blocks after any text
+    if let Some(tool_calls) = msg.tool_calls.filter(|calls| !calls.is_empty()) {
+        let mut parts = content.into_parts();
+        parts.extend(tool_calls.into_iter().map(|call| {
+            AnthropicContentPart {
+                content_type: "tool_use".to_string(),
+                id: Some(call.id),
+                name: Some(call.function.name),
+                input: Some(
+                    serde_json::from_str(&call.function.arguments)
+                        .unwrap_or_else(|_| Value::Object(Default::default())),
+                ),
+                ..Default::default()
+            }
+        }));
+        content = AnthropicMessageContent::Parts(parts);
+    }
+
+    // Signed thinking goes back first, as Anthropic requires when thinking
+    // is enabled on a turn that continues after tool use
+    if let (Role::Assistant, Some(thinking), Some(signature)) =
+        (&msg.role, msg.reasoning_content, msg.reasoning_signature)
+    {
+        let mut parts = content.into_parts();
+        parts.insert(
+            0,
+            AnthropicContentPart {
+                content_type: "thinking".to_string(),
+                thinking: Some(thinking),
+                signature: Some(signature),
+                ..Default::default()
+            },
+        );
+        content = AnthropicMessageContent::Parts(parts);
+    }
+
     AnthropicMessage {
         role: match msg.role {
-            Role::User => "user".to_string(),
-            Role::Assistant => "assistant".to_string(),
-            Role::System => "user".to_string(), // Anthropic doesn't have system role
-            Role::Tool => "user".to_string(),   // Tool results are sent as user messages
+            // Anthropic has no system or tool role; system prompts are split
+            // out beforehand and tool results are sent as user messages
+            Role::System | Role::Tool => Role::User.to_string(),
+            ref role => role.to_string(),
         },
         content,
     }
 }
 
+/// Anthropic requires user and assistant turns to alternate, so merge runs
+/// of same-role messages after conversion. Tool results are user messages
+/// here, so consecutive results end up as blocks of a single message.
+fn merge_consecutive_roles(messages: Vec<AnthropicMessage>) -> Vec<AnthropicMessage> {
+    let mut merged: Vec<AnthropicMessage> = Vec::with_capacity(messages.len());
+    for message in messages {
+        let Some(last) = merged.last_mut().filter(|last| last.role == message.role) else {
+            merged.push(message);
+            continue;
+        };
+        let content = std::mem::replace(&mut last.content, AnthropicMessageContent::Parts(vec![]));
+        last.content = match (content, message.content) {
+            (AnthropicMessageContent::Text(a), AnthropicMessageContent::Text(b)) => {
+                AnthropicMessageContent::Text(format!("{}\n\n{}", a, b))
+            }
+            (a, b) => {
+                let mut parts = a.into_parts();
+                parts.extend(b.into_parts());
+                AnthropicMessageContent::Parts(parts)
+            }
+        };
+    }
+    merged
+}
+
+impl AnthropicMessageContent {
+    fn into_parts(self) -> Vec<AnthropicContentPart> {
+        match self {
+            AnthropicMessageContent::Text(text) if text.is_empty() => Vec::new(),
+            AnthropicMessageContent::Text(text) => vec![AnthropicContentPart::text(text)],
+            AnthropicMessageContent::Parts(parts) => parts,
+        }
+    }
+}
+
+/// A tool result as a user message holding a tool_result block, whose
+/// content is an array of blocks. A JSON result becomes a single text block
+/// of the serialized value.
+fn convert_tool_result_to_anthropic(
+    tool_use_id: String,
+    content: MessageContent,
+) -> AnthropicMessage {
+    let blocks = match content.as_json() {
+        Some(value) => vec![AnthropicContentPart::text(value.to_string())],
+        None => match convert_message_to_anthropic(Message::new(Role::User, content)).content {
+            AnthropicMessageContent::Text(text) => vec![AnthropicContentPart::text(text)],
+            AnthropicMessageContent::Parts(parts) => parts,
+        },
+    };
+
+    AnthropicMessage {
+        role: "user".to_string(),
+        content: AnthropicMessageContent::Parts(vec![AnthropicContentPart {
+            content_type: "tool_result".to_string(),
+            tool_use_id: Some(tool_use_id),
+            content: Some(blocks),
+            ..Default::default()
+        }]),
+    }
+}
+
 fn extract_text_from_content(content: &MessageContent) -> String {
     match content {
         MessageContent::Text(s) => s.clone(),
@@ -427,6 +835,7 @@ fn extract_text_from_content(content: &MessageContent) -> String {
             .iter()
             .filter_map(|p| match p {
                 ContentPart::Text { text } => Some(text.clone()),
+                ContentPart::Json { value } => Some(value.to_string()),
                 _ => None,
             })
             .collect::<Vec<_>>()
@@ -458,74 +867,979 @@ fn split_system_message(messages: Vec<Message>) -> (Option<String>, Vec<Message>
     (system, other_messages)
 }
 
-fn parse_anthropic_sse(data: &str) -> Result<Option<StreamChunk>> {
-    for line in data.lines() {
-        if let Some(event_type) = line.strip_prefix("event: ") {
-
-            // Find the corresponding data line
-            if let Some(data_line) = data.lines().find(|l| l.starts_with("data: ")) {
-                let json_str = &data_line[6..];
-                if let Ok(json) = serde_json::from_str::<serde_json::Value>(json_str) {
-                    match event_type {
-                        "content_block_delta" => {
-                            if let Some(delta) = json.get("delta") {
-                                if let Some(text) = delta.get("text").and_then(|t| t.as_str()) {
-                                    return Ok(Some(StreamChunk {
-                                        id: "stream".to_string(),
-                                        choices: vec![StreamChoice {
-                                            index: 0,
-                                            delta: Delta {
-                                                role: None,
-                                                content: Some(text.to_string()),
-                                                tool_calls: None,
-                                            },
-                                            finish_reason: None,
-                                        }],
-                                        model: None,
-                                    }));
-                                }
-                            }
-                        }
-                        "content_block_start" => {
-                            if let Some(content_block) = json.get("content_block") {
-                                if content_block.get("type").and_then(|t| t.as_str())
-                                    == Some("tool_use")
-                                {
-                                    // Handle tool call start
-                                    if let (Some(id), Some(name)) = (
-                                        content_block.get("id").and_then(|i| i.as_str()),
-                                        content_block.get("name").and_then(|n| n.as_str()),
-                                    ) {
-                                        return Ok(Some(StreamChunk {
-                                            id: "stream".to_string(),
-                                            choices: vec![StreamChoice {
-                                                index: 0,
-                                                delta: Delta {
-                                                    role: None,
-                                                    content: None,
-                                                    tool_calls: Some(vec![ToolCallDelta {
-                                                        index: Some(0),
-                                                        id: Some(id.to_string()),
-                                                        r#type: Some(ToolType::Function),
-                                                        function: Some(crate::FunctionCallDelta {
-                                                            name: Some(name.to_string()),
-                                                            arguments: Some("".to_string()),
-                                                        }),
-                                                    }]),
-                                                },
-                                                finish_reason: None,
-                                            }],
-                                            model: None,
-                                        }));
-                                    }
-                                }
-                            }
-                        }
-                        _ => {}
+/// Identity of the message being streamed, filled in from `message_start`,
+/// the tool_use blocks whose input is still arriving, and the stop reason
+/// and token counts reported until `message_stop`
+struct AnthropicStreamState {
+    id: String,
+    model: String,
+    stop_reason: Option<String>,
+    input_tokens: u32,
+    output_tokens: u32,
+    /// The request's tools with their compiled parameter schemas, by name
+    tool_schemas: HashMap<String, (ToolFunction, CompiledSchema)>,
+    /// Open tool_use blocks, by content block index
+    tool_blocks: HashMap<u64, PendingToolUse>,
+    tool_calls: usize,
+}
+
+/// A tool_use block whose `input_json_delta`s are being buffered
+struct PendingToolUse {
+    id: String,
+    name: String,
+    input_json: String,
+}
+
+impl AnthropicStreamState {
+    fn new(model: String) -> Self {
+        Self {
+            id: format!("msg_{}", uuid::Uuid::new_v4().simple()),
+            model,
+            stop_reason: None,
+            input_tokens: 0,
+            output_tokens: 0,
+            tool_schemas: HashMap::new(),
+            tool_blocks: HashMap::new(),
+            tool_calls: 0,
+        }
+    }
+
+    /// Compile each tool's parameter schema once for the whole stream.
+    /// Fails if any tool's `parameters` is not a valid JSON schema.
+    fn with_tools(mut self, tools: &[Tool]) -> Result<Self> {
+        for tool in tools {
+            let function = &tool.function;
+            let compiled = CompiledSchema::new(&function.parameters).map_err(|e| match e {
+                AiError::ConfigurationError {
+                    message,
+                    suggestion,
+                    ..
+                } => AiError::ConfigurationError {
+                    field: format!("tools.{}.parameters", function.name),
+                    message,
+                    suggestion,
+                },
+                other => other,
+            })?;
+            self.tool_schemas
+                .insert(function.name.clone(), (function.clone(), compiled));
+        }
+        Ok(self)
+    }
+
+    /// Parse the buffered input of a finished tool_use block and check it
+    /// against the tool's schema, so only complete, valid arguments are
+    /// handed on
+    fn finish_tool_use(&mut self, block: PendingToolUse) -> Result<StreamChunk> {
+        let malformed = |message: String| AiError::MalformedResponse {
+            message,
+            raw_response: Some(block.input_json.clone()),
+        };
+
+        // A tool without parameters may stream no input at all
+        let input: Value = if block.input_json.trim().is_empty() {
+            Value::Object(Default::default())
+        } else {
+            serde_json::from_str(&block.input_json).map_err(|e| {
+                malformed(format!(
+                    "Invalid JSON input for tool '{}': {}",
+                    block.name, e
+                ))
+            })?
+        };
+
+        if let Some((function, compiled)) = self.tool_schemas.get(&block.name) {
+            match function.check_arguments(compiled, &input) {
+                Ok(()) => {}
+                Err(AiError::InvalidToolParameters { message, .. }) => {
+                    return Err(malformed(format!(
+                        "Input for tool '{}' does not match its schema: {}",
+                        block.name, message
+                    )));
+                }
+                Err(e) => return Err(e),
+            }
+        }
+
+        let index = self.tool_calls;
+        self.tool_calls += 1;
+        Ok(self.chunk(Delta {
+            role: None,
+            content: None,
+            tool_calls: Some(vec![ToolCallDelta {
+                index: Some(index as u32),
+                id: Some(block.id),
+                r#type: Some(ToolType::Function),
+                function: Some(crate::FunctionCallDelta {
+                    name: Some(block.name),
+                    arguments: Some(input.to_string()),
+                }),
+            }]),
+        }))
+    }
+
+    /// Token counts are cumulative, so later events replace earlier ones
+    fn record_usage(&mut self, usage: Option<&Value>) {
+        let count = |key: &str| {
+            usage
+                .and_then(|u| u.get(key))
+                .and_then(|t| t.as_u64())
+                .map(|t| t as u32)
+        };
+        if let Some(input_tokens) = count("input_tokens") {
+            self.input_tokens = input_tokens;
+        }
+        if let Some(output_tokens) = count("output_tokens") {
+            self.output_tokens = output_tokens;
+        }
+    }
+
+    /// Final chunk carrying the stop reason and usage of the whole message
+    fn finish(&mut self) -> StreamChunk {
+        let mut chunk = self.chunk(Delta {
+            role: None,
+            content: None,
+            tool_calls: None,
+        });
+        chunk.choices[0].finish_reason = Some(
+            self.stop_reason
+                .take()
+                .unwrap_or_else(|| "stop".to_string()),
+        );
+        chunk.usage = Some(Usage {
+            prompt_tokens: self.input_tokens,
+            completion_tokens: self.output_tokens,
+            total_tokens: self.input_tokens + self.output_tokens,
+            reasoning_tokens: None,
+        });
+        chunk
+    }
+
+    fn chunk(&self, delta: Delta) -> StreamChunk {
+        StreamChunk {
+            id: self.id.clone(),
+            choices: vec![StreamChoice {
+                index: 0,
+                delta,
+                finish_reason: None,
+            }],
+            model: Some(self.model.clone()),
+            usage: None,
+            metadata: None,
+        }
+    }
+}
+
+/// Convert one stream event; the event name falls back to the `type` field
+/// of its data
+fn parse_anthropic_event(
+    event: &SseEvent,
+    state: &mut AnthropicStreamState,
+) -> Result<Option<StreamChunk>> {
+    let Ok(json) = serde_json::from_str::<Value>(&event.data) else {
+        if event.event.as_deref() == Some("error") {
+            return Err(anthropic_stream_error(&event.data));
+        }
+        return Ok(None);
+    };
+    let event_type = match &event.event {
+        Some(event_type) => event_type.as_str(),
+        None => json
+            .get("type")
+            .and_then(|t| t.as_str())
+            .unwrap_or_default(),
+    };
+
+    match event_type {
+        "message_start" => {
+            if let Some(message) = json.get("message") {
+                if let Some(id) = message.get("id").and_then(|i| i.as_str()) {
+                    state.id = id.to_string();
+                }
+                if let Some(model) = message.get("model").and_then(|m| m.as_str()) {
+                    state.model = model.to_string();
+                }
+                state.record_usage(message.get("usage"));
+            }
+        }
+        "message_delta" => {
+            if let Some(stop_reason) = json
+                .get("delta")
+                .and_then(|d| d.get("stop_reason"))
+                .and_then(|r| r.as_str())
+            {
+                state.stop_reason = Some(stop_reason.to_string());
+            }
+            state.record_usage(json.get("usage"));
+        }
+        "message_stop" => return Ok(Some(state.finish())),
+        "content_block_delta" => {
+            let delta = json.get("delta");
+            if let Some(partial_json) = delta
+                .filter(|d| d.get("type").and_then(|t| t.as_str()) == Some("input_json_delta"))
+                .and_then(|d| d.get("partial_json"))
+                .and_then(|p| p.as_str())
+            {
+                let index = json.get("index").and_then(|i| i.as_u64()).unwrap_or(0);
+                if let Some(block) = state.tool_blocks.get_mut(&index) {
+                    block.input_json.push_str(partial_json);
+                }
+            } else if let Some(text) = delta.and_then(|d| d.get("text")).and_then(|t| t.as_str()) {
+                return Ok(Some(state.chunk(Delta {
+                    role: None,
+                    content: Some(text.to_string()),
+                    tool_calls: None,
+                })));
+            }
+        }
+        "content_block_start" => {
+            if let Some(content_block) = json.get("content_block") {
+                if content_block.get("type").and_then(|t| t.as_str()) == Some("tool_use") {
+                    // Arguments arrive as input_json_delta fragments; the
+                    // tool call is emitted once the block stops
+                    if let (Some(id), Some(name)) = (
+                        content_block.get("id").and_then(|i| i.as_str()),
+                        content_block.get("name").and_then(|n| n.as_str()),
+                    ) {
+                        let index = json.get("index").and_then(|i| i.as_u64()).unwrap_or(0);
+                        state.tool_blocks.insert(
+                            index,
+                            PendingToolUse {
+                                id: id.to_string(),
+                                name: name.to_string(),
+                                input_json: String::new(),
+                            },
+                        );
                     }
                 }
             }
         }
+        "content_block_stop" => {
+            let index = json.get("index").and_then(|i| i.as_u64()).unwrap_or(0);
+            if let Some(block) = state.tool_blocks.remove(&index) {
+                return state.finish_tool_use(block).map(Some);
+            }
+        }
+        "error" => return Err(anthropic_stream_error(&event.data)),
+        _ => {}
     }
     Ok(None)
 }
+
+/// An `error` event sent in place of the rest of the stream. It carries no
+/// status code, so `overloaded_error` and `api_error` are marked retryable
+/// from their type.
+fn anthropic_stream_error(data: &str) -> AiError {
+    let error_type = serde_json::from_str::<Value>(data)
+        .ok()
+        .and_then(|v| v["error"]["type"].as_str().map(str::to_string));
+    AiError::ProviderError {
+        provider: "anthropic".to_string(),
+        message: format!("Anthropic stream error: {}", data),
+        retryable: matches!(
+            error_type.as_deref(),
+            Some("overloaded_error" | "api_error")
+        ),
+        error_code: error_type,
+        status_code: None,
+        request_id: None,
+    }
+}
+
+#[derive(Deserialize)]
+struct AnthropicBatch {
+    id: String,
+    processing_status: String,
+    #[serde(default)]
+    request_counts: AnthropicBatchRequestCounts,
+    #[serde(default)]
+    results_url: Option<String>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct AnthropicBatchRequestCounts {
+    processing: usize,
+    succeeded: usize,
+    errored: usize,
+    canceled: usize,
+    expired: usize,
+}
+
+#[derive(Deserialize)]
+struct AnthropicBatchResultLine {
+    custom_id: String,
+    result: AnthropicBatchResult,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum AnthropicBatchResult {
+    Succeeded { message: AnthropicResponse },
+    Errored { error: Value },
+    Canceled,
+    Expired,
+}
+
+fn convert_anthropic_batch_status(batch: &AnthropicBatch) -> BatchStatus {
+    let counts = &batch.request_counts;
+    let state = match batch.processing_status.as_str() {
+        "ended" if counts.succeeded == 0 && counts.expired > 0 && counts.errored == 0 => {
+            BatchState::Expired
+        }
+        "ended" if counts.succeeded == 0 && counts.canceled > 0 && counts.errored == 0 => {
+            BatchState::Cancelled
+        }
+        "ended" => BatchState::Completed,
+        "canceling" => BatchState::Cancelling,
+        _ => BatchState::InProgress,
+    };
+
+    BatchStatus {
+        state,
+        total: counts.processing
+            + counts.succeeded
+            + counts.errored
+            + counts.canceled
+            + counts.expired,
+        succeeded: counts.succeeded,
+        failed: counts.errored + counts.canceled + counts.expired,
+    }
+}
+
+/// Map a results file back to submission order
+fn parse_anthropic_batch_results(
+    content: &str,
+    request_count: usize,
+) -> Vec<Result<CompletionResponse>> {
+    let mut results: Vec<Option<Result<CompletionResponse>>> =
+        (0..request_count).map(|_| None).collect();
+
+    for line in content.lines().filter(|l| !l.trim().is_empty()) {
+        let Ok(parsed) = serde_json::from_str::<AnthropicBatchResultLine>(line) else {
+            continue;
+        };
+        let index = parsed
+            .custom_id
+            .strip_prefix("request-")
+            .and_then(|i| i.parse::<usize>().ok());
+        let Some(slot) = index.and_then(|i| results.get_mut(i)) else {
+            continue;
+        };
+
+        let failure = |message: String| AiError::ProviderError {
+            provider: "anthropic".to_string(),
+            message,
+            error_code: None,
+            retryable: false,
+            status_code: None,
+            request_id: None,
+        };
+        *slot = Some(match parsed.result {
+            AnthropicBatchResult::Succeeded { message } => Ok(convert_anthropic_response(message)),
+            AnthropicBatchResult::Errored { error } => {
+                Err(failure(format!("Anthropic API error: {}", error)))
+            }
+            AnthropicBatchResult::Canceled => {
+                Err(failure("Batch request was canceled".to_string()))
+            }
+            AnthropicBatchResult::Expired => Err(failure("Batch request expired".to_string())),
+        });
+    }
+
+    results
+        .into_iter()
+        .enumerate()
+        .map(|(i, result)| {
+            result.unwrap_or_else(|| {
+                Err(AiError::ProviderError {
+                    provider: "anthropic".to_string(),
+                    message: format!("No batch result for request-{}", i),
+                    error_code: None,
+                    retryable: false,
+                    status_code: None,
+                    request_id: None,
+                })
+            })
+        })
+        .collect()
+}
+
+impl AnthropicProvider {
+    async fn send_batch_request(
+        &self,
+        builder: reqwest::RequestBuilder,
+    ) -> Result<reqwest::Response> {
+        let response = self
+            .versioned(builder)
+            .send_authenticated(self.auth.as_ref())
+            .await?;
+
+        let response = super::check_response(response, |error| {
+            anthropic_api_error(
+                "Anthropic Message Batches API",
+                error.status_code,
+                &error.body,
+                error.request_id,
+            )
+        })
+        .await?;
+        Ok(response)
+    }
+
+    async fn get_batch(&self, id: &str) -> Result<AnthropicBatch> {
+        let response = self
+            .send_batch_request(
+                self.client
+                    .get(format!("{}/messages/batches/{}", self.base_url, id)),
+            )
+            .await?;
+        Ok(response.json().await?)
+    }
+}
+
+#[async_trait]
+impl BatchProvider for AnthropicProvider {
+    async fn submit_batch(&self, requests: Vec<CompletionRequest>) -> Result<BatchHandle> {
+        if requests.is_empty() {
+            return Err(AiError::InvalidRequest {
+                message: "A batch needs at least one request".to_string(),
+                field: Some("requests".to_string()),
+                code: None,
+            });
+        }
+
+        let request_count = requests.len();
+        let mut entries = Vec::with_capacity(request_count);
+        for (i, request) in requests.into_iter().enumerate() {
+            request.validate()?;
+            if self.validate_models {
+                super::validate_model(self, &request.model)?;
+            }
+            if self.check_context_window {
+                super::check_context_window(&request)?;
+            }
+            let mut params = build_anthropic_request(request, false)?;
+            params.stream = None;
+            entries.push(serde_json::json!({
+                "custom_id": format!("request-{}", i),
+                "params": params,
+            }));
+        }
+
+        let response = self
+            .send_batch_request(
+                self.client
+                    .post(format!("{}/messages/batches", self.base_url))
+                    .json(&serde_json::json!({ "requests": entries })),
+            )
+            .await?;
+        let batch: AnthropicBatch = response.json().await?;
+
+        Ok(BatchHandle {
+            id: batch.id,
+            provider: "anthropic".to_string(),
+            request_count,
+        })
+    }
+
+    async fn poll(&self, handle: &BatchHandle) -> Result<BatchStatus> {
+        let batch = self.get_batch(&handle.id).await?;
+        Ok(convert_anthropic_batch_status(&batch))
+    }
+
+    async fn results(&self, handle: &BatchHandle) -> Result<Vec<Result<CompletionResponse>>> {
+        let batch = self.get_batch(&handle.id).await?;
+        let Some(results_url) = batch.results_url else {
+            return Err(AiError::InvalidRequest {
+                message: format!(
+                    "Batch {} has not finished ({})",
+                    handle.id, batch.processing_status
+                ),
+                field: None,
+                code: Some("batch_not_finished".to_string()),
+            });
+        };
+
+        let content = self
+            .send_batch_request(self.client.get(results_url))
+            .await?
+            .text()
+            .await?;
+        Ok(parse_anthropic_batch_results(
+            &content,
+            handle.request_count,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request() -> CompletionRequest {
+        CompletionRequest::new("claude-3-7-sonnet-20250219", vec![Message::user("Hello")])
+    }
+
+    #[test]
+    fn test_trailing_assistant_message_is_sent_as_prefill() {
+        let mut req = request();
+        req.messages.push(Message::assistant("{\"answer\": "));
+        let body = serde_json::to_value(build_anthropic_request(req, false).unwrap()).unwrap();
+
+        let messages = body["messages"].as_array().unwrap();
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[1]["role"], "assistant");
+        // Trailing whitespace is rejected by the API, so it is trimmed
+        assert_eq!(messages[1]["content"], "{\"answer\":");
+
+        let mut req = request();
+        req.messages.push(Message::assistant("Sure"));
+        req.thinking_budget_tokens = Some(2048);
+        assert!(matches!(
+            build_anthropic_request(req, false),
+            Err(AiError::InvalidRequest { .. })
+        ));
+    }
+
+    #[test]
+    fn test_thinking_budget_serialization() {
+        let mut req = request();
+        req.thinking_budget_tokens = Some(2048);
+        let body = serde_json::to_value(build_anthropic_request(req, false).unwrap()).unwrap();
+        assert_eq!(body["thinking"]["type"], "enabled");
+        assert_eq!(body["thinking"]["budget_tokens"], 2048);
+        assert!(body["max_tokens"].as_u64().unwrap() > 2048);
+
+        let body =
+            serde_json::to_value(build_anthropic_request(request(), false).unwrap()).unwrap();
+        assert!(body.get("thinking").is_none());
+        assert_eq!(body["max_tokens"], 1024);
+    }
+
+    #[test]
+    fn test_consecutive_user_messages_are_merged() {
+        let mut req = request();
+        req.messages = vec![
+            Message::user("First question"),
+            Message::user("Second question"),
+            Message::assistant("Answer"),
+            Message::user("Follow-up"),
+        ];
+        let body = serde_json::to_value(build_anthropic_request(req, false).unwrap()).unwrap();
+        let messages = body["messages"].as_array().unwrap();
+        assert_eq!(messages.len(), 3);
+        let roles: Vec<_> = messages
+            .iter()
+            .map(|m| m["role"].as_str().unwrap())
+            .collect();
+        assert_eq!(roles, ["user", "assistant", "user"]);
+        assert_eq!(messages[0]["content"], "First question\n\nSecond question");
+    }
+
+    #[test]
+    fn test_consecutive_tool_results_share_one_message() {
+        let call = |id: &str| ToolCall {
+            id: id.to_string(),
+            r#type: ToolType::Function,
+            function: FunctionCall {
+                name: "lookup".to_string(),
+                arguments: "{}".to_string(),
+            },
+        };
+        let mut req = request();
+        req.messages = vec![
+            Message::user("Look up both"),
+            Message {
+                tool_calls: Some(vec![call("call_1"), call("call_2")]),
+                ..Message::assistant("")
+            },
+            Message::tool("call_1", "first"),
+            Message::tool("call_2", "second"),
+            Message::user("Now compare them"),
+        ];
+        let body = serde_json::to_value(build_anthropic_request(req, false).unwrap()).unwrap();
+        let messages = body["messages"].as_array().unwrap();
+        assert_eq!(messages.len(), 3);
+
+        let blocks = messages[2]["content"].as_array().unwrap();
+        assert_eq!(messages[2]["role"], "user");
+        assert_eq!(blocks.len(), 3);
+        assert_eq!(blocks[0]["type"], "tool_result");
+        assert_eq!(blocks[0]["tool_use_id"], "call_1");
+        assert_eq!(blocks[1]["tool_use_id"], "call_2");
+        assert_eq!(blocks[2]["type"], "text");
+        assert_eq!(blocks[2]["text"], "Now compare them");
+    }
+
+    #[test]
+    fn test_thinking_blocks_populate_reasoning_content() {
+        let raw = serde_json::json!({
+            "id": "msg_1",
+            "model": "claude-3-7-sonnet-20250219",
+            "role": "assistant",
+            "content": [
+                {"type": "thinking", "thinking": "Let me add the numbers.", "signature": "sig"},
+                {"type": "text", "text": "The answer is 4."}
+            ],
+            "usage": {"input_tokens": 12, "output_tokens": 40}
+        });
+        let resp: AnthropicResponse = serde_json::from_value(raw).unwrap();
+        let response = convert_anthropic_response(resp);
+        let message = &response.choices[0].message;
+        assert_eq!(message.content.as_text(), Some("The answer is 4."));
+        assert_eq!(
+            message.reasoning_content.as_deref(),
+            Some("Let me add the numbers.")
+        );
+        assert_eq!(message.reasoning_signature.as_deref(), Some("sig"));
+
+        // The signed thinking is sent back ahead of the reply
+        let body = serde_json::to_value(convert_message_to_anthropic(message.clone())).unwrap();
+        let blocks = body["content"].as_array().unwrap();
+        assert_eq!(blocks.len(), 2);
+        assert_eq!(blocks[0]["type"], "thinking");
+        assert_eq!(blocks[0]["thinking"], "Let me add the numbers.");
+        assert_eq!(blocks[0]["signature"], "sig");
+        assert_eq!(blocks[1]["type"], "text");
+        assert_eq!(blocks[1]["text"], "The answer is 4.");
+    }
+
+    #[test]
+    fn test_document_part_conversion() {
+        let message = convert_message_to_anthropic(Message::new(
+            Role::User,
+            MessageContent::Parts(vec![
+                ContentPart::Text {
+                    text: "Summarize these".to_string(),
+                },
+                ContentPart::Audio {
+                    data: "UklGRg==".to_string(),
+                    format: "wav".to_string(),
+                },
+                ContentPart::Document {
+                    data: "JVBERi0=".to_string(),
+                    mime_type: "application/pdf".to_string(),
+                    name: Some("report.pdf".to_string()),
+                },
+            ]),
+        ));
+
+        let body = serde_json::to_value(message).unwrap();
+        let parts = body["content"].as_array().unwrap();
+        assert_eq!(parts[1]["type"], "text");
+        assert_eq!(parts[2]["type"], "document");
+        assert_eq!(parts[2]["source"]["type"], "base64");
+        assert_eq!(parts[2]["source"]["media_type"], "application/pdf");
+        assert_eq!(parts[2]["title"], "report.pdf");
+    }
+
+    #[test]
+    fn test_system_field_becomes_system_prompt() {
+        let mut req = request();
+        req.system = Some("You are terse.".to_string());
+        let body = serde_json::to_value(build_anthropic_request(req, false).unwrap()).unwrap();
+        assert_eq!(body["system"], "You are terse.");
+        assert_eq!(body["messages"].as_array().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_custom_api_version_header() {
+        let provider = AnthropicProvider::new("key".to_string());
+        assert_eq!(provider.api_version(), DEFAULT_API_VERSION);
+
+        let provider = AnthropicProvider::with_version("key".to_string(), "2023-06-01");
+        let request = provider.messages_request().build().unwrap();
+        assert_eq!(request.headers()["anthropic-version"], "2023-06-01");
+    }
+
+    /// Feed raw SSE text through the parser the stream uses
+    fn parse_sse(text: &str, state: &mut AnthropicStreamState) -> Result<Vec<StreamChunk>> {
+        let mut chunks = Vec::new();
+        for event in crate::providers::SseParser::new().push(text.as_bytes()) {
+            chunks.extend(parse_anthropic_event(&event, state)?);
+        }
+        Ok(chunks)
+    }
+
+    #[test]
+    fn test_api_error_classification() {
+        let overloaded =
+            r#"{"type":"error","error":{"type":"overloaded_error","message":"Overloaded"}}"#;
+        let error = anthropic_api_error("Anthropic API", 529, overloaded, None);
+        assert!(error.is_retryable());
+        assert!(matches!(
+            error,
+            AiError::ProviderError { error_code: Some(ref code), status_code: Some(529), .. }
+                if code == "overloaded_error"
+        ));
+
+        let no_credit = r#"{"type":"error","error":{"type":"invalid_request_error","message":"Your credit balance is too low to access the Anthropic API."}}"#;
+        let error = anthropic_api_error("Anthropic API", 400, no_credit, Some("req_1".into()));
+        assert!(matches!(error, AiError::QuotaExceeded { .. }));
+        assert!(error.to_string().contains("credit balance is too low"));
+        assert_eq!(error.request_id(), Some("req_1"));
+        assert!(!error.is_retryable());
+
+        let invalid = r#"{"type":"error","error":{"type":"invalid_request_error","message":"max_tokens: field required"}}"#;
+        assert!(!anthropic_api_error("Anthropic API", 400, invalid, None).is_retryable());
+        let rate_limited =
+            r#"{"type":"error","error":{"type":"rate_limit_error","message":"slow down"}}"#;
+        assert!(anthropic_api_error("Anthropic API", 429, rate_limited, None).is_retryable());
+    }
+
+    #[test]
+    fn test_stream_error_event_is_an_error() {
+        let mut state = AnthropicStreamState::new("claude-3-5-sonnet-latest".to_string());
+        let overloaded = concat!(
+            "event: error\n",
+            "data: {\"type\":\"error\",\"error\":{\"type\":\"overloaded_error\",\"message\":\"Overloaded\"}}\n\n",
+        );
+        let error = parse_sse(overloaded, &mut state).unwrap_err();
+        assert!(error.is_transient());
+        assert!(matches!(
+            error,
+            AiError::ProviderError { error_code: Some(ref code), .. } if code == "overloaded_error"
+        ));
+
+        let invalid = concat!(
+            "event: error\n",
+            "data: {\"type\":\"error\",\"error\":{\"type\":\"invalid_request_error\",\"message\":\"bad\"}}\n\n",
+        );
+        assert!(!parse_sse(invalid, &mut state).unwrap_err().is_transient());
+        assert!(parse_sse("event: error\ndata: upstream reset\n\n", &mut state).is_err());
+    }
+
+    #[test]
+    fn test_stream_chunks_carry_message_id_and_model() {
+        let mut state = AnthropicStreamState::new("claude-3-5-sonnet-latest".to_string());
+        let start = concat!(
+            "event: message_start\n",
+            "data: {\"type\":\"message_start\",\"message\":{\"id\":\"msg_abc\",\"model\":\"claude-3-5-sonnet-20241022\"}}\n\n",
+            "event: content_block_delta\n",
+            "data: {\"type\":\"content_block_delta\",\"delta\":{\"type\":\"text_delta\",\"text\":\"Hi\"}}\n\n",
+        );
+        let first = parse_sse(start, &mut state).unwrap().pop().unwrap();
+
+        let delta = concat!(
+            "event: content_block_delta\n",
+            "data: {\"type\":\"content_block_delta\",\"delta\":{\"type\":\"text_delta\",\"text\":\" there\"}}\n\n",
+        );
+        let second = parse_sse(delta, &mut state).unwrap().pop().unwrap();
+
+        for chunk in [&first, &second] {
+            assert_eq!(chunk.id, "msg_abc");
+            assert_eq!(chunk.model.as_deref(), Some("claude-3-5-sonnet-20241022"));
+        }
+        assert_eq!(first.choices[0].delta.content.as_deref(), Some("Hi"));
+        assert_eq!(second.choices[0].delta.content.as_deref(), Some(" there"));
+    }
+
+    #[test]
+    fn test_stream_ends_with_stop_reason_and_usage() {
+        let mut state = AnthropicStreamState::new("claude-3-5-sonnet-latest".to_string());
+        let stream = concat!(
+            "event: message_start\n",
+            "data: {\"type\":\"message_start\",\"message\":{\"id\":\"msg_abc\",\"type\":\"message\",\"role\":\"assistant\",\"content\":[],\"model\":\"claude-3-5-sonnet-20241022\",\"stop_reason\":null,\"stop_sequence\":null,\"usage\":{\"input_tokens\":25,\"output_tokens\":1}}}\n\n",
+            "event: content_block_start\n",
+            "data: {\"type\":\"content_block_start\",\"index\":0,\"content_block\":{\"type\":\"text\",\"text\":\"\"}}\n\n",
+            "event: ping\n",
+            "data: {\"type\":\"ping\"}\n\n",
+            "event: content_block_delta\n",
+            "data: {\"type\":\"content_block_delta\",\"index\":0,\"delta\":{\"type\":\"text_delta\",\"text\":\"Hello\"}}\n\n",
+            "event: content_block_stop\n",
+            "data: {\"type\":\"content_block_stop\",\"index\":0}\n\n",
+            "event: message_delta\n",
+            "data: {\"type\":\"message_delta\",\"delta\":{\"stop_reason\":\"max_tokens\",\"stop_sequence\":null},\"usage\":{\"output_tokens\":15}}\n\n",
+            "event: message_stop\n",
+            "data: {\"type\":\"message_stop\"}\n\n",
+        );
+        let chunks = parse_sse(stream, &mut state).unwrap();
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(chunks[0].choices[0].delta.content.as_deref(), Some("Hello"));
+        assert!(chunks[0].usage.is_none());
+
+        let last = &chunks[1];
+        assert_eq!(last.id, "msg_abc");
+        assert_eq!(last.choices[0].finish_reason.as_deref(), Some("max_tokens"));
+        let usage = last.usage.as_ref().unwrap();
+        assert_eq!(usage.prompt_tokens, 25);
+        assert_eq!(usage.completion_tokens, 15);
+        assert_eq!(usage.total_tokens, 40);
+    }
+
+    /// Stream a calculator tool_use block whose input is split into
+    /// `partial_json` fragments, one SSE event per read
+    fn tool_use_stream(
+        state: &mut AnthropicStreamState,
+        partial_json: &[&str],
+    ) -> Result<Vec<StreamChunk>> {
+        let mut events = vec![
+            r#"{"type":"content_block_start","index":1,"content_block":{"type":"tool_use","id":"toolu_1","name":"calculator","input":{}}}"#.to_string(),
+        ];
+        for fragment in partial_json {
+            events.push(
+                serde_json::json!({
+                    "type": "content_block_delta",
+                    "index": 1,
+                    "delta": {"type": "input_json_delta", "partial_json": fragment}
+                })
+                .to_string(),
+            );
+        }
+        events.push(r#"{"type":"content_block_stop","index":1}"#.to_string());
+
+        let mut chunks = Vec::new();
+        for event in events {
+            let event_type = serde_json::from_str::<Value>(&event).unwrap()["type"]
+                .as_str()
+                .unwrap()
+                .to_string();
+            let sse = format!("event: {}\ndata: {}\n\n", event_type, event);
+            chunks.extend(parse_sse(&sse, state)?);
+        }
+        Ok(chunks)
+    }
+
+    #[tokio::test]
+    async fn test_split_tool_use_input_is_buffered_until_block_stop() {
+        use crate::agent::tools::{CalculatorTool, ToolExecutor, ToolResult};
+
+        let tools = [Tool {
+            r#type: ToolType::Function,
+            function: CalculatorTool.definition(),
+        }];
+        let mut state = AnthropicStreamState::new("claude".to_string())
+            .with_tools(&tools)
+            .unwrap();
+        let chunks = tool_use_stream(
+            &mut state,
+            &[
+                r#"{"operat"#,
+                r#"ion": "add", "#,
+                r#""a": 2, "b""#,
+                r#": 3}"#,
+            ],
+        )
+        .unwrap();
+
+        // Nothing is emitted for the fragments, only the finished call
+        assert_eq!(chunks.len(), 1);
+        let call = &chunks[0].choices[0].delta.tool_calls.as_ref().unwrap()[0];
+        assert_eq!(call.index, Some(0));
+        assert_eq!(call.id.as_deref(), Some("toolu_1"));
+        let function = call.function.as_ref().unwrap();
+        assert_eq!(function.name.as_deref(), Some("calculator"));
+
+        let arguments = function.arguments.as_deref().unwrap();
+        match CalculatorTool.execute(arguments).await.unwrap() {
+            ToolResult::Success(value) => assert_eq!(value["result"], 5.0),
+            ToolResult::Error(e) => panic!("tool failed: {}", e),
+        }
+    }
+
+    #[test]
+    fn test_tool_use_input_violating_schema_is_an_error() {
+        use crate::agent::tools::{CalculatorTool, ToolExecutor};
+
+        let tools = [Tool {
+            r#type: ToolType::Function,
+            function: CalculatorTool.definition(),
+        }];
+        let mut state = AnthropicStreamState::new("claude".to_string())
+            .with_tools(&tools)
+            .unwrap();
+        let err =
+            tool_use_stream(&mut state, &[r#"{"operation": "add", "#, r#""a": 2}"#]).unwrap_err();
+        assert!(
+            matches!(&err, AiError::MalformedResponse { message, raw_response: Some(raw) }
+                if message.contains("calculator") && raw.contains("\"a\": 2"))
+        );
+
+        let mut state = AnthropicStreamState::new("claude".to_string())
+            .with_tools(&tools)
+            .unwrap();
+        let err = tool_use_stream(&mut state, &[r#"{"operation": "#]).unwrap_err();
+        assert!(matches!(err, AiError::MalformedResponse { .. }));
+    }
+
+    #[test]
+    fn test_invalid_tool_schema_is_reported_before_streaming() {
+        let tools = [Tool {
+            r#type: ToolType::Function,
+            function: ToolFunction {
+                name: "broken".to_string(),
+                description: None,
+                parameters: serde_json::json!({"type": "not-a-type"}),
+            },
+        }];
+        let err = AnthropicStreamState::new("claude".to_string())
+            .with_tools(&tools)
+            .err()
+            .unwrap();
+        assert!(
+            matches!(&err, AiError::ConfigurationError { field, .. } if field == "tools.broken.parameters")
+        );
+    }
+
+    #[test]
+    fn test_batch_status_transitions() {
+        let status = |json: serde_json::Value| {
+            convert_anthropic_batch_status(&serde_json::from_value(json).unwrap())
+        };
+
+        let running = status(serde_json::json!({
+            "id": "msgbatch_1",
+            "processing_status": "in_progress",
+            "request_counts": {"processing": 3, "succeeded": 0, "errored": 0, "canceled": 0, "expired": 0}
+        }));
+        assert_eq!(running.state, BatchState::InProgress);
+        assert_eq!(running.total, 3);
+        assert!(!running.is_finished());
+
+        let ended = status(serde_json::json!({
+            "id": "msgbatch_1",
+            "processing_status": "ended",
+            "request_counts": {"processing": 0, "succeeded": 2, "errored": 1, "canceled": 0, "expired": 0},
+            "results_url": "https://api.anthropic.com/v1/messages/batches/msgbatch_1/results"
+        }));
+        assert_eq!(ended.state, BatchState::Completed);
+        assert_eq!((ended.succeeded, ended.failed), (2, 1));
+        assert!(ended.is_finished());
+
+        let expired = status(serde_json::json!({
+            "id": "msgbatch_1",
+            "processing_status": "ended",
+            "request_counts": {"expired": 3}
+        }));
+        assert_eq!(expired.state, BatchState::Expired);
+    }
+
+    #[test]
+    fn test_batch_results_in_submission_order() {
+        let content = concat!(
+            r#"{"custom_id": "request-2", "result": {"type": "expired"}}"#,
+            "\n",
+            r#"{"custom_id": "request-0", "result": {"type": "succeeded", "message": {"id": "msg_1", "model": "claude-3-5-haiku-20241022", "role": "assistant", "content": [{"type": "text", "text": "done"}], "usage": {"input_tokens": 5, "output_tokens": 1}}}}"#,
+            "\n",
+            r#"{"custom_id": "request-1", "result": {"type": "errored", "error": {"type": "error", "error": {"type": "invalid_request_error", "message": "bad"}}}}"#,
+            "\n"
+        );
+
+        let results = parse_anthropic_batch_results(content, 4);
+        assert_eq!(results.len(), 4);
+        assert_eq!(
+            results[0].as_ref().unwrap().choices[0]
+                .message
+                .content
+                .as_text(),
+            Some("done")
+        );
+        assert!(
+            matches!(&results[1], Err(AiError::ProviderError { message, .. }) if message.contains("bad"))
+        );
+        assert!(results[2].is_err());
+        // No line for the last request
+        assert!(results[3].is_err());
+    }
+}
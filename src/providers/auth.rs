@@ -0,0 +1,174 @@
+use reqwest::header::{HeaderName, HeaderValue, AUTHORIZATION};
+use reqwest::{Request, RequestBuilder, Response};
+use std::sync::Arc;
+
+use crate::{AiError, Result};
+
+/// Adds credentials to every request a provider sends. Providers apply it
+/// to the fully built request, so schemes that sign the URL or body (such as
+/// AWS SigV4) can be implemented as well as plain headers.
+///
+/// Providers default to their API's usual scheme; replace it with
+/// `with_authenticator` to go through a proxy or gateway with its own auth.
+pub trait Authenticator: Send + Sync {
+    fn authenticate(&self, request: &mut Request) -> Result<()>;
+}
+
+impl<A: Authenticator + ?Sized> Authenticator for Arc<A> {
+    fn authenticate(&self, request: &mut Request) -> Result<()> {
+        (**self).authenticate(request)
+    }
+}
+
+/// An API key sent as a header or query parameter
+#[derive(Clone)]
+pub enum ApiKeyAuth {
+    /// `Authorization: {scheme} {key}`
+    Scheme { scheme: String, key: String },
+    /// The key as the whole value of a header, e.g. `X-API-Key`
+    Header { name: String, key: String },
+    /// The key as a query parameter, e.g. `?key=`
+    Query { name: String, key: String },
+}
+
+impl ApiKeyAuth {
+    /// `Authorization: Bearer {key}`
+    pub fn bearer(key: impl Into<String>) -> Self {
+        Self::Scheme {
+            scheme: "Bearer".to_string(),
+            key: key.into(),
+        }
+    }
+
+    /// `Authorization: Token {key}`
+    pub fn token(key: impl Into<String>) -> Self {
+        Self::Scheme {
+            scheme: "Token".to_string(),
+            key: key.into(),
+        }
+    }
+
+    pub fn header(name: impl Into<String>, key: impl Into<String>) -> Self {
+        Self::Header {
+            name: name.into(),
+            key: key.into(),
+        }
+    }
+
+    pub fn query(name: impl Into<String>, key: impl Into<String>) -> Self {
+        Self::Query {
+            name: name.into(),
+            key: key.into(),
+        }
+    }
+}
+
+impl Authenticator for ApiKeyAuth {
+    fn authenticate(&self, request: &mut Request) -> Result<()> {
+        match self {
+            ApiKeyAuth::Scheme { scheme, key } => {
+                let value = header_value(&format!("{} {}", scheme, key))?;
+                request.headers_mut().insert(AUTHORIZATION, value);
+            }
+            ApiKeyAuth::Header { name, key } => {
+                let name = HeaderName::from_bytes(name.as_bytes())
+                    .map_err(|e| invalid(format!("{}: {}", name, e)))?;
+                request.headers_mut().insert(name, header_value(key)?);
+            }
+            ApiKeyAuth::Query { name, key } => {
+                request.url_mut().query_pairs_mut().append_pair(name, key);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Sends requests as they are, for servers without auth such as a local
+/// Ollama
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoAuth;
+
+impl Authenticator for NoAuth {
+    fn authenticate(&self, _request: &mut Request) -> Result<()> {
+        Ok(())
+    }
+}
+
+fn invalid(message: String) -> AiError {
+    AiError::ConfigurationError {
+        field: "authenticator".to_string(),
+        message,
+        suggestion: None,
+    }
+}
+
+/// Header value marked sensitive, so it is left out of debug output
+fn header_value(value: &str) -> Result<HeaderValue> {
+    let mut value = HeaderValue::from_str(value)
+        .map_err(|e| invalid(format!("API key is not a valid header value: {}", e)))?;
+    value.set_sensitive(true);
+    Ok(value)
+}
+
+/// Send a request after applying an [`Authenticator`]
+pub(crate) trait SendAuthenticated {
+    async fn send_authenticated(self, auth: &dyn Authenticator) -> Result<Response>;
+}
+
+impl SendAuthenticated for RequestBuilder {
+    async fn send_authenticated(self, auth: &dyn Authenticator) -> Result<Response> {
+        let (client, request) = self.build_split();
+        let mut request = request?;
+        auth.authenticate(&mut request)?;
+        Ok(client.execute(request).await?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(url: &str) -> Request {
+        reqwest::Client::new().get(url).build().unwrap()
+    }
+
+    #[test]
+    fn test_scheme_sets_authorization() {
+        let mut req = request("https://api.example.com/v1/chat");
+        ApiKeyAuth::bearer("sk-test")
+            .authenticate(&mut req)
+            .unwrap();
+        let value = &req.headers()[AUTHORIZATION];
+        assert_eq!(value, "Bearer sk-test");
+        assert!(value.is_sensitive());
+
+        let mut req = request("https://api.example.com/v1/chat");
+        ApiKeyAuth::token("r8-test").authenticate(&mut req).unwrap();
+        assert_eq!(req.headers()[AUTHORIZATION], "Token r8-test");
+    }
+
+    #[test]
+    fn test_header_sets_named_header() {
+        let mut req = request("https://api.example.com/v1/messages");
+        ApiKeyAuth::header("x-api-key", "sk-ant-test")
+            .authenticate(&mut req)
+            .unwrap();
+        assert_eq!(req.headers()["x-api-key"], "sk-ant-test");
+        assert!(req.headers()["x-api-key"].is_sensitive());
+        assert!(req.headers().get(AUTHORIZATION).is_none());
+
+        let mut req = request("https://api.example.com/v1/messages");
+        let result = ApiKeyAuth::header("bad header", "key").authenticate(&mut req);
+        assert!(matches!(result, Err(AiError::ConfigurationError { .. })));
+    }
+
+    #[test]
+    fn test_query_appends_parameter() {
+        let mut req = request("https://api.example.com/v1/models?alt=sse");
+        ApiKeyAuth::query("key", "AIza test")
+            .authenticate(&mut req)
+            .unwrap();
+        assert_eq!(req.url().query(), Some("alt=sse&key=AIza+test"));
+        assert!(req.headers().is_empty());
+    }
+}
@@ -1,19 +1,31 @@
 use async_trait::async_trait;
-use futures::stream::{Stream, StreamExt};
+use futures::stream::{Stream, StreamExt, TryStreamExt};
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
 use std::env;
 use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
 
 use crate::{
-    AiError, Choice, CompletionProvider, CompletionRequest, CompletionResponse, Message,
-    MessageContent, Result, Role, StreamChunk, Usage,
+    providers::{auth::SendAuthenticated, ApiKeyAuth, Authenticator, ProviderConfig},
+    AiError, Choice, CompletionProvider, CompletionRequest, CompletionResponse, FunctionCall,
+    Message, MessageContent, ProviderCapabilities, Result, Role, StreamChunk, Tool, ToolCall,
+    ToolType, Usage,
 };
 
 /// Cohere provider for their AI models
 pub struct CohereProvider {
     client: Client,
-    api_key: String,
+    /// Set by `with_client`, so `with_config` keeps the injected client
+    custom_client: bool,
+    auth: Arc<dyn Authenticator>,
+    base_url: String,
+    stream_idle_timeout: Option<Duration>,
+    validate_models: bool,
+    check_context_window: bool,
 }
 
 impl CohereProvider {
@@ -22,19 +34,77 @@ impl CohereProvider {
     /// # Arguments
     /// * `api_key` - Optional API key. If not provided, will look for COHERE_API_KEY env var
     pub fn new(api_key: Option<String>) -> Result<Self> {
+        Self::with_base_url(api_key, "https://api.cohere.ai/v1".to_string())
+    }
+
+    /// Create a Cohere provider that sends requests to `base_url`, e.g. a
+    /// self-hosted gateway
+    pub fn with_base_url(api_key: Option<String>, base_url: String) -> Result<Self> {
         let api_key = api_key
             .or_else(|| env::var("COHERE_API_KEY").ok())
             .ok_or_else(|| AiError::MissingConfiguration {
                 field: "api_key".to_string(),
                 description: "Cohere API key not provided. Set COHERE_API_KEY environment variable or pass it explicitly".to_string(),
             })?;
+        super::validate_api_key("cohere", &api_key)?;
 
         Ok(Self {
-            client: Client::new(),
-            api_key,
+            client: super::config::default_client(),
+            custom_client: false,
+            auth: Arc::new(ApiKeyAuth::bearer(api_key)),
+            base_url,
+            stream_idle_timeout: None,
+            validate_models: false,
+            check_context_window: false,
         })
     }
 
+    /// Reject models missing from `available_models()` before sending,
+    /// instead of waiting for the API to refuse them
+    pub fn with_model_validation(mut self, enabled: bool) -> Self {
+        self.validate_models = enabled;
+        self
+    }
+
+    /// Reject a request whose estimated size exceeds the model's context
+    /// window before sending it (see [`context_window`]). Off by default:
+    /// the estimate is rough and could turn away requests the API accepts.
+    ///
+    /// [`context_window`]: crate::providers::context_window
+    pub fn with_context_window_check(mut self, enabled: bool) -> Self {
+        self.check_context_window = enabled;
+        self
+    }
+
+    /// Send every request with the user agent and default headers from
+    /// `config`. A client given to [`with_client`](Self::with_client),
+    /// before or after this call, is kept; the user agent and headers are
+    /// then up to that client.
+    pub fn with_config(mut self, config: &ProviderConfig) -> Result<Self> {
+        let client = config.build_client()?;
+        if !self.custom_client {
+            self.client = client;
+        }
+        self.stream_idle_timeout = config.stream_idle_timeout;
+        Ok(self)
+    }
+
+    /// Send requests through `client`, sharing its connection pool and DNS
+    /// cache with other providers. It takes precedence over the client
+    /// [`with_config`](Self::with_config) builds.
+    pub fn with_client(mut self, client: Client) -> Self {
+        self.client = client;
+        self.custom_client = true;
+        self
+    }
+
+    /// Authenticate requests with `auth` instead of the API key, e.g. for a
+    /// proxy or gateway with its own credentials
+    pub fn with_authenticator(mut self, auth: impl Authenticator + 'static) -> Self {
+        self.auth = Arc::new(auth);
+        self
+    }
+
     fn convert_role(&self, role: &Role) -> String {
         match role {
             Role::System => "SYSTEM".to_string(),
@@ -44,26 +114,144 @@ impl CohereProvider {
         }
     }
 
-    fn convert_message(&self, message: &Message) -> CohereChatMessage {
+    /// `calls` holds the tool calls made so far by id, so a tool result
+    /// can name the call it answers
+    fn convert_message(
+        &self,
+        message: &Message,
+        calls: &HashMap<&str, &ToolCall>,
+    ) -> CohereChatMessage {
         let message_text = match &message.content {
             MessageContent::Text(text) => text.clone(),
             MessageContent::Parts(parts) => parts
                 .iter()
                 .filter_map(|part| match part {
                     crate::ContentPart::Text { text } => Some(text.clone()),
+                    crate::ContentPart::Json { value } => Some(value.to_string()),
                     _ => None,
                 })
                 .collect::<Vec<_>>()
                 .join(" "),
         };
 
-        CohereChatMessage {
+        let mut converted = CohereChatMessage {
             role: self.convert_role(&message.role),
             message: message_text,
+            tool_calls: message
+                .tool_calls
+                .as_ref()
+                .map(|calls| calls.iter().map(CohereToolCall::from_call).collect()),
+            tool_results: None,
+        };
+
+        // Cohere takes tool output as objects attached to the call
+        let call = message.tool_call_id.as_deref().and_then(|id| calls.get(id));
+        if let (Role::Tool, Some(call)) = (&message.role, call) {
+            let output = match message.content.as_json() {
+                Some(Value::Object(object)) => Value::Object(object.clone()),
+                Some(value) => serde_json::json!({ "result": value }),
+                None => serde_json::json!({ "result": converted.message }),
+            };
+            converted.tool_results = Some(vec![CohereToolResult {
+                call: CohereToolCall::from_call(call),
+                outputs: vec![output],
+            }]);
+            converted.message = String::new();
+        }
+
+        converted
+    }
+
+    /// Build a v1 chat request. Results for the last turn's tool calls are
+    /// sent as `tool_results` with an empty `message`; otherwise the last
+    /// message must be from the user.
+    fn build_chat_request(
+        &self,
+        request: &CompletionRequest,
+        stream: bool,
+    ) -> Result<CohereChatRequest> {
+        // Extract system message as preamble
+        let mut preamble = None;
+        let mut history = Vec::new();
+        let mut calls = HashMap::new();
+
+        for msg in &request.messages {
+            for call in msg.tool_calls.iter().flatten() {
+                calls.insert(call.id.as_str(), call);
+            }
+            match msg.role {
+                Role::System => {
+                    if let MessageContent::Text(text) = &msg.content {
+                        preamble = Some(text.clone());
+                    }
+                }
+                _ => history.push(self.convert_message(msg, &calls)),
+            }
         }
+
+        let pending = history
+            .iter()
+            .rev()
+            .take_while(|m| m.tool_results.is_some())
+            .count();
+        let tool_results: Vec<CohereToolResult> = history
+            .split_off(history.len() - pending)
+            .into_iter()
+            .flat_map(|m| m.tool_results.unwrap_or_default())
+            .collect();
+
+        let message = if tool_results.is_empty() {
+            history
+                .pop()
+                .filter(|m| m.role == "USER")
+                .map(|m| m.message)
+                .ok_or_else(|| AiError::InvalidRequest {
+                    message: "Last message must be from user".to_string(),
+                    field: Some("messages".to_string()),
+                    code: None,
+                })?
+        } else {
+            String::new()
+        };
+
+        let mut body = CohereChatRequest {
+            message,
+            model: Some(request.model.clone()),
+            preamble,
+            chat_history: if history.is_empty() {
+                None
+            } else {
+                Some(history)
+            },
+            temperature: request.temperature,
+            max_tokens: request.max_tokens,
+            p: request.top_p,
+            stop_sequences: request.stop.clone(),
+            tools: request
+                .tools
+                .as_ref()
+                .map(|tools| tools.iter().map(convert_tool).collect()),
+            tool_results: if tool_results.is_empty() {
+                None
+            } else {
+                Some(tool_results)
+            },
+            stream,
+            extra: HashMap::new(),
+        };
+        super::check_extra(&body, &request.extra)?;
+        body.extra = request.extra.clone();
+        Ok(body)
     }
 
-    fn convert_to_standard_response(&self, response: CohereChatResponse) -> CompletionResponse {
+    /// `model` is the requested model, used when the response doesn't name
+    /// one. Cohere's upper-case finish reasons are kept as sent;
+    /// [`Choice::stop_reason`] maps them to the shared [`crate::FinishReason`].
+    fn convert_to_standard_response(
+        &self,
+        response: CohereChatResponse,
+        model: &str,
+    ) -> CompletionResponse {
         CompletionResponse {
             id: response
                 .response_id
@@ -71,16 +259,27 @@ impl CohereProvider {
             model: response
                 .generation_info
                 .map(|info| info.model)
-                .unwrap_or_else(|| "command".to_string()),
+                .unwrap_or_else(|| model.to_string()),
             choices: vec![Choice {
                 index: 0,
                 message: Message {
-                    role: Role::Assistant,
-                    content: MessageContent::text(response.text),
-                    tool_calls: None,
-                    tool_call_id: None,
+                    tool_calls: response.tool_calls.map(|calls| {
+                        calls
+                            .into_iter()
+                            .map(|call| ToolCall {
+                                id: uuid::Uuid::new_v4().to_string(),
+                                r#type: ToolType::Function,
+                                function: FunctionCall {
+                                    name: call.name,
+                                    arguments: call.parameters.to_string(),
+                                },
+                            })
+                            .collect()
+                    }),
+                    ..Message::assistant(response.text)
                 },
                 finish_reason: Some(response.finish_reason.unwrap_or_else(|| "stop".to_string())),
+                logprobs: None,
             }],
             usage: response.meta.map(|meta| Usage {
                 prompt_tokens: meta.billed_units.input_tokens.unwrap_or(0) as u32,
@@ -88,235 +287,125 @@ impl CohereProvider {
                 total_tokens: (meta.billed_units.input_tokens.unwrap_or(0)
                     + meta.billed_units.output_tokens.unwrap_or(0))
                     as u32,
+                reasoning_tokens: None,
             }),
+            metadata: None,
         }
     }
-}
 
-#[async_trait]
-impl CompletionProvider for CohereProvider {
-    async fn complete(&self, request: CompletionRequest) -> Result<CompletionResponse> {
-        let url = "https://api.cohere.ai/v1/chat";
-
-        // Extract system message as preamble
-        let (preamble, chat_history) = {
-            let mut preamble = None;
-            let mut history = Vec::new();
-
-            for msg in &request.messages {
-                match msg.role {
-                    Role::System => {
-                        preamble = Some(match &msg.content {
-                            MessageContent::Text(text) => text.clone(),
-                            MessageContent::Parts(_) => continue,
-                        });
-                    }
-                    _ => history.push(self.convert_message(msg)),
-                }
-            }
-
-            (preamble, history)
-        };
-
-        // The last message should be from the user
-        let message = chat_history
-            .last()
-            .filter(|m| m.role == "USER")
-            .map(|m| m.message.clone())
-            .ok_or_else(|| AiError::InvalidRequest {
-                message: "Last message must be from user".to_string(),
-                field: Some("messages".to_string()),
-                code: None,
-            })?;
-
-        // Remove the last message from history
-        let chat_history = chat_history[..chat_history.len() - 1].to_vec();
-
-        let cohere_request = CohereChatRequest {
-            message,
-            model: Some(request.model.clone()),
-            preamble,
-            chat_history: if chat_history.is_empty() {
-                None
-            } else {
-                Some(chat_history)
-            },
-            temperature: request.temperature,
-            max_tokens: request.max_tokens,
-            p: request.top_p,
-            stop_sequences: request.stop.clone(),
-            stream: false,
-        };
+    /// Send a single chat request
+    async fn complete_once(&self, mut request: CompletionRequest) -> Result<CompletionResponse> {
+        request.normalize_system();
+        request.validate()?;
+        if self.validate_models {
+            super::validate_model(self, &request.model)?;
+        }
+        if self.check_context_window {
+            super::check_context_window(&request)?;
+        }
+        let url = format!("{}/chat", self.base_url);
+        let cohere_request = self.build_chat_request(&request, false)?;
 
         let response = self
             .client
             .post(url)
-            .header("Authorization", format!("Bearer {}", self.api_key))
             .header("Content-Type", "application/json")
             .json(&cohere_request)
-            .send()
+            .send_authenticated(self.auth.as_ref())
             .await?;
 
-        let status = response.status();
-        if !status.is_success() {
-            let error_text = response.text().await?;
-            return Err(AiError::ProviderError {
-                provider: "cohere".to_string(),
-                message: format!("Cohere API error: {}", error_text),
-                error_code: None,
-                retryable: status.is_server_error(),
-            });
-        }
+        let response = super::check_response(response, |error| {
+            error.into_provider_error("cohere", "Cohere API error")
+        })
+        .await?;
 
         let cohere_response: CohereChatResponse = response.json().await?;
-        Ok(self.convert_to_standard_response(cohere_response))
+        Ok(self.convert_to_standard_response(cohere_response, &request.model))
+    }
+}
+
+#[async_trait]
+impl CompletionProvider for CohereProvider {
+    async fn complete(&self, request: CompletionRequest) -> Result<CompletionResponse> {
+        // No native JSON mode, so JSON requests are emulated
+        super::json_mode::complete_with_json_fallback(request, |request| {
+            self.complete_once(request)
+        })
+        .await
     }
 
     async fn complete_stream(
         &self,
-        request: CompletionRequest,
+        mut request: CompletionRequest,
     ) -> Result<Pin<Box<dyn Stream<Item = Result<StreamChunk>> + Send>>> {
-        let url = "https://api.cohere.ai/v1/chat";
-
-        // Extract system message as preamble
-        let (preamble, chat_history) = {
-            let mut preamble = None;
-            let mut history = Vec::new();
-
-            for msg in &request.messages {
-                match msg.role {
-                    Role::System => {
-                        preamble = Some(match &msg.content {
-                            MessageContent::Text(text) => text.clone(),
-                            MessageContent::Parts(_) => continue,
-                        });
-                    }
-                    _ => history.push(self.convert_message(msg)),
-                }
-            }
-
-            (preamble, history)
-        };
-
-        // The last message should be from the user
-        let message = chat_history
-            .last()
-            .filter(|m| m.role == "USER")
-            .map(|m| m.message.clone())
-            .ok_or_else(|| AiError::InvalidRequest {
-                message: "Last message must be from user".to_string(),
-                field: Some("messages".to_string()),
-                code: None,
-            })?;
-
-        // Remove the last message from history
-        let chat_history = chat_history[..chat_history.len() - 1].to_vec();
-
-        let cohere_request = CohereChatRequest {
-            message,
-            model: Some(request.model.clone()),
-            preamble,
-            chat_history: if chat_history.is_empty() {
-                None
-            } else {
-                Some(chat_history)
-            },
-            temperature: request.temperature,
-            max_tokens: request.max_tokens,
-            p: request.top_p,
-            stop_sequences: request.stop.clone(),
-            stream: true,
-        };
+        request.normalize_system();
+        request.validate()?;
+        if self.validate_models {
+            super::validate_model(self, &request.model)?;
+        }
+        if self.check_context_window {
+            super::check_context_window(&request)?;
+        }
+        let url = format!("{}/chat", self.base_url);
+        let cohere_request = self.build_chat_request(&request, true)?;
 
         let response = self
             .client
             .post(url)
-            .header("Authorization", format!("Bearer {}", self.api_key))
             .header("Content-Type", "application/json")
             .json(&cohere_request)
-            .send()
+            .send_authenticated(self.auth.as_ref())
             .await?;
 
-        let status = response.status();
-        if !status.is_success() {
-            let error_text = response.text().await?;
-            return Err(AiError::ProviderError {
-                provider: "cohere".to_string(),
-                message: format!("Cohere API error: {}", error_text),
-                error_code: None,
-                retryable: status.is_server_error(),
-            });
-        }
-
-        // Convert the response stream
-        let stream = response.bytes_stream();
-        let mapped_stream = stream.map(move |chunk_result| {
-            match chunk_result {
-                Ok(chunk) => {
-                    // Parse the server-sent event
-                    let text = String::from_utf8_lossy(&chunk);
-
-                    // Cohere uses server-sent events format
-                    if let Some(json_str) = text.strip_prefix("data: ") {
-                        match serde_json::from_str::<CohereStreamEvent>(json_str.trim()) {
-                            Ok(event) => {
-                                match event.event_type.as_str() {
-                                    "text-generation" => Ok(StreamChunk {
-                                        id: "cohere_stream".to_string(),
-                                        choices: vec![crate::StreamChoice {
-                                            index: 0,
-                                            delta: crate::Delta {
-                                                role: None,
-                                                content: event.text,
-                                                tool_calls: None,
-                                            },
-                                            finish_reason: None,
-                                        }],
-                                        model: None,
-                                    }),
-                                    "stream-end" => Ok(StreamChunk {
-                                        id: "cohere_stream".to_string(),
-                                        choices: vec![crate::StreamChoice {
-                                            index: 0,
-                                            delta: crate::Delta {
-                                                role: None,
-                                                content: None,
-                                                tool_calls: None,
-                                            },
-                                            finish_reason: Some("stop".to_string()),
-                                        }],
-                                        model: None,
-                                    }),
-                                    _ => {
-                                        // Ignore other event types
-                                        Ok(StreamChunk {
-                                            id: "cohere_stream".to_string(),
-                                            choices: vec![],
-                                            model: None,
-                                        })
-                                    }
-                                }
-                            }
-                            Err(e) => Err(AiError::StreamError {
-                                message: format!("Failed to parse Cohere stream event: {}", e),
-                                retryable: false,
-                            }),
-                        }
-                    } else {
-                        // Skip non-data lines (like empty lines)
-                        Ok(StreamChunk {
-                            id: "cohere_stream".to_string(),
-                            choices: vec![],
-                            model: None,
-                        })
+        let response = super::check_response(response, |error| {
+            error.into_provider_error("cohere", "Cohere API error")
+        })
+        .await?;
+
+        // Every chunk carries the generation id from `stream-start`
+        let mut id = format!("cohere-{}", uuid::Uuid::new_v4());
+        let model = request.model.clone();
+
+        // Cohere's v1 chat endpoint streams newline-delimited JSON, one
+        // event per line; a line can be split across network chunks
+        let mut buffer = Vec::new();
+        let body = response.bytes_stream().map_err(|e| AiError::StreamError {
+            message: e.to_string(),
+            retryable: true,
+        });
+        let mapped_stream = super::sse::idle_timeout(body, self.stream_idle_timeout)
+            .map(move |chunk| {
+                let chunk = match chunk {
+                    Ok(chunk) => chunk,
+                    Err(e) => return vec![Err(e)],
+                };
+                buffer.extend_from_slice(&chunk);
+
+                let mut chunks = Vec::new();
+                while let Some(end) = buffer.iter().position(|&b| b == b'\n') {
+                    let line: Vec<u8> = buffer.drain(..=end).collect();
+                    let line = line.trim_ascii();
+                    if line.is_empty() {
+                        continue;
                     }
+                    chunks.push(match serde_json::from_slice::<CohereStreamEvent>(line) {
+                        Ok(event) => Ok(convert_stream_event(event, &mut id, &model)),
+                        Err(e) => Err(AiError::StreamError {
+                            message: format!("Failed to parse Cohere stream event: {}", e),
+                            retryable: false,
+                        }),
+                    });
                 }
-                Err(e) => Err(AiError::StreamError {
-                    message: e.to_string(),
-                    retryable: true,
-                }),
-            }
-        });
+                chunks
+            })
+            .flat_map(futures::stream::iter)
+            // Events such as `stream-start` carry nothing for the caller
+            .filter(|chunk| {
+                let empty = matches!(chunk, Ok(chunk) if chunk.choices.is_empty()
+                    && chunk.usage.is_none()
+                    && chunk.metadata.is_none());
+                futures::future::ready(!empty)
+            });
 
         Ok(Box::pin(mapped_stream))
     }
@@ -338,6 +427,98 @@ impl CompletionProvider for CohereProvider {
             "command-nightly",
         ]
     }
+
+    fn capabilities(&self) -> ProviderCapabilities {
+        ProviderCapabilities {
+            streaming: true,
+            tools: true,
+            vision: false,
+            json_mode: false,
+            max_context_tokens: Some(128_000),
+            max_output_tokens: Some(4_096),
+            alternating_roles: false,
+            assistant_prefill: false,
+        }
+    }
+}
+
+/// Convert one stream event into a chunk, remembering the generation id
+/// from `stream-start` in `id`
+fn convert_stream_event(event: CohereStreamEvent, id: &mut String, model: &str) -> StreamChunk {
+    if let Some(generation_id) = &event.generation_id {
+        *id = generation_id.clone();
+    }
+
+    let choice = |delta: crate::Delta, finish_reason: Option<String>| crate::StreamChoice {
+        index: 0,
+        delta,
+        finish_reason,
+    };
+    let mut chunk = StreamChunk {
+        id: id.clone(),
+        choices: vec![],
+        model: Some(model.to_string()),
+        usage: None,
+        metadata: None,
+    };
+
+    match event.event_type.as_str() {
+        "text-generation" => {
+            chunk.choices.push(choice(
+                crate::Delta {
+                    role: None,
+                    content: event.text,
+                    tool_calls: None,
+                },
+                None,
+            ));
+        }
+        "tool-calls-generation" => {
+            // Cohere sends every call complete in one event, without ids
+            let tool_calls = event
+                .tool_calls
+                .unwrap_or_default()
+                .into_iter()
+                .enumerate()
+                .map(|(index, call)| crate::ToolCallDelta {
+                    index: Some(index as u32),
+                    id: Some(uuid::Uuid::new_v4().to_string()),
+                    r#type: Some(crate::ToolType::Function),
+                    function: Some(crate::FunctionCallDelta {
+                        name: Some(call.name),
+                        arguments: Some(call.parameters.to_string()),
+                    }),
+                })
+                .collect();
+            chunk.choices.push(choice(
+                crate::Delta {
+                    role: None,
+                    content: None,
+                    tool_calls: Some(tool_calls),
+                },
+                None,
+            ));
+        }
+        "citation-generation" => {
+            chunk.metadata = event
+                .citations
+                .map(|citations| serde_json::json!({ "citations": citations }));
+        }
+        "stream-end" => {
+            chunk.choices.push(choice(
+                crate::Delta {
+                    role: None,
+                    content: None,
+                    tool_calls: None,
+                },
+                Some(event.finish_reason.unwrap_or_else(|| "stop".to_string())),
+            ));
+        }
+        // Ignore other event types
+        _ => {}
+    }
+
+    chunk
 }
 
 // Cohere API types
@@ -359,19 +540,98 @@ struct CohereChatRequest {
     p: Option<f32>,
     #[serde(skip_serializing_if = "Option::is_none")]
     stop_sequences: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tools: Option<Vec<CohereTool>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tool_results: Option<Vec<CohereToolResult>>,
     stream: bool,
+    #[serde(flatten)]
+    extra: HashMap<String, Value>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize)]
 struct CohereChatMessage {
     role: String,
+    #[serde(skip_serializing_if = "String::is_empty")]
     message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tool_calls: Option<Vec<CohereToolCall>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tool_results: Option<Vec<CohereToolResult>>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct CohereTool {
+    name: String,
+    description: String,
+    parameter_definitions: HashMap<String, CohereParameterDefinition>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct CohereParameterDefinition {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description: Option<String>,
+    #[serde(rename = "type")]
+    param_type: String,
+    required: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct CohereToolResult {
+    call: CohereToolCall,
+    outputs: Vec<Value>,
+}
+
+/// Cohere describes each top-level parameter with a Python-style type
+/// rather than a JSON schema
+fn convert_tool(tool: &Tool) -> CohereTool {
+    let schema = &tool.function.parameters;
+    let required: Vec<&str> = schema["required"]
+        .as_array()
+        .map(|names| names.iter().filter_map(Value::as_str).collect())
+        .unwrap_or_default();
+    let parameter_definitions = schema["properties"]
+        .as_object()
+        .map(|properties| {
+            properties
+                .iter()
+                .map(|(name, property)| {
+                    let definition = CohereParameterDefinition {
+                        description: property["description"].as_str().map(str::to_string),
+                        param_type: python_type(property),
+                        required: required.contains(&name.as_str()),
+                    };
+                    (name.clone(), definition)
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    CohereTool {
+        name: tool.function.name.clone(),
+        description: tool.function.description.clone().unwrap_or_default(),
+        parameter_definitions,
+    }
+}
+
+fn python_type(property: &Value) -> String {
+    match property["type"].as_str() {
+        Some("string") => "str".to_string(),
+        Some("integer") => "int".to_string(),
+        Some("number") => "float".to_string(),
+        Some("boolean") => "bool".to_string(),
+        Some("array") => format!("List[{}]", python_type(&property["items"])),
+        Some("object") => "Dict".to_string(),
+        _ => "str".to_string(),
+    }
 }
 
 #[derive(Debug, Clone, Deserialize)]
 struct CohereChatResponse {
     text: String,
     #[serde(default)]
+    tool_calls: Option<Vec<CohereToolCall>>,
+    #[serde(default)]
     response_id: Option<String>,
     #[serde(default)]
     generation_info: Option<GenerationInfo>,
@@ -404,6 +664,32 @@ struct CohereStreamEvent {
     event_type: String,
     #[serde(default)]
     text: Option<String>,
+    #[serde(default)]
+    generation_id: Option<String>,
+    #[serde(default)]
+    tool_calls: Option<Vec<CohereToolCall>>,
+    #[serde(default)]
+    citations: Option<Vec<serde_json::Value>>,
+    /// Set on `stream-end`
+    #[serde(default)]
+    finish_reason: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CohereToolCall {
+    name: String,
+    #[serde(default)]
+    parameters: serde_json::Value,
+}
+
+impl CohereToolCall {
+    fn from_call(call: &ToolCall) -> Self {
+        Self {
+            name: call.function.name.clone(),
+            parameters: serde_json::from_str(&call.function.arguments)
+                .unwrap_or_else(|_| Value::Object(Default::default())),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -421,6 +707,12 @@ mod tests {
         assert_eq!(provider.default_model(), "command-r-plus");
     }
 
+    #[test]
+    fn test_blank_api_key_rejected() {
+        let result = CohereProvider::new(Some("   ".to_string()));
+        assert!(matches!(result, Err(AiError::InvalidApiKey { .. })));
+    }
+
     #[test]
     fn test_role_conversion() {
         let provider = CohereProvider::new(Some("test-key".to_string())).unwrap();
@@ -429,4 +721,145 @@ mod tests {
         assert_eq!(provider.convert_role(&Role::Assistant), "CHATBOT");
         assert_eq!(provider.convert_role(&Role::Tool), "TOOL");
     }
+
+    #[test]
+    fn test_max_tokens_response_is_normalized() {
+        let provider = CohereProvider::new(Some("test-key".to_string())).unwrap();
+        let response: CohereChatResponse = serde_json::from_value(serde_json::json!({
+            "text": "The answer is",
+            "response_id": "r1",
+            "finish_reason": "MAX_TOKENS"
+        }))
+        .unwrap();
+
+        let response = provider.convert_to_standard_response(response, "command-r");
+        assert_eq!(response.model, "command-r");
+        let choice = &response.choices[0];
+        assert_eq!(choice.finish_reason.as_deref(), Some("MAX_TOKENS"));
+        assert_eq!(choice.stop_reason(), Some(crate::FinishReason::Length));
+
+        for (raw, expected) in [
+            ("COMPLETE", crate::FinishReason::Stop),
+            ("STOP_SEQUENCE", crate::FinishReason::Stop),
+            ("ERROR_TOXIC", crate::FinishReason::ContentFilter),
+            ("ERROR_LIMIT", crate::FinishReason::Length),
+            ("ERROR", crate::FinishReason::Other("ERROR".to_string())),
+        ] {
+            assert_eq!(crate::FinishReason::from_raw(raw), expected);
+        }
+
+        // Streams report the reason on `stream-end`
+        let event: CohereStreamEvent = serde_json::from_value(serde_json::json!({
+            "event_type": "stream-end",
+            "finish_reason": "MAX_TOKENS"
+        }))
+        .unwrap();
+        let chunk = convert_stream_event(event, &mut String::new(), "command-r");
+        assert_eq!(
+            chunk.choices[0].stop_reason(),
+            Some(crate::FinishReason::Length)
+        );
+    }
+
+    #[test]
+    fn test_tools_and_tool_results_are_sent() {
+        let provider = CohereProvider::new(Some("test-key".to_string())).unwrap();
+        let call = ToolCall {
+            id: "call_1".to_string(),
+            r#type: ToolType::Function,
+            function: FunctionCall {
+                name: "search".to_string(),
+                arguments: r#"{"query":"rust"}"#.to_string(),
+            },
+        };
+        let request = CompletionRequest {
+            model: "command-r".to_string(),
+            messages: vec![
+                Message::system("Be brief."),
+                Message::user("Look up rust"),
+                Message {
+                    tool_calls: Some(vec![call]),
+                    ..Message::assistant("")
+                },
+                Message::tool("call_1", "Rust is a language"),
+            ],
+            tools: Some(vec![Tool {
+                r#type: ToolType::Function,
+                function: crate::ToolFunction {
+                    name: "search".to_string(),
+                    description: Some("Search the web".to_string()),
+                    parameters: serde_json::json!({
+                        "type": "object",
+                        "properties": {
+                            "query": {"type": "string", "description": "Search terms"},
+                            "limit": {"type": "integer"},
+                            "sites": {"type": "array", "items": {"type": "string"}}
+                        },
+                        "required": ["query"]
+                    }),
+                },
+            }]),
+            ..Default::default()
+        };
+
+        let body =
+            serde_json::to_value(provider.build_chat_request(&request, false).unwrap()).unwrap();
+        let params = &body["tools"][0]["parameter_definitions"];
+        assert_eq!(params["query"]["type"], "str");
+        assert_eq!(params["query"]["required"], true);
+        assert_eq!(params["query"]["description"], "Search terms");
+        assert_eq!(params["limit"]["type"], "int");
+        assert_eq!(params["limit"]["required"], false);
+        assert_eq!(params["sites"]["type"], "List[str]");
+
+        // The tool result answers the last turn in place of a message
+        assert!(body.get("message").is_none_or(|m| m == ""));
+        let result = &body["tool_results"][0];
+        assert_eq!(result["call"]["name"], "search");
+        assert_eq!(result["call"]["parameters"]["query"], "rust");
+        assert_eq!(result["outputs"][0]["result"], "Rust is a language");
+        let history = body["chat_history"].as_array().unwrap();
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[1]["role"], "CHATBOT");
+        assert_eq!(history[1]["tool_calls"][0]["name"], "search");
+
+        // Calls in a response become tool calls on the message
+        let response: CohereChatResponse = serde_json::from_value(serde_json::json!({
+            "text": "",
+            "tool_calls": [{"name": "search", "parameters": {"query": "rust"}}]
+        }))
+        .unwrap();
+        let response = provider.convert_to_standard_response(response, "command-r");
+        let calls = response.choices[0].message.tool_calls.as_ref().unwrap();
+        assert_eq!(calls[0].function.name, "search");
+        assert_eq!(calls[0].function.arguments, r#"{"query":"rust"}"#);
+    }
+
+    #[test]
+    fn test_stream_tool_call_and_citation_events() {
+        let events = [
+            r#"{"event_type":"stream-start","generation_id":"gen-1"}"#,
+            r#"{"event_type":"tool-calls-generation","text":"I will look it up.","tool_calls":[{"name":"search","parameters":{"query":"rust"}},{"name":"calculator","parameters":{"a":1}}]}"#,
+            r#"{"event_type":"citation-generation","citations":[{"start":0,"end":4,"text":"Rust","document_ids":["doc_0"]}]}"#,
+        ];
+        let mut id = "unset".to_string();
+        let chunks: Vec<StreamChunk> = events
+            .iter()
+            .map(|event| {
+                let event: CohereStreamEvent = serde_json::from_str(event).unwrap();
+                convert_stream_event(event, &mut id, "command-r")
+            })
+            .collect();
+
+        assert_eq!(chunks[1].id, "gen-1");
+        let calls = chunks[1].choices[0].delta.tool_calls.as_ref().unwrap();
+        assert_eq!(calls.len(), 2);
+        assert_eq!(calls[1].index, Some(1));
+        let function = calls[0].function.as_ref().unwrap();
+        assert_eq!(function.name.as_deref(), Some("search"));
+        assert_eq!(function.arguments.as_deref(), Some(r#"{"query":"rust"}"#));
+
+        let metadata = chunks[2].metadata.as_ref().unwrap();
+        assert_eq!(metadata["citations"][0]["document_ids"][0], "doc_0");
+    }
 }
@@ -0,0 +1,85 @@
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use reqwest::Client;
+use std::collections::HashMap;
+use std::time::Duration;
+
+use crate::{AiError, Result};
+
+/// `User-Agent` sent by every provider unless overridden
+pub const DEFAULT_USER_AGENT: &str = concat!("lib_ai/", env!("CARGO_PKG_VERSION"));
+
+/// HTTP settings applied to every request a provider sends, so gateways
+/// can route and track traffic
+#[derive(Debug, Clone)]
+pub struct ProviderConfig {
+    /// `User-Agent` header, `lib_ai/{version}` by default
+    pub user_agent: String,
+    /// Extra headers sent with every request
+    pub default_headers: HashMap<String, String>,
+    /// End a streamed reply with `AiError::TimeoutError` when no bytes, not
+    /// even a keepalive, arrive for this long. Unset by default.
+    pub stream_idle_timeout: Option<Duration>,
+}
+
+impl Default for ProviderConfig {
+    fn default() -> Self {
+        Self {
+            user_agent: DEFAULT_USER_AGENT.to_string(),
+            default_headers: HashMap::new(),
+            stream_idle_timeout: None,
+        }
+    }
+}
+
+impl ProviderConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn user_agent(mut self, user_agent: impl Into<String>) -> Self {
+        self.user_agent = user_agent.into();
+        self
+    }
+
+    /// Add a header sent with every request
+    pub fn header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.default_headers.insert(name.into(), value.into());
+        self
+    }
+
+    /// Give up on a streamed reply that goes quiet for `timeout`
+    pub fn stream_idle_timeout(mut self, timeout: Duration) -> Self {
+        self.stream_idle_timeout = Some(timeout);
+        self
+    }
+
+    /// HTTP client carrying the user agent and default headers
+    pub(crate) fn build_client(&self) -> Result<Client> {
+        let invalid = |field: &str, message: String| AiError::ConfigurationError {
+            field: field.to_string(),
+            message,
+            suggestion: None,
+        };
+
+        let mut headers = HeaderMap::new();
+        for (name, value) in &self.default_headers {
+            let header_name = HeaderName::from_bytes(name.as_bytes())
+                .map_err(|e| invalid("default_headers", format!("{}: {}", name, e)))?;
+            let header_value = HeaderValue::from_str(value)
+                .map_err(|e| invalid("default_headers", format!("{}: {}", name, e)))?;
+            headers.insert(header_name, header_value);
+        }
+
+        Client::builder()
+            .user_agent(self.user_agent.as_str())
+            .default_headers(headers)
+            .build()
+            .map_err(|e| invalid("user_agent", e.to_string()))
+    }
+}
+
+/// Client with the default user agent, used until a provider is given a
+/// `ProviderConfig`
+pub(crate) fn default_client() -> Client {
+    ProviderConfig::default().build_client().unwrap_or_default()
+}
@@ -2,31 +2,108 @@ use async_trait::async_trait;
 use futures::stream::{Stream, StreamExt};
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
 use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
 
 use crate::{
-    AiError, Choice, CompletionProvider, CompletionRequest, CompletionResponse, ContentPart, Delta,
-    Message, MessageContent, Result, Role, StreamChunk, Usage,
+    providers::{auth::SendAuthenticated, ApiKeyAuth, Authenticator, EventStream, ProviderConfig},
+    Choice, CompletionProvider, CompletionRequest, CompletionResponse, ContentPart, Delta, Message,
+    MessageContent, ProviderCapabilities, Result, Role, StreamChunk, Usage,
 };
 
 pub struct GeminiProvider {
     client: Client,
-    api_key: String,
+    /// Set by `with_client`, so `with_config` keeps the injected client
+    custom_client: bool,
+    auth: Arc<dyn Authenticator>,
+    base_url: String,
+    stream_idle_timeout: Option<Duration>,
+    validate_models: bool,
+    check_context_window: bool,
 }
 
 impl GeminiProvider {
     pub fn new(api_key: String) -> Self {
-        Self {
-            client: Client::new(),
+        Self::with_base_url(
             api_key,
+            "https://generativelanguage.googleapis.com/v1".to_string(),
+        )
+    }
+
+    pub fn with_base_url(api_key: String, base_url: String) -> Self {
+        Self {
+            client: super::config::default_client(),
+            custom_client: false,
+            auth: Arc::new(ApiKeyAuth::query("key", api_key)),
+            base_url,
+            stream_idle_timeout: None,
+            validate_models: false,
+            check_context_window: false,
         }
     }
+
+    /// Reject models missing from `available_models()` before sending,
+    /// instead of waiting for the API to refuse them
+    pub fn with_model_validation(mut self, enabled: bool) -> Self {
+        self.validate_models = enabled;
+        self
+    }
+
+    /// Reject a request whose estimated size exceeds the model's context
+    /// window before sending it (see [`context_window`]). Off by default:
+    /// the estimate is rough and could turn away requests the API accepts.
+    ///
+    /// [`context_window`]: crate::providers::context_window
+    pub fn with_context_window_check(mut self, enabled: bool) -> Self {
+        self.check_context_window = enabled;
+        self
+    }
+
+    /// Send every request with the user agent and default headers from
+    /// `config`. A client given to [`with_client`](Self::with_client),
+    /// before or after this call, is kept; the user agent and headers are
+    /// then up to that client.
+    pub fn with_config(mut self, config: &ProviderConfig) -> Result<Self> {
+        let client = config.build_client()?;
+        if !self.custom_client {
+            self.client = client;
+        }
+        self.stream_idle_timeout = config.stream_idle_timeout;
+        Ok(self)
+    }
+
+    /// Send requests through `client`, sharing its connection pool and DNS
+    /// cache with other providers. It takes precedence over the client
+    /// [`with_config`](Self::with_config) builds.
+    pub fn with_client(mut self, client: Client) -> Self {
+        self.client = client;
+        self.custom_client = true;
+        self
+    }
+
+    /// Authenticate requests with `auth` instead of the API key, e.g. for a
+    /// proxy or gateway with its own credentials
+    pub fn with_authenticator(mut self, auth: impl Authenticator + 'static) -> Self {
+        self.auth = Arc::new(auth);
+        self
+    }
+
+    /// Create a provider, rejecting an empty or whitespace-only API key
+    pub fn try_new(api_key: String) -> Result<Self> {
+        super::validate_api_key("gemini", &api_key)?;
+        Ok(Self::new(api_key))
+    }
 }
 
 #[derive(Serialize)]
 struct GeminiRequest {
     contents: Vec<GeminiContent>,
     generation_config: Option<GenerationConfig>,
+    #[serde(flatten)]
+    extra: HashMap<String, Value>,
 }
 
 #[derive(Serialize)]
@@ -37,7 +114,25 @@ struct GeminiContent {
 
 #[derive(Serialize)]
 struct GeminiPart {
-    text: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    text: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    inline_data: Option<GeminiInlineData>,
+}
+
+#[derive(Serialize)]
+struct GeminiInlineData {
+    mime_type: String,
+    data: String,
+}
+
+impl GeminiPart {
+    fn text(text: String) -> Self {
+        Self {
+            text: Some(text),
+            inline_data: None,
+        }
+    }
 }
 
 #[derive(Serialize)]
@@ -49,51 +144,108 @@ struct GenerationConfig {
 }
 
 #[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
 struct GeminiResponse {
+    #[serde(default)]
     candidates: Vec<GeminiCandidate>,
     usage_metadata: Option<GeminiUsage>,
 }
 
 #[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
 struct GeminiCandidate {
+    #[serde(default)]
     content: GeminiResponseContent,
     finish_reason: Option<String>,
+    #[serde(default)]
     index: u32,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Default)]
 struct GeminiResponseContent {
+    #[serde(default)]
     parts: Vec<GeminiResponsePart>,
-    #[allow(dead_code)]
-    role: String,
 }
 
 #[derive(Deserialize)]
 struct GeminiResponsePart {
+    #[serde(default)]
     text: String,
+    /// Set on thinking-model parts that carry the model's reasoning
+    #[serde(default)]
+    thought: bool,
 }
 
 #[derive(Deserialize)]
 #[serde(rename_all = "camelCase")]
 struct GeminiUsage {
+    #[serde(default)]
     prompt_token_count: u32,
+    #[serde(default)]
     candidates_token_count: u32,
+    #[serde(default)]
     total_token_count: u32,
+    thoughts_token_count: Option<u32>,
+}
+
+impl GeminiUsage {
+    /// Thinking tokens are billed as output, so they count towards
+    /// `completion_tokens` as they do for OpenAI's reasoning models.
+    /// `totalTokenCount` already includes them when present.
+    fn into_usage(self) -> Usage {
+        let thoughts = self.thoughts_token_count.unwrap_or(0);
+        let completion_tokens = self.candidates_token_count + thoughts;
+        Usage {
+            prompt_tokens: self.prompt_token_count,
+            completion_tokens,
+            total_tokens: self
+                .total_token_count
+                .max(self.prompt_token_count + completion_tokens),
+            reasoning_tokens: self.thoughts_token_count,
+        }
+    }
+}
+
+impl GeminiResponseContent {
+    /// Join answer text and thinking text separately
+    fn split_text(&self) -> (String, Option<String>) {
+        let mut text = String::new();
+        let mut thoughts = String::new();
+        for part in &self.parts {
+            if part.thought {
+                thoughts.push_str(&part.text);
+            } else {
+                text.push_str(&part.text);
+            }
+        }
+        (text, Some(thoughts).filter(|t| !t.is_empty()))
+    }
 }
 
 #[async_trait]
 impl CompletionProvider for GeminiProvider {
-    async fn complete(&self, request: CompletionRequest) -> Result<CompletionResponse> {
+    async fn complete(&self, mut request: CompletionRequest) -> Result<CompletionResponse> {
+        request.normalize_system();
+        request.validate()?;
+        if self.validate_models {
+            super::validate_model(self, request.model.trim_start_matches("models/"))?;
+        }
+        if self.check_context_window {
+            super::check_context_window(&request)?;
+        }
         let contents = convert_messages_to_gemini(request.messages);
 
-        let gemini_request = GeminiRequest {
+        let mut gemini_request = GeminiRequest {
             contents,
             generation_config: Some(GenerationConfig {
                 temperature: request.temperature,
                 max_output_tokens: request.max_tokens,
                 top_p: request.top_p,
             }),
+            extra: HashMap::new(),
         };
+        super::check_extra(&gemini_request, &request.extra)?;
+        gemini_request.extra = request.extra;
 
         let model_name = if request.model.starts_with("models/") {
             request.model
@@ -103,77 +255,46 @@ impl CompletionProvider for GeminiProvider {
 
         let response = self
             .client
-            .post(format!(
-                "https://generativelanguage.googleapis.com/v1/{}:generateContent?key={}",
-                model_name, self.api_key
-            ))
+            .post(format!("{}/{}:generateContent", self.base_url, model_name))
             .json(&gemini_request)
-            .send()
+            .send_authenticated(self.auth.as_ref())
             .await?;
 
-        if !response.status().is_success() {
-            let error_text = response.text().await?;
-            return Err(AiError::ProviderError {
-                provider: "gemini".to_string(),
-                message: format!("Gemini API error: {}", error_text),
-                error_code: None,
-                retryable: true,
-            });
-        }
+        let response = super::check_response(response, |error| {
+            error.into_provider_error("gemini", "Gemini API error")
+        })
+        .await?;
 
         let gemini_response: GeminiResponse = response.json().await?;
 
-        let choices = gemini_response
-            .candidates
-            .into_iter()
-            .map(|candidate| Choice {
-                index: candidate.index,
-                message: Message {
-                    role: Role::Assistant,
-                    content: MessageContent::text(
-                        candidate
-                            .content
-                            .parts
-                            .iter()
-                            .map(|p| p.text.clone())
-                            .collect::<Vec<_>>()
-                            .join(""),
-                    ),
-                    tool_calls: None,
-                    tool_call_id: None,
-                },
-                finish_reason: candidate.finish_reason,
-            })
-            .collect();
-
-        let usage = gemini_response.usage_metadata.map(|u| Usage {
-            prompt_tokens: u.prompt_token_count,
-            completion_tokens: u.candidates_token_count,
-            total_tokens: u.total_token_count,
-        });
-
-        Ok(CompletionResponse {
-            id: uuid::Uuid::new_v4().to_string(),
-            model: model_name,
-            choices,
-            usage,
-        })
+        Ok(convert_gemini_response(gemini_response, model_name))
     }
 
     async fn complete_stream(
         &self,
-        request: CompletionRequest,
+        mut request: CompletionRequest,
     ) -> Result<Pin<Box<dyn Stream<Item = Result<StreamChunk>> + Send>>> {
+        request.normalize_system();
+        request.validate()?;
+        if self.validate_models {
+            super::validate_model(self, request.model.trim_start_matches("models/"))?;
+        }
+        if self.check_context_window {
+            super::check_context_window(&request)?;
+        }
         let contents = convert_messages_to_gemini(request.messages);
 
-        let gemini_request = GeminiRequest {
+        let mut gemini_request = GeminiRequest {
             contents,
             generation_config: Some(GenerationConfig {
                 temperature: request.temperature,
                 max_output_tokens: request.max_tokens,
                 top_p: request.top_p,
             }),
+            extra: HashMap::new(),
         };
+        super::check_extra(&gemini_request, &request.extra)?;
+        gemini_request.extra = request.extra;
 
         let model_name = if request.model.starts_with("models/") {
             request.model
@@ -184,34 +305,24 @@ impl CompletionProvider for GeminiProvider {
         let response = self
             .client
             .post(format!(
-                "https://generativelanguage.googleapis.com/v1/{}:streamGenerateContent?key={}",
-                model_name, self.api_key
+                "{}/{}:streamGenerateContent?alt=sse",
+                self.base_url, model_name
             ))
             .json(&gemini_request)
-            .send()
+            .send_authenticated(self.auth.as_ref())
             .await?;
 
-        if !response.status().is_success() {
-            let error_text = response.text().await?;
-            return Err(AiError::ProviderError {
-                provider: "gemini".to_string(),
-                message: format!("Gemini API error: {}", error_text),
-                error_code: None,
-                retryable: true,
-            });
-        }
-
-        let stream = response.bytes_stream();
-        let stream = stream
-            .map(move |result| match result {
-                Ok(bytes) => {
-                    let text = String::from_utf8_lossy(&bytes);
-                    parse_gemini_stream(&text, &model_name)
-                }
-                Err(e) => Err(AiError::StreamError {
-                    message: e.to_string(),
-                    retryable: true,
-                }),
+        let response = super::check_response(response, |error| {
+            error.into_provider_error("gemini", "Gemini API error")
+        })
+        .await?;
+
+        // Gemini events carry no id, so one is shared by every chunk
+        let stream_id = uuid::Uuid::new_v4().to_string();
+        let stream = EventStream::new(response.bytes_stream())
+            .with_optional_idle_timeout(self.stream_idle_timeout)
+            .map(move |event| {
+                event.and_then(|event| parse_gemini_event(&event.data, &model_name, &stream_id))
             })
             .filter_map(|result| async move {
                 match result {
@@ -241,6 +352,19 @@ impl CompletionProvider for GeminiProvider {
             "gemini-1.5-flash-8b",
         ]
     }
+
+    fn capabilities(&self) -> ProviderCapabilities {
+        ProviderCapabilities {
+            streaming: true,
+            tools: false,
+            vision: true,
+            json_mode: false,
+            max_context_tokens: Some(2_097_152),
+            max_output_tokens: Some(8_192),
+            alternating_roles: false,
+            assistant_prefill: false,
+        }
+    }
 }
 
 fn convert_messages_to_gemini(messages: Vec<Message>) -> Vec<GeminiContent> {
@@ -258,25 +382,27 @@ fn convert_messages_to_gemini(messages: Vec<Message>) -> Vec<GeminiContent> {
                     content = format!("{}\n\n{}", sys, content);
                     system_message = None;
                 }
+                let mut parts = vec![GeminiPart::text(content)];
+                parts.extend(extract_inline_data(&message.content));
                 contents.push(GeminiContent {
-                    parts: vec![GeminiPart { text: content }],
+                    parts,
                     role: "user".to_string(),
                 });
             }
             Role::Assistant => {
                 contents.push(GeminiContent {
-                    parts: vec![GeminiPart {
-                        text: extract_text_from_content(&message.content),
-                    }],
+                    parts: vec![GeminiPart::text(extract_text_from_content(
+                        &message.content,
+                    ))],
                     role: "model".to_string(),
                 });
             }
             Role::Tool => {
                 // Tool responses are sent as user messages in Gemini
                 contents.push(GeminiContent {
-                    parts: vec![GeminiPart {
-                        text: extract_text_from_content(&message.content),
-                    }],
+                    parts: vec![GeminiPart::text(extract_text_from_content(
+                        &message.content,
+                    ))],
                     role: "user".to_string(),
                 });
             }
@@ -293,6 +419,7 @@ fn extract_text_from_content(content: &MessageContent) -> String {
             .iter()
             .filter_map(|p| match p {
                 ContentPart::Text { text } => Some(text.clone()),
+                ContentPart::Json { value } => Some(value.to_string()),
                 _ => None,
             })
             .collect::<Vec<_>>()
@@ -300,25 +427,203 @@ fn extract_text_from_content(content: &MessageContent) -> String {
     }
 }
 
-fn parse_gemini_stream(data: &str, model: &str) -> Result<Option<StreamChunk>> {
-    if let Ok(response) = serde_json::from_str::<GeminiResponse>(data) {
-        if let Some(candidate) = response.candidates.first() {
-            if let Some(part) = candidate.content.parts.first() {
-                return Ok(Some(StreamChunk {
-                    id: uuid::Uuid::new_v4().to_string(),
-                    choices: vec![crate::StreamChoice {
-                        index: 0,
-                        delta: Delta {
-                            role: None,
-                            content: Some(part.text.clone()),
-                            tool_calls: None,
-                        },
-                        finish_reason: candidate.finish_reason.clone(),
-                    }],
-                    model: Some(model.to_string()),
-                }));
+/// Collect audio, document and base64 image parts as Gemini inline data
+fn extract_inline_data(content: &MessageContent) -> Vec<GeminiPart> {
+    let parts = match content {
+        MessageContent::Text(_) => return Vec::new(),
+        MessageContent::Parts(parts) => parts,
+    };
+
+    parts
+        .iter()
+        .filter_map(|p| {
+            let (mime_type, data) = match p {
+                ContentPart::Audio { data, format } => (format!("audio/{}", format), data.clone()),
+                ContentPart::Document {
+                    data, mime_type, ..
+                } => (mime_type.clone(), data.clone()),
+                ContentPart::Image { image_url } => {
+                    let (mime_type, data) = image_url
+                        .url
+                        .strip_prefix("data:")?
+                        .split_once(";base64,")?;
+                    (mime_type.to_string(), data.to_string())
+                }
+                ContentPart::Text { .. } | ContentPart::Json { .. } => return None,
+            };
+            Some(GeminiPart {
+                text: None,
+                inline_data: Some(GeminiInlineData { mime_type, data }),
+            })
+        })
+        .collect()
+}
+
+fn convert_gemini_response(response: GeminiResponse, model: String) -> CompletionResponse {
+    let choices = response
+        .candidates
+        .into_iter()
+        .map(|candidate| {
+            let (text, reasoning_content) = candidate.content.split_text();
+            Choice {
+                index: candidate.index,
+                message: Message {
+                    reasoning_content,
+                    ..Message::assistant(text)
+                },
+                finish_reason: candidate.finish_reason,
+                logprobs: None,
+            }
+        })
+        .collect();
+
+    let usage = response.usage_metadata.map(GeminiUsage::into_usage);
+
+    CompletionResponse {
+        id: uuid::Uuid::new_v4().to_string(),
+        model,
+        choices,
+        usage,
+        metadata: None,
+    }
+}
+
+/// Parse one event of the `alt=sse` stream format, whose data holds a full
+/// `GenerateContentResponse`, into a chunk with the stream's `id`. Thinking
+/// parts are skipped.
+fn parse_gemini_event(data: &str, model: &str, id: &str) -> Result<Option<StreamChunk>> {
+    let Ok(response) = serde_json::from_str::<GeminiResponse>(data) else {
+        return Ok(None);
+    };
+
+    let (content, finish_reason) = match response.candidates.first() {
+        Some(candidate) => (
+            candidate.content.split_text().0,
+            candidate.finish_reason.clone(),
+        ),
+        None => (String::new(), None),
+    };
+    // Every event carries the running totals
+    let usage = response
+        .usage_metadata
+        .map(|metadata| metadata.into_usage());
+
+    if content.is_empty() && finish_reason.is_none() && usage.is_none() {
+        return Ok(None);
+    }
+
+    Ok(Some(StreamChunk {
+        id: id.to_string(),
+        choices: vec![crate::StreamChoice {
+            index: 0,
+            delta: Delta {
+                role: None,
+                content: Some(content).filter(|c| !c.is_empty()),
+                tool_calls: None,
+            },
+            finish_reason,
+        }],
+        model: Some(model.to_string()),
+        usage,
+        metadata: None,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_media_parts_become_inline_data() {
+        let contents = convert_messages_to_gemini(vec![Message::new(
+            Role::User,
+            MessageContent::Parts(vec![
+                ContentPart::Text {
+                    text: "Summarize these".to_string(),
+                },
+                ContentPart::Audio {
+                    data: "UklGRg==".to_string(),
+                    format: "wav".to_string(),
+                },
+                ContentPart::Document {
+                    data: "JVBERi0=".to_string(),
+                    mime_type: "application/pdf".to_string(),
+                    name: Some("report.pdf".to_string()),
+                },
+            ]),
+        )]);
+
+        let body = serde_json::to_value(&contents[0]).unwrap();
+        let parts = body["parts"].as_array().unwrap();
+        assert_eq!(parts.len(), 3);
+        assert_eq!(parts[0]["text"], "Summarize these");
+        assert_eq!(parts[1]["inline_data"]["mime_type"], "audio/wav");
+        assert_eq!(parts[1]["inline_data"]["data"], "UklGRg==");
+        assert_eq!(parts[2]["inline_data"]["mime_type"], "application/pdf");
+    }
+
+    #[test]
+    fn test_usage_metadata_parsed_into_usage() {
+        let json = r#"{
+            "candidates": [{
+                "content": {
+                    "parts": [
+                        {"text": "Let me think.", "thought": true},
+                        {"text": "The answer is 4."}
+                    ],
+                    "role": "model"
+                },
+                "finishReason": "STOP",
+                "index": 0
+            }],
+            "usageMetadata": {
+                "promptTokenCount": 12,
+                "candidatesTokenCount": 7,
+                "totalTokenCount": 25,
+                "thoughtsTokenCount": 6
             }
-        }
+        }"#;
+
+        let response: GeminiResponse = serde_json::from_str(json).unwrap();
+        let response = convert_gemini_response(response, "models/gemini-2.0-flash".to_string());
+
+        let usage = response.usage.unwrap();
+        assert_eq!(usage.prompt_tokens, 12);
+        assert_eq!(usage.completion_tokens, 13);
+        assert_eq!(usage.total_tokens, 25);
+        assert_eq!(usage.reasoning_tokens, Some(6));
+
+        let choice = &response.choices[0];
+        assert_eq!(choice.message.content.as_text(), Some("The answer is 4."));
+        assert_eq!(
+            choice.message.reasoning_content.as_deref(),
+            Some("Let me think.")
+        );
+        assert_eq!(choice.finish_reason.as_deref(), Some("STOP"));
+    }
+
+    #[test]
+    fn test_parse_sse_stream_events() {
+        let data = concat!(
+            "data: {\"candidates\": [{\"content\": {\"parts\": [{\"text\": \"Hel\"}], \"role\": \"model\"}}]}\r\n\r\n",
+            "data: {\"candidates\": [{\"content\": {\"parts\": [{\"text\": \"lo\"}], \"role\": \"model\"}, \"finishReason\": \"STOP\"}]}\r\n\r\n",
+        );
+
+        let chunks: Vec<StreamChunk> = crate::providers::SseParser::new()
+            .push(data.as_bytes())
+            .iter()
+            .filter_map(|event| {
+                parse_gemini_event(&event.data, "gemini-2.0-flash", "stream-1").unwrap()
+            })
+            .collect();
+        assert_eq!(chunks.len(), 2);
+        assert!(chunks.iter().all(|chunk| chunk.id == "stream-1"));
+        assert_eq!(chunks[0].choices[0].delta.content.as_deref(), Some("Hel"));
+        assert_eq!(chunks[1].choices[0].delta.content.as_deref(), Some("lo"));
+        assert_eq!(chunks[1].choices[0].finish_reason.as_deref(), Some("STOP"));
+
+        assert!(parse_gemini_event("", "gemini-2.0-flash", "stream-1")
+            .unwrap()
+            .is_none());
     }
-    Ok(None)
 }
@@ -0,0 +1,171 @@
+use std::future::Future;
+
+use crate::{
+    AiError, CompletionRequest, CompletionResponse, Message, MessageContent, ResponseFormatType,
+    Result,
+};
+
+const JSON_INSTRUCTION: &str = "Respond with a single valid JSON value only. \
+Do not include any explanation, markdown or code fences.";
+
+const RETRY_INSTRUCTION: &str = "That reply was not valid JSON. \
+Reply again with only the JSON value.";
+
+/// Whether the request asks for JSON output via `response_format`
+pub fn wants_json(request: &CompletionRequest) -> bool {
+    request.response_format.as_ref().is_some_and(|format| {
+        matches!(
+            format.r#type,
+            ResponseFormatType::JsonObject | ResponseFormatType::JsonSchema
+        )
+    })
+}
+
+/// Emulate JSON mode for providers that have no native support.
+///
+/// When the request asks for JSON, the format flag is replaced by a system
+/// instruction (including the schema, if one is set) and the reply is
+/// validated. Invalid JSON is retried once with a corrective message; a
+/// second failure is returned as [`AiError::MalformedResponse`]. Requests
+/// that do not ask for JSON are passed through untouched.
+pub async fn complete_with_json_fallback<F, Fut>(
+    mut request: CompletionRequest,
+    mut complete: F,
+) -> Result<CompletionResponse>
+where
+    F: FnMut(CompletionRequest) -> Fut + Send,
+    Fut: Future<Output = Result<CompletionResponse>> + Send,
+{
+    if !wants_json(&request) {
+        return complete(request).await;
+    }
+
+    let mut instruction = JSON_INSTRUCTION.to_string();
+    if let Some(schema) = &request.json_schema {
+        instruction.push_str(&format!(
+            "\nThe JSON must match this schema:\n{}",
+            schema.schema
+        ));
+    }
+    request.system = Some(match request.system.take() {
+        Some(system) if !system.trim().is_empty() => format!("{}\n\n{}", system, instruction),
+        _ => instruction,
+    });
+    request.response_format = None;
+    request.json_schema = None;
+
+    let mut response = complete(request.clone()).await?;
+    if normalize_json_reply(&mut response) {
+        return Ok(response);
+    }
+
+    let reply = reply_text(&response).to_string();
+    request.messages.push(Message::assistant(reply));
+    request.messages.push(Message::user(RETRY_INSTRUCTION));
+
+    let mut response = complete(request).await?;
+    if normalize_json_reply(&mut response) {
+        return Ok(response);
+    }
+
+    Err(AiError::MalformedResponse {
+        message: "Provider did not return valid JSON after a retry".to_string(),
+        raw_response: Some(reply_text(&response).to_string()),
+    })
+}
+
+fn reply_text(response: &CompletionResponse) -> &str {
+    response
+        .choices
+        .first()
+        .and_then(|choice| choice.message.content.as_text())
+        .unwrap_or_default()
+}
+
+/// Replace the reply with its bare JSON if it parses, stripping any code fence
+fn normalize_json_reply(response: &mut CompletionResponse) -> bool {
+    let json = match extract_json(reply_text(response)) {
+        Some(json) => json,
+        None => return false,
+    };
+    if let Some(choice) = response.choices.first_mut() {
+        choice.message.content = MessageContent::Text(json);
+    }
+    true
+}
+
+fn extract_json(text: &str) -> Option<String> {
+    let unfenced = strip_code_fence(text);
+    serde_json::from_str::<serde_json::Value>(unfenced)
+        .ok()
+        .map(|_| unfenced.to_string())
+}
+
+/// Remove a surrounding Markdown code fence, if any
+pub(crate) fn strip_code_fence(text: &str) -> &str {
+    let trimmed = text.trim();
+    trimmed
+        .strip_prefix("```json")
+        .or_else(|| trimmed.strip_prefix("```"))
+        .and_then(|rest| rest.strip_suffix("```"))
+        .unwrap_or(trimmed)
+        .trim()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::MockProvider;
+    use crate::{CompletionProvider, ResponseFormat, Role};
+
+    fn json_request() -> CompletionRequest {
+        CompletionRequest {
+            model: "mock-model".to_string(),
+            messages: vec![Message::user("List two colors")],
+            response_format: Some(ResponseFormat {
+                r#type: ResponseFormatType::JsonObject,
+            }),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_recovers_from_prose_reply() {
+        let mock = MockProvider::new()
+            .with_response("Sure! Here are two colors: red and blue.")
+            .with_response("```json\n{\"colors\": [\"red\", \"blue\"]}\n```");
+
+        let response = complete_with_json_fallback(json_request(), |r| mock.complete(r))
+            .await
+            .unwrap();
+
+        let json: serde_json::Value =
+            serde_json::from_str(response.choices[0].message.content.as_text().unwrap()).unwrap();
+        assert_eq!(json["colors"][1], "blue");
+
+        let requests = mock.requests();
+        assert_eq!(requests.len(), 2);
+        assert!(requests[0].response_format.is_none());
+        assert!(requests[0]
+            .system
+            .as_deref()
+            .unwrap()
+            .contains("valid JSON"));
+        // The retry carries the failed reply and a corrective instruction
+        assert_eq!(requests[1].messages.len(), 3);
+        assert_eq!(requests[1].messages[1].role, Role::Assistant);
+    }
+
+    #[tokio::test]
+    async fn test_gives_up_after_one_retry() {
+        let mock = MockProvider::new()
+            .with_response("not json")
+            .with_response("still not json");
+
+        let err = complete_with_json_fallback(json_request(), |r| mock.complete(r))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, AiError::MalformedResponse { .. }));
+        assert_eq!(mock.request_count(), 2);
+    }
+}
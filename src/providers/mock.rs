@@ -0,0 +1,424 @@
+use async_trait::async_trait;
+use futures::stream::{self, Stream};
+use std::collections::VecDeque;
+use std::pin::Pin;
+use std::sync::Mutex;
+
+use crate::{
+    AiError, Choice, CompletionProvider, CompletionRequest, CompletionResponse, Delta,
+    FunctionCallDelta, Message, MessageContent, ProviderCapabilities, Result, Role, StreamChoice,
+    StreamChunk, ToolCall, ToolCallDelta, Usage,
+};
+
+/// A scripted response returned by [`MockProvider`]
+#[derive(Debug, Clone)]
+pub enum MockResponse {
+    /// Plain assistant text
+    Text(String),
+    /// Assistant message that calls the given tools
+    ToolCalls(Vec<ToolCall>),
+    /// A fully specified response, returned as-is
+    Completion(CompletionResponse),
+    /// An error returned instead of a response
+    Error(AiError),
+}
+
+/// In-memory provider for tests and examples.
+///
+/// Responses are returned in the order they were scripted. Every request is
+/// recorded so tests can assert on what the caller sent.
+pub struct MockProvider {
+    responses: Mutex<VecDeque<MockResponse>>,
+    default_response: Option<String>,
+    requests: Mutex<Vec<CompletionRequest>>,
+    stream_chunk_size: usize,
+}
+
+impl MockProvider {
+    pub fn new() -> Self {
+        Self {
+            responses: Mutex::new(VecDeque::new()),
+            default_response: None,
+            requests: Mutex::new(Vec::new()),
+            stream_chunk_size: 8,
+        }
+    }
+
+    /// Queue a plain text response
+    pub fn with_response(self, text: impl Into<String>) -> Self {
+        self.push_response(MockResponse::Text(text.into()));
+        self
+    }
+
+    /// Queue a response that calls the given tools
+    pub fn with_tool_calls(self, tool_calls: Vec<ToolCall>) -> Self {
+        self.push_response(MockResponse::ToolCalls(tool_calls));
+        self
+    }
+
+    /// Queue a fully specified response
+    pub fn with_completion(self, response: CompletionResponse) -> Self {
+        self.push_response(MockResponse::Completion(response));
+        self
+    }
+
+    /// Queue an error
+    pub fn with_error(self, error: AiError) -> Self {
+        self.push_response(MockResponse::Error(error));
+        self
+    }
+
+    /// Text returned once the scripted responses are exhausted
+    pub fn with_default_response(mut self, text: impl Into<String>) -> Self {
+        self.default_response = Some(text.into());
+        self
+    }
+
+    /// Number of characters per simulated stream chunk
+    pub fn with_stream_chunk_size(mut self, size: usize) -> Self {
+        self.stream_chunk_size = size.max(1);
+        self
+    }
+
+    /// Queue a response after construction
+    pub fn push_response(&self, response: MockResponse) {
+        self.responses.lock().unwrap().push_back(response);
+    }
+
+    /// All requests received so far
+    pub fn requests(&self) -> Vec<CompletionRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    /// The most recent request, if any
+    pub fn last_request(&self) -> Option<CompletionRequest> {
+        self.requests.lock().unwrap().last().cloned()
+    }
+
+    pub fn request_count(&self) -> usize {
+        self.requests.lock().unwrap().len()
+    }
+
+    /// Number of scripted responses not yet consumed
+    pub fn remaining_responses(&self) -> usize {
+        self.responses.lock().unwrap().len()
+    }
+
+    fn next_response(&self, request: CompletionRequest) -> Result<CompletionResponse> {
+        let model = request.model.clone();
+        self.requests.lock().unwrap().push(request);
+
+        let scripted = self.responses.lock().unwrap().pop_front();
+        let scripted = match scripted {
+            Some(response) => response,
+            None => match &self.default_response {
+                Some(text) => MockResponse::Text(text.clone()),
+                None => {
+                    return Err(AiError::InternalError {
+                        message: "MockProvider has no scripted responses left".to_string(),
+                        component: Some("mock".to_string()),
+                    })
+                }
+            },
+        };
+
+        match scripted {
+            MockResponse::Text(text) => Ok(build_response(model, text, None)),
+            MockResponse::ToolCalls(tool_calls) => {
+                Ok(build_response(model, String::new(), Some(tool_calls)))
+            }
+            MockResponse::Completion(response) => Ok(response),
+            MockResponse::Error(error) => Err(error),
+        }
+    }
+}
+
+impl Default for MockProvider {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn build_response(
+    model: String,
+    text: String,
+    tool_calls: Option<Vec<ToolCall>>,
+) -> CompletionResponse {
+    let completion_tokens = (text.len() / 4) as u32;
+    let finish_reason = if tool_calls.is_some() {
+        "tool_calls"
+    } else {
+        "stop"
+    };
+
+    CompletionResponse {
+        id: format!("mock-{}", uuid::Uuid::new_v4()),
+        model,
+        choices: vec![Choice {
+            index: 0,
+            message: Message {
+                tool_calls,
+                ..Message::new(Role::Assistant, MessageContent::Text(text))
+            },
+            finish_reason: Some(finish_reason.to_string()),
+            logprobs: None,
+        }],
+        usage: Some(Usage {
+            prompt_tokens: 10,
+            completion_tokens,
+            total_tokens: 10 + completion_tokens,
+            reasoning_tokens: None,
+        }),
+        metadata: None,
+    }
+}
+
+/// Split a response into stream chunks, ending with a chunk carrying the
+/// finish reason and usage
+fn stream_chunks(response: CompletionResponse, chunk_size: usize) -> Vec<StreamChunk> {
+    let mut chunks = Vec::new();
+    let choice = match response.choices.into_iter().next() {
+        Some(choice) => choice,
+        None => return chunks,
+    };
+
+    let chunk = |delta: Delta, finish_reason: Option<String>, usage: Option<Usage>| StreamChunk {
+        id: response.id.clone(),
+        choices: vec![StreamChoice {
+            index: 0,
+            delta,
+            finish_reason,
+        }],
+        model: Some(response.model.clone()),
+        usage,
+        metadata: None,
+    };
+
+    let text = choice.message.content.as_text().unwrap_or_default();
+    let chars: Vec<char> = text.chars().collect();
+    for (i, piece) in chars.chunks(chunk_size).enumerate() {
+        chunks.push(chunk(
+            Delta {
+                role: if i == 0 { Some(Role::Assistant) } else { None },
+                content: Some(piece.iter().collect()),
+                tool_calls: None,
+            },
+            None,
+            None,
+        ));
+    }
+
+    if let Some(tool_calls) = choice.message.tool_calls {
+        let deltas = tool_calls
+            .into_iter()
+            .enumerate()
+            .map(|(index, call)| ToolCallDelta {
+                index: Some(index as u32),
+                id: Some(call.id),
+                r#type: Some(call.r#type),
+                function: Some(FunctionCallDelta {
+                    name: Some(call.function.name),
+                    arguments: Some(call.function.arguments),
+                }),
+            })
+            .collect();
+        chunks.push(chunk(
+            Delta {
+                role: None,
+                content: None,
+                tool_calls: Some(deltas),
+            },
+            None,
+            None,
+        ));
+    }
+
+    chunks.push(chunk(
+        Delta {
+            role: None,
+            content: None,
+            tool_calls: None,
+        },
+        choice.finish_reason,
+        response.usage.clone(),
+    ));
+
+    chunks
+}
+
+#[async_trait]
+impl CompletionProvider for MockProvider {
+    async fn complete(&self, request: CompletionRequest) -> Result<CompletionResponse> {
+        self.next_response(request)
+    }
+
+    async fn complete_stream(
+        &self,
+        request: CompletionRequest,
+    ) -> Result<Pin<Box<dyn Stream<Item = Result<StreamChunk>> + Send>>> {
+        let response = self.next_response(request)?;
+        let chunks = stream_chunks(response, self.stream_chunk_size);
+        Ok(Box::pin(stream::iter(chunks.into_iter().map(Ok))))
+    }
+
+    fn name(&self) -> &'static str {
+        "mock"
+    }
+
+    fn default_model(&self) -> &'static str {
+        "mock-model"
+    }
+
+    fn available_models(&self) -> Vec<&'static str> {
+        vec!["mock-model"]
+    }
+
+    fn capabilities(&self) -> ProviderCapabilities {
+        ProviderCapabilities {
+            streaming: true,
+            tools: true,
+            vision: true,
+            json_mode: true,
+            max_context_tokens: None,
+            max_output_tokens: None,
+            alternating_roles: false,
+            assistant_prefill: true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{FunctionCall, ToolType};
+    use futures::StreamExt;
+
+    fn request(text: &str) -> CompletionRequest {
+        CompletionRequest::new("mock-model", vec![Message::user(text)])
+    }
+
+    #[tokio::test]
+    async fn test_scripted_responses_and_recording() {
+        let provider = MockProvider::new()
+            .with_response("first")
+            .with_error(AiError::RateLimitExceeded {
+                retry_after: None,
+                daily_limit: None,
+                requests_remaining: None,
+            })
+            .with_response("second");
+
+        let response = provider.complete(request("one")).await.unwrap();
+        assert_eq!(response.choices[0].message.content.as_text(), Some("first"));
+
+        let err = provider.complete(request("two")).await.unwrap_err();
+        assert!(matches!(err, AiError::RateLimitExceeded { .. }));
+
+        let response = provider.complete(request("three")).await.unwrap();
+        assert_eq!(
+            response.choices[0].message.content.as_text(),
+            Some("second")
+        );
+
+        // Exhausted without a default
+        assert!(provider.complete(request("four")).await.is_err());
+
+        assert_eq!(provider.request_count(), 4);
+        let last = provider.last_request().unwrap();
+        assert_eq!(last.messages[0].content.as_text(), Some("four"));
+    }
+
+    #[tokio::test]
+    async fn test_tool_call_response() {
+        let provider = MockProvider::new().with_tool_calls(vec![ToolCall {
+            id: "call_1".to_string(),
+            r#type: ToolType::Function,
+            function: FunctionCall {
+                name: "calculator".to_string(),
+                arguments: r#"{"expression":"1+1"}"#.to_string(),
+            },
+        }]);
+
+        let response = provider.complete(request("add")).await.unwrap();
+        let calls = response.choices[0].message.tool_calls.as_ref().unwrap();
+        assert_eq!(calls[0].function.name, "calculator");
+        assert_eq!(
+            response.choices[0].finish_reason.as_deref(),
+            Some("tool_calls")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_simulated_streaming() {
+        let provider = MockProvider::new()
+            .with_stream_chunk_size(4)
+            .with_default_response("Hello, streaming world!");
+
+        let mut stream = provider.complete_stream(request("hi")).await.unwrap();
+        let mut text = String::new();
+        let mut chunks = 0;
+        let mut finish_reason = None;
+        while let Some(chunk) = stream.next().await {
+            let chunk = chunk.unwrap();
+            chunks += 1;
+            if let Some(content) = &chunk.choices[0].delta.content {
+                text.push_str(content);
+            }
+            if chunk.choices[0].finish_reason.is_some() {
+                finish_reason = chunk.choices[0].finish_reason.clone();
+            }
+        }
+
+        assert_eq!(text, "Hello, streaming world!");
+        assert!(chunks > 2);
+        assert_eq!(finish_reason.as_deref(), Some("stop"));
+    }
+
+    #[tokio::test]
+    async fn test_complete_text() {
+        let provider = MockProvider::new()
+            .with_response("Paris")
+            .with_response("   ");
+
+        let text = provider
+            .complete_text("mock-model", "Capital of France?")
+            .await
+            .unwrap();
+        assert_eq!(text, "Paris");
+        let sent = provider.last_request().unwrap();
+        assert_eq!(sent.model, "mock-model");
+        assert_eq!(sent.messages.len(), 1);
+        assert_eq!(sent.messages[0].role, Role::User);
+        assert_eq!(
+            sent.messages[0].content.as_text(),
+            Some("Capital of France?")
+        );
+
+        let err = provider.complete_text("mock-model", "again").await;
+        assert!(matches!(err, Err(AiError::MalformedResponse { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_health_check() {
+        let provider = MockProvider::new()
+            .with_response("pong")
+            .with_error(AiError::ServiceUnavailable {
+                provider: "mock".to_string(),
+                retry_after: None,
+            })
+            .with_error(AiError::InvalidApiKey {
+                provider: "mock".to_string(),
+            });
+
+        assert!(provider.health_check().await.unwrap());
+        let probe = provider.last_request().unwrap();
+        assert_eq!(probe.model, "mock-model");
+        assert_eq!(probe.max_tokens, Some(1));
+
+        assert!(!provider.health_check().await.unwrap());
+        assert!(matches!(
+            provider.health_check().await,
+            Err(AiError::InvalidApiKey { .. })
+        ));
+    }
+}
@@ -1,19 +1,340 @@
 pub mod anthropic;
+pub mod auth;
 pub mod cohere;
+pub mod config;
 pub mod gemini;
+pub mod json_mode;
+pub mod mock;
 pub mod ollama;
 pub mod openai;
 pub mod openrouter;
+pub mod recording;
 pub mod replicate;
+pub mod self_consistency;
+pub mod sse;
+pub mod tee;
 pub mod together;
 pub mod xai;
 
 pub use anthropic::AnthropicProvider;
+pub use auth::{ApiKeyAuth, Authenticator, NoAuth};
 pub use cohere::CohereProvider;
+pub use config::{ProviderConfig, DEFAULT_USER_AGENT};
 pub use gemini::GeminiProvider;
+pub use mock::{MockProvider, MockResponse};
 pub use ollama::OllamaProvider;
 pub use openai::OpenAIProvider;
 pub use openrouter::OpenRouterProvider;
+pub use recording::{Interaction, RecordingMode, RecordingProvider};
 pub use replicate::ReplicateProvider;
+pub use self_consistency::{majority_vote, self_consistency};
+pub use sse::{stream_to_sse, EventStream, SseEvent, SseParser};
+pub use tee::{tee_stream, TeeStream, TEE_BUFFER_SIZE};
 pub use together::TogetherProvider;
 pub use xai::XAIProvider;
+
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::HashMap;
+
+use crate::{AiError, CompletionProvider, CompletionRequest, Result};
+
+/// Reject empty or whitespace-only API keys before any request is made
+pub(crate) fn validate_api_key(provider: &str, api_key: &str) -> Result<()> {
+    if api_key.trim().is_empty() {
+        return Err(AiError::InvalidApiKey {
+            provider: provider.to_string(),
+        });
+    }
+    Ok(())
+}
+
+/// Request id headers used by the supported providers, in lookup order
+const REQUEST_ID_HEADERS: &[&str] = &["x-request-id", "request-id", "x-goog-request-id"];
+
+/// Provider request id from response headers, for error reports
+pub(crate) fn request_id(headers: &reqwest::header::HeaderMap) -> Option<String> {
+    REQUEST_ID_HEADERS
+        .iter()
+        .find_map(|name| headers.get(*name)?.to_str().ok())
+        .map(String::from)
+}
+
+/// Status, request id and body of a failed response
+pub(crate) struct ErrorResponse {
+    pub status_code: u16,
+    pub request_id: Option<String>,
+    pub body: String,
+}
+
+impl ErrorResponse {
+    /// `ProviderError` reading `{context}: {body}`, retryable on 429 and 5xx
+    pub(crate) fn into_provider_error(self, provider: &str, context: &str) -> AiError {
+        AiError::ProviderError {
+            provider: provider.to_string(),
+            message: format!("{}: {}", context, self.body),
+            error_code: None,
+            retryable: self.status_code == 429 || self.status_code >= 500,
+            status_code: Some(self.status_code),
+            request_id: self.request_id,
+        }
+    }
+}
+
+/// Pass a successful response through; otherwise read the failed one's
+/// status, request id and body and return `to_error` of them
+pub(crate) async fn check_response(
+    response: reqwest::Response,
+    to_error: impl FnOnce(ErrorResponse) -> AiError,
+) -> Result<reqwest::Response> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+    let request_id = request_id(response.headers());
+    let body = response.text().await?;
+    Err(to_error(ErrorResponse {
+        status_code: status.as_u16(),
+        request_id,
+        body,
+    }))
+}
+
+/// Reject [`CompletionRequest::extra`] keys that the provider's request
+/// body already sets. Extras are flattened into the body, so a clash would
+/// send the key twice. `body` is checked before the extras are added; keys
+/// are compared ignoring case and underscores, so `generationConfig` clashes
+/// with `generation_config`.
+pub(crate) fn check_extra(body: &impl Serialize, extra: &HashMap<String, Value>) -> Result<()> {
+    if extra.is_empty() {
+        return Ok(());
+    }
+    let normalize = |key: &str| key.replace('_', "").to_ascii_lowercase();
+    let body = serde_json::to_value(body)?;
+    let fields: Vec<String> = body
+        .as_object()
+        .map(|fields| fields.keys().map(|key| normalize(key)).collect())
+        .unwrap_or_default();
+
+    let mut clashes: Vec<&str> = extra
+        .keys()
+        .filter(|key| fields.contains(&normalize(key)))
+        .map(String::as_str)
+        .collect();
+    if clashes.is_empty() {
+        return Ok(());
+    }
+    clashes.sort_unstable();
+    Err(AiError::InvalidRequest {
+        message: format!(
+            "extra parameters already set by the request: {}; use the matching request field instead",
+            clashes.join(", ")
+        ),
+        field: Some("extra".to_string()),
+        code: None,
+    })
+}
+
+/// Print `value` with object keys sorted, so equal objects print the same
+/// even when serde_json's `preserve_order` feature is enabled
+pub(crate) fn canonical_json(value: &Value) -> String {
+    match value {
+        Value::Object(map) => {
+            let mut entries: Vec<_> = map.iter().collect();
+            entries.sort_by_key(|(key, _)| *key);
+            let fields: Vec<String> = entries
+                .into_iter()
+                .map(|(key, value)| {
+                    format!("{}:{}", Value::from(key.as_str()), canonical_json(value))
+                })
+                .collect();
+            format!("{{{}}}", fields.join(","))
+        }
+        Value::Array(items) => {
+            let items: Vec<String> = items.iter().map(canonical_json).collect();
+            format!("[{}]", items.join(","))
+        }
+        other => other.to_string(),
+    }
+}
+
+/// Reject a model that is not in the provider's `available_models()`
+pub(crate) fn validate_model(provider: &impl CompletionProvider, model: &str) -> Result<()> {
+    let available_models = provider.available_models();
+    if model.is_empty() || available_models.contains(&model) {
+        return Ok(());
+    }
+    Err(AiError::UnsupportedModel {
+        model: model.to_string(),
+        provider: provider.name().to_string(),
+        available_models: available_models.into_iter().map(String::from).collect(),
+    })
+}
+
+/// Context window sizes in tokens by model name prefix; the longest
+/// matching prefix wins
+const CONTEXT_WINDOWS: &[(&str, u32)] = &[
+    ("gpt-3.5-turbo", 16_385),
+    ("gpt-4", 8_192),
+    ("gpt-4-32k", 32_768),
+    ("gpt-4-0125", 128_000),
+    ("gpt-4-1106", 128_000),
+    ("gpt-4-turbo", 128_000),
+    ("gpt-4-vision", 128_000),
+    ("gpt-4o", 128_000),
+    ("gpt-4.1", 1_047_576),
+    ("gpt-4.5", 128_000),
+    ("o1", 200_000),
+    ("o1-mini", 128_000),
+    ("o3", 200_000),
+    ("o4-mini", 200_000),
+    ("claude-", 200_000),
+    ("gemini-1.5-flash", 1_048_576),
+    ("gemini-1.5-pro", 2_097_152),
+    ("gemini-2.0-flash", 1_048_576),
+    ("gemini-2.5", 1_048_576),
+    ("command-r", 128_000),
+    ("command-a", 256_000),
+    ("grok-", 131_072),
+    ("llama-2-", 4_096),
+    ("meta-llama-3-", 8_192),
+    ("meta-llama-3.1-", 131_072),
+    ("mistral-7b-instruct-v0.1", 8_192),
+    ("mistral-7b-instruct-v0.2", 32_768),
+    ("mixtral-8x7b", 32_768),
+    ("mixtral-8x22b", 65_536),
+    ("qwen1.5-72b", 32_768),
+    ("qwen2-72b", 32_768),
+    ("codellama-", 16_384),
+    ("deepseek-coder-", 16_384),
+];
+
+/// Context window of `model` in tokens, if known. Routing prefixes such as
+/// `openai/` or `models/` and case are ignored.
+pub fn context_window(model: &str) -> Option<u32> {
+    let model = model
+        .rsplit('/')
+        .next()
+        .unwrap_or(model)
+        .to_ascii_lowercase();
+    CONTEXT_WINDOWS
+        .iter()
+        .filter(|(prefix, _)| model.starts_with(prefix))
+        .max_by_key(|(prefix, _)| prefix.len())
+        .map(|(_, tokens)| *tokens)
+}
+
+/// Reject a request whose estimated prompt plus `max_tokens` exceeds the
+/// model's context window, before it is sent. The estimate is rough (see
+/// [`CompletionRequest::estimate_tokens`]) and models with an unknown
+/// window are always let through, so providers only run this when asked
+/// to with `with_context_window_check`.
+pub(crate) fn check_context_window(request: &CompletionRequest) -> Result<()> {
+    let Some(max_size) = context_window(&request.model) else {
+        return Ok(());
+    };
+    let size = request.estimate_tokens() + request.max_tokens.unwrap_or(0) as usize;
+    if size > max_size as usize {
+        return Err(AiError::ContextTooLarge {
+            size,
+            max_size: max_size as usize,
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        CompletionProvider, CompletionRequest, ContentPart, ImageUrl, Message, MessageContent, Role,
+    };
+
+    #[test]
+    fn test_context_window_lookup() {
+        assert_eq!(context_window("gpt-4"), Some(8_192));
+        assert_eq!(context_window("gpt-4o-mini"), Some(128_000));
+        assert_eq!(context_window("claude-3-5-sonnet-20241022"), Some(200_000));
+        assert_eq!(context_window("models/gemini-1.5-pro"), Some(2_097_152));
+        assert_eq!(context_window("openai/gpt-4-turbo"), Some(128_000));
+        assert_eq!(context_window("gpt-4-1106-preview"), Some(128_000));
+        assert_eq!(context_window("gpt-4-0125-preview"), Some(128_000));
+        assert_eq!(context_window("gpt-4.5-preview"), Some(128_000));
+        assert_eq!(
+            context_window("mistralai/Mixtral-8x7B-Instruct-v0.1"),
+            Some(32_768)
+        );
+        assert_eq!(context_window("meta/llama-2-70b-chat"), Some(4_096));
+        assert_eq!(
+            context_window("meta-llama/Meta-Llama-3.1-70B-Instruct-Turbo"),
+            Some(131_072)
+        );
+        assert_eq!(context_window("llama3.2"), None);
+    }
+
+    #[test]
+    fn test_provider_capabilities() {
+        let key = || "test-key".to_string();
+
+        let openai = OpenAIProvider::new(key()).capabilities();
+        assert!(openai.streaming && openai.tools && openai.vision && openai.json_mode);
+
+        let anthropic = AnthropicProvider::new(key()).capabilities();
+        assert!(anthropic.tools && anthropic.vision);
+        assert!(!anthropic.json_mode);
+        assert_eq!(anthropic.max_context_tokens, Some(200_000));
+
+        // Gemini sends images as inline data but does not forward tools yet
+        let gemini = GeminiProvider::new(key()).capabilities();
+        assert!(gemini.vision && !gemini.tools);
+
+        let ollama = OllamaProvider::new(None, None).capabilities();
+        assert!(ollama.json_mode && !ollama.vision && !ollama.tools);
+        assert_eq!(ollama.max_context_tokens, None);
+
+        let cohere = CohereProvider::new(Some(key())).unwrap().capabilities();
+        assert!(cohere.tools && !cohere.vision && !cohere.json_mode);
+
+        assert!(
+            TogetherProvider::new(Some(key()))
+                .unwrap()
+                .capabilities()
+                .json_mode
+        );
+        assert!(
+            !ReplicateProvider::new(Some(key()))
+                .unwrap()
+                .capabilities()
+                .json_mode
+        );
+        assert!(XAIProvider::new(key()).capabilities().tools);
+        assert!(OpenRouterProvider::new(key()).capabilities().tools);
+    }
+
+    #[test]
+    fn test_capabilities_route_image_requests() {
+        let request = CompletionRequest {
+            model: "any".to_string(),
+            messages: vec![Message::new(
+                Role::User,
+                MessageContent::Parts(vec![ContentPart::Image {
+                    image_url: ImageUrl {
+                        url: "https://example.com/cat.png".to_string(),
+                        detail: None,
+                    },
+                }]),
+            )],
+            ..Default::default()
+        };
+
+        let providers: Vec<Box<dyn CompletionProvider>> = vec![
+            Box::new(CohereProvider::new(Some("key".to_string())).unwrap()),
+            Box::new(OpenAIProvider::new("key".to_string())),
+        ];
+        let chosen = providers
+            .iter()
+            .find(|p| p.capabilities().supports(&request))
+            .unwrap();
+        assert_eq!(chosen.name(), OpenAIProvider::new("key".to_string()).name());
+    }
+}
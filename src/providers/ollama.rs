@@ -1,18 +1,30 @@
 use async_trait::async_trait;
-use futures::stream::{Stream, StreamExt};
+use futures::stream::{Stream, StreamExt, TryStreamExt};
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
 use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
 
 use crate::{
+    providers::{auth::SendAuthenticated, Authenticator, NoAuth, ProviderConfig},
     AiError, Choice, CompletionProvider, CompletionRequest, CompletionResponse, Message,
-    MessageContent, Result, Role, StreamChunk, Usage,
+    MessageContent, ProviderCapabilities, Result, Role, StreamChunk, Usage,
 };
 
 /// Ollama provider for local LLM support
+///
+/// There is no `with_context_window_check`: a model's window depends on the
+/// server's `num_ctx` setting, so oversized prompts are left to Ollama.
 pub struct OllamaProvider {
     client: Client,
+    /// Set by `with_client`, so `with_config` keeps the injected client
+    custom_client: bool,
+    auth: Arc<dyn Authenticator>,
     base_url: String,
+    stream_idle_timeout: Option<Duration>,
     #[allow(dead_code)]
     default_model: String,
 }
@@ -25,26 +37,57 @@ impl OllamaProvider {
     /// * `default_model` - The default model to use (e.g., "llama2", "mistral", "codellama")
     pub fn new(base_url: Option<String>, default_model: Option<String>) -> Self {
         Self {
-            client: Client::new(),
+            client: super::config::default_client(),
+            custom_client: false,
+            auth: Arc::new(NoAuth),
             base_url: base_url.unwrap_or_else(|| "http://localhost:11434".to_string()),
+            stream_idle_timeout: None,
             default_model: default_model.unwrap_or_else(|| "llama2".to_string()),
         }
     }
 
+    /// Send every request with the user agent and default headers from
+    /// `config`. A client given to [`with_client`](Self::with_client),
+    /// before or after this call, is kept; the user agent and headers are
+    /// then up to that client.
+    pub fn with_config(mut self, config: &ProviderConfig) -> Result<Self> {
+        let client = config.build_client()?;
+        if !self.custom_client {
+            self.client = client;
+        }
+        self.stream_idle_timeout = config.stream_idle_timeout;
+        Ok(self)
+    }
+
+    /// Send requests through `client`, sharing its connection pool and DNS
+    /// cache with other providers. It takes precedence over the client
+    /// [`with_config`](Self::with_config) builds.
+    pub fn with_client(mut self, client: Client) -> Self {
+        self.client = client;
+        self.custom_client = true;
+        self
+    }
+
+    /// Authenticate requests with `auth`, e.g. for an Ollama server behind
+    /// a proxy; requests are sent without credentials by default
+    pub fn with_authenticator(mut self, auth: impl Authenticator + 'static) -> Self {
+        self.auth = Arc::new(auth);
+        self
+    }
+
     /// List available models on the Ollama server
     pub async fn list_models(&self) -> Result<Vec<OllamaModel>> {
         let url = format!("{}/api/tags", self.base_url);
-        let response = self.client.get(&url).send().await?;
-
-        if !response.status().is_success() {
-            let error_text = response.text().await?;
-            return Err(AiError::ProviderError {
-                provider: "ollama".to_string(),
-                message: format!("Failed to list models: {}", error_text),
-                error_code: None,
-                retryable: false,
-            });
-        }
+        let response = self
+            .client
+            .get(&url)
+            .send_authenticated(self.auth.as_ref())
+            .await?;
+
+        let response = super::check_response(response, |error| {
+            error.into_provider_error("ollama", "Failed to list models")
+        })
+        .await?;
 
         let models_response: OllamaModelsResponse = response.json().await?;
         Ok(models_response.models)
@@ -58,30 +101,21 @@ impl OllamaProvider {
             stream: false,
         };
 
-        let response = self.client.post(&url).json(&request).send().await?;
+        let response = self
+            .client
+            .post(&url)
+            .json(&request)
+            .send_authenticated(self.auth.as_ref())
+            .await?;
 
-        if !response.status().is_success() {
-            let error_text = response.text().await?;
-            return Err(AiError::ProviderError {
-                provider: "ollama".to_string(),
-                message: format!("Failed to pull model {}: {}", model_name, error_text),
-                error_code: None,
-                retryable: true,
-            });
-        }
+        super::check_response(response, |error| {
+            error.into_provider_error("ollama", &format!("Failed to pull model {}", model_name))
+        })
+        .await?;
 
         Ok(())
     }
 
-    /// Check if Ollama is running and accessible
-    pub async fn health_check(&self) -> Result<bool> {
-        let url = format!("{}/api/tags", self.base_url);
-        match self.client.get(&url).send().await {
-            Ok(response) => Ok(response.status().is_success()),
-            Err(_) => Ok(false),
-        }
-    }
-
     fn convert_message(&self, message: &Message) -> OllamaMessage {
         let content = match &message.content {
             MessageContent::Text(text) => text.clone(),
@@ -92,6 +126,7 @@ impl OllamaProvider {
                     .iter()
                     .filter_map(|part| match part {
                         crate::ContentPart::Text { text } => Some(text.clone()),
+                        crate::ContentPart::Json { value } => Some(value.to_string()),
                         _ => None,
                     })
                     .collect::<Vec<_>>()
@@ -101,10 +136,8 @@ impl OllamaProvider {
 
         OllamaMessage {
             role: match message.role {
-                Role::System => "system".to_string(),
-                Role::User => "user".to_string(),
-                Role::Assistant => "assistant".to_string(),
                 Role::Tool => "assistant".to_string(), // Ollama doesn't have a specific tool role
+                ref role => role.to_string(),
             },
             content,
             images: None, // TODO: Extract images from multimodal content
@@ -120,31 +153,31 @@ impl OllamaProvider {
             model: response.model,
             choices: vec![Choice {
                 index: 0,
-                message: Message {
-                    role: Role::Assistant,
-                    content: MessageContent::text(response.message.content),
-                    tool_calls: None,
-                    tool_call_id: None,
-                },
+                message: Message::assistant(response.message.content),
                 finish_reason: if response.done {
                     Some("stop".to_string())
                 } else {
                     None
                 },
+                logprobs: None,
             }],
             usage: Some(Usage {
                 prompt_tokens: response.prompt_eval_count.unwrap_or(0) as u32,
                 completion_tokens: response.eval_count.unwrap_or(0) as u32,
                 total_tokens: (response.prompt_eval_count.unwrap_or(0)
                     + response.eval_count.unwrap_or(0)) as u32,
+                reasoning_tokens: None,
             }),
+            metadata: None,
         }
     }
 }
 
 #[async_trait]
 impl CompletionProvider for OllamaProvider {
-    async fn complete(&self, request: CompletionRequest) -> Result<CompletionResponse> {
+    async fn complete(&self, mut request: CompletionRequest) -> Result<CompletionResponse> {
+        request.normalize_system();
+        request.validate()?;
         let url = format!("{}/api/chat", self.base_url);
 
         // Convert messages
@@ -155,7 +188,7 @@ impl CompletionProvider for OllamaProvider {
             .collect();
 
         // Build Ollama request
-        let ollama_request = OllamaChatRequest {
+        let mut ollama_request = OllamaChatRequest {
             model: request.model.clone(),
             messages,
             stream: false,
@@ -173,20 +206,22 @@ impl CompletionProvider for OllamaProvider {
                 num_predict: request.max_tokens.map(|t| t as i32),
                 stop: request.stop.clone(),
             },
+            extra: HashMap::new(),
         };
+        super::check_extra(&ollama_request, &request.extra)?;
+        ollama_request.extra = request.extra.clone();
 
-        let response = self.client.post(&url).json(&ollama_request).send().await?;
-
-        let status = response.status();
-        if !status.is_success() {
-            let error_text = response.text().await?;
-            return Err(AiError::ProviderError {
-                provider: "ollama".to_string(),
-                message: format!("Ollama API error: {}", error_text),
-                error_code: None,
-                retryable: status.is_server_error(),
-            });
-        }
+        let response = self
+            .client
+            .post(&url)
+            .json(&ollama_request)
+            .send_authenticated(self.auth.as_ref())
+            .await?;
+
+        let response = super::check_response(response, |error| {
+            error.into_provider_error("ollama", "Ollama API error")
+        })
+        .await?;
 
         let ollama_response: OllamaResponse = response.json().await?;
         Ok(self.convert_to_standard_response(ollama_response))
@@ -194,8 +229,10 @@ impl CompletionProvider for OllamaProvider {
 
     async fn complete_stream(
         &self,
-        request: CompletionRequest,
+        mut request: CompletionRequest,
     ) -> Result<Pin<Box<dyn Stream<Item = Result<StreamChunk>> + Send>>> {
+        request.normalize_system();
+        request.validate()?;
         let url = format!("{}/api/chat", self.base_url);
 
         // Convert messages
@@ -206,7 +243,7 @@ impl CompletionProvider for OllamaProvider {
             .collect();
 
         // Build Ollama request with streaming enabled
-        let ollama_request = OllamaChatRequest {
+        let mut ollama_request = OllamaChatRequest {
             model: request.model.clone(),
             messages,
             stream: true,
@@ -224,30 +261,47 @@ impl CompletionProvider for OllamaProvider {
                 num_predict: request.max_tokens.map(|t| t as i32),
                 stop: request.stop.clone(),
             },
+            extra: HashMap::new(),
         };
+        super::check_extra(&ollama_request, &request.extra)?;
+        ollama_request.extra = request.extra.clone();
 
-        let response = self.client.post(&url).json(&ollama_request).send().await?;
-
-        let status = response.status();
-        if !status.is_success() {
-            let error_text = response.text().await?;
-            return Err(AiError::ProviderError {
-                provider: "ollama".to_string(),
-                message: format!("Ollama API error: {}", error_text),
-                error_code: None,
-                retryable: status.is_server_error(),
-            });
-        }
+        let response = self
+            .client
+            .post(&url)
+            .json(&ollama_request)
+            .send_authenticated(self.auth.as_ref())
+            .await?;
+
+        let response = super::check_response(response, |error| {
+            error.into_provider_error("ollama", "Ollama API error")
+        })
+        .await?;
+
+        // Ollama has no response id, so the first chunk's timestamp is used
+        // for the whole stream, matching `complete`
+        let mut stream_id: Option<String> = None;
 
         // Convert the response stream
-        let stream = response.bytes_stream();
+        let stream = response.bytes_stream().map_err(|e| AiError::StreamError {
+            message: e.to_string(),
+            retryable: true,
+        });
+        let stream = super::sse::idle_timeout(stream, self.stream_idle_timeout);
         let mapped_stream = stream.map(move |chunk_result| {
             match chunk_result {
                 Ok(chunk) => {
                     // Parse the JSON line
                     match serde_json::from_slice::<OllamaStreamResponse>(&chunk) {
                         Ok(ollama_chunk) => Ok(StreamChunk {
-                            id: "ollama_stream".to_string(),
+                            id: stream_id
+                                .get_or_insert_with(|| {
+                                    ollama_chunk
+                                        .created_at
+                                        .clone()
+                                        .unwrap_or_else(|| "ollama_response".to_string())
+                                })
+                                .clone(),
                             choices: vec![crate::StreamChoice {
                                 index: 0,
                                 delta: crate::Delta {
@@ -270,6 +324,8 @@ impl CompletionProvider for OllamaProvider {
                                 },
                             }],
                             model: Some(ollama_chunk.model),
+                            usage: None,
+                            metadata: None,
                         }),
                         Err(e) => Err(AiError::StreamError {
                             message: format!("Failed to parse Ollama stream chunk: {}", e),
@@ -277,16 +333,27 @@ impl CompletionProvider for OllamaProvider {
                         }),
                     }
                 }
-                Err(e) => Err(AiError::StreamError {
-                    message: e.to_string(),
-                    retryable: true,
-                }),
+                Err(e) => Err(e),
             }
         });
 
         Ok(Box::pin(mapped_stream))
     }
 
+    /// Check if Ollama is running and accessible
+    async fn health_check(&self) -> Result<bool> {
+        let url = format!("{}/api/tags", self.base_url);
+        match self
+            .client
+            .get(&url)
+            .send_authenticated(self.auth.as_ref())
+            .await
+        {
+            Ok(response) => Ok(response.status().is_success()),
+            Err(_) => Ok(false),
+        }
+    }
+
     fn name(&self) -> &'static str {
         "ollama"
     }
@@ -314,6 +381,19 @@ impl CompletionProvider for OllamaProvider {
             "dolphin-mistral",
         ]
     }
+
+    fn capabilities(&self) -> ProviderCapabilities {
+        ProviderCapabilities {
+            streaming: true,
+            tools: false,
+            vision: false,
+            json_mode: true,
+            max_context_tokens: None,
+            max_output_tokens: None,
+            alternating_roles: false,
+            assistant_prefill: false,
+        }
+    }
 }
 
 // Ollama API types
@@ -334,6 +414,8 @@ struct OllamaChatRequest {
     #[serde(skip_serializing_if = "Option::is_none")]
     format: Option<String>,
     options: OllamaOptions,
+    #[serde(flatten)]
+    extra: HashMap<String, Value>,
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -365,7 +447,6 @@ struct OllamaResponse {
 #[derive(Debug, Clone, Deserialize)]
 struct OllamaStreamResponse {
     model: String,
-    #[allow(dead_code)]
     created_at: Option<String>,
     message: OllamaMessage,
     done: bool,
@@ -2,18 +2,33 @@ use async_trait::async_trait;
 use futures::stream::{Stream, StreamExt};
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
 use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
 
 use crate::{
-    AiError, Choice, CompletionProvider, CompletionRequest, CompletionResponse, ContentPart, Delta,
-    Message, MessageContent, ResponseFormat, Result, Role, StreamChoice, StreamChunk, Tool,
-    ToolCall, ToolCallDelta, ToolChoice, Usage,
+    providers::{auth::SendAuthenticated, ApiKeyAuth, Authenticator, EventStream, ProviderConfig},
+    AiError, BatchHandle, BatchProvider, BatchState, BatchStatus, Choice, CompletionProvider,
+    CompletionRequest, CompletionResponse, ContentPart, Delta, GeneratedImage, ImageData,
+    ImageGenerationProvider, ImageSize, LogProbs, Message, MessageContent, ModelInfo,
+    ProviderCapabilities, ReasoningEffort, ResponseFormat, Result, Role, StreamChoice, StreamChunk,
+    TokenLogProb, Tool, ToolCall, ToolCallDelta, ToolChoice, Transcript, TranscriptSegment,
+    TranscriptionProvider, Usage,
 };
 
 pub struct OpenAIProvider {
     client: Client,
-    api_key: String,
+    /// Set by `with_client`, so `with_config` keeps the injected client
+    custom_client: bool,
+    auth: Arc<dyn Authenticator>,
     base_url: String,
+    stream_idle_timeout: Option<Duration>,
+    validate_models: bool,
+    check_context_window: bool,
+    image_model: String,
+    transcription_model: String,
 }
 
 impl OpenAIProvider {
@@ -21,47 +36,201 @@ impl OpenAIProvider {
         Self::with_base_url(api_key, "https://api.openai.com/v1".to_string())
     }
 
+    /// Create a provider, rejecting an empty or whitespace-only API key
+    pub fn try_new(api_key: String) -> Result<Self> {
+        super::validate_api_key("openai", &api_key)?;
+        Ok(Self::new(api_key))
+    }
+
     pub fn with_base_url(api_key: String, base_url: String) -> Self {
         Self {
-            client: Client::new(),
-            api_key,
+            client: super::config::default_client(),
+            custom_client: false,
+            auth: Arc::new(ApiKeyAuth::bearer(api_key)),
             base_url,
+            stream_idle_timeout: None,
+            validate_models: false,
+            check_context_window: false,
+            image_model: "gpt-image-1".to_string(),
+            transcription_model: "whisper-1".to_string(),
         }
     }
 
+    /// Reject models missing from `available_models()` before sending,
+    /// instead of waiting for the API to refuse them
+    pub fn with_model_validation(mut self, enabled: bool) -> Self {
+        self.validate_models = enabled;
+        self
+    }
+
+    /// Reject a request whose estimated size exceeds the model's context
+    /// window before sending it (see [`context_window`]). Off by default:
+    /// the estimate is rough and could turn away requests the API accepts.
+    ///
+    /// [`context_window`]: crate::providers::context_window
+    pub fn with_context_window_check(mut self, enabled: bool) -> Self {
+        self.check_context_window = enabled;
+        self
+    }
+
+    /// Send every request with the user agent and default headers from
+    /// `config`. A client given to [`with_client`](Self::with_client),
+    /// before or after this call, is kept; the user agent and headers are
+    /// then up to that client.
+    pub fn with_config(mut self, config: &ProviderConfig) -> Result<Self> {
+        let client = config.build_client()?;
+        if !self.custom_client {
+            self.client = client;
+        }
+        self.stream_idle_timeout = config.stream_idle_timeout;
+        Ok(self)
+    }
+
+    /// Send requests through `client`, sharing its connection pool and DNS
+    /// cache with other providers. It takes precedence over the client
+    /// [`with_config`](Self::with_config) builds.
+    pub fn with_client(mut self, client: Client) -> Self {
+        self.client = client;
+        self.custom_client = true;
+        self
+    }
+
+    /// Authenticate requests with `auth` instead of the API key, e.g. for a
+    /// proxy or gateway with its own credentials
+    pub fn with_authenticator(mut self, auth: impl Authenticator + 'static) -> Self {
+        self.auth = Arc::new(auth);
+        self
+    }
+
+    /// Model used by `generate_image` (default `gpt-image-1`), e.g.
+    /// `dall-e-3`
+    pub fn with_image_model(mut self, model: impl Into<String>) -> Self {
+        self.image_model = model.into();
+        self
+    }
+
+    /// Model used by `transcribe` (default `whisper-1`), e.g.
+    /// `gpt-4o-transcribe`
+    pub fn with_transcription_model(mut self, model: impl Into<String>) -> Self {
+        self.transcription_model = model.into();
+        self
+    }
+
     fn convert_message(&self, msg: Message) -> OpenAIMessage {
-        let content = match msg.content {
-            MessageContent::Text(text) => OpenAIContent::String(text),
-            MessageContent::Parts(parts) => OpenAIContent::Array(
-                parts
-                    .into_iter()
-                    .map(|part| match part {
-                        ContentPart::Text { text } => OpenAIContentPart {
-                            r#type: "text".to_string(),
-                            text: Some(text),
-                            image_url: None,
-                        },
-                        ContentPart::Image { image_url } => OpenAIContentPart {
-                            r#type: "image_url".to_string(),
-                            text: None,
-                            image_url: Some(image_url),
-                        },
-                    })
-                    .collect(),
-            ),
+        // Structured tool results are sent as a JSON string
+        let content = if let Some(value) = msg.content.as_json() {
+            OpenAIContent::String(value.to_string())
+        } else {
+            match msg.content {
+                MessageContent::Text(text) => OpenAIContent::String(text),
+                MessageContent::Parts(parts) => OpenAIContent::Array(
+                    parts
+                        .into_iter()
+                        .map(|part| match part {
+                            ContentPart::Text { text } => OpenAIContentPart {
+                                r#type: "text".to_string(),
+                                text: Some(text),
+                                ..Default::default()
+                            },
+                            ContentPart::Image { image_url } => OpenAIContentPart {
+                                r#type: "image_url".to_string(),
+                                image_url: Some(image_url),
+                                ..Default::default()
+                            },
+                            ContentPart::Audio { data, format } => OpenAIContentPart {
+                                r#type: "input_audio".to_string(),
+                                input_audio: Some(OpenAIInputAudio { data, format }),
+                                ..Default::default()
+                            },
+                            ContentPart::Document {
+                                data,
+                                mime_type,
+                                name,
+                            } => OpenAIContentPart {
+                                r#type: "file".to_string(),
+                                file: Some(OpenAIFile {
+                                    file_data: format!("data:{};base64,{}", mime_type, data),
+                                    filename: name,
+                                }),
+                                ..Default::default()
+                            },
+                            ContentPart::Json { value } => OpenAIContentPart {
+                                r#type: "text".to_string(),
+                                text: Some(value.to_string()),
+                                ..Default::default()
+                            },
+                        })
+                        .collect(),
+                ),
+            }
         };
 
         OpenAIMessage {
-            role: match msg.role {
-                Role::System => "system".to_string(),
-                Role::User => "user".to_string(),
-                Role::Assistant => "assistant".to_string(),
-                Role::Tool => "tool".to_string(),
-            },
+            role: msg.role.to_string(),
             content: Some(content),
             tool_calls: msg.tool_calls,
             tool_call_id: msg.tool_call_id,
+            reasoning_content: None,
+            name: msg.name,
+            refusal: None,
+        }
+    }
+
+    fn build_request(&self, mut request: CompletionRequest, stream: bool) -> Result<OpenAIRequest> {
+        request.normalize_system();
+        request.validate()?;
+        if self.validate_models {
+            super::validate_model(self, &request.model)?;
+        }
+        if self.check_context_window {
+            super::check_context_window(&request)?;
         }
+
+        if let Some(tool_choice) = &request.tool_choice {
+            tool_choice.validate(request.tools.as_deref())?;
+        }
+
+        // Reasoning models reject `max_tokens`
+        let (max_tokens, max_completion_tokens) = if uses_max_completion_tokens(&request.model) {
+            (None, request.max_tokens)
+        } else {
+            (request.max_tokens, None)
+        };
+
+        let mut body = OpenAIRequest {
+            model: request.model,
+            messages: request
+                .messages
+                .into_iter()
+                .map(|m| self.convert_message(m))
+                .collect(),
+            temperature: request.temperature,
+            max_tokens,
+            max_completion_tokens,
+            stream: Some(stream),
+            stream_options: stream.then_some(OpenAIStreamOptions {
+                include_usage: true,
+            }),
+            top_p: request.top_p,
+            frequency_penalty: request.frequency_penalty,
+            presence_penalty: request.presence_penalty,
+            stop: request.stop,
+            tools: request.tools,
+            tool_choice: request.tool_choice,
+            response_format: request.response_format,
+            reasoning_effort: request.reasoning_effort,
+            // `top_logprobs` is rejected unless `logprobs` is also set;
+            // `validate` has ruled out an explicit `false`
+            logprobs: request
+                .logprobs
+                .or(request.top_logprobs.is_some().then_some(true)),
+            top_logprobs: request.top_logprobs,
+            service_tier: request.service_tier,
+            extra: HashMap::new(),
+        };
+        super::check_extra(&body, &request.extra)?;
+        body.extra = request.extra;
+        Ok(body)
     }
 
     fn convert_response(&self, resp: OpenAIResponse) -> CompletionResponse {
@@ -74,43 +243,100 @@ impl OpenAIProvider {
                 .map(|c| Choice {
                     index: c.index,
                     message: Message {
-                        role: match c.message.role.as_str() {
-                            "system" => Role::System,
-                            "user" => Role::User,
-                            "assistant" => Role::Assistant,
-                            "tool" => Role::Tool,
-                            _ => Role::Assistant,
-                        },
-                        content: match c.message.content {
-                            Some(OpenAIContent::String(s)) => MessageContent::Text(s),
-                            Some(OpenAIContent::Array(parts)) => MessageContent::Parts(
-                                parts
-                                    .into_iter()
-                                    .filter_map(|p| {
-                                        if p.r#type == "text" {
-                                            p.text.map(|text| ContentPart::Text { text })
-                                        } else if p.r#type == "image_url" {
-                                            p.image_url
-                                                .map(|image_url| ContentPart::Image { image_url })
-                                        } else {
-                                            None
-                                        }
-                                    })
-                                    .collect(),
-                            ),
-                            None => MessageContent::Text("".to_string()),
-                        },
                         tool_calls: c.message.tool_calls,
-                        tool_call_id: None,
+                        reasoning_content: c.message.reasoning_content,
+                        name: c.message.name,
+                        refusal: c.message.refusal,
+                        ..Message::new(
+                            c.message.role.parse().unwrap_or(Role::Assistant),
+                            match c.message.content {
+                                Some(OpenAIContent::String(s)) => MessageContent::Text(s),
+                                Some(OpenAIContent::Array(parts)) => MessageContent::Parts(
+                                    parts
+                                        .into_iter()
+                                        .filter_map(|p| {
+                                            if p.r#type == "text" {
+                                                p.text.map(|text| ContentPart::Text { text })
+                                            } else if p.r#type == "image_url" {
+                                                p.image_url.map(|image_url| ContentPart::Image {
+                                                    image_url,
+                                                })
+                                            } else {
+                                                None
+                                            }
+                                        })
+                                        .collect(),
+                                ),
+                                None => MessageContent::Text("".to_string()),
+                            },
+                        )
                     },
                     finish_reason: c.finish_reason,
+                    logprobs: c.logprobs.map(|logprobs| LogProbs {
+                        content: logprobs.content.unwrap_or_default(),
+                    }),
                 })
                 .collect(),
-            usage: resp.usage,
+            usage: resp.usage.map(OpenAIUsage::into_usage),
+            metadata: resp
+                .service_tier
+                .map(|tier| serde_json::json!({ "service_tier": tier })),
         }
     }
 }
 
+/// Classify a failed response from its status and `error.code`:
+/// `insufficient_quota` is a non-retryable `QuotaExceeded` even though it
+/// arrives as a 429, while rate limits and 5xx are retried
+fn openai_api_error(
+    api: &str,
+    status_code: u16,
+    body: &str,
+    request_id: Option<String>,
+) -> AiError {
+    let error_code = serde_json::from_str::<serde_json::Value>(body)
+        .ok()
+        .and_then(|v| {
+            let error = &v["error"];
+            error["code"]
+                .as_str()
+                .or_else(|| error["type"].as_str())
+                .map(str::to_string)
+        });
+
+    let message = format!("{} error: {}", api, body);
+
+    if matches!(
+        error_code.as_deref(),
+        Some("insufficient_quota" | "billing_hard_limit_reached")
+    ) {
+        return AiError::QuotaExceeded {
+            provider: "openai".to_string(),
+            quota_type: "billing".to_string(),
+            reset_time: None,
+            message,
+            request_id,
+        };
+    }
+
+    AiError::ProviderError {
+        provider: "openai".to_string(),
+        message,
+        error_code,
+        retryable: status_code == 429 || status_code >= 500,
+        status_code: Some(status_code),
+        request_id,
+    }
+}
+
+/// Whether the model takes `max_completion_tokens` instead of `max_tokens`
+/// (the o-series reasoning models and gpt-5)
+fn uses_max_completion_tokens(model: &str) -> bool {
+    let mut chars = model.chars();
+    let o_series = chars.next() == Some('o') && chars.next().is_some_and(|c| c.is_ascii_digit());
+    o_series || model.starts_with("gpt-5")
+}
+
 #[derive(Serialize)]
 struct OpenAIRequest {
     model: String,
@@ -120,8 +346,12 @@ struct OpenAIRequest {
     #[serde(skip_serializing_if = "Option::is_none")]
     max_tokens: Option<u32>,
     #[serde(skip_serializing_if = "Option::is_none")]
+    max_completion_tokens: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     stream: Option<bool>,
     #[serde(skip_serializing_if = "Option::is_none")]
+    stream_options: Option<OpenAIStreamOptions>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     top_p: Option<f32>,
     #[serde(skip_serializing_if = "Option::is_none")]
     frequency_penalty: Option<f32>,
@@ -135,6 +365,22 @@ struct OpenAIRequest {
     tool_choice: Option<ToolChoice>,
     #[serde(skip_serializing_if = "Option::is_none")]
     response_format: Option<ResponseFormat>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reasoning_effort: Option<ReasoningEffort>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    logprobs: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_logprobs: Option<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    service_tier: Option<String>,
+    #[serde(flatten)]
+    extra: HashMap<String, Value>,
+}
+
+/// Asks for a final chunk carrying the usage of the whole response
+#[derive(Serialize)]
+struct OpenAIStreamOptions {
+    include_usage: bool,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -146,6 +392,14 @@ struct OpenAIMessage {
     tool_calls: Option<Vec<ToolCall>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     tool_call_id: Option<String>,
+    /// Returned by OpenAI-compatible reasoning models (e.g. via OpenRouter)
+    #[serde(default, skip_serializing)]
+    reasoning_content: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+    /// Set instead of `content` when the model declines to answer
+    #[serde(default, skip_serializing)]
+    refusal: Option<String>,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -155,13 +409,30 @@ enum OpenAIContent {
     Array(Vec<OpenAIContentPart>),
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Default, Serialize, Deserialize)]
 struct OpenAIContentPart {
     r#type: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     text: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     image_url: Option<crate::ImageUrl>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    input_audio: Option<OpenAIInputAudio>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    file: Option<OpenAIFile>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct OpenAIInputAudio {
+    data: String,
+    format: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct OpenAIFile {
+    file_data: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    filename: Option<String>,
 }
 
 #[derive(Deserialize)]
@@ -170,7 +441,38 @@ struct OpenAIResponse {
     model: String,
     choices: Vec<OpenAIChoice>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    usage: Option<Usage>,
+    usage: Option<OpenAIUsage>,
+    /// Tier that actually processed the request
+    #[serde(default)]
+    service_tier: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct OpenAIUsage {
+    prompt_tokens: u32,
+    completion_tokens: u32,
+    total_tokens: u32,
+    #[serde(default)]
+    completion_tokens_details: Option<OpenAICompletionTokensDetails>,
+}
+
+impl OpenAIUsage {
+    fn into_usage(self) -> Usage {
+        Usage {
+            prompt_tokens: self.prompt_tokens,
+            completion_tokens: self.completion_tokens,
+            total_tokens: self.total_tokens,
+            reasoning_tokens: self
+                .completion_tokens_details
+                .and_then(|d| d.reasoning_tokens),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct OpenAICompletionTokensDetails {
+    #[serde(default)]
+    reasoning_tokens: Option<u32>,
 }
 
 #[derive(Deserialize)]
@@ -179,6 +481,15 @@ struct OpenAIChoice {
     message: OpenAIMessage,
     #[serde(skip_serializing_if = "Option::is_none")]
     finish_reason: Option<String>,
+    #[serde(default)]
+    logprobs: Option<OpenAILogProbs>,
+}
+
+/// `content` is null when the reply has no text, e.g. only tool calls
+#[derive(Deserialize)]
+struct OpenAILogProbs {
+    #[serde(default)]
+    content: Option<Vec<TokenLogProb>>,
 }
 
 #[derive(Deserialize)]
@@ -190,6 +501,8 @@ struct OpenAIStreamChunk {
     created: u64,
     model: String,
     choices: Vec<OpenAIStreamChoice>,
+    #[serde(default)]
+    usage: Option<OpenAIUsage>,
 }
 
 #[derive(Deserialize)]
@@ -213,42 +526,24 @@ struct OpenAIDelta {
 #[async_trait]
 impl CompletionProvider for OpenAIProvider {
     async fn complete(&self, request: CompletionRequest) -> Result<CompletionResponse> {
-        let openai_request = OpenAIRequest {
-            model: request.model,
-            messages: request
-                .messages
-                .into_iter()
-                .map(|m| self.convert_message(m))
-                .collect(),
-            temperature: request.temperature,
-            max_tokens: request.max_tokens,
-            stream: Some(false),
-            top_p: request.top_p,
-            frequency_penalty: request.frequency_penalty,
-            presence_penalty: request.presence_penalty,
-            stop: request.stop,
-            tools: request.tools,
-            tool_choice: request.tool_choice,
-            response_format: request.response_format,
-        };
+        let openai_request = self.build_request(request, false)?;
 
         let response = self
             .client
             .post(format!("{}/chat/completions", self.base_url))
-            .header("Authorization", format!("Bearer {}", self.api_key))
             .json(&openai_request)
-            .send()
+            .send_authenticated(self.auth.as_ref())
             .await?;
 
-        if !response.status().is_success() {
-            let error_text = response.text().await?;
-            return Err(AiError::ProviderError {
-                provider: "openai".to_string(),
-                message: format!("OpenAI API error: {}", error_text),
-                error_code: None,
-                retryable: true,
-            });
-        }
+        let response = super::check_response(response, |error| {
+            openai_api_error(
+                "OpenAI API",
+                error.status_code,
+                &error.body,
+                error.request_id,
+            )
+        })
+        .await?;
 
         let openai_response: OpenAIResponse = response.json().await?;
         Ok(self.convert_response(openai_response))
@@ -258,55 +553,28 @@ impl CompletionProvider for OpenAIProvider {
         &self,
         request: CompletionRequest,
     ) -> Result<Pin<Box<dyn Stream<Item = Result<StreamChunk>> + Send>>> {
-        let openai_request = OpenAIRequest {
-            model: request.model,
-            messages: request
-                .messages
-                .into_iter()
-                .map(|m| self.convert_message(m))
-                .collect(),
-            temperature: request.temperature,
-            max_tokens: request.max_tokens,
-            stream: Some(true),
-            top_p: request.top_p,
-            frequency_penalty: request.frequency_penalty,
-            presence_penalty: request.presence_penalty,
-            stop: request.stop,
-            tools: request.tools,
-            tool_choice: request.tool_choice,
-            response_format: request.response_format,
-        };
+        let openai_request = self.build_request(request, true)?;
 
         let response = self
             .client
             .post(format!("{}/chat/completions", self.base_url))
-            .header("Authorization", format!("Bearer {}", self.api_key))
             .json(&openai_request)
-            .send()
+            .send_authenticated(self.auth.as_ref())
             .await?;
 
-        if !response.status().is_success() {
-            let error_text = response.text().await?;
-            return Err(AiError::ProviderError {
-                provider: "openai".to_string(),
-                message: format!("OpenAI API error: {}", error_text),
-                error_code: None,
-                retryable: true,
-            });
-        }
+        let response = super::check_response(response, |error| {
+            openai_api_error(
+                "OpenAI API",
+                error.status_code,
+                &error.body,
+                error.request_id,
+            )
+        })
+        .await?;
 
-        let stream = response.bytes_stream();
-        let stream = stream
-            .map(|result| match result {
-                Ok(bytes) => {
-                    let text = String::from_utf8_lossy(&bytes);
-                    parse_openai_sse(&text)
-                }
-                Err(e) => Err(AiError::StreamError {
-                    message: e.to_string(),
-                    retryable: true,
-                }),
-            })
+        let stream = EventStream::new(response.bytes_stream())
+            .with_optional_idle_timeout(self.stream_idle_timeout)
+            .map(|event| event.and_then(|event| parse_openai_event(&event.data)))
             .filter_map(|result| async move {
                 match result {
                     Ok(Some(chunk)) => Some(Ok(chunk)),
@@ -337,41 +605,859 @@ impl CompletionProvider for OpenAIProvider {
             "o1-mini",
         ]
     }
+
+    fn capabilities(&self) -> ProviderCapabilities {
+        ProviderCapabilities {
+            streaming: true,
+            tools: true,
+            vision: true,
+            json_mode: true,
+            max_context_tokens: Some(128_000),
+            max_output_tokens: Some(16_384),
+            alternating_roles: false,
+            assistant_prefill: false,
+        }
+    }
+
+    fn model_catalog(&self) -> Vec<ModelInfo> {
+        let capabilities = self.capabilities();
+        self.available_models()
+            .into_iter()
+            .map(|model| {
+                let mut info = ModelInfo::from_capabilities(self.name(), model, &capabilities);
+                // Older models and o1-mini take text only, and o1-mini has
+                // no function calling
+                info.supports_vision = !matches!(model, "gpt-4" | "gpt-3.5-turbo" | "o1-mini");
+                info.supports_functions = model != "o1-mini";
+                info
+            })
+            .collect()
+    }
 }
 
-fn parse_openai_sse(data: &str) -> Result<Option<StreamChunk>> {
-    for line in data.lines() {
-        if let Some(json_str) = line.strip_prefix("data: ") {
-            if json_str == "[DONE]" {
-                return Ok(None);
+const BATCH_ENDPOINT: &str = "/v1/chat/completions";
+
+fn batch_custom_id(index: usize) -> String {
+    format!("request-{}", index)
+}
+
+fn batch_index(custom_id: &str) -> Option<usize> {
+    custom_id.strip_prefix("request-")?.parse().ok()
+}
+
+#[derive(Deserialize)]
+struct OpenAIFileObject {
+    id: String,
+}
+
+#[derive(Deserialize)]
+struct OpenAIBatch {
+    id: String,
+    status: String,
+    #[serde(default)]
+    request_counts: Option<OpenAIBatchRequestCounts>,
+    #[serde(default)]
+    output_file_id: Option<String>,
+    #[serde(default)]
+    error_file_id: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct OpenAIBatchRequestCounts {
+    total: usize,
+    completed: usize,
+    failed: usize,
+}
+
+#[derive(Deserialize)]
+struct OpenAIBatchResultLine {
+    custom_id: String,
+    #[serde(default)]
+    response: Option<OpenAIBatchResultResponse>,
+    #[serde(default)]
+    error: Option<serde_json::Value>,
+}
+
+#[derive(Deserialize)]
+struct OpenAIBatchResultResponse {
+    status_code: u16,
+    #[serde(default)]
+    request_id: Option<String>,
+    body: serde_json::Value,
+}
+
+fn convert_batch_status(batch: &OpenAIBatch) -> BatchStatus {
+    let state = match batch.status.as_str() {
+        "validating" => BatchState::Validating,
+        "in_progress" => BatchState::InProgress,
+        "finalizing" => BatchState::Finalizing,
+        "completed" => BatchState::Completed,
+        "expired" => BatchState::Expired,
+        "cancelling" => BatchState::Cancelling,
+        "cancelled" => BatchState::Cancelled,
+        _ => BatchState::Failed,
+    };
+    let (total, succeeded, failed) = batch
+        .request_counts
+        .as_ref()
+        .map(|c| (c.total, c.completed, c.failed))
+        .unwrap_or_default();
+
+    BatchStatus {
+        state,
+        total,
+        succeeded,
+        failed,
+    }
+}
+
+impl OpenAIProvider {
+    async fn send_batch_request(
+        &self,
+        builder: reqwest::RequestBuilder,
+    ) -> Result<reqwest::Response> {
+        let response = builder.send_authenticated(self.auth.as_ref()).await?;
+
+        let response = super::check_response(response, |error| {
+            openai_api_error(
+                "OpenAI Batch API",
+                error.status_code,
+                &error.body,
+                error.request_id,
+            )
+        })
+        .await?;
+        Ok(response)
+    }
+
+    async fn get_batch(&self, id: &str) -> Result<OpenAIBatch> {
+        let response = self
+            .send_batch_request(self.client.get(format!("{}/batches/{}", self.base_url, id)))
+            .await?;
+        Ok(response.json().await?)
+    }
+
+    async fn file_content(&self, file_id: &str) -> Result<String> {
+        let response = self
+            .send_batch_request(
+                self.client
+                    .get(format!("{}/files/{}/content", self.base_url, file_id)),
+            )
+            .await?;
+        Ok(response.text().await?)
+    }
+
+    /// Upload the batch input as a JSONL file
+    async fn upload_batch_file(&self, jsonl: String) -> Result<OpenAIFileObject> {
+        let file = reqwest::multipart::Part::text(jsonl)
+            .file_name("batch.jsonl")
+            .mime_str("application/jsonl")?;
+        let form = reqwest::multipart::Form::new()
+            .text("purpose", "batch")
+            .part("file", file);
+
+        let response = self
+            .send_batch_request(
+                self.client
+                    .post(format!("{}/files", self.base_url))
+                    .multipart(form),
+            )
+            .await?;
+        Ok(response.json().await?)
+    }
+
+    /// Map result and error file lines back to submission order
+    fn parse_batch_results(
+        &self,
+        contents: &[String],
+        request_count: usize,
+    ) -> Vec<Result<CompletionResponse>> {
+        let mut results: Vec<Option<Result<CompletionResponse>>> =
+            (0..request_count).map(|_| None).collect();
+
+        for line in contents.iter().flat_map(|c| c.lines()) {
+            if line.trim().is_empty() {
+                continue;
             }
+            let Ok(parsed) = serde_json::from_str::<OpenAIBatchResultLine>(line) else {
+                continue;
+            };
+            let Some(slot) = batch_index(&parsed.custom_id).and_then(|i| results.get_mut(i)) else {
+                continue;
+            };
 
-            if let Ok(chunk) = serde_json::from_str::<OpenAIStreamChunk>(json_str) {
-                return Ok(Some(StreamChunk {
-                    id: chunk.id,
-                    choices: chunk
-                        .choices
-                        .into_iter()
-                        .map(|c| StreamChoice {
-                            index: c.index,
-                            delta: Delta {
-                                role: c.delta.role.map(|r| match r.as_str() {
-                                    "system" => Role::System,
-                                    "user" => Role::User,
-                                    "assistant" => Role::Assistant,
-                                    "tool" => Role::Tool,
-                                    _ => Role::User,
-                                }),
-                                content: c.delta.content,
-                                tool_calls: c.delta.tool_calls,
-                            },
-                            finish_reason: c.finish_reason,
+            *slot = Some(match parsed.response {
+                Some(response) if response.status_code == 200 => {
+                    serde_json::from_value::<OpenAIResponse>(response.body)
+                        .map(|r| self.convert_response(r))
+                        .map_err(|e| AiError::MalformedResponse {
+                            message: format!("Invalid batch result: {}", e),
+                            raw_response: Some(line.to_string()),
                         })
-                        .collect(),
-                    model: Some(chunk.model),
-                }));
+                }
+                Some(response) => Err(openai_api_error(
+                    "OpenAI API",
+                    response.status_code,
+                    &response.body.to_string(),
+                    response.request_id,
+                )),
+                None => Err(AiError::ProviderError {
+                    provider: "openai".to_string(),
+                    message: format!(
+                        "OpenAI batch request failed: {}",
+                        parsed.error.unwrap_or_default()
+                    ),
+                    error_code: None,
+                    retryable: false,
+                    status_code: None,
+                    request_id: None,
+                }),
+            });
+        }
+
+        results
+            .into_iter()
+            .enumerate()
+            .map(|(i, result)| {
+                result.unwrap_or_else(|| {
+                    Err(AiError::ProviderError {
+                        provider: "openai".to_string(),
+                        message: format!("No batch result for {}", batch_custom_id(i)),
+                        error_code: None,
+                        retryable: false,
+                        status_code: None,
+                        request_id: None,
+                    })
+                })
+            })
+            .collect()
+    }
+}
+
+#[async_trait]
+impl BatchProvider for OpenAIProvider {
+    async fn submit_batch(&self, requests: Vec<CompletionRequest>) -> Result<BatchHandle> {
+        if requests.is_empty() {
+            return Err(AiError::InvalidRequest {
+                message: "A batch needs at least one request".to_string(),
+                field: Some("requests".to_string()),
+                code: None,
+            });
+        }
+
+        let request_count = requests.len();
+        let mut jsonl = String::new();
+        for (i, request) in requests.into_iter().enumerate() {
+            let mut body = self.build_request(request, false)?;
+            body.stream = None;
+            let line = serde_json::json!({
+                "custom_id": batch_custom_id(i),
+                "method": "POST",
+                "url": BATCH_ENDPOINT,
+                "body": body,
+            });
+            jsonl.push_str(&line.to_string());
+            jsonl.push('\n');
+        }
+
+        let file = self.upload_batch_file(jsonl).await?;
+        let response = self
+            .send_batch_request(self.client.post(format!("{}/batches", self.base_url)).json(
+                &serde_json::json!({
+                    "input_file_id": file.id,
+                    "endpoint": BATCH_ENDPOINT,
+                    "completion_window": "24h",
+                }),
+            ))
+            .await?;
+        let batch: OpenAIBatch = response.json().await?;
+
+        Ok(BatchHandle {
+            id: batch.id,
+            provider: "openai".to_string(),
+            request_count,
+        })
+    }
+
+    async fn poll(&self, handle: &BatchHandle) -> Result<BatchStatus> {
+        let batch = self.get_batch(&handle.id).await?;
+        Ok(convert_batch_status(&batch))
+    }
+
+    async fn results(&self, handle: &BatchHandle) -> Result<Vec<Result<CompletionResponse>>> {
+        let batch = self.get_batch(&handle.id).await?;
+        let status = convert_batch_status(&batch);
+        if !status.is_finished() {
+            return Err(AiError::InvalidRequest {
+                message: format!("Batch {} has not finished ({:?})", handle.id, status.state),
+                field: None,
+                code: Some("batch_not_finished".to_string()),
+            });
+        }
+
+        let mut contents = Vec::new();
+        for file_id in [&batch.output_file_id, &batch.error_file_id]
+            .into_iter()
+            .flatten()
+        {
+            contents.push(self.file_content(file_id).await?);
+        }
+
+        Ok(self.parse_batch_results(&contents, handle.request_count))
+    }
+}
+
+#[derive(Serialize)]
+struct OpenAIImageRequest<'a> {
+    model: &'a str,
+    prompt: &'a str,
+    n: u32,
+    size: String,
+}
+
+#[derive(Deserialize)]
+struct OpenAIImageResponse {
+    data: Vec<OpenAIImage>,
+    /// Set by `gpt-image-*` models, which return base64 in this format
+    #[serde(default)]
+    output_format: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct OpenAIImage {
+    #[serde(default)]
+    url: Option<String>,
+    #[serde(default)]
+    b64_json: Option<String>,
+}
+
+fn convert_image_response(response: OpenAIImageResponse) -> Result<Vec<GeneratedImage>> {
+    let mime = format!(
+        "image/{}",
+        response.output_format.as_deref().unwrap_or("png")
+    );
+    response
+        .data
+        .into_iter()
+        .map(|image| {
+            let url_or_b64 = match (image.b64_json, image.url) {
+                (Some(b64), _) => ImageData::Base64(b64),
+                (None, Some(url)) => ImageData::Url(url),
+                (None, None) => {
+                    return Err(AiError::MalformedResponse {
+                        message: "OpenAI image has neither url nor b64_json".to_string(),
+                        raw_response: None,
+                    })
+                }
+            };
+            Ok(GeneratedImage {
+                url_or_b64,
+                mime: mime.clone(),
+            })
+        })
+        .collect()
+}
+
+#[async_trait]
+impl ImageGenerationProvider for OpenAIProvider {
+    async fn generate_image(
+        &self,
+        prompt: &str,
+        size: ImageSize,
+        n: u32,
+    ) -> Result<Vec<GeneratedImage>> {
+        let response = self
+            .client
+            .post(format!("{}/images/generations", self.base_url))
+            .json(&OpenAIImageRequest {
+                model: &self.image_model,
+                prompt,
+                n,
+                size: size.to_string(),
+            })
+            .send_authenticated(self.auth.as_ref())
+            .await?;
+
+        let response = super::check_response(response, |error| {
+            openai_api_error(
+                "OpenAI images API",
+                error.status_code,
+                &error.body,
+                error.request_id,
+            )
+        })
+        .await?;
+
+        convert_image_response(response.json().await?)
+    }
+}
+
+#[derive(Deserialize)]
+struct OpenAITranscription {
+    text: String,
+    #[serde(default)]
+    language: Option<String>,
+    #[serde(default)]
+    duration: Option<f32>,
+    #[serde(default)]
+    segments: Vec<OpenAITranscriptionSegment>,
+}
+
+#[derive(Deserialize)]
+struct OpenAITranscriptionSegment {
+    start: f32,
+    end: f32,
+    text: String,
+}
+
+#[async_trait]
+impl TranscriptionProvider for OpenAIProvider {
+    async fn transcribe(
+        &self,
+        audio: Vec<u8>,
+        format: &str,
+        language: Option<&str>,
+    ) -> Result<Transcript> {
+        let file = reqwest::multipart::Part::bytes(audio)
+            .file_name(format!("audio.{}", format))
+            .mime_str(&format!("audio/{}", format))
+            .map_err(|e| AiError::InvalidRequest {
+                message: format!("Unsupported audio format '{}': {}", format, e),
+                field: Some("format".to_string()),
+                code: None,
+            })?;
+        // Only Whisper returns segment timestamps; newer models accept
+        // plain JSON
+        let response_format = if self.transcription_model.starts_with("whisper") {
+            "verbose_json"
+        } else {
+            "json"
+        };
+        let mut form = reqwest::multipart::Form::new()
+            .part("file", file)
+            .text("model", self.transcription_model.clone())
+            .text("response_format", response_format);
+        if let Some(language) = language {
+            form = form.text("language", language.to_string());
+        }
+
+        let response = self
+            .client
+            .post(format!("{}/audio/transcriptions", self.base_url))
+            .multipart(form)
+            .send_authenticated(self.auth.as_ref())
+            .await?;
+
+        let response = super::check_response(response, |error| {
+            openai_api_error(
+                "OpenAI transcription API",
+                error.status_code,
+                &error.body,
+                error.request_id,
+            )
+        })
+        .await?;
+
+        let transcription: OpenAITranscription = response.json().await?;
+        Ok(Transcript {
+            text: transcription.text,
+            language: transcription
+                .language
+                .or_else(|| language.map(String::from)),
+            duration: transcription.duration,
+            segments: transcription
+                .segments
+                .into_iter()
+                .map(|s| TranscriptSegment {
+                    start: s.start,
+                    end: s.end,
+                    text: s.text,
+                })
+                .collect(),
+        })
+    }
+}
+
+/// Convert the `data` of one stream event; `[DONE]` and unrecognised
+/// payloads yield nothing, and an `error` object ends the stream with that
+/// error
+fn parse_openai_event(data: &str) -> Result<Option<StreamChunk>> {
+    if data == "[DONE]" {
+        return Ok(None);
+    }
+
+    let Ok(chunk) = serde_json::from_str::<OpenAIStreamChunk>(data) else {
+        let is_error = serde_json::from_str::<Value>(data)
+            .is_ok_and(|v| v.get("error").is_some_and(Value::is_object));
+        if is_error {
+            // The request was accepted before the stream failed, so treat
+            // the error as a server fault
+            return Err(openai_api_error("OpenAI stream", 500, data, None));
+        }
+        return Ok(None);
+    };
+    Ok(Some(StreamChunk {
+        id: chunk.id,
+        choices: chunk
+            .choices
+            .into_iter()
+            .map(|c| StreamChoice {
+                index: c.index,
+                delta: Delta {
+                    // Deltas only come from the assistant, so an unknown
+                    // role is dropped rather than guessed
+                    role: c.delta.role.and_then(|r| r.parse().ok()),
+                    content: c.delta.content,
+                    tool_calls: c.delta.tool_calls,
+                },
+                finish_reason: c.finish_reason,
+            })
+            .collect(),
+        model: Some(chunk.model),
+        usage: chunk.usage.map(OpenAIUsage::into_usage),
+        metadata: None,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(model: &str) -> CompletionRequest {
+        CompletionRequest::new(model, vec![Message::user("Hello")])
+    }
+
+    #[test]
+    fn test_try_new_rejects_blank_api_key() {
+        for key in ["", "   ", "\n\t"] {
+            let result = OpenAIProvider::try_new(key.to_string());
+            assert!(matches!(result, Err(AiError::InvalidApiKey { .. })));
+        }
+        assert!(OpenAIProvider::try_new("sk-test".to_string()).is_ok());
+    }
+
+    #[test]
+    fn test_reasoning_effort_serialization() {
+        let provider = OpenAIProvider::new("test-key".to_string());
+
+        let mut req = request("o1");
+        req.reasoning_effort = Some(ReasoningEffort::High);
+        let body = serde_json::to_value(provider.build_request(req, false).unwrap()).unwrap();
+        assert_eq!(body["reasoning_effort"], "high");
+
+        let body = serde_json::to_value(provider.build_request(request("gpt-4o"), false).unwrap())
+            .unwrap();
+        assert!(body.get("reasoning_effort").is_none());
+    }
+
+    #[test]
+    fn test_api_error_classification() {
+        let quota = r#"{"error":{"message":"You exceeded your current quota","type":"insufficient_quota","code":"insufficient_quota"}}"#;
+        let error = openai_api_error("OpenAI API", 429, quota, Some("req_1".into()));
+        assert!(matches!(error, AiError::QuotaExceeded { .. }));
+        assert!(error
+            .to_string()
+            .contains("You exceeded your current quota"));
+        assert_eq!(error.request_id(), Some("req_1"));
+        assert!(!error.is_retryable());
+
+        let rate_limited = r#"{"error":{"message":"Rate limit reached","type":"requests","code":"rate_limit_exceeded"}}"#;
+        let error = openai_api_error("OpenAI API", 429, rate_limited, None);
+        assert!(error.is_retryable());
+        assert!(matches!(
+            error,
+            AiError::ProviderError { error_code: Some(ref code), .. } if code == "rate_limit_exceeded"
+        ));
+
+        assert!(openai_api_error("OpenAI API", 503, "upstream connect error", None).is_retryable());
+        let invalid = r#"{"error":{"message":"bad","type":"invalid_request_error","code":null}}"#;
+        assert!(!openai_api_error("OpenAI API", 400, invalid, None).is_retryable());
+    }
+
+    #[test]
+    fn test_max_completion_tokens_for_reasoning_models() {
+        let provider = OpenAIProvider::new("test-key".to_string());
+
+        let mut req = request("o1");
+        req.max_tokens = Some(256);
+        let body = serde_json::to_value(provider.build_request(req, false).unwrap()).unwrap();
+        assert_eq!(body["max_completion_tokens"], 256);
+        assert!(body.get("max_tokens").is_none());
+
+        let mut req = request("gpt-4o");
+        req.max_tokens = Some(256);
+        let body = serde_json::to_value(provider.build_request(req, false).unwrap()).unwrap();
+        assert_eq!(body["max_tokens"], 256);
+        assert!(body.get("max_completion_tokens").is_none());
+
+        assert!(uses_max_completion_tokens("o3-mini"));
+        assert!(uses_max_completion_tokens("gpt-5"));
+        assert!(!uses_max_completion_tokens("omni-moderation-latest"));
+    }
+
+    #[test]
+    fn test_reasoning_tokens_parsing() {
+        let provider = OpenAIProvider::new("test-key".to_string());
+        let raw = serde_json::json!({
+            "id": "chatcmpl-1",
+            "model": "o1",
+            "choices": [{
+                "index": 0,
+                "message": {"role": "assistant", "content": "42"},
+                "finish_reason": "stop"
+            }],
+            "usage": {
+                "prompt_tokens": 10,
+                "completion_tokens": 120,
+                "total_tokens": 130,
+                "completion_tokens_details": {"reasoning_tokens": 100}
             }
+        });
+        let resp: OpenAIResponse = serde_json::from_value(raw).unwrap();
+        let usage = provider.convert_response(resp).usage.unwrap();
+        assert_eq!(usage.completion_tokens, 120);
+        assert_eq!(usage.reasoning_tokens, Some(100));
+    }
+
+    #[test]
+    fn test_model_catalog() {
+        let provider = OpenAIProvider::new("test-key".to_string());
+        let catalog = provider.model_catalog();
+        assert_eq!(catalog.len(), provider.available_models().len());
+
+        let gpt4o = catalog.iter().find(|m| m.name == "gpt-4o").unwrap();
+        assert_eq!(gpt4o.context_window, 128_000);
+        assert!(gpt4o.supports_vision && gpt4o.supports_functions);
+        assert_eq!(gpt4o.input_price_per_1k_tokens, Some(0.0025));
+
+        let o1_mini = catalog.iter().find(|m| m.name == "o1-mini").unwrap();
+        assert!(!o1_mini.supports_vision && !o1_mini.supports_functions);
+    }
+
+    #[test]
+    fn test_logprobs_requested_and_parsed() {
+        let provider = OpenAIProvider::new("test-key".to_string());
+
+        let mut req = request("gpt-4o");
+        req.top_logprobs = Some(2);
+        let body = serde_json::to_value(provider.build_request(req, false).unwrap()).unwrap();
+        assert_eq!(body["logprobs"], true);
+        assert_eq!(body["top_logprobs"], 2);
+
+        // Explicitly turning logprobs off contradicts top_logprobs
+        let mut req = request("gpt-4o");
+        req.logprobs = Some(false);
+        req.top_logprobs = Some(2);
+        assert!(matches!(
+            provider.build_request(req, false),
+            Err(AiError::InvalidRequest { field: Some(ref f), .. }) if f == "top_logprobs"
+        ));
+
+        let body = serde_json::to_value(provider.build_request(request("gpt-4o"), false).unwrap())
+            .unwrap();
+        assert!(body.get("logprobs").is_none());
+
+        let raw = serde_json::json!({
+            "id": "chatcmpl-1",
+            "model": "gpt-4o",
+            "choices": [{
+                "index": 0,
+                "message": {"role": "assistant", "content": "Yes"},
+                "finish_reason": "stop",
+                "logprobs": {
+                    "content": [{
+                        "token": "Yes",
+                        "logprob": -0.01,
+                        "bytes": [89, 101, 115],
+                        "top_logprobs": [
+                            {"token": "Yes", "logprob": -0.01, "bytes": [89, 101, 115]},
+                            {"token": "No", "logprob": -4.6, "bytes": [78, 111]}
+                        ]
+                    }],
+                    "refusal": null
+                }
+            }]
+        });
+        let resp: OpenAIResponse = serde_json::from_value(raw).unwrap();
+        let choice = &provider.convert_response(resp).choices[0];
+        let logprobs = choice.logprobs.as_ref().unwrap();
+        assert_eq!(logprobs.content.len(), 1);
+        assert_eq!(logprobs.content[0].token, "Yes");
+        assert_eq!(logprobs.content[0].logprob, -0.01);
+        assert_eq!(logprobs.content[0].top_logprobs[1].token, "No");
+    }
+
+    #[test]
+    fn test_message_name_is_sent() {
+        let provider = OpenAIProvider::new("test-key".to_string());
+
+        let mut req = request("gpt-4o");
+        req.messages = vec![
+            Message::user("I vote for pizza").with_name("alice"),
+            Message::user("Sushi for me"),
+        ];
+        let body = serde_json::to_value(provider.build_request(req, false).unwrap()).unwrap();
+        assert_eq!(body["messages"][0]["name"], "alice");
+        assert!(body["messages"][1].get("name").is_none());
+    }
+
+    #[test]
+    fn test_service_tier_sent_and_returned() {
+        let provider = OpenAIProvider::new("test-key".to_string());
+
+        let mut req = request("gpt-4o");
+        req.service_tier = Some("flex".to_string());
+        let body = serde_json::to_value(provider.build_request(req, false).unwrap()).unwrap();
+        assert_eq!(body["service_tier"], "flex");
+
+        let body = serde_json::to_value(provider.build_request(request("gpt-4o"), false).unwrap())
+            .unwrap();
+        assert!(body.get("service_tier").is_none());
+
+        let raw = serde_json::json!({
+            "id": "chatcmpl-1",
+            "model": "gpt-4o",
+            "choices": [{
+                "index": 0,
+                "message": {"role": "assistant", "content": "Hi"},
+                "finish_reason": "stop"
+            }],
+            "service_tier": "default"
+        });
+        let resp: OpenAIResponse = serde_json::from_value(raw).unwrap();
+        let metadata = provider.convert_response(resp).metadata.unwrap();
+        assert_eq!(metadata["service_tier"], "default");
+    }
+
+    #[test]
+    fn test_audio_and_document_parts() {
+        let provider = OpenAIProvider::new("test-key".to_string());
+        let message = provider.convert_message(Message::new(
+            Role::User,
+            MessageContent::Parts(vec![
+                ContentPart::Text {
+                    text: "Summarize these".to_string(),
+                },
+                ContentPart::Audio {
+                    data: "UklGRg==".to_string(),
+                    format: "wav".to_string(),
+                },
+                ContentPart::Document {
+                    data: "JVBERi0=".to_string(),
+                    mime_type: "application/pdf".to_string(),
+                    name: Some("report.pdf".to_string()),
+                },
+            ]),
+        ));
+
+        let body = serde_json::to_value(message).unwrap();
+        let parts = body["content"].as_array().unwrap();
+        assert_eq!(parts[1]["type"], "input_audio");
+        assert_eq!(parts[1]["input_audio"]["format"], "wav");
+        assert_eq!(parts[2]["type"], "file");
+        assert_eq!(
+            parts[2]["file"]["file_data"],
+            "data:application/pdf;base64,JVBERi0="
+        );
+        assert_eq!(parts[2]["file"]["filename"], "report.pdf");
+    }
+
+    #[tokio::test]
+    async fn test_unknown_model_rejected_when_validating() {
+        let provider = OpenAIProvider::new("sk-test".to_string()).with_model_validation(true);
+
+        let err = provider.complete(request("gpt-bogus")).await.unwrap_err();
+        match err {
+            AiError::UnsupportedModel {
+                model,
+                available_models,
+                ..
+            } => {
+                assert_eq!(model, "gpt-bogus");
+                assert!(available_models.contains(&"gpt-4o".to_string()));
+            }
+            other => panic!("expected UnsupportedModel, got {:?}", other),
         }
+
+        // Known models pass validation and build normally
+        assert!(provider.build_request(request("gpt-4o"), false).is_ok());
+    }
+
+    #[test]
+    fn test_batch_status_transitions() {
+        let cases = [
+            ("validating", BatchState::Validating, false),
+            ("in_progress", BatchState::InProgress, false),
+            ("finalizing", BatchState::Finalizing, false),
+            ("completed", BatchState::Completed, true),
+            ("expired", BatchState::Expired, true),
+            ("cancelled", BatchState::Cancelled, true),
+            ("failed", BatchState::Failed, true),
+        ];
+        for (raw, state, finished) in cases {
+            let batch: OpenAIBatch = serde_json::from_value(serde_json::json!({
+                "id": "batch_1",
+                "status": raw,
+                "request_counts": {"total": 4, "completed": 3, "failed": 1}
+            }))
+            .unwrap();
+            let status = convert_batch_status(&batch);
+            assert_eq!(status.state, state);
+            assert_eq!(status.is_finished(), finished);
+            assert_eq!((status.total, status.succeeded, status.failed), (4, 3, 1));
+        }
+    }
+
+    #[test]
+    fn test_batch_results_include_failures() {
+        let provider = OpenAIProvider::new("test-key".to_string());
+        let output = r#"{"custom_id": "request-0", "response": {"status_code": 400, "request_id": "req_9", "body": {"error": {"message": "bad"}}}}"#;
+        let errors = r#"{"custom_id": "request-1", "response": null, "error": {"code": "batch_expired", "message": "expired"}}"#;
+
+        let results = provider.parse_batch_results(&[output.to_string(), errors.to_string()], 2);
+        assert_eq!(results[0].as_ref().unwrap_err().http_status(), Some(400));
+        assert_eq!(results[0].as_ref().unwrap_err().request_id(), Some("req_9"));
+        assert!(results[1].is_err());
+    }
+
+    #[test]
+    fn test_stream_requests_and_parses_usage() {
+        let provider = OpenAIProvider::new("test-key".to_string());
+        let body =
+            serde_json::to_value(provider.build_request(request("gpt-4o"), true).unwrap()).unwrap();
+        assert_eq!(body["stream_options"]["include_usage"], true);
+        let body = serde_json::to_value(provider.build_request(request("gpt-4o"), false).unwrap())
+            .unwrap();
+        assert!(body.get("stream_options").is_none());
+
+        let data = r#"{"id":"chatcmpl-1","object":"chat.completion.chunk","created":1,"model":"gpt-4o","choices":[],"usage":{"prompt_tokens":9,"completion_tokens":3,"total_tokens":12}}"#;
+        let chunk = parse_openai_event(data).unwrap().unwrap();
+        assert!(chunk.choices.is_empty());
+        assert_eq!(chunk.usage.unwrap().total_tokens, 12);
+    }
+
+    #[test]
+    fn test_stream_error_frame_is_an_error() {
+        let data = r#"{"error":{"message":"The server had an error while processing your request.","type":"server_error"}}"#;
+        let error = parse_openai_event(data).unwrap_err();
+        assert!(error.is_retryable());
+        assert!(error.to_string().contains("server had an error"));
+
+        let quota = r#"{"error":{"message":"You exceeded your current quota","type":"insufficient_quota","code":"insufficient_quota"}}"#;
+        assert!(matches!(
+            parse_openai_event(quota).unwrap_err(),
+            AiError::QuotaExceeded { .. }
+        ));
+        assert!(parse_openai_event(r#"{"unexpected":true}"#)
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn test_unknown_delta_role_is_dropped() {
+        let data = r#"{"id":"chatcmpl-1","object":"chat.completion.chunk","created":1,"model":"gpt-4o","choices":[{"index":0,"delta":{"role":"critic","content":"Hi"},"finish_reason":null}]}"#;
+        let chunk = parse_openai_event(data).unwrap().unwrap();
+        assert_eq!(chunk.choices[0].delta.role, None);
+        assert_eq!(chunk.choices[0].delta.content.as_deref(), Some("Hi"));
     }
-    Ok(None)
 }
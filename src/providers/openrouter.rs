@@ -3,48 +3,90 @@ use futures::stream::Stream;
 use reqwest::Client;
 use serde::Deserialize;
 use std::pin::Pin;
+use std::sync::Arc;
 
 use crate::{
-    providers::openai::OpenAIProvider, AiError, CompletionProvider, CompletionRequest,
-    CompletionResponse, Result, StreamChunk,
+    providers::{
+        auth::SendAuthenticated, openai::OpenAIProvider, ApiKeyAuth, Authenticator, ProviderConfig,
+    },
+    CompletionProvider, CompletionRequest, CompletionResponse, ProviderCapabilities, Result,
+    StreamChunk,
 };
 
 pub struct OpenRouterProvider {
     openai_provider: OpenAIProvider,
     client: Client,
-    api_key: String,
+    /// Set by `with_client`, so `with_config` keeps the injected client
+    custom_client: bool,
+    auth: Arc<dyn Authenticator>,
+    base_url: String,
 }
 
 impl OpenRouterProvider {
     pub fn new(api_key: String) -> Self {
-        let client = Client::new();
+        Self::with_base_url(api_key, "https://openrouter.ai/api/v1".to_string())
+    }
+
+    pub fn with_base_url(api_key: String, base_url: String) -> Self {
+        let client = super::config::default_client();
         Self {
-            openai_provider: OpenAIProvider::with_base_url(
-                api_key.clone(),
-                "https://openrouter.ai/api/v1".to_string(),
-            ),
+            openai_provider: OpenAIProvider::with_base_url(api_key.clone(), base_url.clone()),
             client,
-            api_key,
+            custom_client: false,
+            auth: Arc::new(ApiKeyAuth::bearer(api_key)),
+            base_url,
+        }
+    }
+
+    /// Create a provider, rejecting an empty or whitespace-only API key
+    pub fn try_new(api_key: String) -> Result<Self> {
+        super::validate_api_key("openrouter", &api_key)?;
+        Ok(Self::new(api_key))
+    }
+
+    /// Send every request with the user agent and default headers from
+    /// `config`. A client given to [`with_client`](Self::with_client),
+    /// before or after this call, is kept; the user agent and headers are
+    /// then up to that client.
+    pub fn with_config(mut self, config: &ProviderConfig) -> Result<Self> {
+        self.openai_provider = self.openai_provider.with_config(config)?;
+        let client = config.build_client()?;
+        if !self.custom_client {
+            self.client = client;
         }
+        Ok(self)
+    }
+
+    /// Send requests through `client`, sharing its connection pool and DNS
+    /// cache with other providers. It takes precedence over the client
+    /// [`with_config`](Self::with_config) builds.
+    pub fn with_client(mut self, client: Client) -> Self {
+        self.openai_provider = self.openai_provider.with_client(client.clone());
+        self.client = client;
+        self.custom_client = true;
+        self
+    }
+
+    /// Authenticate requests with `auth` instead of the API key, e.g. for a
+    /// proxy or gateway with its own credentials
+    pub fn with_authenticator(mut self, auth: impl Authenticator + 'static) -> Self {
+        let auth = Arc::new(auth);
+        self.openai_provider = self.openai_provider.with_authenticator(auth.clone());
+        self.auth = auth;
+        self
     }
 
     pub async fn list_available_models(&self) -> Result<Vec<OpenRouterModel>> {
         let response = self
             .client
-            .get("https://openrouter.ai/api/v1/models")
-            .header("Authorization", format!("Bearer {}", self.api_key))
-            .send()
+            .get(format!("{}/models", self.base_url))
+            .send_authenticated(self.auth.as_ref())
             .await?;
 
-        if !response.status().is_success() {
-            let error_text = response.text().await?;
-            return Err(AiError::ProviderError {
-                provider: "openrouter".to_string(),
-                message: format!("OpenRouter API error: {}", error_text),
-                error_code: None,
-                retryable: true,
-            });
-        }
+        let response = super::check_response(response, |error| {
+            error.into_provider_error("openrouter", "OpenRouter API error")
+        })
+        .await?;
 
         let models_response: OpenRouterModelsResponse = response.json().await?;
         Ok(models_response.data)
@@ -113,4 +155,17 @@ impl CompletionProvider for OpenRouterProvider {
             "x-ai/grok-2-1212",
         ]
     }
+
+    fn capabilities(&self) -> ProviderCapabilities {
+        ProviderCapabilities {
+            streaming: true,
+            tools: true,
+            vision: true,
+            json_mode: true,
+            max_context_tokens: None,
+            max_output_tokens: None,
+            alternating_roles: false,
+            assistant_prefill: false,
+        }
+    }
 }
@@ -0,0 +1,313 @@
+use async_trait::async_trait;
+use futures::stream::{self, Stream, StreamExt};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+
+use crate::providers::canonical_json;
+use crate::{
+    AiError, CompletionProvider, CompletionRequest, CompletionResponse, ProviderCapabilities,
+    Result, StreamChunk,
+};
+
+/// How a [`RecordingProvider`] treats its cassette
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecordingMode {
+    /// Always call the inner provider and store the result
+    Record,
+    /// Only serve stored interactions; a miss is an error
+    Replay,
+    /// Serve stored interactions and record the ones that are missing
+    Auto,
+}
+
+/// A recorded request/response pair
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Interaction {
+    pub request_hash: String,
+    pub request: CompletionRequest,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response: Option<CompletionResponse>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stream: Option<Vec<StreamChunk>>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Cassette {
+    interactions: Vec<Interaction>,
+}
+
+/// Records provider interactions to a JSON cassette and replays them by
+/// request hash, so tests can run deterministically without network access.
+pub struct RecordingProvider {
+    inner: Arc<dyn CompletionProvider>,
+    path: PathBuf,
+    mode: RecordingMode,
+    cassette: Mutex<Cassette>,
+}
+
+impl RecordingProvider {
+    /// Create a recording provider, loading the cassette at `path` if it exists
+    pub fn new(
+        inner: Arc<dyn CompletionProvider>,
+        path: impl Into<PathBuf>,
+        mode: RecordingMode,
+    ) -> Result<Self> {
+        let path = path.into();
+        let cassette = if path.exists() {
+            serde_json::from_str(&std::fs::read_to_string(&path)?)?
+        } else if mode == RecordingMode::Replay {
+            return Err(AiError::MissingConfiguration {
+                field: "cassette".to_string(),
+                description: format!("Cassette {} does not exist", path.display()),
+            });
+        } else {
+            Cassette::default()
+        };
+
+        Ok(Self {
+            inner,
+            path,
+            mode,
+            cassette: Mutex::new(cassette),
+        })
+    }
+
+    /// Number of interactions currently stored
+    pub fn len(&self) -> usize {
+        self.cassette.lock().unwrap().interactions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Stable hash of a request, used as the cassette lookup key
+    pub fn request_hash(request: &CompletionRequest, streaming: bool) -> Result<String> {
+        // `metadata` and `extra` are hash maps, so sort their keys to keep
+        // the hash stable across processes
+        let json = canonical_json(&serde_json::to_value(request)?);
+        let kind = if streaming { "stream" } else { "complete" };
+        Ok(format!(
+            "{:016x}",
+            fnv1a(format!("{}:{}", kind, json).as_bytes())
+        ))
+    }
+
+    fn lookup(&self, hash: &str) -> Option<Interaction> {
+        if self.mode == RecordingMode::Record {
+            return None;
+        }
+        self.cassette
+            .lock()
+            .unwrap()
+            .interactions
+            .iter()
+            .find(|i| i.request_hash == hash)
+            .cloned()
+    }
+
+    fn miss(&self, hash: &str) -> AiError {
+        AiError::InvalidRequest {
+            message: format!(
+                "No recorded interaction for request {} in {}",
+                hash,
+                self.path.display()
+            ),
+            field: None,
+            code: Some("cassette_miss".to_string()),
+        }
+    }
+
+    fn store(&self, interaction: Interaction) -> Result<()> {
+        let mut cassette = self.cassette.lock().unwrap();
+        cassette
+            .interactions
+            .retain(|i| i.request_hash != interaction.request_hash);
+        cassette.interactions.push(interaction);
+
+        if let Some(parent) = self.path.parent() {
+            if !parent.as_os_str().is_empty() {
+                std::fs::create_dir_all(parent)?;
+            }
+        }
+        std::fs::write(&self.path, serde_json::to_string_pretty(&*cassette)?)?;
+        Ok(())
+    }
+}
+
+pub(crate) fn fnv1a(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in bytes {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+#[async_trait]
+impl CompletionProvider for RecordingProvider {
+    async fn complete(&self, request: CompletionRequest) -> Result<CompletionResponse> {
+        let hash = Self::request_hash(&request, false)?;
+
+        if let Some(response) = self.lookup(&hash).and_then(|i| i.response) {
+            return Ok(response);
+        }
+        if self.mode == RecordingMode::Replay {
+            return Err(self.miss(&hash));
+        }
+
+        let response = self.inner.complete(request.clone()).await?;
+        self.store(Interaction {
+            request_hash: hash,
+            request,
+            response: Some(response.clone()),
+            stream: None,
+        })?;
+        Ok(response)
+    }
+
+    async fn complete_stream(
+        &self,
+        request: CompletionRequest,
+    ) -> Result<Pin<Box<dyn Stream<Item = Result<StreamChunk>> + Send>>> {
+        let hash = Self::request_hash(&request, true)?;
+
+        if let Some(chunks) = self.lookup(&hash).and_then(|i| i.stream) {
+            return Ok(Box::pin(stream::iter(chunks.into_iter().map(Ok))));
+        }
+        if self.mode == RecordingMode::Replay {
+            return Err(self.miss(&hash));
+        }
+
+        // The stream has to be drained before it can be stored
+        let mut inner_stream = self.inner.complete_stream(request.clone()).await?;
+        let mut chunks = Vec::new();
+        while let Some(chunk) = inner_stream.next().await {
+            chunks.push(chunk?);
+        }
+
+        self.store(Interaction {
+            request_hash: hash,
+            request,
+            response: None,
+            stream: Some(chunks.clone()),
+        })?;
+        Ok(Box::pin(stream::iter(chunks.into_iter().map(Ok))))
+    }
+
+    fn name(&self) -> &'static str {
+        self.inner.name()
+    }
+
+    fn default_model(&self) -> &'static str {
+        self.inner.default_model()
+    }
+
+    fn available_models(&self) -> Vec<&'static str> {
+        self.inner.available_models()
+    }
+
+    fn capabilities(&self) -> ProviderCapabilities {
+        self.inner.capabilities()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::MockProvider;
+    use crate::Message;
+
+    fn request(text: &str) -> CompletionRequest {
+        CompletionRequest {
+            model: "mock-model".to_string(),
+            messages: vec![Message::user(text)],
+            temperature: Some(0.2),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_request_hash_ignores_map_order() {
+        let keys = ["feature", "tenant", "region", "experiment", "tier"];
+        let build = |keys: &[&str]| {
+            let mut request = request("question");
+            for key in keys {
+                request
+                    .metadata
+                    .insert(key.to_string(), format!("{}-tag", key));
+                request.extra.insert(
+                    format!("x_{}", key),
+                    serde_json::json!({ "b": 1, "a": key }),
+                );
+            }
+            request
+        };
+        let reversed: Vec<&str> = keys.iter().rev().copied().collect();
+
+        assert_eq!(
+            RecordingProvider::request_hash(&build(&keys), false).unwrap(),
+            RecordingProvider::request_hash(&build(&reversed), false).unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_record_then_replay() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("cassette.json");
+
+        let mock = Arc::new(MockProvider::new().with_response("recorded answer"));
+        let recorder = RecordingProvider::new(mock.clone(), &path, RecordingMode::Record).unwrap();
+        let recorded = recorder.complete(request("question")).await.unwrap();
+        assert_eq!(mock.request_count(), 1);
+        assert!(path.exists());
+
+        // Replay serves from disk without touching the inner provider
+        let empty = Arc::new(MockProvider::new());
+        let replayer = RecordingProvider::new(empty.clone(), &path, RecordingMode::Replay).unwrap();
+        let replayed = replayer.complete(request("question")).await.unwrap();
+        assert_eq!(replayed.id, recorded.id);
+        assert_eq!(
+            replayed.choices[0].message.content.as_text(),
+            Some("recorded answer")
+        );
+        assert_eq!(empty.request_count(), 0);
+
+        // A different request is a cassette miss
+        assert!(replayer.complete(request("other")).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_stream_record_and_replay() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("stream.json");
+
+        let mock = Arc::new(MockProvider::new().with_response("streamed text"));
+        let recorder = RecordingProvider::new(mock, &path, RecordingMode::Auto).unwrap();
+        let chunks: Vec<_> = recorder
+            .complete_stream(request("stream me"))
+            .await
+            .unwrap()
+            .collect()
+            .await;
+
+        let replayer =
+            RecordingProvider::new(Arc::new(MockProvider::new()), &path, RecordingMode::Replay)
+                .unwrap();
+        let replayed: Vec<_> = replayer
+            .complete_stream(request("stream me"))
+            .await
+            .unwrap()
+            .collect()
+            .await;
+        assert_eq!(chunks.len(), replayed.len());
+
+        let text: String = replayed
+            .into_iter()
+            .filter_map(|c| c.unwrap().choices[0].delta.content.clone())
+            .collect();
+        assert_eq!(text, "streamed text");
+    }
+}
@@ -5,18 +5,29 @@ use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::env;
 use std::pin::Pin;
+use std::sync::Arc;
 use std::time::Duration;
-use tokio::time::sleep;
+use tokio::time::{sleep, Instant};
 
 use crate::{
-    AiError, Choice, CompletionProvider, CompletionRequest, CompletionResponse, Message,
-    MessageContent, Result, Role, StreamChunk,
+    backoff,
+    providers::{auth::SendAuthenticated, ApiKeyAuth, Authenticator, ProviderConfig},
+    AiError, BackoffStrategy, Choice, CompletionProvider, CompletionRequest, CompletionResponse,
+    GeneratedImage, ImageData, ImageGenerationProvider, ImageSize, JitterStrategy, Message,
+    MessageContent, ProviderCapabilities, Result, RetryConfig, Role, StreamChunk,
 };
 
 /// Replicate provider for open-source models
 pub struct ReplicateProvider {
     client: Client,
-    api_key: String,
+    /// Set by `with_client`, so `with_config` keeps the injected client
+    custom_client: bool,
+    auth: Arc<dyn Authenticator>,
+    base_url: String,
+    poll_interval: Duration,
+    max_wait: Duration,
+    image_model: String,
+    check_context_window: bool,
 }
 
 impl ReplicateProvider {
@@ -25,19 +36,90 @@ impl ReplicateProvider {
     /// # Arguments
     /// * `api_key` - Optional API key. If not provided, will look for REPLICATE_API_TOKEN env var
     pub fn new(api_key: Option<String>) -> Result<Self> {
+        Self::with_base_url(api_key, "https://api.replicate.com/v1".to_string())
+    }
+
+    /// Create a Replicate provider that sends requests to `base_url`, e.g. a
+    /// self-hosted gateway
+    pub fn with_base_url(api_key: Option<String>, base_url: String) -> Result<Self> {
         let api_key = api_key
             .or_else(|| env::var("REPLICATE_API_TOKEN").ok())
             .ok_or_else(|| AiError::MissingConfiguration {
                 field: "api_key".to_string(),
                 description: "Replicate API token not provided. Set REPLICATE_API_TOKEN environment variable or pass it explicitly".to_string(),
             })?;
+        super::validate_api_key("replicate", &api_key)?;
 
         Ok(Self {
-            client: Client::new(),
-            api_key,
+            client: super::config::default_client(),
+            custom_client: false,
+            auth: Arc::new(ApiKeyAuth::token(api_key)),
+            base_url,
+            poll_interval: Duration::from_millis(500),
+            max_wait: Duration::from_secs(300),
+            image_model: "stability-ai/sdxl".to_string(),
+            check_context_window: false,
         })
     }
 
+    /// Reject a request whose estimated size exceeds the model's context
+    /// window before sending it (see [`context_window`]). Off by default:
+    /// the estimate is rough and could turn away requests the API accepts.
+    ///
+    /// [`context_window`]: crate::providers::context_window
+    pub fn with_context_window_check(mut self, enabled: bool) -> Self {
+        self.check_context_window = enabled;
+        self
+    }
+
+    /// Send every request with the user agent and default headers from
+    /// `config`. A client given to [`with_client`](Self::with_client),
+    /// before or after this call, is kept; the user agent and headers are
+    /// then up to that client.
+    pub fn with_config(mut self, config: &ProviderConfig) -> Result<Self> {
+        let client = config.build_client()?;
+        if !self.custom_client {
+            self.client = client;
+        }
+        Ok(self)
+    }
+
+    /// Send requests through `client`, sharing its connection pool and DNS
+    /// cache with other providers. It takes precedence over the client
+    /// [`with_config`](Self::with_config) builds.
+    pub fn with_client(mut self, client: Client) -> Self {
+        self.client = client;
+        self.custom_client = true;
+        self
+    }
+
+    /// Authenticate requests with `auth` instead of the API key, e.g. for a
+    /// proxy or gateway with its own credentials
+    pub fn with_authenticator(mut self, auth: impl Authenticator + 'static) -> Self {
+        self.auth = Arc::new(auth);
+        self
+    }
+
+    /// Delay before the first prediction status check (default 500ms).
+    /// Later checks back off exponentially, up to 10 seconds apart.
+    pub fn with_poll_interval(mut self, interval: Duration) -> Self {
+        self.poll_interval = interval;
+        self
+    }
+
+    /// Give up on a prediction that hasn't finished after `max_wait`
+    /// (default 5 minutes)
+    pub fn with_max_wait(mut self, max_wait: Duration) -> Self {
+        self.max_wait = max_wait;
+        self
+    }
+
+    /// Model used by `generate_image` (default `stability-ai/sdxl`)
+    pub fn with_image_model(mut self, model: impl Into<String>) -> Self {
+        self.image_model = model.into();
+        self
+    }
+
     /// Get model version ID for a given model identifier
     async fn get_model_version(&self, model: &str) -> Result<String> {
         // For now, we'll use a mapping of known models to their versions
@@ -79,6 +161,7 @@ impl ReplicateProvider {
                     .iter()
                     .filter_map(|part| match part {
                         crate::ContentPart::Text { text } => Some(text.clone()),
+                        crate::ContentPart::Json { value } => Some(value.to_string()),
                         _ => None,
                     })
                     .collect::<Vec<_>>()
@@ -109,25 +192,27 @@ impl ReplicateProvider {
 
     /// Wait for a prediction to complete
     async fn wait_for_prediction(&self, prediction_url: &str) -> Result<ReplicatePrediction> {
-        let max_attempts = 300; // 5 minutes with 1 second intervals
+        let deadline = Instant::now() + self.max_wait;
+        // Poll quickly at first, backing off on long jobs
+        let mut delays = backoff::iter(&RetryConfig {
+            initial_delay: self.poll_interval,
+            max_delay: self.poll_interval.max(Duration::from_secs(10)),
+            backoff: BackoffStrategy::Exponential { multiplier: 1.5 },
+            jitter: JitterStrategy::Full,
+            ..RetryConfig::default()
+        });
 
-        for _ in 0..max_attempts {
+        while Instant::now() < deadline {
             let response = self
                 .client
                 .get(prediction_url)
-                .header("Authorization", format!("Token {}", self.api_key))
-                .send()
+                .send_authenticated(self.auth.as_ref())
                 .await?;
 
-            if !response.status().is_success() {
-                let error_text = response.text().await?;
-                return Err(AiError::ProviderError {
-                    provider: "replicate".to_string(),
-                    message: format!("Failed to get prediction status: {}", error_text),
-                    error_code: None,
-                    retryable: false,
-                });
-            }
+            let response = super::check_response(response, |error| {
+                error.into_provider_error("replicate", "Failed to get prediction status")
+            })
+            .await?;
 
             let prediction: ReplicatePrediction = response.json().await?;
 
@@ -145,11 +230,14 @@ impl ReplicateProvider {
                         ),
                         error_code: None,
                         retryable: false,
+                        status_code: None,
+                        request_id: None,
                     });
                 }
                 "starting" | "processing" => {
                     // Still running, wait and retry
-                    sleep(Duration::from_secs(1)).await;
+                    let delay = delays.next().unwrap_or(self.poll_interval);
+                    sleep(delay.min(deadline.saturating_duration_since(Instant::now()))).await;
                 }
                 _ => {
                     return Err(AiError::ProviderError {
@@ -157,22 +245,52 @@ impl ReplicateProvider {
                         message: format!("Unknown prediction status: {}", prediction.status),
                         error_code: None,
                         retryable: false,
+                        status_code: None,
+                        request_id: None,
                     });
                 }
             }
         }
 
         Err(AiError::TimeoutError {
-            timeout: Duration::from_secs(300),
+            timeout: self.max_wait,
             retryable: false,
         })
     }
-}
 
-#[async_trait]
-impl CompletionProvider for ReplicateProvider {
-    async fn complete(&self, request: CompletionRequest) -> Result<CompletionResponse> {
-        let url = "https://api.replicate.com/v1/predictions";
+    /// Create a prediction and wait for it to finish
+    async fn run_prediction(&self, version: String, input: Value) -> Result<ReplicatePrediction> {
+        let replicate_request = ReplicateCreatePrediction {
+            version,
+            input,
+            webhook: None,
+            webhook_events_filter: None,
+        };
+
+        let response = self
+            .client
+            .post(format!("{}/predictions", self.base_url))
+            .header("Content-Type", "application/json")
+            .json(&replicate_request)
+            .send_authenticated(self.auth.as_ref())
+            .await?;
+
+        let response = super::check_response(response, |error| {
+            error.into_provider_error("replicate", "Replicate API error")
+        })
+        .await?;
+
+        let prediction: ReplicatePrediction = response.json().await?;
+        self.wait_for_prediction(&prediction.urls.get).await
+    }
+
+    /// Run a single prediction and wait for its output
+    async fn complete_once(&self, mut request: CompletionRequest) -> Result<CompletionResponse> {
+        request.normalize_system();
+        request.validate()?;
+        if self.check_context_window {
+            super::check_context_window(&request)?;
+        }
 
         // Get the model version
         let version = self.get_model_version(&request.model).await?;
@@ -201,38 +319,12 @@ impl CompletionProvider for ReplicateProvider {
             input["stop_sequences"] = serde_json::json!(stop.join(","));
         }
 
-        let replicate_request = ReplicateCreatePrediction {
-            version,
-            input,
-            webhook: None,
-            webhook_events_filter: None,
-        };
-
-        // Create the prediction
-        let response = self
-            .client
-            .post(url)
-            .header("Authorization", format!("Token {}", self.api_key))
-            .header("Content-Type", "application/json")
-            .json(&replicate_request)
-            .send()
-            .await?;
-
-        let status = response.status();
-        if !status.is_success() {
-            let error_text = response.text().await?;
-            return Err(AiError::ProviderError {
-                provider: "replicate".to_string(),
-                message: format!("Replicate API error: {}", error_text),
-                error_code: None,
-                retryable: status.is_server_error(),
-            });
+        // Model-specific inputs pass through as is
+        for (name, value) in &request.extra {
+            input[name.as_str()] = value.clone();
         }
 
-        let prediction: ReplicatePrediction = response.json().await?;
-
-        // Wait for the prediction to complete
-        let completed_prediction = self.wait_for_prediction(&prediction.urls.get).await?;
+        let completed_prediction = self.run_prediction(version, input).await?;
 
         // Extract the output
         let output_text = match &completed_prediction.output {
@@ -252,22 +344,31 @@ impl CompletionProvider for ReplicateProvider {
             model: request.model.clone(),
             choices: vec![Choice {
                 index: 0,
-                message: Message {
-                    role: Role::Assistant,
-                    content: MessageContent::text(output_text),
-                    tool_calls: None,
-                    tool_call_id: None,
-                },
+                message: Message::assistant(output_text),
                 finish_reason: Some("stop".to_string()),
+                logprobs: None,
             }],
             usage: None, // Replicate doesn't provide token usage info
+            metadata: None,
         })
     }
+}
+
+#[async_trait]
+impl CompletionProvider for ReplicateProvider {
+    async fn complete(&self, request: CompletionRequest) -> Result<CompletionResponse> {
+        // No native JSON mode, so JSON requests are emulated
+        super::json_mode::complete_with_json_fallback(request, |request| {
+            self.complete_once(request)
+        })
+        .await
+    }
 
     async fn complete_stream(
         &self,
-        request: CompletionRequest,
+        mut request: CompletionRequest,
     ) -> Result<Pin<Box<dyn Stream<Item = Result<StreamChunk>> + Send>>> {
+        request.normalize_system();
         // Replicate doesn't support true streaming for language models
         // We'll simulate it by getting the full response and streaming it back
         let response = self.complete(request).await?;
@@ -304,6 +405,8 @@ impl CompletionProvider for ReplicateProvider {
                             finish_reason: None,
                         }],
                         model: None,
+                        usage: None,
+                        metadata: None,
                     })
                 })
                 .chain(std::iter::once(Ok(StreamChunk {
@@ -318,6 +421,8 @@ impl CompletionProvider for ReplicateProvider {
                         finish_reason: Some("stop".to_string()),
                     }],
                     model: None,
+                    usage: None,
+                    metadata: None,
                 }))),
         );
 
@@ -338,9 +443,74 @@ impl CompletionProvider for ReplicateProvider {
             "meta/llama-2-13b-chat",
             "meta/llama-2-7b-chat",
             "mistralai/mistral-7b-instruct-v0.2",
-            "stability-ai/sdxl", // For image generation
+            "stability-ai/sdxl", // Image model, used through ImageGenerationProvider
         ]
     }
+
+    fn capabilities(&self) -> ProviderCapabilities {
+        ProviderCapabilities {
+            streaming: true,
+            tools: false,
+            vision: false,
+            json_mode: false,
+            max_context_tokens: None,
+            max_output_tokens: None,
+            alternating_roles: false,
+            assistant_prefill: false,
+        }
+    }
+}
+
+#[async_trait]
+impl ImageGenerationProvider for ReplicateProvider {
+    async fn generate_image(
+        &self,
+        prompt: &str,
+        size: ImageSize,
+        n: u32,
+    ) -> Result<Vec<GeneratedImage>> {
+        let version = self.get_model_version(&self.image_model).await?;
+        let input = serde_json::json!({
+            "prompt": prompt,
+            "width": size.width,
+            "height": size.height,
+            "num_outputs": n,
+        });
+        let prediction = self.run_prediction(version, input).await?;
+
+        // SDXL returns a list of file URLs; some models return a single one
+        let urls = match prediction.output {
+            Some(Value::String(url)) => vec![url],
+            Some(Value::Array(items)) => items
+                .into_iter()
+                .filter_map(|v| v.as_str().map(String::from))
+                .collect(),
+            _ => Vec::new(),
+        };
+        Ok(urls
+            .into_iter()
+            .map(|url| GeneratedImage {
+                mime: image_mime(&url).to_string(),
+                url_or_b64: ImageData::Url(url),
+            })
+            .collect())
+    }
+}
+
+/// MIME type from an image URL's extension, defaulting to PNG
+fn image_mime(url: &str) -> &'static str {
+    let path = url.split(['?', '#']).next().unwrap_or(url);
+    match path
+        .rsplit('.')
+        .next()
+        .map(str::to_ascii_lowercase)
+        .as_deref()
+    {
+        Some("jpg" | "jpeg") => "image/jpeg",
+        Some("webp") => "image/webp",
+        Some("gif") => "image/gif",
+        _ => "image/png",
+    }
 }
 
 // Replicate API types
@@ -391,20 +561,7 @@ mod tests {
     fn test_prompt_formatting() {
         let provider = ReplicateProvider::new(Some("test-token".to_string())).unwrap();
 
-        let messages = vec![
-            Message {
-                role: Role::System,
-                content: MessageContent::text("You are helpful"),
-                tool_calls: None,
-                tool_call_id: None,
-            },
-            Message {
-                role: Role::User,
-                content: MessageContent::text("Hello"),
-                tool_calls: None,
-                tool_call_id: None,
-            },
-        ];
+        let messages = vec![Message::system("You are helpful"), Message::user("Hello")];
 
         let prompt = provider.format_prompt(&messages);
         assert_eq!(
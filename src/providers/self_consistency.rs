@@ -0,0 +1,154 @@
+//! Self-consistency sampling: ask the same question several times and keep
+//! the answer the samples agree on.
+
+use futures::future::join_all;
+use std::collections::HashMap;
+
+use crate::providers::canonical_json;
+use crate::{AiError, CompletionProvider, CompletionRequest, CompletionResponse, Result};
+
+/// Send `request` `n` times concurrently and let `reducer` pick the final
+/// response, e.g. [`majority_vote`].
+///
+/// Samples that fail are dropped; the call only fails if all of them do,
+/// returning the first error. Use a non-zero temperature, or the samples
+/// will usually agree trivially.
+pub async fn self_consistency<P, R>(
+    provider: &P,
+    request: CompletionRequest,
+    n: usize,
+    reducer: R,
+) -> Result<CompletionResponse>
+where
+    P: CompletionProvider + ?Sized,
+    R: FnOnce(Vec<CompletionResponse>) -> Result<CompletionResponse>,
+{
+    if n == 0 {
+        return Err(AiError::InvalidRequest {
+            message: "self_consistency needs at least one sample".to_string(),
+            field: Some("n".to_string()),
+            code: None,
+        });
+    }
+
+    let results = join_all((0..n).map(|_| provider.complete(request.clone()))).await;
+
+    let mut responses = Vec::with_capacity(n);
+    let mut first_error = None;
+    for result in results {
+        match result {
+            Ok(response) => responses.push(response),
+            Err(e) => {
+                first_error.get_or_insert(e);
+            }
+        }
+    }
+    match first_error {
+        Some(e) if responses.is_empty() => Err(e),
+        _ => reducer(responses),
+    }
+}
+
+/// Reducer returning the response whose answer occurs most often, the
+/// earliest one on a tie.
+///
+/// Answers are compared after trimming whitespace; answers that parse as
+/// JSON are compared as values, so key order and formatting don't matter.
+pub fn majority_vote(responses: Vec<CompletionResponse>) -> Result<CompletionResponse> {
+    let answers: Vec<String> = responses.iter().map(vote_key).collect();
+
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    for answer in &answers {
+        *counts.entry(answer).or_default() += 1;
+    }
+
+    // `max_by_key` keeps the last maximum, so scan in reverse to favour the
+    // earliest response on a tie
+    let winner = answers
+        .iter()
+        .enumerate()
+        .rev()
+        .max_by_key(|(_, answer)| counts[answer.as_str()])
+        .map(|(index, _)| index)
+        .ok_or_else(|| AiError::MalformedResponse {
+            message: "No responses to vote on".to_string(),
+            raw_response: None,
+        })?;
+
+    Ok(responses
+        .into_iter()
+        .nth(winner)
+        .expect("winner is in range"))
+}
+
+fn vote_key(response: &CompletionResponse) -> String {
+    let text = response
+        .choices
+        .first()
+        .map(|choice| choice.message.content.as_text_lossy().into_owned())
+        .unwrap_or_default();
+    let text = text.trim();
+    match serde_json::from_str::<serde_json::Value>(text) {
+        Ok(value) => canonical_json(&value),
+        Err(_) => text.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::MockProvider;
+    use crate::Message;
+
+    fn request() -> CompletionRequest {
+        CompletionRequest {
+            model: "mock-model".to_string(),
+            messages: vec![Message::user("Capital of France?")],
+            temperature: Some(0.8),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_majority_vote_picks_most_common_answer() {
+        let provider = MockProvider::new()
+            .with_response("Lyon")
+            .with_response("Paris")
+            .with_error(AiError::InternalError {
+                message: "sample failed".to_string(),
+                component: None,
+            })
+            .with_response(" Paris\n")
+            .with_response("Marseille");
+
+        let response = self_consistency(&provider, request(), 5, majority_vote)
+            .await
+            .unwrap();
+        assert_eq!(provider.request_count(), 5);
+        assert_eq!(response.text().map(str::trim), Some("Paris"));
+
+        // Structured answers are compared as JSON
+        let provider = MockProvider::new()
+            .with_response(r#"{"city": "Lyon"}"#)
+            .with_response(r#"{"country": "FR", "city": "Paris"}"#)
+            .with_response(r#"{"city":"Paris","country":"FR"}"#);
+        let response = self_consistency(&provider, request(), 3, majority_vote)
+            .await
+            .unwrap();
+        assert!(response.text().unwrap().contains("Paris"));
+
+        // Any closure can reduce the samples
+        let provider = MockProvider::new()
+            .with_response("short")
+            .with_response("the longest answer");
+        let response = self_consistency(&provider, request(), 2, |responses| {
+            Ok(responses
+                .into_iter()
+                .max_by_key(|r| r.text().map_or(0, str::len))
+                .unwrap())
+        })
+        .await
+        .unwrap();
+        assert_eq!(response.text(), Some("the longest answer"));
+    }
+}
@@ -0,0 +1,389 @@
+use bytes::Bytes;
+use futures::stream::{self, BoxStream, Stream, StreamExt};
+use std::collections::VecDeque;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use crate::{AiError, Result, StreamChunk};
+
+const DONE_FRAME: &[u8] = b"data: [DONE]\n\n";
+
+/// Format a completion stream as Server-Sent Events.
+///
+/// Each chunk becomes a `data: {json}` frame and a successful stream ends
+/// with `data: [DONE]`. A provider error is sent as an `event: error` frame
+/// and ends the stream. Chunks are only pulled from `stream` when the
+/// returned stream is polled, so a slow client applies backpressure all the
+/// way to the provider.
+pub fn stream_to_sse<S>(stream: S) -> impl Stream<Item = Result<Bytes>> + Send
+where
+    S: Stream<Item = Result<StreamChunk>> + Send + Unpin + 'static,
+{
+    stream::unfold(Some(stream), |state| async move {
+        let mut stream = state?;
+        match stream.next().await {
+            Some(Ok(chunk)) => Some((chunk_frame(&chunk), Some(stream))),
+            Some(Err(error)) => Some((Ok(error_frame(&error)), None)),
+            None => Some((Ok(Bytes::from_static(DONE_FRAME)), None)),
+        }
+    })
+}
+
+fn chunk_frame(chunk: &StreamChunk) -> Result<Bytes> {
+    let json = serde_json::to_string(chunk)?;
+    Ok(Bytes::from(format!("data: {}\n\n", json)))
+}
+
+fn error_frame(error: &AiError) -> Bytes {
+    let json = serde_json::json!({ "error": error.to_string() });
+    Bytes::from(format!("event: error\ndata: {}\n\n", json))
+}
+
+/// One Server-Sent Event read from a provider response
+#[derive(Debug, Clone, PartialEq)]
+pub struct SseEvent {
+    /// Value of the `event:` field, if the server named the event
+    pub event: Option<String>,
+    /// `data:` lines of the event joined with `\n`
+    pub data: String,
+}
+
+/// Incremental SSE parser. Bytes can be split anywhere, including inside a
+/// line or a UTF-8 sequence; an event is only emitted once the blank line
+/// ending it has arrived.
+#[derive(Debug, Default)]
+pub struct SseParser {
+    buffer: Vec<u8>,
+    event: Option<String>,
+    data: Vec<String>,
+}
+
+impl SseParser {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Feed the next bytes and return the events they complete
+    pub fn push(&mut self, bytes: &[u8]) -> Vec<SseEvent> {
+        self.buffer.extend_from_slice(bytes);
+
+        let mut events = Vec::new();
+        while let Some(end) = self.buffer.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = self.buffer.drain(..=end).collect();
+            let line = String::from_utf8_lossy(&line);
+            if let Some(event) = self.process_line(line.trim_end_matches(['\n', '\r'])) {
+                events.push(event);
+            }
+        }
+        events
+    }
+
+    /// Emit the event still pending when the body ends without a trailing
+    /// blank line
+    pub fn finish(&mut self) -> Option<SseEvent> {
+        let rest = std::mem::take(&mut self.buffer);
+        let rest = String::from_utf8_lossy(&rest);
+        let line = rest.trim_end_matches('\r');
+        if !line.is_empty() {
+            self.process_line(line);
+        }
+        self.dispatch()
+    }
+
+    fn process_line(&mut self, line: &str) -> Option<SseEvent> {
+        if line.is_empty() {
+            return self.dispatch();
+        }
+        // Lines starting with a colon are comments, often used as keep-alives
+        if line.starts_with(':') {
+            return None;
+        }
+
+        let (field, value) = line.split_once(':').unwrap_or((line, ""));
+        let value = value.strip_prefix(' ').unwrap_or(value);
+        match field {
+            "event" => self.event = Some(value.to_string()),
+            "data" => self.data.push(value.to_string()),
+            _ => {}
+        }
+        None
+    }
+
+    fn dispatch(&mut self) -> Option<SseEvent> {
+        let event = self.event.take();
+        if self.data.is_empty() {
+            return None;
+        }
+        Some(SseEvent {
+            event,
+            data: std::mem::take(&mut self.data).join("\n"),
+        })
+    }
+}
+
+/// Event names providers use for keepalives
+const KEEPALIVE_EVENTS: &[&str] = &["ping", "keepalive", "heartbeat"];
+
+impl SseEvent {
+    /// Whether the event only keeps the connection open, such as Anthropic's
+    /// `ping` or a `data:` line with nothing in it
+    pub fn is_keepalive(&self) -> bool {
+        self.data.trim().is_empty()
+            || self
+                .event
+                .as_deref()
+                .is_some_and(|name| KEEPALIVE_EVENTS.contains(&name))
+    }
+}
+
+/// End `stream` with `AiError::TimeoutError` when no item arrives for
+/// `timeout`; with `None` the stream is left as is. Used for response
+/// bodies, so any bytes, even a keepalive, count as activity.
+pub(crate) fn idle_timeout<S, T>(
+    stream: S,
+    timeout: Option<Duration>,
+) -> BoxStream<'static, Result<T>>
+where
+    S: Stream<Item = Result<T>> + Send + 'static,
+    T: Send + 'static,
+{
+    let Some(timeout) = timeout else {
+        return stream.boxed();
+    };
+    stream::unfold(Some(stream.boxed()), move |state| async move {
+        let mut stream = state?;
+        match tokio::time::timeout(timeout, stream.next()).await {
+            Ok(Some(item)) => Some((item, Some(stream))),
+            Ok(None) => None,
+            Err(_) => Some((
+                Err(AiError::TimeoutError {
+                    timeout,
+                    retryable: true,
+                }),
+                None,
+            )),
+        }
+    })
+    .boxed()
+}
+
+/// Adapts a response body, such as `reqwest::Response::bytes_stream()`,
+/// into a stream of complete events. Comments and keepalive events are
+/// dropped, but still count as activity for the idle timeout.
+pub struct EventStream {
+    inner: Option<BoxStream<'static, Result<Bytes>>>,
+    parser: SseParser,
+    pending: VecDeque<SseEvent>,
+}
+
+impl EventStream {
+    pub fn new<S, E>(bytes: S) -> Self
+    where
+        S: Stream<Item = std::result::Result<Bytes, E>> + Send + 'static,
+        E: std::fmt::Display,
+    {
+        let inner = bytes.map(|result| {
+            result.map_err(|e| AiError::StreamError {
+                message: e.to_string(),
+                retryable: true,
+            })
+        });
+        Self {
+            inner: Some(inner.boxed()),
+            parser: SseParser::new(),
+            pending: VecDeque::new(),
+        }
+    }
+
+    /// End the stream with `AiError::TimeoutError` when no bytes, not even a
+    /// keepalive, arrive for `timeout`
+    pub fn with_idle_timeout(self, timeout: Duration) -> Self {
+        self.with_optional_idle_timeout(Some(timeout))
+    }
+
+    /// [`with_idle_timeout`](Self::with_idle_timeout) when `timeout` is set,
+    /// as configured by [`ProviderConfig::stream_idle_timeout`]
+    ///
+    /// [`ProviderConfig::stream_idle_timeout`]: super::ProviderConfig::stream_idle_timeout
+    pub fn with_optional_idle_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.inner = self.inner.take().map(|inner| idle_timeout(inner, timeout));
+        self
+    }
+}
+
+impl Stream for EventStream {
+    type Item = Result<SseEvent>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        loop {
+            if let Some(event) = self.pending.pop_front() {
+                return Poll::Ready(Some(Ok(event)));
+            }
+            let Some(inner) = self.inner.as_mut() else {
+                return Poll::Ready(None);
+            };
+
+            match inner.poll_next_unpin(cx) {
+                Poll::Pending => return Poll::Pending,
+                Poll::Ready(Some(Ok(bytes))) => {
+                    let events = self.parser.push(&bytes);
+                    self.pending
+                        .extend(events.into_iter().filter(|event| !event.is_keepalive()));
+                }
+                Poll::Ready(Some(Err(e))) => {
+                    self.inner = None;
+                    return Poll::Ready(Some(Err(e)));
+                }
+                Poll::Ready(None) => {
+                    self.inner = None;
+                    let last = self.parser.finish();
+                    self.pending
+                        .extend(last.filter(|event| !event.is_keepalive()));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Delta, StreamChoice};
+    use tokio::time::Instant;
+
+    fn chunk(text: &str) -> StreamChunk {
+        StreamChunk {
+            id: "chunk-1".to_string(),
+            choices: vec![StreamChoice {
+                index: 0,
+                delta: Delta {
+                    role: None,
+                    content: Some(text.to_string()),
+                    tool_calls: None,
+                },
+                finish_reason: None,
+            }],
+            model: None,
+            usage: None,
+            metadata: None,
+        }
+    }
+
+    async fn collect(items: Vec<Result<StreamChunk>>) -> String {
+        let frames: Vec<_> = stream_to_sse(stream::iter(items)).collect().await;
+        frames
+            .into_iter()
+            .map(|frame| String::from_utf8(frame.unwrap().to_vec()).unwrap())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_sse_framing() {
+        let body = collect(vec![Ok(chunk("Hel")), Ok(chunk("lo"))]).await;
+        let frames: Vec<&str> = body.split_terminator("\n\n").collect();
+
+        assert_eq!(frames.len(), 3);
+        for frame in &frames[..2] {
+            let json = frame.strip_prefix("data: ").unwrap();
+            let parsed: StreamChunk = serde_json::from_str(json).unwrap();
+            assert_eq!(parsed.id, "chunk-1");
+        }
+        assert_eq!(frames[2], "data: [DONE]");
+    }
+
+    #[tokio::test]
+    async fn test_sse_error_frame_ends_stream() {
+        let body = collect(vec![
+            Ok(chunk("partial")),
+            Err(AiError::TimeoutError {
+                timeout: std::time::Duration::from_secs(5),
+                retryable: true,
+            }),
+            Ok(chunk("never sent")),
+        ])
+        .await;
+
+        assert!(body.contains("event: error\ndata: {\"error\":"));
+        assert!(!body.contains("never sent"));
+        assert!(!body.contains("[DONE]"));
+    }
+
+    #[tokio::test]
+    async fn test_event_stream_reassembles_split_events() {
+        let body = concat!(
+            ": keep-alive\n",
+            "event: message_start\n",
+            "data: {\"text\": \"caf\u{e9}\"}\n\n",
+            "data: first line\r\n",
+            "data: second line\r\n\r\n",
+            "data: [DONE]",
+        )
+        .as_bytes();
+
+        // Split every few bytes, cutting through lines and the `é`
+        for size in [1, 2, 3, 7, body.len()] {
+            let pieces: Vec<std::result::Result<Bytes, std::io::Error>> = body
+                .chunks(size)
+                .map(|piece| Ok(Bytes::copy_from_slice(piece)))
+                .collect();
+            let events: Vec<SseEvent> = EventStream::new(stream::iter(pieces))
+                .map(|event| event.unwrap())
+                .collect()
+                .await;
+
+            assert_eq!(
+                events,
+                vec![
+                    SseEvent {
+                        event: Some("message_start".to_string()),
+                        data: "{\"text\": \"caf\u{e9}\"}".to_string(),
+                    },
+                    SseEvent {
+                        event: None,
+                        data: "first line\nsecond line".to_string(),
+                    },
+                    SseEvent {
+                        event: None,
+                        data: "[DONE]".to_string(),
+                    },
+                ],
+                "split size {}",
+                size
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_keepalives_are_dropped_but_reset_idle_timeout() {
+        let pieces: Vec<std::result::Result<Bytes, std::io::Error>> = vec![
+            Ok(Bytes::from_static(b": ping\n\n")),
+            Ok(Bytes::from_static(
+                b"event: ping\ndata: {\"type\": \"ping\"}\n\n",
+            )),
+            Ok(Bytes::from_static(b"data:\n\n\n")),
+            Ok(Bytes::from_static(b"data: {\"text\": \"hi\"}\n\n")),
+            Ok(Bytes::from_static(b": ping\n\n")),
+        ];
+        let events: Vec<SseEvent> = EventStream::new(stream::iter(pieces))
+            .map(|event| event.unwrap())
+            .collect()
+            .await;
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].data, "{\"text\": \"hi\"}");
+
+        // Keepalives arriving within the timeout keep the stream open; the
+        // timeout only fires once the body goes quiet
+        let keepalives = stream::iter(0..3).then(|_| async {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            Ok::<_, std::io::Error>(Bytes::from_static(b": ping\n\n"))
+        });
+        let body = keepalives.chain(stream::pending());
+        let mut events = EventStream::new(body).with_idle_timeout(Duration::from_millis(50));
+        let started = Instant::now();
+        let result = events.next().await.unwrap();
+        assert!(matches!(result, Err(AiError::TimeoutError { .. })));
+        assert!(started.elapsed() >= Duration::from_millis(100));
+        assert!(events.next().await.is_none());
+    }
+}
@@ -0,0 +1,115 @@
+//! Share one completion stream between several consumers without
+//! re-issuing the request.
+
+use futures::stream::{self, Stream, StreamExt};
+use std::pin::Pin;
+use tokio::sync::mpsc;
+
+use crate::{Result, StreamChunk};
+
+/// Chunks buffered for each half before the faster consumer has to wait
+pub const TEE_BUFFER_SIZE: usize = 32;
+
+/// One half of a [`tee_stream`]
+pub type TeeStream = Pin<Box<dyn Stream<Item = Result<StreamChunk>> + Send>>;
+
+/// Split a completion stream into two streams that each see every chunk,
+/// errors included, in the same order.
+///
+/// A background task pulls from `stream` and buffers up to
+/// [`TEE_BUFFER_SIZE`] chunks per half, so a consumer that falls that far
+/// behind holds the other one back. Dropping one half lets the other carry
+/// on alone; the source is dropped once both halves are gone. Must be called
+/// from within a Tokio runtime.
+pub fn tee_stream<S>(stream: S) -> (TeeStream, TeeStream)
+where
+    S: Stream<Item = Result<StreamChunk>> + Send + 'static,
+{
+    let (first_tx, first_rx) = mpsc::channel(TEE_BUFFER_SIZE);
+    let (second_tx, second_rx) = mpsc::channel(TEE_BUFFER_SIZE);
+
+    tokio::spawn(async move {
+        let mut stream = Box::pin(stream);
+        let mut first = Some(first_tx);
+        let mut second = Some(second_tx);
+
+        while first.is_some() || second.is_some() {
+            let Some(item) = stream.next().await else {
+                break;
+            };
+            if let Some(tx) = &first {
+                if tx.send(item.clone()).await.is_err() {
+                    first = None;
+                }
+            }
+            if let Some(tx) = &second {
+                if tx.send(item).await.is_err() {
+                    second = None;
+                }
+            }
+        }
+    });
+
+    (receiver_stream(first_rx), receiver_stream(second_rx))
+}
+
+fn receiver_stream(rx: mpsc::Receiver<Result<StreamChunk>>) -> TeeStream {
+    Box::pin(stream::unfold(rx, |mut rx| async move {
+        let item = rx.recv().await?;
+        Some((item, rx))
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::MockProvider;
+    use crate::{CompletionProvider, CompletionRequest, Message};
+
+    fn request() -> CompletionRequest {
+        CompletionRequest {
+            model: "mock-model".to_string(),
+            messages: vec![Message::user("hi")],
+            stream: Some(true),
+            ..Default::default()
+        }
+    }
+
+    fn describe(chunks: Vec<Result<StreamChunk>>) -> Vec<(String, Option<String>)> {
+        chunks
+            .into_iter()
+            .map(|chunk| {
+                let chunk = chunk.unwrap();
+                let content = chunk.choices.first().and_then(|c| c.delta.content.clone());
+                (chunk.id, content)
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_both_halves_see_the_same_chunks() {
+        // More chunks than the buffer holds, so the halves must interleave
+        let text = "a fairly long answer ".repeat(20);
+        let provider = MockProvider::new()
+            .with_stream_chunk_size(2)
+            .with_default_response(&text);
+        let stream = provider.complete_stream(request()).await.unwrap();
+
+        let (first, second) = tee_stream(stream);
+        let (first, second) = tokio::join!(first.collect::<Vec<_>>(), second.collect::<Vec<_>>());
+        let first = describe(first);
+        let second = describe(second);
+
+        assert!(first.len() > TEE_BUFFER_SIZE);
+        assert_eq!(first, second);
+        let streamed: String = first.iter().filter_map(|(_, c)| c.clone()).collect();
+        assert_eq!(streamed, text);
+        assert_eq!(provider.request_count(), 1);
+
+        // Dropping one half doesn't stall the other
+        let stream = provider.complete_stream(request()).await.unwrap();
+        let (first, second) = tee_stream(stream);
+        drop(second);
+        assert!(!first.collect::<Vec<_>>().await.is_empty());
+    }
+}
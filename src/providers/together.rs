@@ -2,18 +2,30 @@ use async_trait::async_trait;
 use futures::stream::{Stream, StreamExt};
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
 use std::env;
 use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
 
 use crate::{
+    providers::{auth::SendAuthenticated, ApiKeyAuth, Authenticator, EventStream, ProviderConfig},
     AiError, Choice, CompletionProvider, CompletionRequest, CompletionResponse, Message,
-    MessageContent, Result, Role, StreamChunk, Usage,
+    MessageContent, ProviderCapabilities, Result, Role, StreamChunk, Tool, ToolCall, ToolCallDelta,
+    ToolChoice, Usage,
 };
 
 /// Together AI provider for various open models
 pub struct TogetherProvider {
     client: Client,
-    api_key: String,
+    /// Set by `with_client`, so `with_config` keeps the injected client
+    custom_client: bool,
+    auth: Arc<dyn Authenticator>,
+    base_url: String,
+    stream_idle_timeout: Option<Duration>,
+    validate_models: bool,
+    check_context_window: bool,
 }
 
 impl TogetherProvider {
@@ -22,19 +34,93 @@ impl TogetherProvider {
     /// # Arguments
     /// * `api_key` - Optional API key. If not provided, will look for TOGETHER_API_KEY env var
     pub fn new(api_key: Option<String>) -> Result<Self> {
+        Self::with_base_url(api_key, "https://api.together.xyz/v1".to_string())
+    }
+
+    /// Create a Together AI provider that sends requests to `base_url`, e.g.
+    /// a self-hosted gateway
+    pub fn with_base_url(api_key: Option<String>, base_url: String) -> Result<Self> {
         let api_key = api_key
             .or_else(|| env::var("TOGETHER_API_KEY").ok())
             .ok_or_else(|| AiError::MissingConfiguration {
                 field: "api_key".to_string(),
                 description: "Together AI API key not provided. Set TOGETHER_API_KEY environment variable or pass it explicitly".to_string(),
             })?;
+        super::validate_api_key("together", &api_key)?;
 
         Ok(Self {
-            client: Client::new(),
-            api_key,
+            client: super::config::default_client(),
+            custom_client: false,
+            auth: Arc::new(ApiKeyAuth::bearer(api_key)),
+            base_url,
+            stream_idle_timeout: None,
+            validate_models: false,
+            check_context_window: false,
         })
     }
 
+    /// Reject models missing from `available_models()` before sending,
+    /// instead of waiting for the API to refuse them. Together hosts many
+    /// more models than the list names, so this is off by default.
+    pub fn with_model_validation(mut self, enabled: bool) -> Self {
+        self.validate_models = enabled;
+        self
+    }
+
+    /// Reject a request whose estimated size exceeds the model's context
+    /// window before sending it (see [`context_window`]). Off by default:
+    /// the estimate is rough and could turn away requests the API accepts.
+    ///
+    /// [`context_window`]: crate::providers::context_window
+    pub fn with_context_window_check(mut self, enabled: bool) -> Self {
+        self.check_context_window = enabled;
+        self
+    }
+
+    /// Send every request with the user agent and default headers from
+    /// `config`. A client given to [`with_client`](Self::with_client),
+    /// before or after this call, is kept; the user agent and headers are
+    /// then up to that client.
+    pub fn with_config(mut self, config: &ProviderConfig) -> Result<Self> {
+        let client = config.build_client()?;
+        if !self.custom_client {
+            self.client = client;
+        }
+        self.stream_idle_timeout = config.stream_idle_timeout;
+        Ok(self)
+    }
+
+    /// Send requests through `client`, sharing its connection pool and DNS
+    /// cache with other providers. It takes precedence over the client
+    /// [`with_config`](Self::with_config) builds.
+    pub fn with_client(mut self, client: Client) -> Self {
+        self.client = client;
+        self.custom_client = true;
+        self
+    }
+
+    /// Authenticate requests with `auth` instead of the API key, e.g. for a
+    /// proxy or gateway with its own credentials
+    pub fn with_authenticator(mut self, auth: impl Authenticator + 'static) -> Self {
+        self.auth = Arc::new(auth);
+        self
+    }
+
+    /// Capabilities when serving `model`; some model families, such as
+    /// Mistral's instruct models and Llama 2 chat, need alternating roles
+    pub fn capabilities_for(&self, model: &str) -> ProviderCapabilities {
+        ProviderCapabilities {
+            streaming: true,
+            tools: true,
+            vision: false,
+            json_mode: true,
+            max_context_tokens: None,
+            max_output_tokens: None,
+            alternating_roles: requires_alternating_roles(model),
+            assistant_prefill: false,
+        }
+    }
+
     fn convert_message(&self, message: &Message) -> TogetherMessage {
         let content = match &message.content {
             MessageContent::Text(text) => text.clone(),
@@ -42,6 +128,7 @@ impl TogetherProvider {
                 .iter()
                 .filter_map(|part| match part {
                     crate::ContentPart::Text { text } => Some(text.clone()),
+                    crate::ContentPart::Json { value } => Some(value.to_string()),
                     _ => None,
                 })
                 .collect::<Vec<_>>()
@@ -49,16 +136,55 @@ impl TogetherProvider {
         };
 
         TogetherMessage {
-            role: match message.role {
-                Role::System => "system",
-                Role::User => "user",
-                Role::Assistant => "assistant",
-                Role::Tool => "tool",
-            },
+            role: message.role.as_str(),
             content,
+            tool_calls: message.tool_calls.clone(),
+            tool_call_id: message.tool_call_id.clone(),
         }
     }
 
+    fn build_request(
+        &self,
+        request: &CompletionRequest,
+        stream: bool,
+    ) -> Result<TogetherChatRequest> {
+        let mut body = TogetherChatRequest {
+            model: request.model.clone(),
+            messages: request
+                .messages
+                .iter()
+                .map(|msg| self.convert_message(msg))
+                .collect(),
+            temperature: request.temperature,
+            max_tokens: request.max_tokens.map(|t| t as i32),
+            top_p: request.top_p,
+            frequency_penalty: request.frequency_penalty,
+            presence_penalty: request.presence_penalty,
+            stop: request.stop.clone(),
+            stream,
+            stream_options: stream.then_some(TogetherStreamOptions {
+                include_usage: true,
+            }),
+            response_format: request
+                .response_format
+                .as_ref()
+                .map(|f| TogetherResponseFormat {
+                    r#type: match &f.r#type {
+                        crate::ResponseFormatType::Text => "text",
+                        crate::ResponseFormatType::JsonObject => "json_object",
+                        crate::ResponseFormatType::JsonSchema => "json_schema",
+                    }
+                    .to_string(),
+                }),
+            tools: request.tools.clone(),
+            tool_choice: request.tool_choice.clone(),
+            extra: HashMap::new(),
+        };
+        super::check_extra(&body, &request.extra)?;
+        body.extra = request.extra.clone();
+        Ok(body)
+    }
+
     fn convert_to_standard_response(&self, response: TogetherResponse) -> CompletionResponse {
         CompletionResponse {
             id: response.id,
@@ -69,82 +195,52 @@ impl TogetherProvider {
                 .map(|choice| Choice {
                     index: choice.index,
                     message: Message {
-                        role: match choice.message.role.as_str() {
-                            "system" => Role::System,
-                            "user" => Role::User,
-                            "assistant" => Role::Assistant,
-                            "tool" => Role::Tool,
-                            _ => Role::Assistant,
-                        },
-                        content: MessageContent::text(choice.message.content),
-                        tool_calls: None,
-                        tool_call_id: None,
+                        tool_calls: choice.message.tool_calls,
+                        ..Message::new(
+                            choice.message.role.parse().unwrap_or(Role::Assistant),
+                            MessageContent::text(choice.message.content.unwrap_or_default()),
+                        )
                     },
                     finish_reason: choice.finish_reason,
+                    logprobs: None,
                 })
                 .collect(),
-            usage: response.usage.map(|u| Usage {
-                prompt_tokens: u.prompt_tokens as u32,
-                completion_tokens: u.completion_tokens as u32,
-                total_tokens: u.total_tokens as u32,
-            }),
+            usage: response.usage.map(TogetherUsage::into_usage),
+            metadata: None,
         }
     }
 }
 
 #[async_trait]
 impl CompletionProvider for TogetherProvider {
-    async fn complete(&self, request: CompletionRequest) -> Result<CompletionResponse> {
-        let url = "https://api.together.xyz/v1/chat/completions";
+    async fn complete(&self, mut request: CompletionRequest) -> Result<CompletionResponse> {
+        request.normalize_system();
+        request.validate()?;
+        if self.validate_models {
+            super::validate_model(self, &request.model)?;
+        }
+        if self.check_context_window {
+            super::check_context_window(&request)?;
+        }
+        if self.capabilities_for(&request.model).alternating_roles {
+            request.merge_consecutive_roles();
+        }
+        let url = format!("{}/chat/completions", self.base_url);
 
-        let messages: Vec<TogetherMessage> = request
-            .messages
-            .iter()
-            .map(|msg| self.convert_message(msg))
-            .collect();
-
-        let together_request = TogetherChatRequest {
-            model: request.model.clone(),
-            messages,
-            temperature: request.temperature,
-            max_tokens: request.max_tokens.map(|t| t as i32),
-            top_p: request.top_p,
-            frequency_penalty: request.frequency_penalty,
-            presence_penalty: request.presence_penalty,
-            stop: request.stop.clone(),
-            stream: false,
-            response_format: request
-                .response_format
-                .as_ref()
-                .map(|f| TogetherResponseFormat {
-                    r#type: match &f.r#type {
-                        crate::ResponseFormatType::Text => "text",
-                        crate::ResponseFormatType::JsonObject => "json_object",
-                        crate::ResponseFormatType::JsonSchema => "json_schema",
-                    }
-                    .to_string(),
-                }),
-        };
+        let together_request = self.build_request(&request, false)?;
 
         let response = self
             .client
             .post(url)
-            .header("Authorization", format!("Bearer {}", self.api_key))
             .header("Content-Type", "application/json")
             .json(&together_request)
-            .send()
+            .send_authenticated(self.auth.as_ref())
             .await?;
 
-        let status = response.status();
-        if !status.is_success() {
-            let error_text = response.text().await?;
-            return Err(AiError::ProviderError {
-                provider: "together".to_string(),
-                message: format!("Together AI API error: {}", error_text),
-                error_code: None,
-                retryable: status.is_server_error(),
-            });
-        }
+        let response = super::check_response(response, |error| {
+            error.into_provider_error("together", "Together AI API error")
+        })
+        .await?;
 
         let together_response: TogetherResponse = response.json().await?;
         Ok(self.convert_to_standard_response(together_response))
@@ -152,119 +248,44 @@ impl CompletionProvider for TogetherProvider {
 
     async fn complete_stream(
         &self,
-        request: CompletionRequest,
+        mut request: CompletionRequest,
     ) -> Result<Pin<Box<dyn Stream<Item = Result<StreamChunk>> + Send>>> {
-        let url = "https://api.together.xyz/v1/chat/completions";
+        request.normalize_system();
+        request.validate()?;
+        if self.validate_models {
+            super::validate_model(self, &request.model)?;
+        }
+        if self.check_context_window {
+            super::check_context_window(&request)?;
+        }
+        if self.capabilities_for(&request.model).alternating_roles {
+            request.merge_consecutive_roles();
+        }
+        let url = format!("{}/chat/completions", self.base_url);
 
-        let messages: Vec<TogetherMessage> = request
-            .messages
-            .iter()
-            .map(|msg| self.convert_message(msg))
-            .collect();
-
-        let together_request = TogetherChatRequest {
-            model: request.model.clone(),
-            messages,
-            temperature: request.temperature,
-            max_tokens: request.max_tokens.map(|t| t as i32),
-            top_p: request.top_p,
-            frequency_penalty: request.frequency_penalty,
-            presence_penalty: request.presence_penalty,
-            stop: request.stop.clone(),
-            stream: true,
-            response_format: request
-                .response_format
-                .as_ref()
-                .map(|f| TogetherResponseFormat {
-                    r#type: match &f.r#type {
-                        crate::ResponseFormatType::Text => "text",
-                        crate::ResponseFormatType::JsonObject => "json_object",
-                        crate::ResponseFormatType::JsonSchema => "json_schema",
-                    }
-                    .to_string(),
-                }),
-        };
+        let together_request = self.build_request(&request, true)?;
 
         let response = self
             .client
             .post(url)
-            .header("Authorization", format!("Bearer {}", self.api_key))
             .header("Content-Type", "application/json")
             .json(&together_request)
-            .send()
+            .send_authenticated(self.auth.as_ref())
             .await?;
 
-        let status = response.status();
-        if !status.is_success() {
-            let error_text = response.text().await?;
-            return Err(AiError::ProviderError {
-                provider: "together".to_string(),
-                message: format!("Together AI API error: {}", error_text),
-                error_code: None,
-                retryable: status.is_server_error(),
-            });
-        }
+        let response = super::check_response(response, |error| {
+            error.into_provider_error("together", "Together AI API error")
+        })
+        .await?;
 
-        let stream = response.bytes_stream();
-        let mapped_stream = stream.map(move |chunk_result| {
-            match chunk_result {
-                Ok(chunk) => {
-                    let text = String::from_utf8_lossy(&chunk);
-
-                    // Together uses server-sent events like OpenAI
-                    if let Some(json_str) = text.strip_prefix("data: ") {
-                        if json_str.trim() == "[DONE]" {
-                            return Ok(StreamChunk {
-                                id: "together_stream".to_string(),
-                                choices: vec![],
-                                model: None,
-                            });
-                        }
-
-                        match serde_json::from_str::<TogetherStreamResponse>(json_str.trim()) {
-                            Ok(together_chunk) => Ok(StreamChunk {
-                                id: together_chunk.id,
-                                choices: together_chunk
-                                    .choices
-                                    .into_iter()
-                                    .map(|choice| crate::StreamChoice {
-                                        index: choice.index,
-                                        delta: crate::Delta {
-                                            role: choice.delta.role.map(|r| match r.as_str() {
-                                                "system" => Role::System,
-                                                "user" => Role::User,
-                                                "assistant" => Role::Assistant,
-                                                "tool" => Role::Tool,
-                                                _ => Role::Assistant,
-                                            }),
-                                            content: choice.delta.content,
-                                            tool_calls: None,
-                                        },
-                                        finish_reason: choice.finish_reason,
-                                    })
-                                    .collect(),
-                                model: Some(together_chunk.model),
-                            }),
-                            Err(e) => Err(AiError::StreamError {
-                                message: format!("Failed to parse Together stream chunk: {}", e),
-                                retryable: false,
-                            }),
-                        }
-                    } else {
-                        // Skip non-data lines
-                        Ok(StreamChunk {
-                            id: "together_stream".to_string(),
-                            choices: vec![],
-                            model: None,
-                        })
-                    }
-                }
-                Err(e) => Err(AiError::StreamError {
-                    message: e.to_string(),
-                    retryable: true,
-                }),
-            }
-        });
+        // Together uses server-sent events like OpenAI
+        let mapped_stream = EventStream::new(response.bytes_stream())
+            .with_optional_idle_timeout(self.stream_idle_timeout)
+            .filter(|event| {
+                let done = matches!(event, Ok(event) if event.data.trim() == "[DONE]");
+                futures::future::ready(!done)
+            })
+            .map(|event| parse_stream_chunk(&event?.data));
 
         Ok(Box::pin(mapped_stream))
     }
@@ -306,6 +327,21 @@ impl CompletionProvider for TogetherProvider {
             "codellama/CodeLlama-70b-Instruct-hf",
         ]
     }
+
+    /// Capabilities of the default model; see
+    /// [`capabilities_for`](TogetherProvider::capabilities_for)
+    fn capabilities(&self) -> ProviderCapabilities {
+        self.capabilities_for(self.default_model())
+    }
+}
+
+/// Models whose chat templates reject consecutive messages with the same
+/// role, such as Mistral's instruct models and Llama 2 chat
+fn requires_alternating_roles(model: &str) -> bool {
+    let model = model.to_ascii_lowercase();
+    ["mistral", "mixtral", "llama-2", "gemma"]
+        .iter()
+        .any(|family| model.contains(family))
 }
 
 // Together AI API types
@@ -328,19 +364,40 @@ struct TogetherChatRequest {
     stop: Option<Vec<String>>,
     stream: bool,
     #[serde(skip_serializing_if = "Option::is_none")]
+    stream_options: Option<TogetherStreamOptions>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     response_format: Option<TogetherResponseFormat>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tools: Option<Vec<Tool>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tool_choice: Option<ToolChoice>,
+    #[serde(flatten)]
+    extra: HashMap<String, Value>,
+}
+
+/// Asks for a final chunk carrying the usage of the whole response
+#[derive(Debug, Clone, Serialize)]
+struct TogetherStreamOptions {
+    include_usage: bool,
 }
 
 #[derive(Debug, Clone, Serialize)]
 struct TogetherMessage {
     role: &'static str,
     content: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tool_calls: Option<Vec<ToolCall>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tool_call_id: Option<String>,
 }
 
 #[derive(Debug, Clone, Deserialize)]
 struct TogetherMessageResponse {
     role: String,
-    content: String,
+    #[serde(default)]
+    content: Option<String>,
+    #[serde(default)]
+    tool_calls: Option<Vec<ToolCall>>,
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -369,7 +426,11 @@ struct TogetherChoice {
 struct TogetherStreamResponse {
     id: String,
     model: String,
+    #[serde(default)]
     choices: Vec<TogetherStreamChoice>,
+    /// Set on the final chunk
+    #[serde(default)]
+    usage: Option<TogetherUsage>,
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -386,6 +447,8 @@ struct TogetherDelta {
     role: Option<String>,
     #[serde(default)]
     content: Option<String>,
+    #[serde(default)]
+    tool_calls: Option<Vec<ToolCallDelta>>,
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -395,6 +458,48 @@ struct TogetherUsage {
     total_tokens: usize,
 }
 
+impl TogetherUsage {
+    fn into_usage(self) -> Usage {
+        Usage {
+            prompt_tokens: self.prompt_tokens as u32,
+            completion_tokens: self.completion_tokens as u32,
+            total_tokens: self.total_tokens as u32,
+            reasoning_tokens: None,
+        }
+    }
+}
+
+fn parse_stream_chunk(data: &str) -> Result<StreamChunk> {
+    let chunk: TogetherStreamResponse =
+        serde_json::from_str(data.trim()).map_err(|e| AiError::StreamError {
+            message: format!("Failed to parse Together stream chunk: {}", e),
+            retryable: false,
+        })?;
+
+    Ok(StreamChunk {
+        id: chunk.id,
+        choices: chunk
+            .choices
+            .into_iter()
+            .map(|choice| crate::StreamChoice {
+                index: choice.index,
+                delta: crate::Delta {
+                    role: choice
+                        .delta
+                        .role
+                        .map(|r| r.parse().unwrap_or(Role::Assistant)),
+                    content: choice.delta.content,
+                    tool_calls: choice.delta.tool_calls,
+                },
+                finish_reason: choice.finish_reason,
+            })
+            .collect(),
+        model: Some(chunk.model),
+        usage: chunk.usage.map(TogetherUsage::into_usage),
+        metadata: None,
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -412,19 +517,180 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_alternating_roles_depend_on_model() {
+        assert!(requires_alternating_roles(
+            "mistralai/Mixtral-8x7B-Instruct-v0.1"
+        ));
+        assert!(requires_alternating_roles(
+            "togethercomputer/llama-2-70b-chat"
+        ));
+        assert!(!requires_alternating_roles(
+            "meta-llama/Meta-Llama-3.1-70B-Instruct-Turbo"
+        ));
+        assert!(!requires_alternating_roles(
+            "Qwen/Qwen2.5-72B-Instruct-Turbo"
+        ));
+
+        // The capability follows the model
+        let provider = TogetherProvider::new(Some("test-key".to_string())).unwrap();
+        assert!(provider.capabilities().alternating_roles);
+        assert!(
+            provider
+                .capabilities_for("google/gemma-2-27b-it")
+                .alternating_roles
+        );
+        assert!(
+            !provider
+                .capabilities_for("Qwen/Qwen2.5-72B-Instruct-Turbo")
+                .alternating_roles
+        );
+    }
+
+    #[tokio::test]
+    async fn test_unknown_model_rejected_when_validating() {
+        let provider = TogetherProvider::new(Some("test-key".to_string()))
+            .unwrap()
+            .with_model_validation(true);
+        let request = CompletionRequest::new("acme/unknown-7b", vec![Message::user("hi")]);
+
+        match provider.complete(request).await {
+            Err(AiError::UnsupportedModel {
+                model,
+                available_models,
+                ..
+            }) => {
+                assert_eq!(model, "acme/unknown-7b");
+                assert!(available_models.contains(&provider.default_model().to_string()));
+            }
+            other => panic!("expected UnsupportedModel, got {:?}", other.map(|_| ())),
+        }
+    }
+
     #[test]
     fn test_message_conversion() {
         let provider = TogetherProvider::new(Some("test-key".to_string())).unwrap();
 
-        let message = Message {
-            role: Role::User,
-            content: MessageContent::text("Hello"),
-            tool_calls: None,
-            tool_call_id: None,
-        };
+        let message = Message::user("Hello");
 
         let together_message = provider.convert_message(&message);
         assert_eq!(together_message.role, "user");
         assert_eq!(together_message.content, "Hello");
     }
+
+    #[test]
+    fn test_tool_call_round_trip() {
+        let provider = TogetherProvider::new(Some("test-key".to_string())).unwrap();
+
+        let request = CompletionRequest {
+            model: "mistralai/Mixtral-8x7B-Instruct-v0.1".to_string(),
+            messages: vec![Message::user("What is 2 + 3?")],
+            tools: Some(vec![Tool {
+                r#type: crate::ToolType::Function,
+                function: crate::ToolFunction {
+                    name: "add".to_string(),
+                    description: Some("Add two numbers".to_string()),
+                    parameters: serde_json::json!({"type": "object"}),
+                },
+            }]),
+            tool_choice: Some(ToolChoice::auto()),
+            ..Default::default()
+        };
+
+        let body = serde_json::to_value(provider.build_request(&request, false).unwrap()).unwrap();
+        assert_eq!(body["tools"][0]["type"], "function");
+        assert_eq!(body["tools"][0]["function"]["name"], "add");
+        assert_eq!(body["tool_choice"], "auto");
+
+        let response: TogetherResponse = serde_json::from_value(serde_json::json!({
+            "id": "chatcmpl-1",
+            "model": "mistralai/Mixtral-8x7B-Instruct-v0.1",
+            "choices": [{
+                "index": 0,
+                "message": {
+                    "role": "assistant",
+                    "content": null,
+                    "tool_calls": [{
+                        "id": "call_1",
+                        "type": "function",
+                        "function": {"name": "add", "arguments": "{\"a\":2,\"b\":3}"}
+                    }]
+                },
+                "finish_reason": "tool_calls"
+            }]
+        }))
+        .unwrap();
+        let message = &provider.convert_to_standard_response(response).choices[0].message;
+        let tool_calls = message.tool_calls.as_ref().unwrap();
+        assert_eq!(tool_calls[0].id, "call_1");
+        assert_eq!(tool_calls[0].function.arguments, r#"{"a":2,"b":3}"#);
+
+        // The assistant turn and the tool result are sent back with their ids
+        let follow_up = provider.convert_message(message);
+        assert_eq!(follow_up.tool_calls.as_ref().unwrap()[0].id, "call_1");
+        let result = provider.convert_message(&Message::tool("call_1", "5"));
+        assert_eq!(result.tool_call_id.as_deref(), Some("call_1"));
+
+        let chunk: TogetherStreamResponse = serde_json::from_value(serde_json::json!({
+            "id": "chatcmpl-1",
+            "model": "mistralai/Mixtral-8x7B-Instruct-v0.1",
+            "choices": [{
+                "index": 0,
+                "delta": {"tool_calls": [{"index": 0, "function": {"arguments": "{\"a\""}}]}
+            }]
+        }))
+        .unwrap();
+        let delta = chunk.choices[0].delta.tool_calls.as_ref().unwrap();
+        assert_eq!(delta[0].index, Some(0));
+    }
+
+    #[test]
+    fn test_extra_params_are_added_to_body() {
+        let provider = TogetherProvider::new(Some("test-key".to_string())).unwrap();
+        let request = CompletionRequest {
+            model: "mistralai/Mixtral-8x7B-Instruct-v0.1".to_string(),
+            messages: vec![Message::user("Hi")],
+            temperature: Some(0.5),
+            extra: HashMap::from([("repetition_penalty".to_string(), serde_json::json!(1.1))]),
+            ..Default::default()
+        };
+
+        let body = serde_json::to_value(provider.build_request(&request, false).unwrap()).unwrap();
+        assert_eq!(body["repetition_penalty"], 1.1);
+        assert_eq!(body["temperature"], 0.5);
+        assert!(body.get("extra").is_none());
+
+        // A key the body already sets is rejected rather than sent twice
+        let mut clashing = request;
+        clashing.extra = HashMap::from([("temperature".to_string(), serde_json::json!(0.9))]);
+        match provider.build_request(&clashing, false) {
+            Err(AiError::InvalidRequest { field, message, .. }) => {
+                assert_eq!(field.as_deref(), Some("extra"));
+                assert!(message.contains("temperature"));
+            }
+            other => panic!("expected InvalidRequest, got {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn test_stream_requests_and_parses_usage() {
+        let provider = TogetherProvider::new(Some("test-key".to_string())).unwrap();
+        let request = CompletionRequest::new(
+            "mistralai/Mixtral-8x7B-Instruct-v0.1",
+            vec![Message::user("Hi")],
+        );
+        let body = serde_json::to_value(provider.build_request(&request, true).unwrap()).unwrap();
+        assert_eq!(body["stream_options"]["include_usage"], true);
+        let body = serde_json::to_value(provider.build_request(&request, false).unwrap()).unwrap();
+        assert!(body.get("stream_options").is_none());
+
+        let chunk = parse_stream_chunk(
+            r#"{"id":"chatcmpl-1","model":"mixtral","choices":[],"usage":{"prompt_tokens":12,"completion_tokens":5,"total_tokens":17}}"#,
+        )
+        .unwrap();
+        assert!(chunk.choices.is_empty());
+        let usage = chunk.usage.unwrap();
+        assert_eq!(usage.prompt_tokens, 12);
+        assert_eq!(usage.total_tokens, 17);
+    }
 }
@@ -1,30 +1,72 @@
 use async_trait::async_trait;
 use futures::stream::Stream;
+use reqwest::Client;
 use std::pin::Pin;
 
 use crate::{
-    providers::openai::OpenAIProvider, CompletionProvider, CompletionRequest, CompletionResponse,
-    Result, StreamChunk,
+    providers::{openai::OpenAIProvider, Authenticator, ProviderConfig},
+    CompletionProvider, CompletionRequest, CompletionResponse, ProviderCapabilities, Result,
+    StreamChunk,
 };
 
 pub struct XAIProvider {
     openai_provider: OpenAIProvider,
+    validate_models: bool,
 }
 
 impl XAIProvider {
     pub fn new(api_key: String) -> Self {
+        Self::with_base_url(api_key, "https://api.x.ai/v1".to_string())
+    }
+
+    pub fn with_base_url(api_key: String, base_url: String) -> Self {
         Self {
-            openai_provider: OpenAIProvider::with_base_url(
-                api_key,
-                "https://api.x.ai/v1".to_string(),
-            ),
+            openai_provider: OpenAIProvider::with_base_url(api_key, base_url),
+            validate_models: false,
         }
     }
+
+    /// Reject models missing from `available_models()` before sending,
+    /// instead of waiting for the API to refuse them
+    pub fn with_model_validation(mut self, enabled: bool) -> Self {
+        self.validate_models = enabled;
+        self
+    }
+
+    /// Send every request with the user agent and default headers from
+    /// `config`
+    pub fn with_config(mut self, config: &ProviderConfig) -> Result<Self> {
+        self.openai_provider = self.openai_provider.with_config(config)?;
+        Ok(self)
+    }
+
+    /// Send requests through `client`, sharing its connection pool and DNS
+    /// cache with other providers
+    pub fn with_client(mut self, client: Client) -> Self {
+        self.openai_provider = self.openai_provider.with_client(client);
+        self
+    }
+
+    /// Authenticate requests with `auth` instead of the API key, e.g. for a
+    /// proxy or gateway with its own credentials
+    pub fn with_authenticator(mut self, auth: impl Authenticator + 'static) -> Self {
+        self.openai_provider = self.openai_provider.with_authenticator(auth);
+        self
+    }
+
+    /// Create a provider, rejecting an empty or whitespace-only API key
+    pub fn try_new(api_key: String) -> Result<Self> {
+        super::validate_api_key("xai", &api_key)?;
+        Ok(Self::new(api_key))
+    }
 }
 
 #[async_trait]
 impl CompletionProvider for XAIProvider {
     async fn complete(&self, request: CompletionRequest) -> Result<CompletionResponse> {
+        if self.validate_models {
+            super::validate_model(self, &request.model)?;
+        }
         self.openai_provider.complete(request).await
     }
 
@@ -32,6 +74,9 @@ impl CompletionProvider for XAIProvider {
         &self,
         request: CompletionRequest,
     ) -> Result<Pin<Box<dyn Stream<Item = Result<StreamChunk>> + Send>>> {
+        if self.validate_models {
+            super::validate_model(self, &request.model)?;
+        }
         self.openai_provider.complete_stream(request).await
     }
 
@@ -46,4 +91,17 @@ impl CompletionProvider for XAIProvider {
     fn available_models(&self) -> Vec<&'static str> {
         vec!["grok-2-latest", "grok-2-1212", "grok-beta"]
     }
+
+    fn capabilities(&self) -> ProviderCapabilities {
+        ProviderCapabilities {
+            streaming: true,
+            tools: true,
+            vision: true,
+            json_mode: true,
+            max_context_tokens: Some(131_072),
+            max_output_tokens: None,
+            alternating_roles: false,
+            assistant_prefill: false,
+        }
+    }
 }
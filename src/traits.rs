@@ -2,7 +2,10 @@ use async_trait::async_trait;
 use futures::stream::Stream;
 use std::pin::Pin;
 
-use crate::{error::Result, models::*};
+use crate::{
+    error::{AiError, Result},
+    models::*,
+};
 
 #[async_trait]
 pub trait CompletionProvider: Send + Sync {
@@ -18,6 +21,138 @@ pub trait CompletionProvider: Send + Sync {
     fn default_model(&self) -> &'static str;
 
     fn available_models(&self) -> Vec<&'static str>;
+
+    /// Send `prompt` as a single user message and return the text of the
+    /// first choice. An empty reply is an error.
+    async fn complete_text(&self, model: &str, prompt: &str) -> Result<String> {
+        let request = CompletionRequest::new(model, vec![Message::user(prompt)]);
+
+        let response = self.complete(request).await?;
+        response
+            .choices
+            .first()
+            .map(|choice| choice.message.content.as_text_lossy().into_owned())
+            .filter(|text| !text.trim().is_empty())
+            .ok_or_else(|| AiError::MalformedResponse {
+                message: format!("{} returned no text", self.name()),
+                raw_response: None,
+            })
+    }
+
+    /// Readiness probe. The default sends a one-token request to the default
+    /// model: a transient failure reports unhealthy, while errors that a
+    /// retry cannot fix (such as a bad API key) are returned.
+    async fn health_check(&self) -> Result<bool> {
+        let request = CompletionRequest {
+            model: self.default_model().to_string(),
+            messages: vec![Message::user("ping")],
+            max_tokens: Some(1),
+            ..Default::default()
+        };
+
+        match self.complete(request).await {
+            Ok(_) => Ok(true),
+            Err(e) if e.is_retryable() => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// What this provider supports. The default assumes streaming only.
+    fn capabilities(&self) -> ProviderCapabilities {
+        ProviderCapabilities {
+            streaming: true,
+            ..Default::default()
+        }
+    }
+
+    /// Metadata for each of `available_models()`, e.g. for a model picker.
+    /// The default applies the provider's capabilities to every model; see
+    /// [`ModelInfo::from_capabilities`].
+    fn model_catalog(&self) -> Vec<ModelInfo> {
+        let capabilities = self.capabilities();
+        self.available_models()
+            .into_iter()
+            .map(|model| ModelInfo::from_capabilities(self.name(), model, &capabilities))
+            .collect()
+    }
+}
+
+/// Features a provider supports, used to route requests to a suitable provider
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ProviderCapabilities {
+    pub streaming: bool,
+    pub tools: bool,
+    pub vision: bool,
+    pub json_mode: bool,
+    /// Largest context window across the provider's models, if fixed
+    pub max_context_tokens: Option<u32>,
+    /// Largest completion length across the provider's models, if fixed
+    pub max_output_tokens: Option<u32>,
+    /// Rejects consecutive messages with the same role; they are merged
+    /// with [`CompletionRequest::merge_consecutive_roles`] before sending
+    pub alternating_roles: bool,
+    /// Continues a trailing assistant message instead of starting a new
+    /// reply, so a dropped stream can be resumed from the text received
+    pub assistant_prefill: bool,
+}
+
+impl ProviderCapabilities {
+    /// Whether a request's tools, images and response format are all supported
+    pub fn supports(&self, request: &CompletionRequest) -> bool {
+        let needs_tools = request.tools.as_ref().is_some_and(|t| !t.is_empty());
+        let needs_vision = request.messages.iter().any(|m| match &m.content {
+            MessageContent::Parts(parts) => {
+                parts.iter().any(|p| matches!(p, ContentPart::Image { .. }))
+            }
+            MessageContent::Text(_) => false,
+        });
+        let needs_json = request
+            .response_format
+            .as_ref()
+            .is_some_and(|f| !matches!(f.r#type, ResponseFormatType::Text));
+
+        (!needs_tools || self.tools)
+            && (!needs_vision || self.vision)
+            && (!needs_json || self.json_mode)
+    }
+}
+
+/// Bulk completions processed asynchronously by the provider, typically at a
+/// reduced price. Submit once, poll until finished, then fetch the results.
+#[async_trait]
+pub trait BatchProvider: Send + Sync {
+    async fn submit_batch(&self, requests: Vec<CompletionRequest>) -> Result<BatchHandle>;
+
+    async fn poll(&self, handle: &BatchHandle) -> Result<BatchStatus>;
+
+    /// One result per submitted request, in submission order
+    async fn results(&self, handle: &BatchHandle) -> Result<Vec<Result<CompletionResponse>>>;
+}
+
+/// Text-to-image generation, kept apart from chat completion
+#[async_trait]
+pub trait ImageGenerationProvider: Send + Sync {
+    /// Generate `n` images of `size` for `prompt`
+    async fn generate_image(
+        &self,
+        prompt: &str,
+        size: ImageSize,
+        n: u32,
+    ) -> Result<Vec<GeneratedImage>>;
+}
+
+/// Speech-to-text
+#[async_trait]
+pub trait TranscriptionProvider: Send + Sync {
+    /// Transcribe `audio`, encoded as `format` (e.g. "wav" or "mp3").
+    /// `language` is an ISO-639-1 hint such as "en"; without it the
+    /// language is detected.
+    async fn transcribe(
+        &self,
+        audio: Vec<u8>,
+        format: &str,
+        language: Option<&str>,
+    ) -> Result<Transcript>;
 }
 
 #[async_trait]
@@ -31,8 +166,46 @@ pub trait ModelProvider {
 pub struct ModelInfo {
     pub name: String,
     pub display_name: String,
+    /// Context window in tokens, 0 if unknown
     pub context_window: u32,
+    /// Longest completion in tokens, 0 if unknown
     pub max_output_tokens: u32,
     pub supports_streaming: bool,
     pub supports_functions: bool,
+    pub supports_vision: bool,
+    /// USD per 1,000 input tokens, if known
+    pub input_price_per_1k_tokens: Option<f64>,
+    /// USD per 1,000 output tokens, if known
+    pub output_price_per_1k_tokens: Option<f64>,
+}
+
+impl ModelInfo {
+    /// Info for `model` with feature support taken from the provider's
+    /// capabilities, the context window from
+    /// [`context_window`](crate::providers::context_window) and prices from
+    /// the cost tracker's default pricing
+    pub fn from_capabilities(
+        provider: &str,
+        model: &str,
+        capabilities: &ProviderCapabilities,
+    ) -> Self {
+        let pricing = crate::observability::cost_tracker::get_default_pricing().remove(&format!(
+            "{}:{}",
+            provider.to_ascii_lowercase(),
+            model
+        ));
+        Self {
+            name: model.to_string(),
+            display_name: model.to_string(),
+            context_window: crate::providers::context_window(model)
+                .or(capabilities.max_context_tokens)
+                .unwrap_or(0),
+            max_output_tokens: capabilities.max_output_tokens.unwrap_or(0),
+            supports_streaming: capabilities.streaming,
+            supports_functions: capabilities.tools,
+            supports_vision: capabilities.vision,
+            input_price_per_1k_tokens: pricing.as_ref().map(|p| p.input_price_per_1k_tokens),
+            output_price_per_1k_tokens: pricing.as_ref().map(|p| p.output_price_per_1k_tokens),
+        }
+    }
 }
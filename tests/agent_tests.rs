@@ -1,11 +1,16 @@
 use async_trait::async_trait;
 use lib_ai::{
     agent::{
-        tools::CalculatorTool, AgentBuilder, InMemoryStore, ToolExecutor, ToolRegistry, ToolResult,
+        memory::MemoryStats, tools::CalculatorTool, AgentBuilder, AgentError, InMemoryStore,
+        MemoryBackend, MemoryRecord, ToolExecutor, ToolRegistry, ToolResult,
     },
-    providers::OpenAIProvider,
+    moderation::{ModerationResult, Moderator},
+    providers::{MockProvider, OpenAIProvider},
+    AiError, Role,
 };
 use mockito::{Server, ServerGuard};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
 
 async fn create_mock_server() -> ServerGuard {
     Server::new_async().await
@@ -163,6 +168,704 @@ async fn test_agent_memory() {
     assert!(response.contains("blue"));
 }
 
+/// A backend keeping records in a shared vector, so the test can inspect them
+struct VecBackend(Arc<Mutex<Vec<MemoryRecord>>>);
+
+#[async_trait]
+impl MemoryBackend for VecBackend {
+    async fn add(&mut self, record: MemoryRecord) -> Result<(), AgentError> {
+        self.0.lock().unwrap().push(record);
+        Ok(())
+    }
+
+    async fn search(&self, query: &str, limit: usize) -> Result<Vec<MemoryRecord>, AgentError> {
+        let query = query.to_lowercase();
+        Ok(self
+            .0
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|record| {
+                query
+                    .split_whitespace()
+                    .any(|word| record.input.to_lowercase().contains(word))
+            })
+            .take(limit)
+            .cloned()
+            .collect())
+    }
+
+    async fn clear(&mut self) -> Result<(), AgentError> {
+        self.0.lock().unwrap().clear();
+        Ok(())
+    }
+
+    async fn stats(&self) -> Result<MemoryStats, AgentError> {
+        let records = self.0.lock().unwrap();
+        Ok(MemoryStats {
+            total_entries: records.len(),
+            total_size_bytes: records.iter().map(|r| r.input.len() + r.output.len()).sum(),
+        })
+    }
+}
+
+#[tokio::test]
+async fn test_custom_memory_backend_stores_and_retrieves() {
+    let records = Arc::new(Mutex::new(Vec::new()));
+    let provider = Arc::new(MockProvider::new().with_default_response("Noted."));
+    let mut agent = AgentBuilder::new()
+        .provider_arc(provider.clone())
+        .prompt("You have perfect memory")
+        .memory_backend(Box::new(VecBackend(records.clone())))
+        .build()
+        .unwrap();
+
+    agent.execute("My favorite color is blue").await.unwrap();
+    assert_eq!(records.lock().unwrap().len(), 1);
+    assert_eq!(records.lock().unwrap()[0].output, "Noted.");
+
+    agent.execute("What is my favorite color?").await.unwrap();
+    let request = provider.last_request().unwrap();
+    assert!(request.messages.iter().any(|m| m
+        .content
+        .as_text()
+        .is_some_and(|text| text.starts_with("Relevant context from memory:")
+            && text.contains("favorite color is blue"))));
+}
+
+#[tokio::test]
+async fn test_retrieved_memories_form_one_block_before_user_turn() {
+    let provider = Arc::new(MockProvider::new().with_default_response("Noted."));
+    let mut agent = AgentBuilder::new()
+        .provider_arc(provider.clone())
+        .prompt("You have perfect memory")
+        .memory(InMemoryStore::new(10))
+        .build()
+        .unwrap();
+
+    agent.execute("My favorite color is blue").await.unwrap();
+    agent.execute("My favorite food is pasta").await.unwrap();
+    agent
+        .execute("What is my favorite color and food?")
+        .await
+        .unwrap();
+
+    let request = provider.last_request().unwrap();
+    let system: Vec<_> = request
+        .messages
+        .iter()
+        .filter(|m| m.role == Role::System)
+        .collect();
+    // The prompt plus a single memory block, not one message per memory
+    assert_eq!(system.len(), 2);
+
+    let block = system[1].content.as_text().unwrap();
+    assert!(block.starts_with("Relevant context from memory:"));
+    assert!(block.contains("favorite color is blue"));
+    assert!(block.contains("favorite food is pasta"));
+
+    // The block comes right before the latest user turn
+    let n = request.messages.len();
+    assert_eq!(request.messages[n - 2].content.as_text(), Some(block));
+    assert_eq!(
+        request.messages[n - 1].content.as_text(),
+        Some("What is my favorite color and food?")
+    );
+}
+
+/// Flags any text mentioning "forbidden"
+struct KeywordModerator;
+
+#[async_trait]
+impl Moderator for KeywordModerator {
+    async fn moderate(&self, text: &str) -> lib_ai::Result<ModerationResult> {
+        Ok(if text.contains("forbidden") {
+            ModerationResult::flagged(vec!["test".to_string()])
+        } else {
+            ModerationResult::allowed()
+        })
+    }
+}
+
+#[tokio::test]
+async fn test_moderator_blocks_flagged_input_and_output() {
+    let provider = Arc::new(
+        MockProvider::new()
+            .with_response("This reply mentions forbidden things")
+            .with_default_response("All good"),
+    );
+    let mut agent = AgentBuilder::new()
+        .provider_arc(provider.clone())
+        .moderator(KeywordModerator)
+        .build()
+        .unwrap();
+
+    // Flagged input never reaches the provider
+    let result = agent.execute("tell me something forbidden").await;
+    assert!(matches!(
+        result,
+        Err(AgentError::ProviderError(AiError::ContentFiltered { .. }))
+    ));
+    assert_eq!(provider.request_count(), 0);
+
+    // A flagged reply is withheld and dropped from the context
+    let result = agent.execute("hello").await;
+    match result {
+        Err(AgentError::ProviderError(AiError::ContentFiltered { reason, category })) => {
+            assert!(reason.starts_with("Response"));
+            assert_eq!(category.as_deref(), Some("test"));
+        }
+        other => panic!("Expected ContentFiltered, got {:?}", other),
+    }
+    assert!(!agent
+        .context()
+        .messages()
+        .any(|m| m.content.as_text_lossy().contains("forbidden")));
+
+    assert_eq!(agent.execute("hello again").await.unwrap(), "All good");
+
+    // A streamed reply is checked once it is complete, and ends the stream
+    // with the error instead of being added to the context
+    let provider = MockProvider::new().with_response("This reply mentions forbidden things");
+    let mut agent = AgentBuilder::new()
+        .provider(provider)
+        .moderator(KeywordModerator)
+        .build()
+        .unwrap();
+    let result = agent.execute_stream_with("hello", |_| {}).await;
+    assert!(matches!(
+        result,
+        Err(AgentError::ProviderError(AiError::ContentFiltered { .. }))
+    ));
+    assert!(!agent
+        .context()
+        .messages()
+        .any(|m| m.content.as_text_lossy().contains("forbidden")));
+}
+
+#[tokio::test]
+async fn test_openai_refusal_fails_with_content_filtered() {
+    let mut server = create_mock_server().await;
+    let _mock = server
+        .mock("POST", "/chat/completions")
+        .with_status(200)
+        .with_header("content-type", "application/json")
+        .with_body(
+            r#"{
+            "id": "1",
+            "model": "gpt-4o",
+            "choices": [{
+                "index": 0,
+                "message": {"role": "assistant", "content": null, "refusal": "I can't help with that."},
+                "finish_reason": "stop"
+            }]
+        }"#,
+        )
+        .create_async()
+        .await;
+
+    let provider = OpenAIProvider::with_base_url("test-key".to_string(), server.url());
+    let mut agent = AgentBuilder::new().provider(provider).build().unwrap();
+    // The refusal text, not the empty content, is what the caller sees
+    match agent.execute("help me").await {
+        Err(AgentError::ProviderError(AiError::ContentFiltered { reason, category })) => {
+            assert_eq!(reason, "I can't help with that.");
+            assert_eq!(category.as_deref(), Some("refusal"));
+        }
+        other => panic!("Expected ContentFiltered, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn test_prompt_template_renders_variables() {
+    let provider = Arc::new(MockProvider::new().with_default_response("Hi"));
+    let mut agent = AgentBuilder::new()
+        .provider_arc(provider.clone())
+        .prompt_template("You help {user_name}. Today is {date}. Keep {unknown} as is.")
+        .template_var("user_name", "Ada")
+        .template_var("date", "2024-05-01")
+        .build()
+        .unwrap();
+
+    agent.execute("hello").await.unwrap();
+    let request = provider.last_request().unwrap();
+    assert_eq!(request.messages[0].role, Role::System);
+    assert_eq!(
+        request.messages[0].content.as_text(),
+        Some("You help Ada. Today is 2024-05-01. Keep {unknown} as is.")
+    );
+
+    // Variables set between calls apply to the next request
+    agent.set_template_var("date", "2024-05-02");
+    agent.execute("hello again").await.unwrap();
+    let request = provider.last_request().unwrap();
+    assert_eq!(
+        request.messages[0].content.as_text(),
+        Some("You help Ada. Today is 2024-05-02. Keep {unknown} as is.")
+    );
+    let system_messages = request
+        .messages
+        .iter()
+        .filter(|m| m.role == Role::System)
+        .count();
+    assert_eq!(system_messages, 1);
+}
+
+/// Throws on its first call, then succeeds
+struct FlakyTool {
+    calls: AtomicUsize,
+}
+
+#[async_trait]
+impl ToolExecutor for FlakyTool {
+    async fn execute(
+        &self,
+        _arguments: &str,
+    ) -> std::result::Result<ToolResult, Box<dyn std::error::Error>> {
+        if self.calls.fetch_add(1, Ordering::SeqCst) == 0 {
+            return Err(std::io::Error::from(std::io::ErrorKind::ConnectionReset).into());
+        }
+        Ok(ToolResult::success(serde_json::json!({ "status": "ok" })))
+    }
+
+    fn definition(&self) -> lib_ai::ToolFunction {
+        lib_ai::ToolFunction {
+            name: "flaky".to_string(),
+            description: Some("Fails once".to_string()),
+            parameters: serde_json::json!({ "type": "object", "properties": {} }),
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_tool_retry_recovers_from_transient_failure() {
+    let flaky_call = || lib_ai::ToolCall {
+        id: "call_1".to_string(),
+        r#type: lib_ai::ToolType::Function,
+        function: lib_ai::FunctionCall {
+            name: "flaky".to_string(),
+            arguments: "{}".to_string(),
+        },
+    };
+    let agent_with = |retry: Option<lib_ai::RetryConfig>| {
+        let provider = MockProvider::new()
+            .with_tool_calls(vec![flaky_call()])
+            .with_response("Done");
+        let mut tools = ToolRegistry::new();
+        tools.register(
+            "flaky",
+            FlakyTool {
+                calls: AtomicUsize::new(0),
+            },
+        );
+        let mut builder = AgentBuilder::new().provider(provider).tools(tools);
+        if let Some(retry) = retry {
+            builder = builder.tool_retry(retry);
+        }
+        builder.build().unwrap()
+    };
+
+    // Without a policy the first failure is final
+    let mut agent = agent_with(None);
+    assert!(matches!(
+        agent.execute("run it").await,
+        Err(AgentError::ToolError(_))
+    ));
+
+    let retry = lib_ai::RetryConfigBuilder::new()
+        .max_attempts(2)
+        .initial_delay(std::time::Duration::from_millis(1))
+        .no_jitter()
+        .build();
+    let mut agent = agent_with(Some(retry));
+    assert_eq!(agent.execute("run it").await.unwrap(), "Done");
+    let tool_result = agent
+        .context()
+        .messages()
+        .find(|m| m.role == Role::Tool)
+        .unwrap();
+    assert_eq!(
+        tool_result.content.as_json(),
+        Some(&serde_json::json!({ "status": "ok" }))
+    );
+}
+
+#[tokio::test]
+async fn test_tool_meta_records_duration_and_retries() {
+    let provider = MockProvider::new()
+        .with_tool_calls(vec![lib_ai::ToolCall {
+            id: "call_1".to_string(),
+            r#type: lib_ai::ToolType::Function,
+            function: lib_ai::FunctionCall {
+                name: "flaky".to_string(),
+                arguments: "{}".to_string(),
+            },
+        }])
+        .with_response("Done");
+    let retry = lib_ai::RetryConfigBuilder::new()
+        .max_attempts(2)
+        .initial_delay(std::time::Duration::from_millis(20))
+        .no_jitter()
+        .build();
+    let agent = AgentBuilder::new()
+        .provider(provider)
+        .tool(
+            "flaky",
+            FlakyTool {
+                calls: AtomicUsize::new(0),
+            },
+        )
+        .tool_retry(retry)
+        .record_trace(true)
+        .build()
+        .unwrap();
+    let metrics = Arc::new(lib_ai::observability::MetricsCollector::new());
+    let mut agent = agent.with_observability(Some(metrics.clone()), None, None, None);
+    agent.execute("run it").await.unwrap();
+
+    let meta = &agent.last_trace().unwrap().steps[0].tool_meta;
+    assert_eq!(meta.len(), 1);
+    assert_eq!(meta[0].retries, 1);
+    // The backoff before the retry is part of the duration
+    assert!(meta[0].duration >= std::time::Duration::from_millis(20));
+
+    // Retries also reach the metrics
+    let usage = &metrics
+        .get_agent_metrics(agent.agent_id())
+        .unwrap()
+        .tool_usage["flaky"];
+    assert_eq!(usage.successful_executions, 1);
+    assert_eq!(usage.retries, 1);
+}
+
+#[tokio::test]
+async fn test_missing_tool_reported_to_model() {
+    let agent_with = |tolerate: bool| {
+        let provider = MockProvider::new()
+            .with_tool_calls(vec![lib_ai::ToolCall {
+                id: "call_1".to_string(),
+                r#type: lib_ai::ToolType::Function,
+                function: lib_ai::FunctionCall {
+                    name: "web_search".to_string(),
+                    arguments: r#"{"query": "weather"}"#.to_string(),
+                },
+            }])
+            .with_response("Search is unavailable, sorry");
+        AgentBuilder::new()
+            .provider(provider)
+            .tool("calculator", CalculatorTool)
+            .tolerate_missing_tools(tolerate)
+            .build()
+            .unwrap()
+    };
+
+    let mut agent = agent_with(false);
+    assert!(matches!(
+        agent.execute("What's the weather?").await,
+        Err(AgentError::ToolError(_))
+    ));
+
+    let mut agent = agent_with(true);
+    assert_eq!(
+        agent.execute("What's the weather?").await.unwrap(),
+        "Search is unavailable, sorry"
+    );
+    let tool_result = agent
+        .context()
+        .messages()
+        .find(|m| m.role == Role::Tool)
+        .unwrap();
+    assert_eq!(tool_result.tool_call_id.as_deref(), Some("call_1"));
+    assert_eq!(
+        tool_result.content.as_json(),
+        Some(&serde_json::json!({ "error": "Tool 'web_search' is no longer available" }))
+    );
+}
+
+#[tokio::test]
+async fn test_invalid_tool_arguments_reported_to_model() {
+    let provider = MockProvider::new()
+        .with_tool_calls(vec![lib_ai::ToolCall {
+            id: "call_1".to_string(),
+            r#type: lib_ai::ToolType::Function,
+            function: lib_ai::FunctionCall {
+                name: "calculator".to_string(),
+                arguments: r#"{"operation": "add", "a": 2}"#.to_string(),
+            },
+        }])
+        .with_response("I need both numbers");
+    let mut agent = AgentBuilder::new()
+        .provider(provider)
+        .tool("calculator", CalculatorTool)
+        .build()
+        .unwrap();
+
+    assert_eq!(agent.execute("Add 2").await.unwrap(), "I need both numbers");
+    let tool_result = agent
+        .context()
+        .messages()
+        .find(|m| m.role == Role::Tool)
+        .unwrap();
+    let error = tool_result.content.as_json().unwrap()["error"]
+        .as_str()
+        .unwrap()
+        .to_string();
+    assert!(error.contains("calculator"));
+    assert!(error.contains("\"b\" is a required property"));
+}
+
+/// Counts calls and records how many run at the same time
+#[derive(Clone, Default)]
+struct ConcurrencyProbe {
+    running: Arc<AtomicUsize>,
+    peak: Arc<AtomicUsize>,
+    calls: Arc<AtomicUsize>,
+}
+
+#[async_trait]
+impl ToolExecutor for ConcurrencyProbe {
+    async fn execute(
+        &self,
+        _arguments: &str,
+    ) -> std::result::Result<ToolResult, Box<dyn std::error::Error>> {
+        self.calls.fetch_add(1, Ordering::SeqCst);
+        let running = self.running.fetch_add(1, Ordering::SeqCst) + 1;
+        self.peak.fetch_max(running, Ordering::SeqCst);
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        self.running.fetch_sub(1, Ordering::SeqCst);
+        Ok(ToolResult::success(running))
+    }
+
+    fn definition(&self) -> lib_ai::ToolFunction {
+        lib_ai::ToolFunction {
+            name: "probe".to_string(),
+            description: None,
+            parameters: serde_json::json!({ "type": "object", "properties": {} }),
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_run_until_stops_when_predicate_holds() {
+    let provider = Arc::new(
+        MockProvider::new()
+            .with_response("Draft 1")
+            .with_response("Draft 2")
+            .with_response("FINAL: a haiku")
+            .with_response("never requested"),
+    );
+    let mut agent = AgentBuilder::new()
+        .provider_arc(provider.clone())
+        .build()
+        .unwrap();
+
+    let output = agent
+        .run_until("Write a haiku", |output| output.starts_with("FINAL"), 10)
+        .await
+        .unwrap();
+    assert_eq!(output, "FINAL: a haiku");
+    assert_eq!(provider.request_count(), 3);
+
+    // Each output is fed back as the next input
+    let inputs: Vec<_> = agent
+        .context()
+        .messages()
+        .filter(|m| m.role == Role::User)
+        .map(|m| m.content.as_text().unwrap().to_string())
+        .collect();
+    assert_eq!(inputs, ["Write a haiku", "Draft 1", "Draft 2"]);
+
+    // Running out of steps is an error
+    let provider = MockProvider::new().with_default_response("Still working");
+    let mut agent = AgentBuilder::new().provider(provider).build().unwrap();
+    assert!(matches!(
+        agent.run_until("Write a haiku", |_| false, 3).await,
+        Err(AgentError::ConfigError(_))
+    ));
+}
+
+#[tokio::test]
+async fn test_parallel_tools_respect_limit() {
+    let calls: Vec<lib_ai::ToolCall> = (0..6)
+        .map(|i| lib_ai::ToolCall {
+            id: format!("call_{}", i),
+            r#type: lib_ai::ToolType::Function,
+            function: lib_ai::FunctionCall {
+                name: "probe".to_string(),
+                arguments: "{}".to_string(),
+            },
+        })
+        .collect();
+    let provider = MockProvider::new()
+        .with_tool_calls(calls)
+        .with_response("Done");
+    let probe = ConcurrencyProbe::default();
+    let mut tools = ToolRegistry::new();
+    tools.register("probe", probe.clone());
+
+    let mut agent = AgentBuilder::new()
+        .provider(provider)
+        .tools(tools)
+        .max_parallel_tools(2)
+        .build()
+        .unwrap();
+    assert_eq!(agent.execute("probe six times").await.unwrap(), "Done");
+
+    assert_eq!(probe.peak.load(Ordering::SeqCst), 2);
+    // Every call got its result, in call order
+    let ids: Vec<String> = agent
+        .context()
+        .messages()
+        .filter(|m| m.role == Role::Tool)
+        .filter_map(|m| m.tool_call_id.clone())
+        .collect();
+    let expected: Vec<String> = (0..6).map(|i| format!("call_{}", i)).collect();
+    assert_eq!(ids, expected);
+}
+
+#[tokio::test]
+async fn test_duplicate_tool_calls_run_once() {
+    let call = |id: &str, arguments: &str| lib_ai::ToolCall {
+        id: id.to_string(),
+        r#type: lib_ai::ToolType::Function,
+        function: lib_ai::FunctionCall {
+            name: "probe".to_string(),
+            arguments: arguments.to_string(),
+        },
+    };
+    let provider = MockProvider::new()
+        .with_tool_calls(vec![
+            call("call_a", r#"{"city": "Paris", "units": "c"}"#),
+            call("call_b", r#"{"units":"c","city":"Paris"}"#),
+            call("call_c", r#"{"city": "Rome"}"#),
+        ])
+        .with_response("Done");
+    let probe = ConcurrencyProbe::default();
+    let mut tools = ToolRegistry::new();
+    tools.register("probe", probe.clone());
+
+    let mut agent = AgentBuilder::new()
+        .provider(provider)
+        .tools(tools)
+        .dedup_tool_calls(true)
+        .build()
+        .unwrap();
+    agent.execute("check the weather").await.unwrap();
+
+    let results: Vec<_> = agent
+        .context()
+        .messages()
+        .filter(|m| m.role == Role::Tool)
+        .map(|m| (m.tool_call_id.clone().unwrap(), m.content.clone()))
+        .collect();
+    assert_eq!(results.len(), 3);
+    assert_eq!(results[0].0, "call_a");
+    assert_eq!(results[1].0, "call_b");
+    assert_eq!(results[0].1, results[1].1);
+    assert_eq!(probe.calls.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn test_execution_trace_records_each_step() {
+    let call = |id: &str, arguments: &str| lib_ai::ToolCall {
+        id: id.to_string(),
+        r#type: lib_ai::ToolType::Function,
+        function: lib_ai::FunctionCall {
+            name: "calculator".to_string(),
+            arguments: arguments.to_string(),
+        },
+    };
+    let provider = MockProvider::new()
+        .with_tool_calls(vec![call(
+            "call_1",
+            r#"{"operation": "multiply", "a": 6, "b": 7}"#,
+        )])
+        .with_tool_calls(vec![call(
+            "call_2",
+            r#"{"operation": "add", "a": 42, "b": 1}"#,
+        )])
+        .with_response("The answer is 43");
+
+    let mut agent = AgentBuilder::new()
+        .provider(provider)
+        .tool("calculator", CalculatorTool)
+        .record_trace(true)
+        .build()
+        .unwrap();
+    agent.execute("What is 6 * 7 + 1?").await.unwrap();
+
+    let trace = agent.last_trace().unwrap();
+    assert_eq!(trace.steps.len(), 3);
+    assert_eq!(trace.steps[0].tool_calls[0].id, "call_1");
+    assert_eq!(trace.steps[0].tool_results[0]["result"], 42.0);
+    assert_eq!(trace.steps[1].tool_calls[0].id, "call_2");
+    assert_eq!(trace.steps[1].tool_results[0]["result"], 43.0);
+
+    let last = &trace.steps[2];
+    assert!(last.tool_calls.is_empty() && last.tool_results.is_empty());
+    assert_eq!(last.assistant.content.as_text(), Some("The answer is 43"));
+    assert!(trace.steps.iter().all(|step| step.usage.is_some()));
+
+    // Each step keeps the request it answered, which grows with the context
+    assert_eq!(
+        trace.steps[0]
+            .request
+            .messages
+            .last()
+            .unwrap()
+            .content
+            .as_text(),
+        Some("What is 6 * 7 + 1?")
+    );
+    assert_eq!(
+        trace.steps[1].request.messages.last().unwrap().role,
+        Role::Tool
+    );
+    assert!(trace.steps[2].request.messages.len() > trace.steps[1].request.messages.len());
+
+    // Nothing is recorded unless asked for
+    let mut agent = AgentBuilder::new()
+        .provider(MockProvider::new().with_response("Hi"))
+        .build()
+        .unwrap();
+    agent.execute("Hello").await.unwrap();
+    assert!(agent.last_trace().is_none());
+}
+
+#[tokio::test]
+async fn test_forced_tool_choice_applies_to_first_request() {
+    let provider = Arc::new(
+        MockProvider::new()
+            .with_tool_calls(vec![lib_ai::ToolCall {
+                id: "call_1".to_string(),
+                r#type: lib_ai::ToolType::Function,
+                function: lib_ai::FunctionCall {
+                    name: "calculator".to_string(),
+                    arguments: r#"{"operation": "add", "a": 1, "b": 2}"#.to_string(),
+                },
+            }])
+            .with_response("3")
+            .with_response("Still 3"),
+    );
+
+    let mut agent = AgentBuilder::new()
+        .provider_arc(provider.clone())
+        .tool("calculator", CalculatorTool)
+        .tool_choice(lib_ai::ToolChoice::required())
+        .build()
+        .unwrap();
+    agent.execute("What is 1 + 2?").await.unwrap();
+    // A new turn forces the choice again
+    agent.execute("And again?").await.unwrap();
+
+    let choices: Vec<_> = provider
+        .requests()
+        .into_iter()
+        .map(|r| serde_json::to_value(r.tool_choice.unwrap()).unwrap())
+        .collect();
+    assert_eq!(choices, ["required", "auto", "required"]);
+}
+
 #[tokio::test]
 async fn test_context_management() {
     let mut server = create_mock_server().await;
@@ -1,7 +1,7 @@
 mod common;
 
 use futures::StreamExt;
-use lib_ai::{providers::AnthropicProvider, CompletionProvider, Message, MessageContent, Role};
+use lib_ai::{providers::AnthropicProvider, CompletionProvider, Message, MessageContent};
 
 fn get_provider() -> Option<AnthropicProvider> {
     match std::env::var("ANTHROPIC_API_KEY") {
@@ -130,26 +130,14 @@ async fn test_anthropic_long_context() {
     let long_text = "Hello ".repeat(1000);
     let request = lib_ai::CompletionRequest {
         model: provider.default_model().to_string(),
-        messages: vec![Message {
-            role: Role::User,
-            content: MessageContent::text(format!(
-                "{} Please just say 'I read your message'",
-                long_text
-            )),
-            tool_calls: None,
-            tool_call_id: None,
-        }],
+        messages: vec![Message::user(format!(
+            "{} Please just say 'I read your message'",
+            long_text
+        ))],
         temperature: Some(0.0),
         max_tokens: Some(50),
         stream: Some(false),
-        tools: None,
-        tool_choice: None,
-        response_format: None,
-        top_p: None,
-        frequency_penalty: None,
-        presence_penalty: None,
-        stop: None,
-        json_schema: None,
+        ..Default::default()
     };
 
     let response = provider.complete(request).await.unwrap();
@@ -1,3 +1,6 @@
+// Each test binary uses a different subset of these helpers
+#![allow(dead_code)]
+
 use lib_ai::{
     CompletionRequest, ContentPart, ImageUrl, Message, MessageContent, ResponseFormat,
     ResponseFormatType, Role, Tool, ToolChoice, ToolFunction, ToolType,
@@ -8,30 +11,13 @@ pub fn create_simple_request(model: String) -> CompletionRequest {
     CompletionRequest {
         model,
         messages: vec![
-            Message {
-                role: Role::System,
-                content: MessageContent::text("You are a helpful assistant."),
-                tool_calls: None,
-                tool_call_id: None,
-            },
-            Message {
-                role: Role::User,
-                content: MessageContent::text("Say 'Hello, World!' and nothing else."),
-                tool_calls: None,
-                tool_call_id: None,
-            },
+            Message::system("You are a helpful assistant."),
+            Message::user("Say 'Hello, World!' and nothing else."),
         ],
         temperature: Some(0.0),
         max_tokens: Some(20),
         stream: Some(false),
-        top_p: None,
-        frequency_penalty: None,
-        presence_penalty: None,
-        stop: None,
-        tools: None,
-        tool_choice: None,
-        response_format: None,
-        json_schema: None,
+        ..Default::default()
     }
 }
 
@@ -67,23 +53,13 @@ pub fn create_tool_request(model: String) -> CompletionRequest {
 
     CompletionRequest {
         model,
-        messages: vec![Message {
-            role: Role::User,
-            content: MessageContent::text("What's the weather like in San Francisco?"),
-            tool_calls: None,
-            tool_call_id: None,
-        }],
+        messages: vec![Message::user("What's the weather like in San Francisco?")],
         temperature: Some(0.0),
         max_tokens: Some(150),
         stream: Some(false),
         tools: Some(vec![weather_tool]),
         tool_choice: Some(ToolChoice::String("auto".to_string())),
-        top_p: None,
-        frequency_penalty: None,
-        presence_penalty: None,
-        stop: None,
-        response_format: None,
-        json_schema: None,
+        ..Default::default()
     }
 }
 
@@ -91,20 +67,10 @@ pub fn create_json_request(model: String) -> CompletionRequest {
     CompletionRequest {
         model,
         messages: vec![
-            Message {
-                role: Role::System,
-                content: MessageContent::text("You are a helpful assistant that outputs JSON."),
-                tool_calls: None,
-                tool_call_id: None,
-            },
-            Message {
-                role: Role::User,
-                content: MessageContent::text(
-                    "Return a JSON object with a single field 'message' containing 'Hello, World!'",
-                ),
-                tool_calls: None,
-                tool_call_id: None,
-            },
+            Message::system("You are a helpful assistant that outputs JSON."),
+            Message::user(
+                "Return a JSON object with a single field 'message' containing 'Hello, World!'",
+            ),
         ],
         temperature: Some(0.0),
         max_tokens: Some(50),
@@ -112,13 +78,7 @@ pub fn create_json_request(model: String) -> CompletionRequest {
         response_format: Some(ResponseFormat {
             r#type: ResponseFormatType::JsonObject,
         }),
-        tools: None,
-        tool_choice: None,
-        top_p: None,
-        frequency_penalty: None,
-        presence_penalty: None,
-        stop: None,
-        json_schema: None,
+        ..Default::default()
     }
 }
 
@@ -126,9 +86,7 @@ pub fn create_multimodal_request(model: String) -> CompletionRequest {
     CompletionRequest {
         model,
         messages: vec![
-            Message {
-                role: Role::User,
-                content: MessageContent::Parts(vec![
+            Message::new(Role::User, MessageContent::Parts(vec![
                     ContentPart::Text {
                         text: "Describe this image:".to_string(),
                     },
@@ -139,22 +97,12 @@ pub fn create_multimodal_request(model: String) -> CompletionRequest {
                             detail: Some("low".to_string()),
                         },
                     },
-                ]),
-                tool_calls: None,
-                tool_call_id: None,
-            },
+                ])),
         ],
         temperature: Some(0.0),
         max_tokens: Some(100),
         stream: Some(false),
-        tools: None,
-        tool_choice: None,
-        response_format: None,
-        top_p: None,
-        frequency_penalty: None,
-        presence_penalty: None,
-        stop: None,
-        json_schema: None,
+        ..Default::default()
     }
 }
 
@@ -162,41 +110,14 @@ pub fn create_conversation_request(model: String) -> CompletionRequest {
     CompletionRequest {
         model,
         messages: vec![
-            Message {
-                role: Role::System,
-                content: MessageContent::text("You are a helpful math tutor."),
-                tool_calls: None,
-                tool_call_id: None,
-            },
-            Message {
-                role: Role::User,
-                content: MessageContent::text("What is 2+2?"),
-                tool_calls: None,
-                tool_call_id: None,
-            },
-            Message {
-                role: Role::Assistant,
-                content: MessageContent::text("2+2 equals 4."),
-                tool_calls: None,
-                tool_call_id: None,
-            },
-            Message {
-                role: Role::User,
-                content: MessageContent::text("And what is 3+3?"),
-                tool_calls: None,
-                tool_call_id: None,
-            },
+            Message::system("You are a helpful math tutor."),
+            Message::user("What is 2+2?"),
+            Message::assistant("2+2 equals 4."),
+            Message::user("And what is 3+3?"),
         ],
         temperature: Some(0.0),
         max_tokens: Some(50),
         stream: Some(false),
-        tools: None,
-        tool_choice: None,
-        response_format: None,
-        top_p: None,
-        frequency_penalty: None,
-        presence_penalty: None,
-        stop: None,
-        json_schema: None,
+        ..Default::default()
     }
 }
@@ -1,7 +1,7 @@
 mod common;
 
 use futures::StreamExt;
-use lib_ai::{providers::GeminiProvider, CompletionProvider, Message, MessageContent, Role};
+use lib_ai::{providers::GeminiProvider, CompletionProvider, Message, MessageContent};
 
 fn get_provider() -> Option<GeminiProvider> {
     match std::env::var("GEMINI_API_KEY") {
@@ -78,32 +78,13 @@ async fn test_gemini_system_message_handling() {
     let request = lib_ai::CompletionRequest {
         model: provider.default_model().to_string(),
         messages: vec![
-            Message {
-                role: Role::System,
-                content: MessageContent::text(
-                    "You must always respond with 'SYSTEM MESSAGE WORKS'",
-                ),
-                tool_calls: None,
-                tool_call_id: None,
-            },
-            Message {
-                role: Role::User,
-                content: MessageContent::text("Say anything"),
-                tool_calls: None,
-                tool_call_id: None,
-            },
+            Message::system("You must always respond with 'SYSTEM MESSAGE WORKS'"),
+            Message::user("Say anything"),
         ],
         temperature: Some(0.0),
         max_tokens: Some(50),
         stream: Some(false),
-        tools: None,
-        tool_choice: None,
-        response_format: None,
-        top_p: None,
-        frequency_penalty: None,
-        presence_penalty: None,
-        stop: None,
-        json_schema: None,
+        ..Default::default()
     };
 
     let response = provider.complete(request).await.unwrap();
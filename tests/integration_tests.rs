@@ -1,8 +1,7 @@
 mod common;
 
 use lib_ai::{
-    providers::*, CompletionProvider, FunctionCall, Message, MessageContent, Role, ToolCall,
-    ToolType,
+    providers::*, CompletionProvider, FunctionCall, Message, MessageContent, ToolCall, ToolType,
 };
 use std::sync::Arc;
 
@@ -56,12 +55,10 @@ async fn test_dynamic_provider_switching() {
 #[tokio::test]
 async fn test_tool_result_message() {
     // Create a tool result message
-    let tool_result = Message {
-        role: Role::Tool,
-        content: MessageContent::text(r#"{"temperature": 72, "condition": "sunny"}"#),
-        tool_calls: None,
-        tool_call_id: Some("call_123".to_string()),
-    };
+    let tool_result = Message::tool(
+        "call_123".to_string(),
+        r#"{"temperature": 72, "condition": "sunny"}"#,
+    );
 
     // Verify serialization
     let json = serde_json::to_string(&tool_result).unwrap();
@@ -148,6 +145,8 @@ async fn test_error_handling() {
             message: "Service unavailable".to_string(),
             error_code: None,
             retryable: false,
+            status_code: None,
+            request_id: None,
         },
         AiError::StreamError {
             message: "Connection lost".to_string(),
@@ -170,26 +169,7 @@ async fn test_error_handling() {
 #[tokio::test]
 async fn test_request_builder_pattern() {
     // Start with minimal request
-    let mut request = lib_ai::CompletionRequest {
-        model: "test-model".to_string(),
-        messages: vec![Message {
-            role: Role::User,
-            content: MessageContent::text("Hello"),
-            tool_calls: None,
-            tool_call_id: None,
-        }],
-        temperature: None,
-        max_tokens: None,
-        stream: None,
-        top_p: None,
-        frequency_penalty: None,
-        presence_penalty: None,
-        stop: None,
-        tools: None,
-        tool_choice: None,
-        response_format: None,
-        json_schema: None,
-    };
+    let mut request = lib_ai::CompletionRequest::new("test-model", vec![Message::user("Hello")]);
 
     // Add options incrementally
     request.temperature = Some(0.7);
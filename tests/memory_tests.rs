@@ -1,5 +1,9 @@
+use async_trait::async_trait;
 use lib_ai::agent::memory::{InMemoryStore, Memory, SemanticMemoryBuilder};
-use lib_ai::embeddings::MockEmbeddingProvider;
+use lib_ai::embeddings::{
+    Embedding, EmbeddingError, EmbeddingProvider, EmbeddingRequest, EmbeddingResponse,
+    MockEmbeddingProvider,
+};
 
 #[tokio::test]
 async fn test_in_memory_store() {
@@ -129,3 +133,82 @@ async fn test_semantic_memory_max_entries() {
     let results = memory.retrieve("Message 0", 5).await.unwrap();
     assert!(results.is_empty() || !results[0].contains("Message 0"));
 }
+
+/// Embeds each text with a fixed vector chosen by its first word
+struct FixedEmbeddingProvider;
+
+#[async_trait]
+impl EmbeddingProvider for FixedEmbeddingProvider {
+    async fn embed(&self, request: EmbeddingRequest) -> Result<EmbeddingResponse, EmbeddingError> {
+        let embeddings = request
+            .input
+            .iter()
+            .enumerate()
+            .map(|(index, text)| {
+                let vector = match text.split_whitespace().next() {
+                    Some("query") => vec![1.0, 0.0, 0.0],
+                    Some("cats") => vec![0.99, 0.14, 0.0],
+                    Some("kittens") => vec![0.99, 0.13, 0.01],
+                    Some("dogs") => vec![0.75, 0.0, 0.66],
+                    _ => vec![0.0, 1.0, 0.0],
+                };
+                Embedding { vector, index }
+            })
+            .collect();
+        Ok(EmbeddingResponse {
+            embeddings,
+            model: "fixed".to_string(),
+            usage: None,
+        })
+    }
+
+    fn default_model(&self) -> &str {
+        "fixed"
+    }
+
+    fn dimension(&self) -> usize {
+        3
+    }
+}
+
+async fn store_pets(memory: &mut dyn Memory) {
+    for input in [
+        "cats are great",
+        "kittens are great",
+        "dogs are loyal",
+        "taxes are due",
+    ] {
+        memory.store(input, "noted").await.unwrap();
+    }
+}
+
+#[tokio::test]
+async fn test_semantic_memory_mmr_diversifies_results() {
+    let mut plain = SemanticMemoryBuilder::new()
+        .embedding_provider(FixedEmbeddingProvider)
+        .min_similarity(0.5)
+        .build()
+        .unwrap();
+    store_pets(&mut plain).await;
+
+    // The unrelated memory is below the threshold, and the near-duplicates
+    // win on similarity alone
+    let results = plain.retrieve("query", 5).await.unwrap();
+    assert_eq!(results.len(), 3);
+    assert!(!results.iter().any(|r| r.contains("taxes")));
+    let top_two = plain.retrieve("query", 2).await.unwrap();
+    assert!(top_two[0].contains("cats") || top_two[0].contains("kittens"));
+    assert!(top_two[1].contains("cats") || top_two[1].contains("kittens"));
+
+    let mut diverse = SemanticMemoryBuilder::new()
+        .embedding_provider(FixedEmbeddingProvider)
+        .min_similarity(0.5)
+        .mmr(0.3)
+        .build()
+        .unwrap();
+    store_pets(&mut diverse).await;
+
+    let top_two = diverse.retrieve("query", 2).await.unwrap();
+    assert!(top_two[0].contains("cats") || top_two[0].contains("kittens"));
+    assert!(top_two[1].contains("dogs"));
+}
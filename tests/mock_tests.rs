@@ -36,6 +36,42 @@ async fn test_openai_error_response() {
     mock.assert_async().await;
 }
 
+#[tokio::test]
+async fn test_provider_error_captures_status_and_request_id() {
+    let mut server = create_mock_server().await;
+
+    let mock = server
+        .mock("POST", "/chat/completions")
+        .with_status(503)
+        .with_header("content-type", "application/json")
+        .with_header("x-request-id", "req_abc123")
+        .with_body(r#"{"error": {"message": "Overloaded", "type": "server_error"}}"#)
+        .create_async()
+        .await;
+
+    let provider =
+        lib_ai::providers::OpenAIProvider::with_base_url("test-key".to_string(), server.url());
+
+    let request = crate::common::create_simple_request("gpt-3.5-turbo".to_string());
+    let err = provider.complete(request).await.unwrap_err();
+
+    assert_eq!(err.http_status(), Some(503));
+    assert_eq!(err.request_id(), Some("req_abc123"));
+    match err {
+        AiError::ProviderError {
+            status_code,
+            request_id,
+            ..
+        } => {
+            assert_eq!(status_code, Some(503));
+            assert_eq!(request_id.as_deref(), Some("req_abc123"));
+        }
+        e => panic!("Expected ProviderError, got {:?}", e),
+    }
+
+    mock.assert_async().await;
+}
+
 #[tokio::test]
 async fn test_openai_success_response() {
     let mut server = create_mock_server().await;
@@ -94,7 +130,7 @@ async fn test_streaming_response() {
     let mut server = create_mock_server().await;
 
     // Mock a streaming response
-    let stream_data = vec![
+    let stream_data = [
         "data: {\"id\":\"chatcmpl-123\",\"object\":\"chat.completion.chunk\",\"created\":1677652288,\"model\":\"gpt-3.5-turbo\",\"choices\":[{\"index\":0,\"delta\":{\"role\":\"assistant\"},\"finish_reason\":null}]}\n\n",
         "data: {\"id\":\"chatcmpl-123\",\"object\":\"chat.completion.chunk\",\"created\":1677652288,\"model\":\"gpt-3.5-turbo\",\"choices\":[{\"index\":0,\"delta\":{\"content\":\"Hello\"},\"finish_reason\":null}]}\n\n",
         "data: {\"id\":\"chatcmpl-123\",\"object\":\"chat.completion.chunk\",\"created\":1677652288,\"model\":\"gpt-3.5-turbo\",\"choices\":[{\"index\":0,\"delta\":{\"content\":\", World!\"},\"finish_reason\":null}]}\n\n",
@@ -206,8 +242,593 @@ async fn test_tool_calling_response() {
     mock.assert_async().await;
 }
 
+#[tokio::test]
+async fn test_openai_batch_lifecycle() {
+    use lib_ai::{BatchProvider, BatchState};
+
+    let mut server = create_mock_server().await;
+
+    let upload = server
+        .mock("POST", "/files")
+        .match_body(mockito::Matcher::Regex(
+            r#""custom_id":"request-1""#.to_string(),
+        ))
+        .with_status(200)
+        .with_body(r#"{"id": "file-in", "object": "file"}"#)
+        .create_async()
+        .await;
+    let create = server
+        .mock("POST", "/batches")
+        .match_body(mockito::Matcher::PartialJsonString(
+            r#"{"input_file_id": "file-in", "completion_window": "24h"}"#.to_string(),
+        ))
+        .with_status(200)
+        .with_body(r#"{"id": "batch_1", "status": "validating"}"#)
+        .create_async()
+        .await;
+    let status = server
+        .mock("GET", "/batches/batch_1")
+        .with_status(200)
+        .with_body(
+            r#"{"id": "batch_1", "status": "completed", "output_file_id": "file-out",
+                "request_counts": {"total": 2, "completed": 2, "failed": 0}}"#,
+        )
+        .expect(2)
+        .create_async()
+        .await;
+    // Results come back out of order and are matched by custom_id
+    let output = server
+        .mock("GET", "/files/file-out/content")
+        .with_status(200)
+        .with_body(concat!(
+            r#"{"custom_id": "request-1", "response": {"status_code": 200, "body": {"id": "b", "model": "gpt-4o", "choices": [{"index": 0, "message": {"role": "assistant", "content": "second"}, "finish_reason": "stop"}]}}}"#,
+            "\n",
+            r#"{"custom_id": "request-0", "response": {"status_code": 200, "body": {"id": "a", "model": "gpt-4o", "choices": [{"index": 0, "message": {"role": "assistant", "content": "first"}, "finish_reason": "stop"}]}}}"#,
+            "\n"
+        ))
+        .create_async()
+        .await;
+
+    let provider =
+        lib_ai::providers::OpenAIProvider::with_base_url("test-key".to_string(), server.url());
+    let handle = provider
+        .submit_batch(vec![
+            common::create_simple_request("gpt-4o".to_string()),
+            common::create_simple_request("gpt-4o".to_string()),
+        ])
+        .await
+        .unwrap();
+    assert_eq!(handle.id, "batch_1");
+    assert_eq!(handle.request_count, 2);
+
+    let batch_status = provider.poll(&handle).await.unwrap();
+    assert_eq!(batch_status.state, BatchState::Completed);
+    assert_eq!(batch_status.succeeded, 2);
+
+    let results = provider.results(&handle).await.unwrap();
+    let texts: Vec<_> = results
+        .iter()
+        .map(|r| {
+            r.as_ref().unwrap().choices[0]
+                .message
+                .content
+                .as_text()
+                .unwrap()
+        })
+        .collect();
+    assert_eq!(texts, ["first", "second"]);
+
+    upload.assert_async().await;
+    create.assert_async().await;
+    status.assert_async().await;
+    output.assert_async().await;
+}
+
+#[tokio::test]
+async fn test_providers_honor_custom_base_url() {
+    use lib_ai::providers::*;
+    use mockito::Matcher;
+
+    let mut server = create_mock_server().await;
+    let base = format!("{}/proxy", server.url());
+    let request = |model: &str| common::create_simple_request(model.to_string());
+
+    let cases: Vec<(&str, Box<dyn CompletionProvider>, &str)> = vec![
+        (
+            "/proxy/messages",
+            Box::new(AnthropicProvider::with_base_url(
+                "key".to_string(),
+                base.clone(),
+            )),
+            "claude-3-5-haiku-20241022",
+        ),
+        (
+            "/proxy/models/gemini-1.5-flash:generateContent",
+            Box::new(GeminiProvider::with_base_url(
+                "key".to_string(),
+                base.clone(),
+            )),
+            "gemini-1.5-flash",
+        ),
+        (
+            "/proxy/chat",
+            Box::new(CohereProvider::with_base_url(Some("key".to_string()), base.clone()).unwrap()),
+            "command-r",
+        ),
+        (
+            "/proxy/chat/completions",
+            Box::new(
+                TogetherProvider::with_base_url(Some("key".to_string()), base.clone()).unwrap(),
+            ),
+            "meta-llama/Llama-3-8b-chat-hf",
+        ),
+        (
+            "/proxy/predictions",
+            Box::new(
+                ReplicateProvider::with_base_url(Some("key".to_string()), base.clone()).unwrap(),
+            ),
+            "meta/llama-2-7b-chat",
+        ),
+        (
+            "/proxy/chat/completions",
+            Box::new(XAIProvider::with_base_url("key".to_string(), base.clone())),
+            "grok-2",
+        ),
+        (
+            "/proxy/chat/completions",
+            Box::new(OpenRouterProvider::with_base_url(
+                "key".to_string(),
+                base.clone(),
+            )),
+            "openai/gpt-4o",
+        ),
+    ];
+
+    for (path, provider, model) in cases {
+        let mock = server
+            .mock("POST", path)
+            .match_query(Matcher::Any)
+            .with_status(503)
+            .with_body("unavailable")
+            .create_async()
+            .await;
+
+        let err = provider.complete(request(model)).await.unwrap_err();
+        assert_eq!(err.http_status(), Some(503), "{}", provider.name());
+        mock.assert_async().await;
+        mock.remove_async().await;
+    }
+}
+
+#[tokio::test]
+async fn test_structured_tool_result_per_provider() {
+    use lib_ai::providers::{AnthropicProvider, OpenAIProvider};
+    use lib_ai::{FunctionCall, Message, ToolCall, ToolType};
+    use serde_json::{json, Value};
+
+    let mut server = create_mock_server().await;
+    let mut request = common::create_simple_request("model".to_string());
+    request.messages = vec![
+        Message::user("What is 2 + 3?"),
+        Message {
+            tool_calls: Some(vec![ToolCall {
+                id: "call_1".to_string(),
+                r#type: ToolType::Function,
+                function: FunctionCall {
+                    name: "calculator".to_string(),
+                    arguments: r#"{"operation":"add","a":2,"b":3}"#.to_string(),
+                },
+            }]),
+            ..Message::assistant("")
+        },
+        Message::tool_json("call_1", json!({"result": 5})),
+    ];
+
+    // The last message in the body, if it matches `check`
+    fn last_message(req: &mockito::Request, check: impl Fn(&Value) -> bool) -> bool {
+        req.body()
+            .ok()
+            .and_then(|body| serde_json::from_slice::<Value>(body).ok())
+            .and_then(|body| body["messages"].as_array()?.last().cloned())
+            .is_some_and(|message| check(&message))
+    }
+
+    let openai = server
+        .mock("POST", "/openai/chat/completions")
+        .match_request(|req| {
+            last_message(req, |m| {
+                m["role"] == "tool" && m["tool_call_id"] == "call_1" && m["content"] == r#"{"result":5}"#
+            })
+        })
+        .with_status(200)
+        .with_body(
+            r#"{"id": "1", "model": "gpt-4o", "choices": [{"index": 0, "message": {"role": "assistant", "content": "5"}, "finish_reason": "stop"}]}"#,
+        )
+        .create_async()
+        .await;
+
+    let anthropic = server
+        .mock("POST", "/anthropic/messages")
+        .match_request(|req| {
+            last_message(req, |m| {
+                let block = &m["content"][0];
+                m["role"] == "user"
+                    && block["type"] == "tool_result"
+                    && block["tool_use_id"] == "call_1"
+                    && block["content"][0]["type"] == "text"
+                    && serde_json::from_str::<Value>(block["content"][0]["text"].as_str().unwrap_or(""))
+                        .ok()
+                        == Some(json!({"result": 5}))
+            }) && req.utf8_lossy_body().is_ok_and(|body| body.contains(r#""type":"tool_use""#))
+        })
+        .with_status(200)
+        .with_body(
+            r#"{"id": "msg_1", "model": "claude-3-5-haiku-20241022", "role": "assistant", "content": [{"type": "text", "text": "5"}], "usage": {"input_tokens": 1, "output_tokens": 1}}"#,
+        )
+        .create_async()
+        .await;
+
+    let url = server.url();
+    OpenAIProvider::with_base_url("key".to_string(), format!("{}/openai", url))
+        .complete(request.clone())
+        .await
+        .unwrap();
+    AnthropicProvider::with_base_url("key".to_string(), format!("{}/anthropic", url))
+        .complete(request)
+        .await
+        .unwrap();
+
+    openai.assert_async().await;
+    anthropic.assert_async().await;
+}
+
 mod common;
 
+#[tokio::test]
+async fn test_provider_config_headers_on_outgoing_requests() {
+    use lib_ai::providers::{OpenAIProvider, ProviderConfig, DEFAULT_USER_AGENT};
+
+    let mut server = create_mock_server().await;
+    let body = r#"{
+        "id": "chatcmpl-1",
+        "model": "gpt-3.5-turbo",
+        "choices": [{
+            "index": 0,
+            "message": {"role": "assistant", "content": "ok"},
+            "finish_reason": "stop"
+        }]
+    }"#;
+    let default_agent = server
+        .mock("POST", "/chat/completions")
+        .match_header("user-agent", DEFAULT_USER_AGENT)
+        .match_header("x-gateway-route", "team-a")
+        .with_body(body)
+        .create_async()
+        .await;
+
+    let config = ProviderConfig::new().header("X-Gateway-Route", "team-a");
+    let provider = OpenAIProvider::with_base_url("test-key".to_string(), server.url())
+        .with_config(&config)
+        .unwrap();
+    let request = common::create_simple_request("gpt-3.5-turbo".to_string());
+    provider.complete(request.clone()).await.unwrap();
+    default_agent.assert_async().await;
+
+    let custom_agent = server
+        .mock("POST", "/chat/completions")
+        .match_header("user-agent", "my-gateway/2.0")
+        .with_body(body)
+        .create_async()
+        .await;
+    let provider = OpenAIProvider::with_base_url("test-key".to_string(), server.url())
+        .with_config(&config.user_agent("my-gateway/2.0"))
+        .unwrap();
+    provider.complete(request).await.unwrap();
+    custom_agent.assert_async().await;
+}
+
+#[tokio::test]
+async fn test_providers_share_injected_client() {
+    use lib_ai::providers::{OpenAIProvider, ProviderConfig, XAIProvider};
+
+    let mut server = create_mock_server().await;
+    let body = r#"{
+        "id": "chatcmpl-1",
+        "model": "gpt-3.5-turbo",
+        "choices": [{
+            "index": 0,
+            "message": {"role": "assistant", "content": "ok"},
+            "finish_reason": "stop"
+        }]
+    }"#;
+    // Only the shared client sends this header, so both requests must use it
+    let mock = server
+        .mock("POST", "/chat/completions")
+        .match_header("x-shared-client", "1")
+        .with_body(body)
+        .expect(2)
+        .create_async()
+        .await;
+
+    let mut headers = reqwest::header::HeaderMap::new();
+    headers.insert("x-shared-client", "1".parse().unwrap());
+    let client = reqwest::Client::builder()
+        .default_headers(headers)
+        .build()
+        .unwrap();
+
+    // The injected client wins whether the config comes before or after it
+    let config = ProviderConfig::new().user_agent("configured-agent");
+    let openai = OpenAIProvider::with_base_url("test-key".to_string(), server.url())
+        .with_config(&config)
+        .unwrap()
+        .with_client(client.clone());
+    let xai = XAIProvider::with_base_url("test-key".to_string(), server.url())
+        .with_client(client)
+        .with_config(&config)
+        .unwrap();
+    let request = common::create_simple_request("gpt-3.5-turbo".to_string());
+    openai.complete(request.clone()).await.unwrap();
+    xai.complete(request).await.unwrap();
+    mock.assert_async().await;
+}
+
+#[tokio::test]
+async fn test_custom_authenticator_signs_openai_requests() {
+    use lib_ai::providers::{Authenticator, OpenAIProvider};
+
+    /// Proxy auth that signs the method and path instead of sending a key
+    struct ProxyAuth;
+
+    impl Authenticator for ProxyAuth {
+        fn authenticate(&self, request: &mut reqwest::Request) -> lib_ai::Result<()> {
+            let signature = format!("{} {}", request.method(), request.url().path());
+            request
+                .headers_mut()
+                .insert("x-proxy-signature", signature.parse().unwrap());
+            Ok(())
+        }
+    }
+
+    let mut server = create_mock_server().await;
+    let mock = server
+        .mock("POST", "/chat/completions")
+        .match_header("x-proxy-signature", "POST /chat/completions")
+        .match_header("authorization", mockito::Matcher::Missing)
+        .with_body(
+            r#"{
+            "id": "chatcmpl-1",
+            "model": "gpt-3.5-turbo",
+            "choices": [{
+                "index": 0,
+                "message": {"role": "assistant", "content": "ok"},
+                "finish_reason": "stop"
+            }]
+        }"#,
+        )
+        .create_async()
+        .await;
+
+    let provider = OpenAIProvider::with_base_url("unused".to_string(), server.url())
+        .with_authenticator(ProxyAuth);
+    let request = common::create_simple_request("gpt-3.5-turbo".to_string());
+    provider.complete(request).await.unwrap();
+    mock.assert_async().await;
+}
+
+#[tokio::test]
+async fn test_openai_compatible_streams_report_usage() {
+    use futures::StreamExt;
+    use lib_ai::providers::{OpenRouterProvider, XAIProvider};
+
+    let stream_body = concat!(
+        "data: {\"id\":\"chatcmpl-1\",\"object\":\"chat.completion.chunk\",\"created\":1,\"model\":\"m\",\"choices\":[{\"index\":0,\"delta\":{\"content\":\"Hi\"},\"finish_reason\":\"stop\"}]}\n\n",
+        "data: {\"id\":\"chatcmpl-1\",\"object\":\"chat.completion.chunk\",\"created\":1,\"model\":\"m\",\"choices\":[],\"usage\":{\"prompt_tokens\":7,\"completion_tokens\":1,\"total_tokens\":8}}\n\n",
+        "data: [DONE]\n\n",
+    );
+
+    for name in ["xai", "openrouter"] {
+        let mut server = create_mock_server().await;
+        let mock = server
+            .mock("POST", "/chat/completions")
+            .match_body(mockito::Matcher::PartialJsonString(
+                r#"{"stream_options": {"include_usage": true}}"#.to_string(),
+            ))
+            .with_header("content-type", "text/event-stream")
+            .with_body(stream_body)
+            .create_async()
+            .await;
+
+        let (provider, model): (Box<dyn CompletionProvider>, &str) = match name {
+            "xai" => (
+                Box::new(XAIProvider::with_base_url("key".to_string(), server.url())),
+                "grok-2-latest",
+            ),
+            _ => (
+                Box::new(OpenRouterProvider::with_base_url(
+                    "key".to_string(),
+                    server.url(),
+                )),
+                "openai/gpt-4o",
+            ),
+        };
+        let request = common::create_streaming_request(model.to_string());
+        let chunks: Vec<_> = provider
+            .complete_stream(request)
+            .await
+            .unwrap()
+            .map(|chunk| chunk.unwrap())
+            .collect()
+            .await;
+
+        let usage = chunks.last().and_then(|c| c.usage.clone()).unwrap();
+        assert_eq!(usage.prompt_tokens, 7, "{}", name);
+        assert_eq!(usage.total_tokens, 8, "{}", name);
+        mock.assert_async().await;
+    }
+}
+
+#[tokio::test]
+async fn test_invalid_requests_are_rejected_before_sending() {
+    use lib_ai::providers::*;
+
+    let mut server = create_mock_server().await;
+    let mock = server
+        .mock("POST", mockito::Matcher::Any)
+        .expect(0)
+        .create_async()
+        .await;
+
+    let providers: Vec<(Box<dyn CompletionProvider>, &str)> = vec![
+        (
+            Box::new(
+                TogetherProvider::with_base_url(Some("key".to_string()), server.url()).unwrap(),
+            ),
+            "meta-llama/Llama-3-8b-chat-hf",
+        ),
+        (
+            Box::new(
+                ReplicateProvider::with_base_url(Some("key".to_string()), server.url()).unwrap(),
+            ),
+            "meta/llama-2-7b-chat",
+        ),
+        (
+            Box::new(OllamaProvider::new(Some(server.url()), None)),
+            "llama2",
+        ),
+    ];
+
+    for (provider, model) in providers {
+        let mut request = common::create_simple_request(model.to_string());
+        request.temperature = Some(3.0);
+        let result = provider.complete(request.clone()).await;
+        assert!(
+            matches!(result, Err(AiError::InvalidRequest { .. })),
+            "{}",
+            provider.name()
+        );
+
+        request.temperature = None;
+        request.messages.clear();
+        let result = provider.complete_stream(request).await;
+        assert!(
+            matches!(result, Err(AiError::InvalidRequest { .. })),
+            "{}",
+            provider.name()
+        );
+    }
+
+    mock.assert_async().await;
+}
+
+#[tokio::test]
+async fn test_request_over_context_window_is_rejected_before_sending() {
+    use lib_ai::providers::*;
+
+    let mut server = create_mock_server().await;
+    let mock = server
+        .mock("POST", mockito::Matcher::Any)
+        .expect(0)
+        .create_async()
+        .await;
+
+    // Each model's window is exceeded by 40k characters, roughly 10k tokens
+    let providers: Vec<(Box<dyn CompletionProvider>, &str, usize)> = vec![
+        (
+            Box::new(
+                OpenAIProvider::with_base_url("test-key".to_string(), server.url())
+                    .with_context_window_check(true),
+            ),
+            "gpt-4",
+            8_192,
+        ),
+        (
+            Box::new(
+                TogetherProvider::with_base_url(Some("key".to_string()), server.url())
+                    .unwrap()
+                    .with_context_window_check(true),
+            ),
+            "meta-llama/Meta-Llama-3-8B-Instruct",
+            8_192,
+        ),
+        (
+            Box::new(
+                ReplicateProvider::with_base_url(Some("key".to_string()), server.url())
+                    .unwrap()
+                    .with_context_window_check(true),
+            ),
+            "meta/llama-2-7b-chat",
+            4_096,
+        ),
+    ];
+
+    for (provider, model, window) in providers {
+        let mut request = common::create_simple_request(model.to_string());
+        request.messages = vec![lib_ai::Message::user("x".repeat(40_000))];
+        let result = provider.complete(request).await;
+
+        match result {
+            Err(AiError::ContextTooLarge { size, max_size }) => {
+                assert_eq!(max_size, window, "{}", provider.name());
+                assert!(size > max_size);
+            }
+            other => panic!(
+                "{}: expected ContextTooLarge, got {:?}",
+                provider.name(),
+                other.map(|_| ())
+            ),
+        }
+    }
+
+    mock.assert_async().await;
+}
+
+#[tokio::test]
+async fn test_replicate_prediction_times_out_at_max_wait() {
+    let mut server = create_mock_server().await;
+    let prediction = serde_json::json!({
+        "id": "abc",
+        "status": "processing",
+        "urls": {
+            "get": format!("{}/predictions/abc", server.url()),
+            "cancel": format!("{}/predictions/abc/cancel", server.url()),
+        }
+    })
+    .to_string();
+    let _create = server
+        .mock("POST", "/predictions")
+        .with_status(201)
+        .with_body(&prediction)
+        .create_async()
+        .await;
+    let _poll = server
+        .mock("GET", "/predictions/abc")
+        .with_body(&prediction)
+        .create_async()
+        .await;
+
+    let max_wait = std::time::Duration::from_millis(300);
+    let provider =
+        lib_ai::providers::ReplicateProvider::with_base_url(Some("key".to_string()), server.url())
+            .unwrap()
+            .with_poll_interval(std::time::Duration::from_millis(20))
+            .with_max_wait(max_wait);
+
+    let started = std::time::Instant::now();
+    let result = provider
+        .complete(common::create_simple_request(
+            "meta/llama-2-7b-chat".to_string(),
+        ))
+        .await;
+
+    match result {
+        Err(AiError::TimeoutError { timeout, .. }) => assert_eq!(timeout, max_wait),
+        other => panic!("Expected TimeoutError, got {:?}", other.map(|_| ())),
+    }
+    assert!(started.elapsed() >= max_wait);
+    assert!(started.elapsed() < std::time::Duration::from_secs(5));
+}
+
 // Add a test for connection errors
 #[tokio::test]
 async fn test_connection_error() {
@@ -229,3 +850,417 @@ async fn test_connection_error() {
         Ok(_) => panic!("Expected error but got success"),
     }
 }
+
+#[tokio::test]
+async fn test_openai_image_generation() {
+    use lib_ai::{ImageData, ImageGenerationProvider, ImageSize};
+
+    let mut server = create_mock_server().await;
+    let mock = server
+        .mock("POST", "/images/generations")
+        .match_body(mockito::Matcher::PartialJsonString(
+            r#"{"model": "gpt-image-1", "prompt": "a red fox", "n": 2, "size": "1024x1536"}"#
+                .to_string(),
+        ))
+        .with_header("content-type", "application/json")
+        .with_body(
+            r#"{"created": 1, "output_format": "webp", "data": [{"b64_json": "AAAA"}, {"b64_json": "BBBB"}]}"#,
+        )
+        .create_async()
+        .await;
+
+    let provider =
+        lib_ai::providers::OpenAIProvider::with_base_url("test-key".to_string(), server.url());
+    let images = provider
+        .generate_image("a red fox", ImageSize::new(1024, 1536), 2)
+        .await
+        .unwrap();
+
+    assert_eq!(images.len(), 2);
+    assert_eq!(images[0].url_or_b64, ImageData::Base64("AAAA".to_string()));
+    assert_eq!(images[1].mime, "image/webp");
+    mock.assert_async().await;
+
+    // DALL-E models answer with hosted URLs
+    let mock = server
+        .mock("POST", "/images/generations")
+        .with_header("content-type", "application/json")
+        .with_body(r#"{"created": 1, "data": [{"url": "https://example.com/fox.png"}]}"#)
+        .create_async()
+        .await;
+    let images = provider
+        .with_image_model("dall-e-3")
+        .generate_image("a red fox", ImageSize::default(), 1)
+        .await
+        .unwrap();
+    assert_eq!(
+        images[0].url_or_b64,
+        ImageData::Url("https://example.com/fox.png".to_string())
+    );
+    assert_eq!(images[0].mime, "image/png");
+    mock.assert_async().await;
+}
+
+#[tokio::test]
+async fn test_openai_transcription() {
+    use lib_ai::TranscriptionProvider;
+
+    let audio = include_bytes!("fixtures/silence.wav").to_vec();
+
+    let mut server = create_mock_server().await;
+    let mock = server
+        .mock("POST", "/audio/transcriptions")
+        .match_header("authorization", "Bearer test-key")
+        .match_header(
+            "content-type",
+            mockito::Matcher::Regex("^multipart/form-data".to_string()),
+        )
+        .match_body(mockito::Matcher::Regex(
+            r#"name="file"; filename="audio.wav""#.to_string(),
+        ))
+        .with_header("content-type", "application/json")
+        .with_body(
+            r#"{"task": "transcribe", "language": "english", "duration": 0.1, "text": "Hello there.",
+                "segments": [{"id": 0, "start": 0.0, "end": 0.1, "text": "Hello there."}]}"#,
+        )
+        .create_async()
+        .await;
+
+    let provider =
+        lib_ai::providers::OpenAIProvider::with_base_url("test-key".to_string(), server.url());
+    let transcript = provider.transcribe(audio, "wav", Some("en")).await.unwrap();
+
+    assert_eq!(transcript.text, "Hello there.");
+    assert_eq!(transcript.language.as_deref(), Some("english"));
+    assert_eq!(transcript.segments.len(), 1);
+    assert_eq!(transcript.segments[0].end, 0.1);
+    mock.assert_async().await;
+}
+
+#[tokio::test]
+async fn test_anthropic_stream_resumes_after_body_error() {
+    use futures::StreamExt;
+    use lib_ai::error::ResilientProviderBuilder;
+    use lib_ai::providers::AnthropicProvider;
+    use std::sync::{Arc, Mutex};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    fn event(name: &str, data: &str) -> String {
+        format!("event: {}\ndata: {}\n\n", name, data)
+    }
+    fn text_delta(text: &str) -> String {
+        event(
+            "content_block_delta",
+            &format!(
+                r#"{{"type": "content_block_delta", "index": 0, "delta": {{"type": "text_delta", "text": "{}"}}}}"#,
+                text
+            ),
+        )
+    }
+    let start = event(
+        "message_start",
+        r#"{"type": "message_start", "message": {"id": "msg_1", "model": "claude-3-5-haiku-20241022", "role": "assistant", "content": [], "usage": {"input_tokens": 1, "output_tokens": 0}}}"#,
+    ) + &event(
+        "content_block_start",
+        r#"{"type": "content_block_start", "index": 0, "content_block": {"type": "text", "text": ""}}"#,
+    );
+    let stop = event(
+        "content_block_stop",
+        r#"{"type": "content_block_stop", "index": 0}"#,
+    ) + &event("message_stop", r#"{"type": "message_stop"}"#);
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let bodies = Arc::new(Mutex::new(Vec::new()));
+    let server_bodies = bodies.clone();
+    tokio::spawn(async move {
+        for attempt in 0..2 {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut request = Vec::new();
+            let mut buf = [0u8; 8192];
+            // Read headers and the full body before responding
+            loop {
+                let n = socket.read(&mut buf).await.unwrap();
+                request.extend_from_slice(&buf[..n]);
+                let text = String::from_utf8_lossy(&request);
+                if let Some(end) = text.find("\r\n\r\n") {
+                    let length = text[..end]
+                        .lines()
+                        .find_map(|line| {
+                            let (name, value) = line.split_once(':')?;
+                            name.eq_ignore_ascii_case("content-length")
+                                .then(|| value.trim().parse::<usize>().ok())?
+                        })
+                        .unwrap_or(0);
+                    if request.len() >= end + 4 + length {
+                        server_bodies
+                            .lock()
+                            .unwrap()
+                            .push(text[end + 4..].to_string());
+                        break;
+                    }
+                }
+            }
+
+            // The first response promises more than it sends, so reading the
+            // body fails part-way through the stream
+            let (body, length) = if attempt == 0 {
+                let body = start.clone() + &text_delta("The quick brown ");
+                let length = body.len() + 1024;
+                (body, length)
+            } else {
+                let body = start.clone() + &text_delta("fox jumps.") + &stop;
+                let length = body.len();
+                (body, length)
+            };
+            let head = format!(
+                "HTTP/1.1 200 OK\r\ncontent-type: text/event-stream\r\ncontent-length: {}\r\nconnection: close\r\n\r\n",
+                length
+            );
+            socket.write_all(head.as_bytes()).await.unwrap();
+            socket.write_all(body.as_bytes()).await.unwrap();
+            socket.flush().await.unwrap();
+        }
+    });
+
+    let provider = ResilientProviderBuilder::new()
+        .stream_reconnects(1)
+        .build(Arc::new(AnthropicProvider::with_base_url(
+            "key".to_string(),
+            url,
+        )));
+    let mut request = crate::common::create_simple_request("claude-3-5-haiku-20241022".to_string());
+    request.stream = Some(true);
+
+    let mut stream = provider.complete_stream(request).await.unwrap();
+    let mut text = String::new();
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk.unwrap();
+        if let Some(content) = chunk.choices.first().and_then(|c| c.delta.content.as_ref()) {
+            text.push_str(content);
+        }
+    }
+    assert_eq!(text, "The quick brown fox jumps.");
+
+    // The second request continues from the text already received
+    let bodies = bodies.lock().unwrap();
+    assert_eq!(bodies.len(), 2);
+    let resumed: serde_json::Value = serde_json::from_str(&bodies[1]).unwrap();
+    let last = resumed["messages"]
+        .as_array()
+        .unwrap()
+        .last()
+        .unwrap()
+        .clone();
+    assert_eq!(last["role"], "assistant");
+    assert!(serde_json::to_string(&last["content"])
+        .unwrap()
+        .contains("The quick brown"));
+}
+
+#[tokio::test]
+async fn test_anthropic_stream_resumes_after_error_event() {
+    use futures::StreamExt;
+    use lib_ai::error::ResilientProviderBuilder;
+    use lib_ai::providers::AnthropicProvider;
+    use std::sync::Arc;
+
+    fn event(name: &str, data: &str) -> String {
+        format!("event: {}\ndata: {}\n\n", name, data)
+    }
+    fn text_delta(text: &str) -> String {
+        event(
+            "content_block_delta",
+            &format!(
+                r#"{{"type": "content_block_delta", "index": 0, "delta": {{"type": "text_delta", "text": "{}"}}}}"#,
+                text
+            ),
+        )
+    }
+    let start = event(
+        "message_start",
+        r#"{"type": "message_start", "message": {"id": "msg_1", "model": "claude-3-5-haiku-20241022", "role": "assistant", "content": [], "usage": {"input_tokens": 1, "output_tokens": 0}}}"#,
+    );
+    let overloaded = event(
+        "error",
+        r#"{"type": "error", "error": {"type": "overloaded_error", "message": "Overloaded"}}"#,
+    );
+    let stop = event("message_stop", r#"{"type": "message_stop"}"#);
+
+    let mut server = mockito::Server::new_async().await;
+    // mockito serves the first matching mock that still expects hits
+    let failing = server
+        .mock("POST", "/v1/messages")
+        .with_header("content-type", "text/event-stream")
+        .with_body(start.clone() + &text_delta("The quick brown ") + &overloaded)
+        .expect(2)
+        .create_async()
+        .await;
+    // The prefill is sent without its trailing space, so the model repeats it
+    let resumed = server
+        .mock("POST", "/v1/messages")
+        .match_body(mockito::Matcher::Regex(r#"The quick brown""#.to_string()))
+        .with_header("content-type", "text/event-stream")
+        .with_body(start.clone() + &text_delta(" fox jumps.") + &stop)
+        .expect(1)
+        .create_async()
+        .await;
+
+    let url = format!("{}/v1", server.url());
+    let mut request = crate::common::create_simple_request("claude-3-5-haiku-20241022".to_string());
+    request.stream = Some(true);
+
+    // Without reconnects the error ends the stream as a transient error
+    let provider = AnthropicProvider::with_base_url("key".to_string(), url.clone());
+    let chunks: Vec<_> = provider
+        .complete_stream(request.clone())
+        .await
+        .unwrap()
+        .collect()
+        .await;
+    let error = chunks.last().unwrap().as_ref().unwrap_err();
+    assert!(error.is_transient());
+
+    let provider = ResilientProviderBuilder::new()
+        .stream_reconnects(1)
+        .build(Arc::new(AnthropicProvider::with_base_url(
+            "key".to_string(),
+            url,
+        )));
+    let mut stream = provider.complete_stream(request).await.unwrap();
+    let mut text = String::new();
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk.unwrap();
+        if let Some(content) = chunk.choices.first().and_then(|c| c.delta.content.as_ref()) {
+            text.push_str(content);
+        }
+    }
+    assert_eq!(text, "The quick brown fox jumps.");
+
+    failing.assert_async().await;
+    resumed.assert_async().await;
+}
+
+#[tokio::test]
+async fn test_cohere_stream_parses_ndjson() {
+    use futures::StreamExt;
+    use lib_ai::providers::CohereProvider;
+
+    let mut server = create_mock_server().await;
+    let body = [
+        r#"{"is_finished":false,"event_type":"stream-start","generation_id":"gen-1"}"#,
+        r#"{"is_finished":false,"event_type":"text-generation","text":"Hello"}"#,
+        r#"{"is_finished":false,"event_type":"text-generation","text":", world"}"#,
+        r#"{"is_finished":true,"event_type":"stream-end","finish_reason":"COMPLETE"}"#,
+    ]
+    .join("\n")
+        + "\n";
+    let mock = server
+        .mock("POST", "/chat")
+        .with_status(200)
+        .with_header("content-type", "application/x-ndjson")
+        .with_body(body)
+        .create_async()
+        .await;
+
+    let provider = CohereProvider::with_base_url(Some("key".to_string()), server.url()).unwrap();
+    let mut request = crate::common::create_simple_request("command-r".to_string());
+    request.stream = Some(true);
+
+    let chunks: Vec<_> = provider
+        .complete_stream(request)
+        .await
+        .unwrap()
+        .collect()
+        .await;
+    let chunks: Vec<_> = chunks.into_iter().map(|chunk| chunk.unwrap()).collect();
+    let text: String = chunks
+        .iter()
+        .filter_map(|chunk| chunk.choices.first()?.delta.content.clone())
+        .collect();
+    assert_eq!(text, "Hello, world");
+    assert!(chunks.iter().all(|chunk| chunk.id == "gen-1"));
+    let last = chunks.last().unwrap();
+    assert_eq!(last.choices[0].finish_reason.as_deref(), Some("COMPLETE"));
+
+    mock.assert_async().await;
+}
+
+#[tokio::test]
+async fn test_stream_idle_timeout_from_provider_config() {
+    use futures::StreamExt;
+    use lib_ai::providers::{CohereProvider, OpenAIProvider, ProviderConfig};
+    use std::time::Duration;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    /// Serves `first` as the start of every response body, then holds the
+    /// connection open without sending anything more
+    async fn stalling_server(content_type: &'static str, first: &'static str) -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            loop {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                tokio::spawn(async move {
+                    let mut buf = [0u8; 8192];
+                    let _ = socket.read(&mut buf).await;
+                    let head = format!(
+                        "HTTP/1.1 200 OK\r\ncontent-type: {}\r\nconnection: close\r\n\r\n",
+                        content_type
+                    );
+                    socket.write_all(head.as_bytes()).await.unwrap();
+                    socket.write_all(first.as_bytes()).await.unwrap();
+                    socket.flush().await.unwrap();
+                    tokio::time::sleep(Duration::from_secs(30)).await;
+                });
+            }
+        });
+        url
+    }
+
+    let config = ProviderConfig::new().stream_idle_timeout(Duration::from_millis(200));
+
+    // Server-sent events
+    let url = stalling_server(
+        "text/event-stream",
+        "data: {\"id\":\"c1\",\"object\":\"chat.completion.chunk\",\"created\":0,\"model\":\"gpt-4o\",\"choices\":[{\"index\":0,\"delta\":{\"content\":\"Hi\"},\"finish_reason\":null}]}\n\n",
+    )
+    .await;
+    let provider = OpenAIProvider::with_base_url("key".to_string(), url)
+        .with_config(&config)
+        .unwrap();
+    let mut stream = provider
+        .complete_stream(crate::common::create_simple_request("gpt-4o".to_string()))
+        .await
+        .unwrap();
+    let first = stream.next().await.unwrap().unwrap();
+    assert_eq!(first.choices[0].delta.content.as_deref(), Some("Hi"));
+    assert!(matches!(
+        stream.next().await,
+        Some(Err(AiError::TimeoutError { .. }))
+    ));
+    assert!(stream.next().await.is_none());
+
+    // Newline-delimited JSON
+    let url = stalling_server(
+        "application/x-ndjson",
+        "{\"is_finished\":false,\"event_type\":\"text-generation\",\"text\":\"Hi\"}\n",
+    )
+    .await;
+    let provider = CohereProvider::with_base_url(Some("key".to_string()), url)
+        .unwrap()
+        .with_config(&config)
+        .unwrap();
+    let mut stream = provider
+        .complete_stream(crate::common::create_simple_request(
+            "command-r".to_string(),
+        ))
+        .await
+        .unwrap();
+    let first = stream.next().await.unwrap().unwrap();
+    assert_eq!(first.choices[0].delta.content.as_deref(), Some("Hi"));
+    assert!(matches!(
+        stream.next().await,
+        Some(Err(AiError::TimeoutError { .. }))
+    ));
+}
@@ -1,5 +1,6 @@
 use lib_ai::agent::memory::{Memory, SurrealMemoryConfig, SurrealMemoryStore};
-use lib_ai::embeddings::MockEmbeddingProvider;
+use lib_ai::embeddings::{MockEmbeddingProvider, SemanticSearch, VectorStore};
+use std::sync::Arc;
 
 #[tokio::test]
 #[ignore] // Requires SurrealDB to be running
@@ -158,3 +159,35 @@ async fn test_surrealdb_memory_persistence() {
         assert!(results[0].contains("Rust") || results[0].contains("systems"));
     }
 }
+
+#[tokio::test]
+#[ignore] // Requires SurrealDB to be running
+async fn test_surrealdb_vector_store() {
+    let config = SurrealMemoryConfig {
+        namespace: "test_vectors".to_string(),
+        ..SurrealMemoryConfig::default()
+    };
+    let memory = SurrealMemoryStore::new(config, Box::new(MockEmbeddingProvider::new(16)))
+        .await
+        .unwrap();
+    let store = Arc::new(memory.vector_store("documents"));
+
+    let search = SemanticSearch::with_store(
+        Arc::new(MockEmbeddingProvider::with_similarity()),
+        store.clone(),
+    );
+    search
+        .add_documents(&[("a", "first"), ("b", "second")])
+        .await
+        .unwrap();
+    // Re-adding an id replaces the document
+    search
+        .add_documents(&[("a", "first, again")])
+        .await
+        .unwrap();
+    assert_eq!(store.len().await.unwrap(), 2);
+
+    let results = search.search("second", 2).await.unwrap();
+    assert_eq!(results.len(), 2);
+    assert!(results[0].score >= results[1].score);
+}